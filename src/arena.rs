@@ -0,0 +1,188 @@
+//! A tree whose nodes live in an arena borrowed from the caller, enabled by the `bumpalo` feature.
+//!
+//! Indexes that are built for a single query, queried, and dropped right after spend much of their
+//! time allocating and freeing the boxes of their nodes one by one. An [`ArenaArt`] is created
+//! with [`ArenaArt::new_in`] from a [`Bump`] that outlives it, and allocates its nodes by bumping
+//! a pointer in that arena. Dropping the tree drops the keys and values of its entries, but leaves
+//! the memory of its nodes to the arena, which frees it all at once when it is dropped or reset.
+//! Several trees can share the same arena.
+//!
+//! Nodes that are deleted or that change their kind do not give their memory back to the arena, so
+//! a tree under heavy churn keeps growing its arena, and is better served by an
+//! [`ART`](crate::ART) or a [`RecyclingArt`](crate::recycle::RecyclingArt). The indices of the
+//! inner nodes with 48 and 256 children are still allocated on the heap.
+
+use std::borrow::Borrow;
+
+pub use bumpalo::Bump;
+
+use crate::{
+    node::{debug_print, Bumped, Node, Path},
+    BytesComparable,
+};
+
+/// An adaptive radix tree whose nodes are allocated in a borrowed arena, see the [module
+/// documentation](self).
+pub struct ArenaArt<'a, K, V, const N: usize = 10>
+where
+    K: 'a,
+    V: 'a,
+{
+    root: Option<Node<K, V, N, Bumped<'a>>>,
+    len: usize,
+    arena: &'a Bump,
+}
+
+impl<'a, K, V, const N: usize> std::fmt::Debug for ArenaArt<'a, K, V, N>
+where
+    K: std::fmt::Debug + 'a,
+    V: std::fmt::Debug + 'a,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(root) = &self.root {
+            debug_print(f, root, 0, 0)
+        } else {
+            writeln!(f, "empty")
+        }
+    }
+}
+
+impl<'a, K, V, const N: usize> ArenaArt<'a, K, V, N>
+where
+    K: 'a,
+    V: 'a,
+{
+    /// Create an empty tree that allocates its nodes in the given arena.
+    #[must_use]
+    pub const fn new_in(arena: &'a Bump) -> Self {
+        Self {
+            root: None,
+            len: 0,
+            arena,
+        }
+    }
+
+    /// Return the arena the nodes of the tree are allocated in.
+    #[must_use]
+    pub const fn arena(&self) -> &'a Bump {
+        self.arena
+    }
+
+    /// Return the number of entries in the tree.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the tree holds no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return an iterator over the entries in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + use<'_, 'a, K, V, N> {
+        let mut path = Path::new(self.root.as_ref());
+        path.seek_first();
+        std::iter::from_fn(move || {
+            let leaf = path.leaf()?;
+            path.next();
+            Some((&leaf.key, &leaf.value))
+        })
+    }
+}
+
+impl<'a, K, V, const N: usize> ArenaArt<'a, K, V, N>
+where
+    K: BytesComparable + 'a,
+    V: 'a,
+{
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.root
+            .as_ref()
+            .and_then(|node| node.search(key.bytes().as_ref(), 0))
+            .map(|leaf| &leaf.value)
+    }
+
+    /// Insert the given key-value pair into the tree, returning the value previously associated
+    /// with the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let replaced = Node::insert_root_in(&mut self.root, self.arena, key, value, true);
+        if replaced.is_none() {
+            self.len += 1;
+        }
+        replaced
+    }
+
+    /// Delete the value associated with the given key.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let leaf = Node::delete_root(&mut self.root, key.bytes().as_ref(), true)?;
+        self.len -= 1;
+        Some(leaf.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, rc::Rc};
+
+    use rand::Rng;
+
+    use super::{ArenaArt, Bump};
+
+    #[test]
+    fn test_matches_btree() {
+        let mut rng = rand::thread_rng();
+        let arena = Bump::new();
+        let mut tree = ArenaArt::<u16, usize>::new_in(&arena);
+        let mut expected = BTreeMap::new();
+        for idx in 0..20_000 {
+            let key = rng.gen_range(0..2000);
+            if rng.gen_bool(0.5) {
+                assert_eq!(tree.insert(key, idx), expected.insert(key, idx));
+            } else {
+                assert_eq!(tree.delete(&key), expected.remove(&key));
+            }
+        }
+        assert_eq!(tree.len(), expected.len());
+        assert!(tree.iter().eq(expected.iter()));
+        for (key, value) in &expected {
+            assert_eq!(tree.search(key), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_nodes_in_arena() {
+        let mut arena = Bump::new();
+        let value = Rc::new(());
+        {
+            let mut tree = ArenaArt::<String, Rc<()>>::new_in(&arena);
+            let mut other = ArenaArt::<u32, u32>::new_in(tree.arena());
+            let before = arena.allocated_bytes();
+            for i in 0..1000 {
+                tree.insert(format!("key{i}"), Rc::clone(&value));
+                other.insert(i, i);
+            }
+            assert!(arena.allocated_bytes() > before);
+            assert_eq!(tree.search("key42"), Some(&value));
+            assert_eq!(other.search(&42), Some(&42));
+            assert_eq!(Rc::strong_count(&value), 1001);
+        }
+        // Dropping the tree drops its values, and the arena frees the nodes afterwards.
+        assert_eq!(Rc::strong_count(&value), 1);
+        arena.reset();
+        let mut tree = ArenaArt::<u8, ()>::new_in(&arena);
+        assert!(tree.is_empty());
+        tree.insert(1, ());
+        assert_eq!(tree.len(), 1);
+    }
+}
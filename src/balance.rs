@@ -0,0 +1,107 @@
+//! How balanced the shape of a tree is.
+//!
+//! The depth of a leaf in a radix tree is bounded by the length of its key rather than by the
+//! number of keys, so keys that are crafted to share long prefixes with a few bytes in between,
+//! such as every prefix of a long string, can turn the tree into a deep chain of inner nodes. A
+//! [`Balance`] report summarizes the depths of the leaves in a single traversal, so that operators
+//! can detect such key patterns and decide to hash or chunk the keys, see [`crate::hashed`].
+
+use crate::{BytesComparable, ART};
+
+/// The depths of the leaves of a tree, where the depth of a leaf is the number of inner nodes
+/// above it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Balance {
+    /// The greatest depth of a leaf.
+    pub height: usize,
+    /// The smallest depth of a leaf.
+    pub min_leaf_depth: usize,
+    /// The mean depth of the leaves.
+    pub mean_leaf_depth: f64,
+    /// The height divided by the smallest height that a tree with as many leaves can have when
+    /// every inner node has 256 children. A tree over random keys has a skew of 1 or 2, while
+    /// adversarial keys lead to a skew that grows with the number of keys. An empty tree has a
+    /// skew of 1.
+    pub skew: f64,
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    ART<K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+    /// Return the depths of the leaves of the tree, computed in a single traversal.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)] // The mean and the skew do not need to be exact.
+    pub fn balance(&self) -> Balance {
+        let (height, min_leaf_depth, total) = self
+            .iter_annotated()
+            .fold((0, usize::MAX, 0), |(height, min, total), (depth, ..)| {
+                (height.max(depth), min.min(depth), total + depth)
+            });
+        if self.is_empty() {
+            return Balance {
+                height: 0,
+                min_leaf_depth: 0,
+                mean_leaf_depth: 0.0,
+                skew: 1.0,
+            };
+        }
+        // The smallest number of levels of inner nodes above the leaves.
+        let mut ideal = 0_u32;
+        let mut capacity = 1_usize;
+        while capacity < self.len() {
+            capacity = capacity.saturating_mul(256);
+            ideal += 1;
+        }
+        Balance {
+            height,
+            min_leaf_depth,
+            mean_leaf_depth: total as f64 / self.len() as f64,
+            skew: height.max(1) as f64 / f64::from(ideal.max(1)),
+        }
+    }
+
+    /// Return the greatest number of inner nodes above a leaf, see [`ART::balance`].
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.balance().height
+    }
+
+    /// Return the smallest number of inner nodes above a leaf, see [`ART::balance`].
+    #[must_use]
+    pub fn min_leaf_depth(&self) -> usize {
+        self.balance().min_leaf_depth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use crate::ART;
+
+    #[test]
+    fn test_balance() {
+        let tree = ART::<u32, ()>::new();
+        let balance = tree.balance();
+        assert_eq!((balance.height, balance.min_leaf_depth), (0, 0));
+        assert!((balance.skew - 1.0).abs() < f64::EPSILON);
+
+        let mut rng = rand::thread_rng();
+        let tree: ART<u64, ()> = (0..10_000).map(|_| (rng.gen(), ())).collect();
+        let balance = tree.balance();
+        assert!(balance.height <= 5, "{balance:?}");
+        assert!(balance.min_leaf_depth >= 1);
+        assert!(balance.skew <= 2.5, "{balance:?}");
+
+        // Every prefix of a string makes a chain of inner nodes.
+        let long = "a".repeat(500);
+        let tree: ART<&str, ()> = (1..=500).map(|len| (&long[..len], ())).collect();
+        let balance = tree.balance();
+        assert_eq!(tree.height(), 499);
+        assert_eq!(tree.min_leaf_depth(), 1);
+        assert!((balance.mean_leaf_depth - 250.0).abs() < 1.0);
+        assert!(balance.skew > 100.0, "{balance:?}");
+    }
+}
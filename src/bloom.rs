@@ -0,0 +1,245 @@
+//! A tree that answers most lookups of missing keys without traversing it.
+//!
+//! A [`BloomArt`] keeps a counting Bloom filter over the hashes of its encoded keys next to the
+//! tree. A search first checks the filter, and a key that the filter has never seen is reported as
+//! missing without visiting a single node. Workloads dominated by misses, such as deduplication or
+//! existence checks in front of a slower store, skip most traversals this way. Keys that are in
+//! the tree are always found, and a missing key passes the filter with the configured false
+//! positive rate, in which case the tree is searched as usual.
+//!
+//! The filter counts the keys hashed into each of its slots, so deleting a key removes it from the
+//! filter as well. A counter that reaches its maximum stays there, which can only cause false
+//! positives and never hides a key.
+
+use std::{
+    borrow::Borrow,
+    hash::{DefaultHasher, Hasher},
+};
+
+use crate::{BytesComparable, ART};
+
+/// A Bloom filter made of small counters, which supports removals.
+#[derive(Debug, Clone)]
+struct CountingBloom {
+    counters: Box<[u8]>,
+    hashes: u32,
+}
+
+impl CountingBloom {
+    /// Size a filter for the given number of keys and false positive rate.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )] // The sizes are positive and far below where floating point numbers lose precision.
+    fn new(expected_items: usize, false_positive_rate: f64) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let slots = (-(expected_items as f64) * false_positive_rate.ln() / (ln2 * ln2)).ceil();
+        let slots = (slots as usize).max(1);
+        let hashes = (slots as f64 / expected_items as f64 * ln2).round() as u32;
+        Self {
+            counters: vec![0; slots].into_boxed_slice(),
+            hashes: hashes.max(1),
+        }
+    }
+
+    /// Return the slots of the key with the given hash, derived from the hash using double hashing.
+    fn slots(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let (h1, h2) = (hash & u64::from(u32::MAX), (hash >> 32) | 1);
+        let len = self.counters.len() as u64;
+        (0..u64::from(self.hashes)).map(move |i| {
+            usize::try_from(h1.wrapping_add(i.wrapping_mul(h2)) % len)
+                .expect("the slot is an index of the counters")
+        })
+    }
+
+    fn contains(&self, hash: u64) -> bool {
+        self.slots(hash).all(|slot| self.counters[slot] > 0)
+    }
+
+    fn insert(&mut self, hash: u64) {
+        for slot in self.slots(hash) {
+            self.counters[slot] = self.counters[slot].saturating_add(1);
+        }
+    }
+
+    fn remove(&mut self, hash: u64) {
+        for slot in self.slots(hash) {
+            // A saturated counter may count more keys than it can hold, so it is never decremented.
+            if self.counters[slot] != u8::MAX {
+                self.counters[slot] -= 1;
+            }
+        }
+    }
+}
+
+/// Hash the encoded bytes of the key.
+fn hash_of<Q>(key: &Q) -> u64
+where
+    Q: BytesComparable + ?Sized,
+{
+    let mut hasher = DefaultHasher::new();
+    hasher.write(key.bytes().as_ref());
+    hasher.finish()
+}
+
+/// An adaptive radix tree with a Bloom filter in front of its lookups.
+#[derive(Debug)]
+pub struct BloomArt<K, V, const N: usize = 10> {
+    tree: ART<K, V, N>,
+    filter: CountingBloom,
+}
+
+impl<K, V, const N: usize> BloomArt<K, V, N> {
+    /// Create an empty tree whose filter has the given false positive rate once it holds
+    /// `expected_items` keys. The rate degrades as more keys are inserted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `expected_items` is zero, or if the rate is not strictly between 0 and 1.
+    #[must_use]
+    pub fn new(expected_items: usize, false_positive_rate: f64) -> Self {
+        assert!(
+            expected_items > 0,
+            "the filter must expect at least one key"
+        );
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "the false positive rate must be strictly between 0 and 1"
+        );
+        Self {
+            tree: ART::new(),
+            filter: CountingBloom::new(expected_items, false_positive_rate),
+        }
+    }
+
+    /// Return the expected false positive rate of the filter for the current number of keys.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)] // The estimate does not need to be exact.
+    pub fn false_positive_rate(&self) -> f64 {
+        let hashes = f64::from(self.filter.hashes);
+        let fill = -hashes * self.tree.len() as f64 / self.filter.counters.len() as f64;
+        (1.0 - fill.exp()).powf(hashes)
+    }
+
+    /// Return the number of bytes taken by the counters of the filter.
+    #[must_use]
+    pub fn filter_bytes(&self) -> usize {
+        self.filter.counters.len()
+    }
+
+    /// Return the tree for reading.
+    #[must_use]
+    pub const fn tree(&self) -> &ART<K, V, N> {
+        &self.tree
+    }
+
+    /// Return the tree, dropping the filter.
+    #[must_use]
+    pub fn into_tree(self) -> ART<K, V, N> {
+        self.tree
+    }
+}
+
+impl<K, V, const N: usize> BloomArt<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Return true if the filter may contain the given key, and false if the key is certainly not
+    /// in the tree.
+    pub fn may_contain<Q>(&self, key: &Q) -> bool
+    where
+        Q: BytesComparable + ?Sized,
+    {
+        self.filter.contains(hash_of(key))
+    }
+
+    /// Search for the value associated with the given key, skipping the tree if the filter rules
+    /// the key out.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        if !self.may_contain(key) {
+            return None;
+        }
+        self.tree.search(key)
+    }
+
+    /// Insert the given key-value pair into the tree, returning the value previously associated
+    /// with the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = hash_of(&key);
+        let previous = self.tree.insert(key, value);
+        if previous.is_none() {
+            self.filter.insert(hash);
+        }
+        previous
+    }
+
+    /// Delete the value associated with the given key.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let hash = hash_of(key);
+        if !self.filter.contains(hash) {
+            return None;
+        }
+        let value = self.tree.delete(key)?;
+        self.filter.remove(hash);
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::Rng;
+
+    use super::BloomArt;
+
+    #[test]
+    fn test_bloom_matches_tree() {
+        let mut rng = rand::thread_rng();
+        let mut tree = BloomArt::<u32, u32>::new(1000, 0.01);
+        let mut expected = BTreeMap::new();
+        for _ in 0..5000 {
+            let key = rng.gen_range(0..2000);
+            if rng.gen_bool(0.6) {
+                assert_eq!(tree.insert(key, key), expected.insert(key, key));
+            } else {
+                assert_eq!(tree.delete(&key), expected.remove(&key));
+            }
+        }
+        for key in 0..2000 {
+            assert_eq!(tree.search(&key), expected.get(&key));
+        }
+        assert!(tree.tree().iter().eq(expected.iter()));
+    }
+
+    #[test]
+    fn test_bloom_false_positive_rate() {
+        let mut tree = BloomArt::<u64, ()>::new(10_000, 0.01);
+        for key in 0..10_000 {
+            tree.insert(key, ());
+        }
+        let rate = tree.false_positive_rate();
+        assert!((0.005..0.02).contains(&rate), "{rate}");
+        let false_positives = (1_000_000..1_100_000)
+            .filter(|key| tree.may_contain(key))
+            .count();
+        assert!(false_positives < 2000, "{false_positives}");
+        // Roughly 9.6 counters per key for a rate of 1%.
+        assert!((90_000..100_000).contains(&tree.filter_bytes()));
+
+        // Deleted keys leave the filter.
+        for key in 0..10_000 {
+            tree.delete(&key);
+        }
+        assert!((1_000_000..1_100_000).all(|key| !tree.may_contain(&key)));
+    }
+}
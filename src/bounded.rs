@@ -0,0 +1,138 @@
+//! A tree that rejects keys longer than a configured limit.
+//!
+//! The depth of a tree grows with the length of its keys, so a tree fed with untrusted keys can be
+//! made arbitrarily deep and slow. A [`BoundedArt`] checks the length of every encoded key before
+//! inserting it and rejects long keys with a [`KeyTooLong`] error instead.
+
+use std::borrow::Borrow;
+
+use crate::{BytesComparable, ART};
+
+/// An error returned when inserting a key whose encoded length exceeds the limit of the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyTooLong {
+    /// The length of the encoded key.
+    pub len: usize,
+    /// The maximum length of an encoded key accepted by the tree.
+    pub max_len: usize,
+}
+
+impl std::fmt::Display for KeyTooLong {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "key of {} bytes exceeds the maximum length of {} bytes",
+            self.len, self.max_len
+        )
+    }
+}
+
+impl std::error::Error for KeyTooLong {}
+
+/// An adaptive radix tree whose encoded keys are at most `max_len` bytes long.
+#[derive(Debug)]
+pub struct BoundedArt<K, V, const N: usize = 10> {
+    tree: ART<K, V, N>,
+    max_len: usize,
+}
+
+impl<K, V, const N: usize> BoundedArt<K, V, N> {
+    /// Create an empty tree accepting encoded keys of at most `max_len` bytes.
+    #[must_use]
+    pub const fn new(max_len: usize) -> Self {
+        Self {
+            tree: ART::new(),
+            max_len,
+        }
+    }
+
+    /// Return the maximum length of an encoded key.
+    #[must_use]
+    pub const fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Return the tree for reading.
+    #[must_use]
+    pub const fn tree(&self) -> &ART<K, V, N> {
+        &self.tree
+    }
+
+    /// Return the tree.
+    #[must_use]
+    pub fn into_tree(self) -> ART<K, V, N> {
+        self.tree
+    }
+}
+
+impl<K, V, const N: usize> BoundedArt<K, V, N>
+where
+    K: BytesComparable,
+{
+    fn check<Q>(&self, key: &Q) -> Result<(), KeyTooLong>
+    where
+        Q: BytesComparable + ?Sized,
+    {
+        let len = key.bytes().as_ref().len();
+        if len > self.max_len {
+            return Err(KeyTooLong {
+                len,
+                max_len: self.max_len,
+            });
+        }
+        Ok(())
+    }
+
+    /// Search for the value associated with the given key. Keys that are too long are never
+    /// searched for, as they can not be in the tree.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.check(key).ok()?;
+        self.tree.search(key)
+    }
+
+    /// Insert the given key-value pair into the tree, returning the value previously associated
+    /// with the key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error without changing the tree if the encoded key is too long.
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<Option<V>, KeyTooLong> {
+        self.check(&key)?;
+        Ok(self.tree.insert(key, value))
+    }
+
+    /// Delete the value associated with the given key.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.check(key).ok()?;
+        self.tree.delete(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoundedArt, KeyTooLong};
+
+    #[test]
+    fn test_bounded_key_length() {
+        let mut tree = BoundedArt::<String, u32>::new(4);
+        assert_eq!(tree.try_insert("abcd".to_string(), 1), Ok(None));
+        assert_eq!(
+            tree.try_insert("abcde".to_string(), 2),
+            Err(KeyTooLong { len: 5, max_len: 4 })
+        );
+        assert_eq!(tree.try_insert("abcd".to_string(), 3), Ok(Some(1)));
+        assert_eq!(tree.search("abcd"), Some(&3));
+        assert_eq!(tree.search("abcde"), None);
+        assert_eq!(tree.delete("abcde"), None);
+        assert_eq!(tree.delete("abcd"), Some(3));
+        assert_eq!(tree.tree().min(), None);
+    }
+}
@@ -0,0 +1,126 @@
+//! Building trees from unsorted input.
+//!
+//! Inserting keys one by one walks the tree from the root for every key. When all entries are known
+//! up front, the tree can instead be built top-down: the entries are partitioned by the byte that
+//! follows their longest common prefix, and every partition becomes the subtree of one child. This
+//! is a most-significant-byte radix sort that emits nodes instead of a sorted sequence, so the
+//! input never has to be sorted by comparison. Partitions are independent of each other, which
+//! also lets the builder spread the work over several threads.
+
+use crate::{node::Node, BytesComparable, ART};
+
+/// A builder that constructs a tree from a batch of entries in arbitrary order.
+///
+/// When several entries have the same key, the one that comes last in the input is kept, as if the
+/// entries had been inserted in order.
+#[derive(Debug, Clone, Copy)]
+pub struct BulkBuilder {
+    threads: usize,
+}
+
+impl BulkBuilder {
+    /// Create a builder that runs on the calling thread.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { threads: 1 }
+    }
+
+    /// Set the number of threads used to build the subtrees below the root. A value of zero or one
+    /// builds the tree on the calling thread.
+    #[must_use]
+    pub const fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Build a tree containing the given entries.
+    #[must_use]
+    pub fn build<K, V, const N: usize>(&self, entries: Vec<(K, V)>) -> ART<K, V, N>
+    where
+        K: BytesComparable + Send,
+        V: Send,
+    {
+        let root = if self.threads > 1 {
+            Node::build_parallel(entries, self.threads)
+        } else {
+            Node::build(entries, 0)
+        };
+        ART::from_root(root)
+    }
+}
+
+impl Default for BulkBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::{seq::SliceRandom, Rng};
+
+    use super::BulkBuilder;
+    use crate::ART;
+
+    fn assert_same_entries<const N: usize>(
+        tree: &ART<Vec<u8>, u32, N>,
+        expected: &BTreeMap<Vec<u8>, u32>,
+    ) {
+        let mut entries = Vec::new();
+        let Ok(()) = tree.try_for_each(|k, v| {
+            entries.push((k.clone(), *v));
+            Ok::<_, std::convert::Infallible>(())
+        });
+        assert_eq!(entries, expected.clone().into_iter().collect::<Vec<_>>());
+        for (k, v) in expected {
+            assert_eq!(tree.search(k), Some(v));
+        }
+    }
+
+    #[test]
+    fn test_build_matches_insertion() {
+        let mut rng = rand::thread_rng();
+        let mut entries = Vec::new();
+        for prefix_len in [0, 3, 12, 40] {
+            let prefix: Vec<u8> = (0..prefix_len).map(|_| rng.gen_range(1..=255)).collect();
+            for _ in 0..500 {
+                let mut key = prefix.clone();
+                key.extend((0..rng.gen_range(1..8)).map(|_| rng.gen_range(1..=4u8)));
+                entries.push((key, rng.gen::<u32>()));
+            }
+        }
+        entries.shuffle(&mut rng);
+
+        let mut expected = BTreeMap::new();
+        for (k, v) in &entries {
+            expected.insert(k.clone(), *v);
+        }
+        let tree: ART<Vec<u8>, u32> = BulkBuilder::new().build(entries.clone());
+        assert_same_entries(&tree, &expected);
+        let tree: ART<Vec<u8>, u32, 4> = BulkBuilder::new().threads(4).build(entries.clone());
+        assert_same_entries(&tree, &expected);
+        let tree: ART<Vec<u8>, u32> = entries.into_iter().collect();
+        assert_same_entries(&tree, &expected);
+    }
+
+    #[test]
+    fn test_build_small_inputs() {
+        let tree: ART<String, u32> = BulkBuilder::new().threads(8).build(Vec::new());
+        assert_eq!(tree.min(), None);
+
+        let tree: ART<String, u32> = BulkBuilder::new()
+            .threads(8)
+            .build(vec![("a".to_string(), 1)]);
+        assert_eq!(tree.search("a"), Some(&1));
+
+        let tree: ART<String, u32> = BulkBuilder::new().threads(8).build(vec![
+            ("same".to_string(), 1),
+            ("same".to_string(), 2),
+            ("same".to_string(), 3),
+        ]);
+        assert_eq!(tree.search("same"), Some(&3));
+        assert_eq!(tree.min(), tree.max());
+    }
+}
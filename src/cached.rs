@@ -0,0 +1,159 @@
+//! Keys that keep their encoded bytes next to them.
+//!
+//! The tree compares keys by their bytes, which are computed again every time a leaf is compared
+//! against a search key and when a leaf is split. This is free for strings and integers, but keys
+//! such as structs that assemble their bytes into a new buffer pay for it on every operation. A
+//! [`Cached`] key encodes its key once when it is created and stores the bytes alongside it in the
+//! leaf, trading the memory of the encoded form for never encoding the key again. Trees that do
+//! not wrap their keys are not affected.
+
+use std::borrow::Borrow;
+
+use crate::BytesComparable;
+
+/// A key stored together with its encoded bytes.
+pub struct Cached<K> {
+    key: K,
+    encoded: Box<[u8]>,
+}
+
+impl<K> Cached<K>
+where
+    K: BytesComparable,
+{
+    /// Create a key, encoding it once.
+    pub fn new(key: K) -> Self {
+        let encoded = key.bytes().as_ref().into();
+        Self { key, encoded }
+    }
+}
+
+impl<K> Cached<K> {
+    /// Return the key.
+    pub const fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Return the key, dropping its encoded bytes.
+    pub fn into_inner(self) -> K {
+        self.key
+    }
+
+    /// Return the encoded bytes of the key.
+    #[must_use]
+    pub const fn encoded(&self) -> &[u8] {
+        &self.encoded
+    }
+}
+
+impl<K> BytesComparable for Cached<K> {
+    type Target<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        &self.encoded
+    }
+}
+
+impl<K> Borrow<[u8]> for Cached<K> {
+    fn borrow(&self) -> &[u8] {
+        &self.encoded
+    }
+}
+
+impl<K> From<K> for Cached<K>
+where
+    K: BytesComparable,
+{
+    fn from(key: K) -> Self {
+        Self::new(key)
+    }
+}
+
+impl<K: Clone> Clone for Cached<K> {
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            encoded: self.encoded.clone(),
+        }
+    }
+}
+
+impl<K: std::fmt::Debug> std::fmt::Debug for Cached<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Cached").field(&self.key).finish()
+    }
+}
+
+impl<K> PartialEq for Cached<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.encoded == other.encoded
+    }
+}
+
+impl<K> Eq for Cached<K> {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::Cached;
+    use crate::{BytesComparable, ART};
+
+    thread_local! {
+        static ENCODED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// A key that assembles its bytes on every call and counts the calls.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Event {
+        tenant: u32,
+        name: String,
+    }
+
+    impl BytesComparable for Event {
+        type Target<'a> = Vec<u8>;
+
+        fn bytes(&self) -> Self::Target<'_> {
+            ENCODED.set(ENCODED.get() + 1);
+            let mut bytes = self.tenant.to_be_bytes().to_vec();
+            bytes.extend_from_slice(self.name.as_bytes());
+            bytes
+        }
+    }
+
+    #[test]
+    fn test_cached_keys_are_encoded_once() {
+        let events: Vec<_> = (0..1000)
+            .map(|i| Event {
+                tenant: i % 7,
+                name: format!("event-{i}"),
+            })
+            .collect();
+
+        ENCODED.set(0);
+        let mut tree = ART::<Event, usize>::new();
+        for (idx, event) in events.iter().enumerate() {
+            tree.insert(event.clone(), idx);
+        }
+        let uncached = ENCODED.get();
+        assert!(uncached > 2 * events.len(), "{uncached}");
+
+        ENCODED.set(0);
+        let mut tree = ART::<Cached<Event>, usize>::new();
+        for (idx, event) in events.iter().enumerate() {
+            tree.insert(Cached::new(event.clone()), idx);
+        }
+        assert_eq!(ENCODED.get(), events.len());
+        for (idx, event) in events.iter().enumerate() {
+            assert_eq!(tree.search(&Cached::from(event.clone())), Some(&idx));
+        }
+        assert_eq!(ENCODED.get(), 2 * events.len());
+
+        let (key, _) = tree.min().unwrap();
+        assert_eq!(key.key().name, "event-0");
+        assert_eq!(key.encoded(), &key.key().bytes()[..]);
+    }
+}
@@ -0,0 +1,355 @@
+//! Keys for prefixes whose length is counted in bits, such as `10.0.0.0/22`, and a routing table
+//! performing longest-prefix matches on them.
+
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use crate::{BytesComparable, ART};
+
+/// A prefix of an address that is `N - 1` bytes wide, whose length is counted in bits.
+///
+/// The prefix is stored as the address with all bits after the prefix cleared, followed by the
+/// length of the prefix. Prefixes are therefore ordered by their address first, then by their
+/// length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BitPrefix<const N: usize> {
+    data: [u8; N],
+}
+
+/// A prefix of an IPv4 address.
+pub type Ipv4Prefix = BitPrefix<5>;
+
+/// A prefix of an IPv6 address.
+pub type Ipv6Prefix = BitPrefix<17>;
+
+/// An error returned when creating or parsing a [`BitPrefix`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrefixError {
+    /// The address does not have the width of the prefix type.
+    AddressWidth,
+    /// The prefix is longer than the address.
+    PrefixLength,
+    /// The text is not an address followed by a slash and a prefix length.
+    Syntax,
+}
+
+impl fmt::Display for PrefixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AddressWidth => write!(f, "address has the wrong width"),
+            Self::PrefixLength => write!(f, "prefix length exceeds the address width"),
+            Self::Syntax => write!(f, "invalid prefix syntax"),
+        }
+    }
+}
+
+impl std::error::Error for PrefixError {}
+
+impl<const N: usize> BitPrefix<N> {
+    /// The number of bits in an address.
+    const ADDRESS_BITS: usize = (N - 1) * 8;
+
+    /// Create the prefix of the given length of an address. Bits of the address after the prefix
+    /// are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address is not `N - 1` bytes long, or if the length exceeds the
+    /// number of bits in the address.
+    pub fn new(address: &[u8], len: u8) -> Result<Self, PrefixError> {
+        if address.len() != N - 1 {
+            return Err(PrefixError::AddressWidth);
+        }
+        if usize::from(len) > Self::ADDRESS_BITS {
+            return Err(PrefixError::PrefixLength);
+        }
+        let mut data = [0; N];
+        data[..N - 1].copy_from_slice(address);
+        data[N - 1] = len;
+        mask(&mut data[..N - 1], len);
+        Ok(Self { data })
+    }
+
+    /// Return the address with all bits after the prefix cleared.
+    #[must_use]
+    pub fn address(&self) -> &[u8] {
+        &self.data[..N - 1]
+    }
+
+    /// Return the length of the prefix in bits.
+    #[must_use]
+    pub const fn prefix_len(&self) -> u8 {
+        self.data[N - 1]
+    }
+
+    /// Return true if the given address starts with this prefix.
+    #[must_use]
+    pub fn contains(&self, address: &[u8]) -> bool {
+        Self::new(address, self.prefix_len()).is_ok_and(|prefix| prefix == *self)
+    }
+}
+
+impl<const N: usize> BytesComparable for BitPrefix<N> {
+    type Target<'a> = &'a [u8];
+
+    fn bytes(&self) -> Self::Target<'_> {
+        &self.data
+    }
+}
+
+impl From<(Ipv4Addr, u8)> for Ipv4Prefix {
+    /// Create a prefix from an address and a length, clamping the length to 32 bits.
+    fn from((address, len): (Ipv4Addr, u8)) -> Self {
+        let len = len.min(32);
+        Self::new(&address.octets(), len).expect("the address and the length must be valid")
+    }
+}
+
+impl From<(Ipv6Addr, u8)> for Ipv6Prefix {
+    /// Create a prefix from an address and a length, clamping the length to 128 bits.
+    fn from((address, len): (Ipv6Addr, u8)) -> Self {
+        let len = len.min(128);
+        Self::new(&address.octets(), len).expect("the address and the length must be valid")
+    }
+}
+
+impl FromStr for Ipv4Prefix {
+    type Err = PrefixError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, len) = split_prefix(s)?;
+        let address: Ipv4Addr = address.parse().map_err(|_| PrefixError::Syntax)?;
+        Self::new(&address.octets(), len)
+    }
+}
+
+impl FromStr for Ipv6Prefix {
+    type Err = PrefixError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, len) = split_prefix(s)?;
+        let address: Ipv6Addr = address.parse().map_err(|_| PrefixError::Syntax)?;
+        Self::new(&address.octets(), len)
+    }
+}
+
+impl fmt::Display for Ipv4Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut octets = [0; 4];
+        octets.copy_from_slice(self.address());
+        write!(f, "{}/{}", Ipv4Addr::from(octets), self.prefix_len())
+    }
+}
+
+impl fmt::Display for Ipv6Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut octets = [0; 16];
+        octets.copy_from_slice(self.address());
+        write!(f, "{}/{}", Ipv6Addr::from(octets), self.prefix_len())
+    }
+}
+
+/// A table of prefixes supporting longest-prefix matches of addresses that are `N - 1` bytes wide.
+///
+/// A lookup only probes the prefix lengths that are present in the table, from the longest to the
+/// shortest, so its cost depends on the number of distinct lengths rather than on the number of
+/// prefixes.
+#[derive(Debug)]
+pub struct RoutingTable<V, const N: usize> {
+    routes: ART<BitPrefix<N>, V>,
+    /// The number of prefixes of each length.
+    counts: Vec<usize>,
+}
+
+impl<V, const N: usize> Default for RoutingTable<V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V, const N: usize> RoutingTable<V, N> {
+    /// Create an empty table.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            routes: ART::new(),
+            counts: vec![0; BitPrefix::<N>::ADDRESS_BITS + 1],
+        }
+    }
+
+    /// Insert a prefix into the table and return the value it was previously associated with.
+    pub fn insert(&mut self, prefix: BitPrefix<N>, value: V) -> Option<V> {
+        let previous = self.routes.delete(&prefix);
+        if previous.is_none() {
+            self.counts[usize::from(prefix.prefix_len())] += 1;
+        }
+        self.routes.insert(prefix, value);
+        previous
+    }
+
+    /// Remove a prefix from the table and return its value.
+    pub fn remove(&mut self, prefix: &BitPrefix<N>) -> Option<V> {
+        let removed = self.routes.delete(prefix);
+        if removed.is_some() {
+            self.counts[usize::from(prefix.prefix_len())] -= 1;
+        }
+        removed
+    }
+
+    /// Return the value of the exact given prefix.
+    #[must_use]
+    pub fn get(&self, prefix: &BitPrefix<N>) -> Option<&V> {
+        self.routes.search(prefix)
+    }
+
+    /// Find the longest prefix in the table that contains the given address.
+    ///
+    /// Returns `None` if the address does not have the width of the table, or if no prefix
+    /// contains it.
+    #[must_use]
+    pub fn longest_match(&self, address: &[u8]) -> Option<(BitPrefix<N>, &V)> {
+        self.counts
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, &count)| count > 0)
+            .find_map(|(len, _)| {
+                let len = u8::try_from(len).ok()?;
+                let prefix = BitPrefix::new(address, len).ok()?;
+                self.routes.search(&prefix).map(|value| (prefix, value))
+            })
+    }
+}
+
+/// Clears all bits after the first `len` bits.
+fn mask(bytes: &mut [u8], len: u8) {
+    let len = usize::from(len);
+    let (full, partial) = (len / 8, len % 8);
+    if full >= bytes.len() {
+        return;
+    }
+    if partial > 0 {
+        bytes[full] &= !(u8::MAX >> partial);
+        bytes[full + 1..].fill(0);
+    } else {
+        bytes[full..].fill(0);
+    }
+}
+
+/// Splits `address/len` into its parts.
+fn split_prefix(s: &str) -> Result<(&str, u8), PrefixError> {
+    let (address, len) = s.split_once('/').ok_or(PrefixError::Syntax)?;
+    let len = len.parse().map_err(|_| PrefixError::Syntax)?;
+    Ok((address, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::{Ipv4Prefix, Ipv6Prefix, PrefixError, RoutingTable};
+
+    fn v4(s: &str) -> Ipv4Prefix {
+        s.parse().expect("prefix must be valid")
+    }
+
+    #[test]
+    fn test_prefix_parse_and_mask() {
+        let prefix = v4("10.0.7.255/22");
+        assert_eq!(prefix.address(), [10, 0, 4, 0]);
+        assert_eq!(prefix.prefix_len(), 22);
+        assert_eq!(prefix.to_string(), "10.0.4.0/22");
+        assert!(prefix.contains(&[10, 0, 5, 1]));
+        assert!(!prefix.contains(&[10, 0, 8, 1]));
+        assert_eq!(v4("1.2.3.4/0").to_string(), "0.0.0.0/0");
+        assert_eq!(v4("1.2.3.4/32").to_string(), "1.2.3.4/32");
+        assert_eq!(
+            "1.2.3.4/33".parse::<Ipv4Prefix>(),
+            Err(PrefixError::PrefixLength)
+        );
+        assert_eq!("1.2.3.4".parse::<Ipv4Prefix>(), Err(PrefixError::Syntax));
+        assert_eq!(
+            Ipv4Prefix::new(&[1, 2, 3], 8),
+            Err(PrefixError::AddressWidth)
+        );
+        assert_eq!(
+            Ipv4Prefix::from((Ipv4Addr::new(192, 168, 1, 1), 16)),
+            v4("192.168.0.0/16")
+        );
+    }
+
+    fn lookup(
+        table: &RoutingTable<&'static str, 5>,
+        address: [u8; 4],
+    ) -> Option<(String, &'static str)> {
+        table
+            .longest_match(&address)
+            .map(|(prefix, value)| (prefix.to_string(), *value))
+    }
+
+    #[test]
+    fn test_longest_match_v4() {
+        let mut table = RoutingTable::<&str, 5>::new();
+        assert_eq!(table.longest_match(&[10, 0, 0, 1]), None);
+        table.insert(v4("0.0.0.0/0"), "default");
+        table.insert(v4("10.0.0.0/8"), "ten");
+        table.insert(v4("10.0.0.0/22"), "ten-22");
+        table.insert(v4("10.0.4.0/22"), "ten-4-22");
+        table.insert(v4("10.0.4.128/25"), "ten-4-128-25");
+
+        assert_eq!(
+            lookup(&table, [10, 0, 3, 255]),
+            Some(("10.0.0.0/22".into(), "ten-22"))
+        );
+        assert_eq!(
+            lookup(&table, [10, 0, 4, 1]),
+            Some(("10.0.4.0/22".into(), "ten-4-22"))
+        );
+        assert_eq!(
+            lookup(&table, [10, 0, 4, 200]),
+            Some(("10.0.4.128/25".into(), "ten-4-128-25"))
+        );
+        assert_eq!(
+            lookup(&table, [10, 0, 8, 0]),
+            Some(("10.0.0.0/8".into(), "ten"))
+        );
+        assert_eq!(
+            lookup(&table, [11, 0, 0, 0]),
+            Some(("0.0.0.0/0".into(), "default"))
+        );
+
+        assert_eq!(table.insert(v4("10.0.0.0/8"), "TEN"), Some("ten"));
+        assert_eq!(
+            lookup(&table, [10, 0, 8, 0]),
+            Some(("10.0.0.0/8".into(), "TEN"))
+        );
+        assert_eq!(table.remove(&v4("0.0.0.0/0")), Some("default"));
+        assert_eq!(table.remove(&v4("0.0.0.0/0")), None);
+        assert_eq!(lookup(&table, [11, 0, 0, 0]), None);
+        assert_eq!(table.get(&v4("10.0.4.0/22")), Some(&"ten-4-22"));
+        assert_eq!(table.longest_match(&[10, 0, 0]), None);
+    }
+
+    #[test]
+    fn test_longest_match_v6() {
+        let mut table = RoutingTable::<u32, 17>::new();
+        let prefix = |s: &str| s.parse::<Ipv6Prefix>().expect("prefix must be valid");
+        table.insert(prefix("2001:db8::/32"), 32);
+        table.insert(prefix("2001:db8:abcd::/47"), 47);
+        let address = "2001:db8:abcd:1::1"
+            .parse::<std::net::Ipv6Addr>()
+            .expect("address must be valid")
+            .octets();
+        let (matched, value) = table.longest_match(&address).expect("a prefix must match");
+        assert_eq!(matched.to_string(), "2001:db8:abcc::/47");
+        assert_eq!(*value, 47);
+        let address = "2001:db8:ffff::1"
+            .parse::<std::net::Ipv6Addr>()
+            .expect("address must be valid")
+            .octets();
+        assert_eq!(table.longest_match(&address).map(|(_, v)| *v), Some(32));
+    }
+}
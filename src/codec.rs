@@ -0,0 +1,305 @@
+//! String keys that are compared by a normalized form of their text.
+//!
+//! A [`Normalized`] key keeps the string it was created from, but its bytes in the tree are the
+//! string as encoded by a [`Codec`]. Strings that encode to the same bytes are treated as the same
+//! key, so a tree of normalized keys can be searched with any spelling of a key, while
+//! [`ART::search_key_value`] still returns the key as it was stored.
+//!
+//! The [`AsciiCaseFold`] codec ignores the case of ASCII letters. With the `unicode-normalization`
+//! feature, `Nfc` and `Nfkc` compare strings by their Unicode normalization forms.
+//!
+//! A codec also decides the order of the keys, because the tree orders keys by their bytes. With
+//! the `icu` feature, the `Collated` codec encodes strings into their collation sort keys, so that
+//! the keys are iterated in the order a locale expects rather than in the order of their UTF-8
+//! bytes. The locale is chosen by an implementation of `Collation`, such as `RootCollation` for the
+//! root locale. Sort keys change with the versions of CLDR and Unicode, so they should not be
+//! persisted across updates of ICU4X, and a persisted tree of collated keys has to be rebuilt from
+//! its strings after such an update.
+//!
+//! [`ART::search_key_value`]: crate::ART::search_key_value
+
+use std::{borrow::Borrow, marker::PhantomData};
+
+use crate::BytesComparable;
+
+/// A way to encode strings such that equivalent strings have the same bytes.
+pub trait Codec {
+    /// Append the encoded bytes of the string to the buffer.
+    fn encode(key: &str, buf: &mut Vec<u8>);
+}
+
+/// Encodes strings with every ASCII letter in lower case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AsciiCaseFold;
+
+impl Codec for AsciiCaseFold {
+    fn encode(key: &str, buf: &mut Vec<u8>) {
+        buf.extend(key.bytes().map(|b| b.to_ascii_lowercase()));
+    }
+}
+
+/// Encodes strings in Unicode Normalization Form C, which composes canonically equivalent
+/// characters.
+#[cfg(feature = "unicode-normalization")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Nfc;
+
+#[cfg(feature = "unicode-normalization")]
+impl Codec for Nfc {
+    fn encode(key: &str, buf: &mut Vec<u8>) {
+        use unicode_normalization::UnicodeNormalization;
+        let mut utf8 = [0; 4];
+        for c in key.nfc() {
+            buf.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+        }
+    }
+}
+
+/// Encodes strings in Unicode Normalization Form KC, which also folds compatibility characters
+/// such as ligatures and full-width letters.
+#[cfg(feature = "unicode-normalization")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Nfkc;
+
+#[cfg(feature = "unicode-normalization")]
+impl Codec for Nfkc {
+    fn encode(key: &str, buf: &mut Vec<u8>) {
+        use unicode_normalization::UnicodeNormalization;
+        let mut utf8 = [0; 4];
+        for c in key.nfkc() {
+            buf.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+        }
+    }
+}
+
+/// A locale whose collation orders the strings encoded by [`Collated`].
+#[cfg(feature = "icu")]
+pub trait Collation {
+    /// Return the collator of the locale, which is created once and kept for the lifetime of the
+    /// program.
+    ///
+    /// Strings that the collator considers equal get the same sort key, so they are the same key
+    /// of a tree. A collator with the identical strength keeps every distinct string apart.
+    fn collator() -> &'static icu_collator::CollatorBorrowed<'static>;
+}
+
+/// The collation of the root locale, which orders strings by the default rules of the Unicode
+/// Collation Algorithm with the tertiary strength.
+#[cfg(feature = "icu")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RootCollation;
+
+#[cfg(feature = "icu")]
+impl Collation for RootCollation {
+    fn collator() -> &'static icu_collator::CollatorBorrowed<'static> {
+        static COLLATOR: std::sync::OnceLock<icu_collator::CollatorBorrowed<'static>> =
+            std::sync::OnceLock::new();
+        COLLATOR.get_or_init(|| {
+            icu_collator::Collator::try_new(
+                icu_collator::CollatorPreferences::default(),
+                icu_collator::options::CollatorOptions::default(),
+            )
+            .expect("the root collation must be compiled into the crate")
+        })
+    }
+}
+
+/// Encodes strings into their collation sort keys in the locale of `L`.
+#[cfg(feature = "icu")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Collated<L>(PhantomData<L>);
+
+#[cfg(feature = "icu")]
+impl<L: Collation> Codec for Collated<L> {
+    fn encode(key: &str, buf: &mut Vec<u8>) {
+        let Ok(()) = L::collator().write_sort_key_to(key, buf);
+    }
+}
+
+/// A string key whose bytes are encoded by the codec `C`.
+pub struct Normalized<C, K = String> {
+    key: K,
+    encoded: Box<[u8]>,
+    codec: PhantomData<C>,
+}
+
+impl<C, K> Normalized<C, K>
+where
+    C: Codec,
+    K: AsRef<str>,
+{
+    /// Create a key from the given string, encoding it with the codec.
+    pub fn new(key: K) -> Self {
+        let mut encoded = Vec::new();
+        C::encode(key.as_ref(), &mut encoded);
+        Self {
+            key,
+            encoded: encoded.into_boxed_slice(),
+            codec: PhantomData,
+        }
+    }
+}
+
+impl<C, K> Normalized<C, K> {
+    /// Return the string the key was created from.
+    pub const fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Return the string the key was created from.
+    pub fn into_inner(self) -> K {
+        self.key
+    }
+
+    /// Return the encoded bytes of the key.
+    #[must_use]
+    pub const fn encoded(&self) -> &[u8] {
+        &self.encoded
+    }
+}
+
+impl<C, K> BytesComparable for Normalized<C, K> {
+    type Target<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        &self.encoded
+    }
+}
+
+impl<C, K> Borrow<[u8]> for Normalized<C, K> {
+    fn borrow(&self) -> &[u8] {
+        &self.encoded
+    }
+}
+
+impl<C, K: Clone> Clone for Normalized<C, K> {
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            encoded: self.encoded.clone(),
+            codec: PhantomData,
+        }
+    }
+}
+
+impl<C, K: std::fmt::Debug> std::fmt::Debug for Normalized<C, K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Normalized").field(&self.key).finish()
+    }
+}
+
+impl<C, K> PartialEq for Normalized<C, K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.encoded == other.encoded
+    }
+}
+
+impl<C, K> Eq for Normalized<C, K> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{AsciiCaseFold, Codec, Normalized};
+    use crate::ART;
+
+    type Folded = Normalized<AsciiCaseFold>;
+
+    #[test]
+    fn test_ascii_case_fold() {
+        let mut tree = ART::<Folded, u32>::new();
+        tree.insert(Folded::new("Hello".to_string()), 1);
+        tree.insert(Folded::new("WORLD".to_string()), 2);
+        assert_eq!(tree.search(&Folded::new("hello".to_string())), Some(&1));
+        let (key, value) = tree
+            .search_key_value(&Folded::new("world".to_string()))
+            .unwrap();
+        assert_eq!((key.key().as_str(), *value), ("WORLD", 2));
+
+        // Searching with the encoded bytes works as well.
+        assert_eq!(tree.search(b"world".as_slice()), Some(&2));
+
+        // A differently cased key replaces the value but keeps the stored key.
+        assert_eq!(tree.insert(Folded::new("HELLO".to_string()), 10), Some(1));
+        assert_eq!(tree.min().map(|(key, _)| key.key().as_str()), Some("Hello"));
+    }
+
+    #[test]
+    fn test_sort_key_codec() {
+        /// Orders strings alphabetically ignoring case, and puts lower case first between strings
+        /// that only differ in case, the way collation sort keys separate their levels.
+        struct Alphabetical;
+
+        impl Codec for Alphabetical {
+            fn encode(key: &str, buf: &mut Vec<u8>) {
+                buf.extend(key.bytes().map(|b| b.to_ascii_lowercase()));
+                buf.push(1);
+                buf.extend(key.bytes().map(|b| u8::from(b.is_ascii_uppercase()) + 1));
+            }
+        }
+
+        let tree: ART<Normalized<Alphabetical, &str>, ()> = ["b", "B", "a", "Ab", "aB", "A"]
+            .into_iter()
+            .map(|key| (Normalized::new(key), ()))
+            .collect();
+        let keys: Vec<_> = tree.iter().map(|(key, ())| *key.key()).collect();
+        assert_eq!(keys, ["a", "A", "aB", "Ab", "b", "B"]);
+    }
+
+    #[cfg(feature = "icu")]
+    #[test]
+    fn test_collation() {
+        use std::sync::OnceLock;
+
+        use icu_collator::{options::CollatorOptions, Collator, CollatorBorrowed};
+
+        use super::{Collated, Collation, RootCollation};
+
+        /// Swedish sorts the letters with diacritics after `z`.
+        struct Swedish;
+
+        impl Collation for Swedish {
+            fn collator() -> &'static CollatorBorrowed<'static> {
+                static COLLATOR: OnceLock<CollatorBorrowed<'static>> = OnceLock::new();
+                COLLATOR.get_or_init(|| {
+                    let locale = icu_locale_core::locale!("sv");
+                    Collator::try_new((&locale).into(), CollatorOptions::default()).unwrap()
+                })
+            }
+        }
+
+        fn sorted<L: Collation>(keys: &[&'static str]) -> Vec<&'static str> {
+            let tree: ART<Normalized<Collated<L>, &str>, ()> =
+                keys.iter().map(|key| (Normalized::new(*key), ())).collect();
+            tree.iter().map(|(key, ())| *key.key()).collect()
+        }
+
+        let keys = [
+            "zebra", "\u{f6}l", "Apple", "apple", "\u{e5}r", "banana", "oliv",
+        ];
+        assert_eq!(
+            sorted::<RootCollation>(&keys),
+            ["apple", "Apple", "\u{e5}r", "banana", "\u{f6}l", "oliv", "zebra"]
+        );
+        assert_eq!(
+            sorted::<Swedish>(&keys),
+            ["apple", "Apple", "banana", "oliv", "zebra", "\u{e5}r", "\u{f6}l"]
+        );
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn test_unicode_normalization() {
+        use super::{Nfc, Nfkc};
+
+        let mut tree = ART::<Normalized<Nfc, &str>, u32>::new();
+        tree.insert(Normalized::new("caf\u{e9}"), 1);
+        assert_eq!(tree.search(&Normalized::new("cafe\u{301}")), Some(&1));
+        assert_eq!(tree.search(&Normalized::new("\u{fb01}le")), None);
+
+        let mut tree = ART::<Normalized<Nfkc, &str>, u32>::new();
+        tree.insert(Normalized::new("file"), 1);
+        assert_eq!(tree.search(&Normalized::new("\u{fb01}le")), Some(&1));
+    }
+}
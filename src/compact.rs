@@ -0,0 +1,543 @@
+//! An immutable tree laid out for reading.
+//!
+//! A [`CompactArt`] is created once from an [`ART`] with [`ART::freeze`] and can only be queried
+//! afterwards. The entries are kept in a single slice in key order, and the inner nodes are encoded
+//! one after another into a single byte buffer in breadth-first order, so the top levels of the
+//! tree that every lookup goes through share a few cache lines. Nodes are not padded, and every
+//! node stores its complete prefix, such that a lookup never has to visit an entry to recover the
+//! bytes that were skipped.
+//!
+//! # Layout
+//!
+//! An inner node is encoded as follows, with all integers in little-endian.
+//!
+//! | Field    | Encoding                                                          |
+//! |----------|-------------------------------------------------------------------|
+//! | prefix   | `u32` length followed by the bytes shared by all keys in the node |
+//! | count    | `u16`, the number of children                                     |
+//! | keys     | `count` bytes, the sorted byte keys of the children               |
+//! | children | `count` times a `u32` reference and a `u32` rank                  |
+//!
+//! A reference is either `u32::MAX` for a child holding a single entry, or the offset of another
+//! inner node in the buffer. The rank of a child is the number of entries under its preceding
+//! siblings, so the index of an entry is the sum of the ranks along its path. Because nodes only
+//! store offsets relative to their own entries, a node does not depend on where its subtree ends up
+//! in the entries. Structurally identical subtrees are therefore encoded only once and shared by
+//! all their parents, which turns the tree into a directed acyclic graph similar to a minimized
+//! finite state transducer. Key sets with a regular structure, such as keys that end in dates,
+//! shrink considerably.
+
+use std::{borrow::Borrow, collections::HashMap, ops::Deref, sync::Arc};
+
+use crate::{BytesComparable, ART};
+
+/// The reference of a child that is a single entry.
+const LEAF: u32 = u32::MAX;
+
+/// An immutable adaptive radix tree optimized for lookups, see [`ART::freeze`].
+#[derive(Clone)]
+pub struct CompactArt<K, V> {
+    nodes: Box<[u8]>,
+    root: Option<u32>,
+    entries: Box<[(K, V)]>,
+}
+
+impl<K, V> CompactArt<K, V> {
+    /// Return the number of entries.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return true if the tree has no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return all entries in key order.
+    #[must_use]
+    pub const fn entries(&self) -> &[(K, V)] {
+        &self.entries
+    }
+
+    /// Consume the tree and return its entries in key order.
+    #[must_use]
+    pub fn into_entries(self) -> Vec<(K, V)> {
+        self.entries.into_vec()
+    }
+
+    /// Return the number of bytes used to encode the inner nodes.
+    #[must_use]
+    pub const fn node_bytes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Find the minimum key-value pair in the tree.
+    #[must_use]
+    pub fn min(&self) -> Option<(&K, &V)> {
+        self.entries.first().map(|(k, v)| (k, v))
+    }
+
+    /// Find the maximum key-value pair in the tree.
+    #[must_use]
+    pub fn max(&self) -> Option<(&K, &V)> {
+        self.entries.last().map(|(k, v)| (k, v))
+    }
+
+    fn read_u16(&self, offset: usize) -> usize {
+        usize::from(u16::from_le_bytes([
+            self.nodes[offset],
+            self.nodes[offset + 1],
+        ]))
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(&self.nodes[offset..offset + 4]);
+        u32::from_le_bytes(buf)
+    }
+}
+
+impl<K, V> CompactArt<K, V>
+where
+    K: BytesComparable,
+{
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let key = key.bytes();
+        self.index_of(key.as_ref()).map(|idx| &self.entries[idx].1)
+    }
+
+    /// Search for the entry with the given key, returning the stored key with its value.
+    pub fn search_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let key = key.bytes();
+        self.index_of(key.as_ref()).map(|idx| {
+            let (k, v) = &self.entries[idx];
+            (k, v)
+        })
+    }
+
+    /// Returns the index of the entry with the given key.
+    fn index_of(&self, key: &[u8]) -> Option<usize> {
+        let mut node = self.root?;
+        let mut depth = 0;
+        let mut rank = 0;
+        while node != LEAF {
+            let mut offset = node as usize;
+            let prefix_len = self.read_u32(offset) as usize;
+            offset += 4;
+            if key.get(depth..depth + prefix_len)? != &self.nodes[offset..offset + prefix_len] {
+                return None;
+            }
+            depth += prefix_len;
+            offset += prefix_len;
+            let count = self.read_u16(offset);
+            offset += 2;
+            let keys = &self.nodes[offset..offset + count];
+            let idx = keys.binary_search(&byte_at(key, depth)).ok()?;
+            let child = offset + count + idx * 8;
+            node = self.read_u32(child);
+            rank += self.read_u32(child + 4) as usize;
+            depth += 1;
+        }
+        let (k, _) = self.entries.get(rank)?;
+        (k.bytes().as_ref() == key).then_some(rank)
+    }
+}
+
+impl<K, V> CompactArt<K, V>
+where
+    K: BytesComparable,
+{
+    /// Lay out the given entries, which must be sorted by their encoded keys without duplicates.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`ART::freeze`].
+    pub(crate) fn from_sorted(entries: Box<[(K, V)]>) -> Self {
+        assert!(
+            u32::try_from(entries.len()).is_ok_and(|len| len != LEAF),
+            "a compact tree can hold at most u32::MAX - 1 entries"
+        );
+        let (nodes, root) = encode(&entries);
+        Self {
+            nodes: nodes.into_boxed_slice(),
+            root,
+            entries,
+        }
+    }
+}
+
+impl<K, V> std::fmt::Debug for CompactArt<K, V>
+where
+    K: std::fmt::Debug,
+    V: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.entries.iter().map(|(k, v)| (k, v)))
+            .finish()
+    }
+}
+
+/// A handle to a [`CompactArt`] shared behind an [`Arc`], see [`ART::freeze_shared`].
+///
+/// Cloning a snapshot only increments a reference count, so read-only copies of a tree can be
+/// handed to worker threads and asynchronous tasks without copying the entries. The snapshot
+/// dereferences to the frozen tree for queries.
+pub struct ArcSnapshot<K, V>(Arc<CompactArt<K, V>>);
+
+impl<K, V> Clone for ArcSnapshot<K, V> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<K, V> Deref for ArcSnapshot<K, V> {
+    type Target = CompactArt<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K, V> From<CompactArt<K, V>> for ArcSnapshot<K, V> {
+    fn from(tree: CompactArt<K, V>) -> Self {
+        Self(Arc::new(tree))
+    }
+}
+
+impl<K, V> std::fmt::Debug for ArcSnapshot<K, V>
+where
+    K: std::fmt::Debug,
+    V: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<K, V, const N: usize> ART<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Consume the tree and re-lay it into a [`CompactArt`], which can no longer be modified but
+    /// is faster to search and uses less memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tree has more than `u32::MAX` entries or if its nodes take more than
+    /// `u32::MAX` bytes.
+    #[must_use]
+    pub fn freeze(self) -> CompactArt<K, V> {
+        let mut leaves = Vec::new();
+        if let Some(root) = self.root {
+            root.into_leaves(&mut leaves);
+        }
+        let entries: Box<[(K, V)]> = leaves
+            .into_iter()
+            .map(|leaf| (leaf.key, leaf.value))
+            .collect();
+        CompactArt::from_sorted(entries)
+    }
+
+    /// Consume the tree and freeze it into an [`ArcSnapshot`] that can be cloned cheaply and
+    /// shared between threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`ART::freeze`].
+    #[must_use]
+    pub fn freeze_shared(self) -> ArcSnapshot<K, V> {
+        self.freeze().into()
+    }
+}
+
+/// An inner node before it is placed into the buffer.
+#[derive(PartialEq, Eq, Hash)]
+struct Draft {
+    prefix: Vec<u8>,
+    /// The byte key, the reference, and the rank of every child. References point to other drafts
+    /// until the nodes are laid out.
+    children: Vec<(u8, u32, u32)>,
+}
+
+impl Draft {
+    const fn encoded_len(&self) -> usize {
+        4 + self.prefix.len() + 2 + self.children.len() * 9
+    }
+}
+
+/// Collects the drafts of a tree, sharing drafts that are structurally identical.
+#[derive(Default)]
+struct Drafts {
+    ids: HashMap<Draft, u32>,
+}
+
+impl Drafts {
+    /// Drafts the node holding the given sorted entries, returning the id of its draft or [`LEAF`].
+    fn draft<K, V>(&mut self, entries: &[(K, V)], depth: usize) -> u32
+    where
+        K: BytesComparable,
+    {
+        if entries.len() == 1 {
+            return LEAF;
+        }
+        let first = entries[0].0.bytes();
+        let last = entries[entries.len() - 1].0.bytes();
+        let (first, last) = (first.as_ref(), last.as_ref());
+        // The entries are sorted, so the prefix shared by the first and the last entry is shared by
+        // all of them.
+        let prefix = &first[depth.min(first.len())..];
+        let prefix_len = prefix
+            .iter()
+            .zip(last.get(depth..).unwrap_or_default())
+            .take_while(|(x, y)| x == y)
+            .count();
+        let depth = depth + prefix_len;
+        let groups = group_by_byte(entries, depth);
+        let children = if groups.len() == 1 {
+            // Keys that only differ by trailing zero bytes can not be told apart by the tree, and
+            // end up in a group that can not be split. Only the first of them can be found.
+            vec![(groups[0].0, LEAF, 0)]
+        } else {
+            groups
+                .into_iter()
+                .map(|(byte_key, group)| {
+                    let child = self.draft(&entries[group.clone()], depth + 1);
+                    (byte_key, child, to_u32(group.start))
+                })
+                .collect()
+        };
+        let draft = Draft {
+            prefix: prefix[..prefix_len].to_vec(),
+            children,
+        };
+        // Identical drafts have identical subtrees, because the references of their children are
+        // already shared. Regular key sets, such as keys ending with dates, repeat the same
+        // subtrees under many prefixes.
+        let next_id = to_u32(self.ids.len());
+        *self.ids.entry(draft).or_insert(next_id)
+    }
+
+    /// Lays out the drafts reachable from the root in breadth-first order, returning the buffer.
+    fn layout(self, root: u32) -> Vec<u8> {
+        let mut drafts: Vec<Option<Draft>> = std::iter::repeat_with(|| None)
+            .take(self.ids.len())
+            .collect();
+        for (draft, id) in self.ids {
+            drafts[id as usize] = Some(draft);
+        }
+        let drafts: Vec<Draft> = drafts.into_iter().flatten().collect();
+        // Assign the offsets first, so every node can be written with the offsets of its children.
+        // A node is placed right after all nodes that were queued before it.
+        let mut offsets = vec![LEAF; drafts.len()];
+        let mut order = vec![root];
+        offsets[root as usize] = 0;
+        let mut len = drafts[root as usize].encoded_len();
+        let mut next = 0;
+        while let Some(&id) = order.get(next) {
+            next += 1;
+            for &(_, child, _) in &drafts[id as usize].children {
+                if child != LEAF && offsets[child as usize] == LEAF {
+                    offsets[child as usize] = to_u32(len);
+                    len += drafts[child as usize].encoded_len();
+                    order.push(child);
+                }
+            }
+        }
+        let mut nodes = Vec::with_capacity(len);
+        for id in order {
+            let draft = &drafts[id as usize];
+            nodes.extend_from_slice(&to_u32(draft.prefix.len()).to_le_bytes());
+            nodes.extend_from_slice(&draft.prefix);
+            let count =
+                u16::try_from(draft.children.len()).expect("a node has at most 256 children");
+            nodes.extend_from_slice(&count.to_le_bytes());
+            nodes.extend(draft.children.iter().map(|(byte_key, _, _)| byte_key));
+            for &(_, child, rank) in &draft.children {
+                let child = if child == LEAF {
+                    LEAF
+                } else {
+                    offsets[child as usize]
+                };
+                nodes.extend_from_slice(&child.to_le_bytes());
+                nodes.extend_from_slice(&rank.to_le_bytes());
+            }
+        }
+        nodes
+    }
+}
+
+/// Encodes the inner nodes of the tree holding the given sorted entries. Returns the buffer and the
+/// reference of the root.
+fn encode<K, V>(entries: &[(K, V)]) -> (Vec<u8>, Option<u32>)
+where
+    K: BytesComparable,
+{
+    if entries.is_empty() {
+        return (Vec::new(), None);
+    }
+    let mut drafts = Drafts::default();
+    match drafts.draft(entries, 0) {
+        LEAF => (Vec::new(), Some(LEAF)),
+        root => (drafts.layout(root), Some(0)),
+    }
+}
+
+/// Splits the sorted entries into ranges of entries that share the byte at the given depth.
+fn group_by_byte<K, V>(entries: &[(K, V)], depth: usize) -> Vec<(u8, std::ops::Range<usize>)>
+where
+    K: BytesComparable,
+{
+    let mut groups: Vec<(u8, std::ops::Range<usize>)> = Vec::new();
+    for (idx, (key, _)) in entries.iter().enumerate() {
+        let byte_key = byte_at(key.bytes().as_ref(), depth);
+        match groups.last_mut() {
+            Some((last, range)) if *last == byte_key => range.end = idx + 1,
+            _ => groups.push((byte_key, idx..idx + 1)),
+        }
+    }
+    groups
+}
+
+fn byte_at(bytes: &[u8], pos: usize) -> u8 {
+    bytes.get(pos).copied().unwrap_or(0)
+}
+
+fn to_u32(value: usize) -> u32 {
+    u32::try_from(value).expect("a compact tree must fit in u32::MAX bytes")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::Rng;
+
+    use crate::ART;
+
+    #[test]
+    fn test_freeze() {
+        let mut rng = rand::thread_rng();
+        let mut tree = ART::<String, u32>::new();
+        let mut expected = BTreeMap::new();
+        for _ in 0..5000 {
+            let key = format!(
+                "{}/{}",
+                rng.gen_range(0..20),
+                (0..rng.gen_range(0..12))
+                    .map(|_| char::from(rng.gen_range(b'a'..=b'f')))
+                    .collect::<String>()
+            );
+            let value = rng.gen();
+            tree.insert(key.clone(), value);
+            expected.insert(key, value);
+        }
+        let frozen = tree.freeze();
+        assert_eq!(frozen.len(), expected.len());
+        assert!(frozen
+            .entries()
+            .iter()
+            .map(|(k, v)| (k, v))
+            .eq(expected.iter()));
+        for (k, v) in &expected {
+            assert_eq!(frozen.search(k), Some(v));
+            let mut missing = k.clone();
+            missing.push('g');
+            assert_eq!(frozen.search(&missing), None);
+            missing.truncate(k.len().saturating_sub(1));
+            assert_eq!(frozen.search(&missing), expected.get(&missing));
+        }
+        assert_eq!(frozen.min(), expected.iter().next());
+        assert_eq!(frozen.max(), expected.iter().next_back());
+    }
+
+    #[test]
+    fn test_freeze_shared() {
+        let tree: ART<u32, u32> = (0..10_000).map(|i| (i, i * 2)).collect();
+        let snapshot = tree.freeze_shared();
+        std::thread::scope(|scope| {
+            for t in 0..4 {
+                let snapshot = snapshot.clone();
+                scope.spawn(move || {
+                    for i in (t..10_000).step_by(4) {
+                        assert_eq!(snapshot.search(&i), Some(&(i * 2)));
+                    }
+                });
+            }
+        });
+        assert_eq!(snapshot.len(), 10_000);
+        assert_eq!(snapshot.max(), Some((&9999, &19998)));
+    }
+
+    #[test]
+    fn test_freeze_shares_identical_subtrees() {
+        let mut tree = ART::<String, usize>::new();
+        let mut keys = Vec::new();
+        for year in 2000..2010 {
+            for month in 1..=12 {
+                for day in 1..=28 {
+                    keys.push(format!("{year}-{month:02}-{day:02}"));
+                }
+            }
+        }
+        for (idx, key) in keys.iter().enumerate() {
+            tree.insert(key.clone(), idx);
+        }
+        let frozen = tree.freeze();
+        for (idx, key) in keys.iter().enumerate() {
+            assert_eq!(frozen.search(key), Some(&idx));
+        }
+        assert_eq!(frozen.search("2000-01-29"), None);
+        assert_eq!(frozen.search("2000-13-01"), None);
+        // The days of every month share a single subtree, and the months of every year as well.
+        // Without sharing, every one of the 1200 months would have its own subtree for the days.
+        assert!(frozen.node_bytes() < 1000, "{}", frozen.node_bytes());
+
+        let mut tree = ART::<&str, u32>::new();
+        for (idx, key) in ["a1", "a2", "b1", "b2"].into_iter().enumerate() {
+            tree.insert(key, u32::try_from(idx).unwrap());
+        }
+        let frozen = tree.freeze();
+        // The root and a single node shared by "a" and "b", each without prefix and with two
+        // children.
+        assert_eq!(frozen.node_bytes(), 2 * (4 + 2 + 2 * 9));
+        assert_eq!(frozen.search(&"b1"), Some(&2));
+        assert_eq!(frozen.search(&"b2"), Some(&3));
+    }
+
+    #[test]
+    fn test_freeze_small() {
+        let frozen = ART::<u32, u32>::new().freeze();
+        assert!(frozen.is_empty());
+        assert_eq!(frozen.search(&0), None);
+        assert_eq!(frozen.node_bytes(), 0);
+
+        let mut tree = ART::<u32, u32>::new();
+        tree.insert(7, 70);
+        let frozen = tree.freeze();
+        assert_eq!(frozen.search(&7), Some(&70));
+        assert_eq!(frozen.search(&8), None);
+        assert_eq!(frozen.node_bytes(), 0);
+
+        let mut tree = ART::<u32, u32>::new();
+        tree.insert(0x0102_0304, 1);
+        tree.insert(0x0102_0305, 2);
+        let frozen = tree.freeze();
+        assert_eq!(format!("{frozen:?}"), "{16909060: 1, 16909061: 2}");
+        // A prefix of 3 bytes and two children.
+        assert_eq!(frozen.node_bytes(), 4 + 3 + 2 + 2 + 2 * 8);
+        assert_eq!(frozen.search(&0x0102_0305), Some(&2));
+        assert_eq!(frozen.search(&0x0102_0306), None);
+    }
+}
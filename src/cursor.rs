@@ -0,0 +1,682 @@
+//! Cursors that move through a tree in key order.
+//!
+//! A cursor points at an entry of the tree or at a position past the end, which sits between the
+//! last and the first entry. Moving forward from past the end goes to the first entry, and moving
+//! backward goes to the last entry.
+//!
+//! A [`Cursor`] keeps the path from the root to its entry, so moving to a neighbouring entry takes
+//! amortized constant time. A [`CursorMut`] can also insert entries next to its position and remove
+//! the entry at its position. Any of these changes can restructure the nodes on its path, so it
+//! remembers the key of its entry when it changes the tree, and walks down from the root to find
+//! the path again on its next operation.
+//!
+//! Moving a [`CursorMut`] takes amortized constant time only with the `unsafe_opt` feature, which
+//! lets it keep its path between moves, see the [crate documentation](crate#unsafe-code). In the
+//! default build, it can not keep references into the tree it borrows mutably, so every operation,
+//! moves included, walks down from the root to the key of its entry and takes time proportional to
+//! the height of the tree. The key is kept in a buffer that is reused between operations.
+//!
+//! The [`Position`] of a cursor is made of the encoded key of its entry and the [`Direction`] in
+//! which it is being moved. It can be encoded into bytes, so that a long-running consumer can
+//! persist its progress and continue from the same place after a restart, even when the tree was
+//! modified in between.
+
+use std::borrow::Borrow;
+
+use crate::{
+    node::{Owned, Path},
+    BytesComparable, ART,
+};
+
+/// An error returned when inserting a key through a [`CursorMut`] would not keep the key next to
+/// the position of the cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnorderedKeyError;
+
+impl std::fmt::Display for UnorderedKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "key is not ordered between the neighbours of the cursor")
+    }
+}
+
+impl std::error::Error for UnorderedKeyError {}
+
+/// The direction in which a cursor is moved through a tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// From smaller to greater keys, using [`Cursor::move_next`].
+    Forward,
+    /// From greater to smaller keys, using [`Cursor::move_prev`].
+    Backward,
+}
+
+/// The position of a [`Cursor`] that can be restored later, see [`Cursor::position`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    /// The encoded key of the entry at the position, or `None` past the end.
+    key: Option<Vec<u8>>,
+    direction: Direction,
+}
+
+/// An error returned when decoding bytes that were not produced by [`Position::to_bytes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidPositionError;
+
+impl std::fmt::Display for InvalidPositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cursor position is truncated or contains an unknown tag")
+    }
+}
+
+impl std::error::Error for InvalidPositionError {}
+
+impl Position {
+    /// Return the encoded key of the entry at the position, or `None` if the position is past the
+    /// end.
+    #[must_use]
+    pub fn key(&self) -> Option<&[u8]> {
+        self.key.as_deref()
+    }
+
+    /// Return the direction in which the cursor was moved.
+    #[must_use]
+    pub const fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Encode the position into bytes: a tag byte for the direction, a tag byte telling whether
+    /// the position is at an entry, and for an entry the length of its key as a big-endian `u32`
+    /// followed by the key itself.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is longer than `u32::MAX` bytes.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![match self.direction {
+            Direction::Forward => 0,
+            Direction::Backward => 1,
+        }];
+        match &self.key {
+            None => bytes.push(0),
+            Some(key) => {
+                bytes.push(1);
+                let len = u32::try_from(key.len()).expect("the key must be shorter than 4 GiB");
+                bytes.extend_from_slice(&len.to_be_bytes());
+                bytes.extend_from_slice(key);
+            }
+        }
+        bytes
+    }
+
+    /// Decode a position from the bytes produced by [`Position::to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes are truncated, have trailing bytes, or contain an unknown
+    /// tag.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidPositionError> {
+        let [direction, tag, rest @ ..] = bytes else {
+            return Err(InvalidPositionError);
+        };
+        let direction = match direction {
+            0 => Direction::Forward,
+            1 => Direction::Backward,
+            _ => return Err(InvalidPositionError),
+        };
+        let key = match (tag, rest) {
+            (0, []) => None,
+            (1, rest) => {
+                let (len, key) = rest.split_first_chunk::<4>().ok_or(InvalidPositionError)?;
+                let len =
+                    usize::try_from(u32::from_be_bytes(*len)).map_err(|_| InvalidPositionError)?;
+                if key.len() != len {
+                    return Err(InvalidPositionError);
+                }
+                Some(key.to_vec())
+            }
+            _ => return Err(InvalidPositionError),
+        };
+        Ok(Self { key, direction })
+    }
+}
+
+/// A cursor over the entries of a tree, see the [module documentation](self).
+pub struct Cursor<'a, K, V, const N: usize = 10> {
+    path: Path<'a, K, V, N, Owned>,
+}
+
+impl<'a, K, V, const N: usize> Cursor<'a, K, V, N> {
+    /// Return the key at the position of the cursor.
+    #[must_use]
+    pub fn key(&self) -> Option<&'a K> {
+        self.path.leaf().map(|leaf| &leaf.key)
+    }
+
+    /// Return the value at the position of the cursor.
+    #[must_use]
+    pub fn value(&self) -> Option<&'a V> {
+        self.path.leaf().map(|leaf| &leaf.value)
+    }
+
+    /// Return the key-value pair at the position of the cursor.
+    #[must_use]
+    pub fn key_value(&self) -> Option<(&'a K, &'a V)> {
+        self.path.leaf().map(|leaf| (&leaf.key, &leaf.value))
+    }
+
+    /// Move the cursor to the next entry.
+    pub fn move_next(&mut self) {
+        self.path.next();
+    }
+
+    /// Move the cursor to the previous entry.
+    pub fn move_prev(&mut self) {
+        self.path.prev();
+    }
+}
+
+impl<K, V, const N: usize> Cursor<'_, K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Return the position of the cursor for a consumer moving it in the given direction, which
+    /// can be restored with [`ART::cursor_from`].
+    #[must_use]
+    pub fn position(&self, direction: Direction) -> Position {
+        Position {
+            key: self.key().map(key_bytes),
+            direction,
+        }
+    }
+}
+
+impl<K, V, const N: usize> std::fmt::Debug for Cursor<'_, K, V, N>
+where
+    K: std::fmt::Debug,
+    V: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Cursor").field(&self.key_value()).finish()
+    }
+}
+
+impl<K, V, const N: usize> ART<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Return a cursor at the minimum entry of the tree.
+    #[must_use]
+    pub fn cursor_front(&self) -> Cursor<'_, K, V, N> {
+        let mut path = Path::new(self.root.as_ref());
+        path.seek_first();
+        Cursor { path }
+    }
+
+    /// Return a cursor at the maximum entry of the tree.
+    #[must_use]
+    pub fn cursor_back(&self) -> Cursor<'_, K, V, N> {
+        let mut path = Path::new(self.root.as_ref());
+        path.seek_last();
+        Cursor { path }
+    }
+
+    /// Return a cursor at the minimum entry whose key is greater than or equal to the given key.
+    pub fn cursor_at<Q>(&self, key: &Q) -> Cursor<'_, K, V, N>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let mut path = Path::new(self.root.as_ref());
+        path.seek(key.bytes().as_ref());
+        Cursor { path }
+    }
+
+    /// Return a cursor at the given position.
+    ///
+    /// If the entry of the position was removed since the position was taken, the cursor is placed
+    /// at the entry that moving in the direction of the position would have reached next, which is
+    /// the minimum entry with a greater key when moving forward, and the maximum entry with a
+    /// smaller key when moving backward.
+    #[must_use]
+    pub fn cursor_from(&self, position: &Position) -> Cursor<'_, K, V, N> {
+        let mut path = Path::new(self.root.as_ref());
+        if let Some(key) = &position.key {
+            path.seek(key);
+            let found = path
+                .leaf()
+                .is_some_and(|leaf| leaf.key.bytes().as_ref() == key.as_slice());
+            if position.direction == Direction::Backward && !found {
+                path.prev();
+            }
+        }
+        Cursor { path }
+    }
+
+    /// Return a mutable cursor at the minimum entry whose key is greater than or equal to the given
+    /// key.
+    pub fn cursor_mut_at<Q>(&mut self, key: &Q) -> CursorMut<'_, K, V, N>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let current = self.cursor_at(key).key().map(key_bytes);
+        CursorMut::new(self, current)
+    }
+
+    /// Return a mutable cursor at the minimum entry of the tree.
+    pub fn cursor_mut_front(&mut self) -> CursorMut<'_, K, V, N> {
+        let current = self.cursor_front().key().map(key_bytes);
+        CursorMut::new(self, current)
+    }
+}
+
+/// A cursor that can modify the tree around its position, see the
+/// [module documentation](self).
+pub struct CursorMut<'a, K, V, const N: usize = 10> {
+    tree: &'a mut ART<K, V, N>,
+    /// The encoded key of the entry at the position, or `None` past the end. It is out of date
+    /// while the cursor keeps a path.
+    current: Option<Vec<u8>>,
+    /// The path to the entry at the position, kept between moves until the tree is changed.
+    #[cfg(feature = "unsafe_opt")]
+    path: Option<Path<'a, K, V, N, Owned>>,
+}
+
+impl<K, V, const N: usize> std::fmt::Debug for CursorMut<'_, K, V, N>
+where
+    K: BytesComparable + std::fmt::Debug,
+    V: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("CursorMut").field(&self.key_value()).finish()
+    }
+}
+
+impl<'a, K, V, const N: usize> CursorMut<'a, K, V, N>
+where
+    K: BytesComparable,
+{
+    const fn new(tree: &'a mut ART<K, V, N>, current: Option<Vec<u8>>) -> Self {
+        Self {
+            tree,
+            current,
+            #[cfg(feature = "unsafe_opt")]
+            path: None,
+        }
+    }
+
+    /// Return a path at the encoded key of the position of the cursor.
+    fn seek_current(&self) -> Path<'_, K, V, N, Owned> {
+        let mut path = Path::new(self.tree.root.as_ref());
+        if let Some(current) = &self.current {
+            path.seek(current);
+        }
+        path
+    }
+
+    /// Call the closure on the path at the position of the cursor, which it can move.
+    ///
+    /// With the `unsafe_opt` feature, the path is kept until the tree is changed, so a move only
+    /// walks between neighbouring leaves. Without it, the path is found from the root, and the key
+    /// of the entry it was moved to is remembered instead.
+    #[cfg(feature = "unsafe_opt")]
+    fn with_path<R>(&mut self, f: impl FnOnce(&mut Path<'_, K, V, N, Owned>) -> R) -> R {
+        let path = self.path.get_or_insert_with(|| {
+            // SAFETY: The tree is borrowed mutably by the cursor for `'a`, so its nodes can only
+            // be changed or dropped through the cursor, and the cursor drops the path before it
+            // changes the tree, see `CursorMut::invalidate`.
+            let root: &'a Option<_> = unsafe { &*std::ptr::addr_of!(self.tree.root) };
+            let mut path = Path::new(root.as_ref());
+            if let Some(current) = &self.current {
+                path.seek(current);
+            }
+            path
+        });
+        f(path)
+    }
+
+    #[cfg(not(feature = "unsafe_opt"))]
+    fn with_path<R>(&mut self, f: impl FnOnce(&mut Path<'_, K, V, N, Owned>) -> R) -> R {
+        let mut path = Path::new(self.tree.root.as_ref());
+        if let Some(current) = &self.current {
+            path.seek(current);
+        }
+        let result = f(&mut path);
+        remember(&mut self.current, path.leaf().map(|leaf| &leaf.key));
+        result
+    }
+
+    /// Drop the path kept by the cursor before the tree is changed, remembering the key of its
+    /// entry to find the path again afterwards.
+    #[cfg(feature = "unsafe_opt")]
+    fn invalidate(&mut self) {
+        if let Some(path) = self.path.take() {
+            remember(&mut self.current, path.leaf().map(|leaf| &leaf.key));
+        }
+    }
+
+    /// Return the key at the position of the cursor.
+    #[must_use]
+    pub fn key(&self) -> Option<&K> {
+        self.key_value().map(|(k, _)| k)
+    }
+
+    /// Return the value at the position of the cursor.
+    #[must_use]
+    pub fn value(&self) -> Option<&V> {
+        self.key_value().map(|(_, v)| v)
+    }
+
+    /// Return the key-value pair at the position of the cursor.
+    #[must_use]
+    pub fn key_value(&self) -> Option<(&K, &V)> {
+        #[cfg(feature = "unsafe_opt")]
+        if let Some(path) = &self.path {
+            return path.leaf().map(|leaf| (&leaf.key, &leaf.value));
+        }
+        self.seek_current()
+            .leaf()
+            .map(|leaf| (&leaf.key, &leaf.value))
+    }
+
+    /// Return the value at the position of the cursor for modification.
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        #[cfg(feature = "unsafe_opt")]
+        self.invalidate();
+        let current = self.current.as_ref()?;
+        let root = self.tree.root.as_mut()?;
+        root.search_mut(current, 0).map(|leaf| &mut leaf.value)
+    }
+
+    /// Move the cursor to the next entry. This takes amortized constant time with the `unsafe_opt`
+    /// feature, and time proportional to the height of the tree without it.
+    #[allow(clippy::redundant_closure_for_method_calls)] // `Path::next` is not higher-ranked.
+    pub fn move_next(&mut self) {
+        self.with_path(|path| path.next());
+    }
+
+    /// Move the cursor to the previous entry. This takes amortized constant time with the
+    /// `unsafe_opt` feature, and time proportional to the height of the tree without it.
+    #[allow(clippy::redundant_closure_for_method_calls)] // `Path::prev` is not higher-ranked.
+    pub fn move_prev(&mut self) {
+        self.with_path(|path| path.prev());
+    }
+
+    /// Insert an entry right after the position of the cursor, without moving the cursor. When the
+    /// cursor is past the end, the entry is inserted at the front.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is not greater than the key at the position, or not less than
+    /// the key of the next entry.
+    pub fn insert_after(&mut self, key: K, value: V) -> Result<(), UnorderedKeyError> {
+        let ordered = {
+            let bytes = key.bytes();
+            let bytes = bytes.as_ref();
+            self.with_path(|path| {
+                let above = path
+                    .leaf()
+                    .is_none_or(|leaf| leaf.key.bytes().as_ref() < bytes);
+                path.next();
+                let below = path
+                    .leaf()
+                    .is_none_or(|leaf| bytes < leaf.key.bytes().as_ref());
+                path.prev();
+                above && below
+            })
+        };
+        self.insert_if(ordered, key, value)
+    }
+
+    /// Insert an entry right before the position of the cursor, without moving the cursor. When the
+    /// cursor is past the end, the entry is inserted at the back.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is not less than the key at the position, or not greater than
+    /// the key of the previous entry.
+    pub fn insert_before(&mut self, key: K, value: V) -> Result<(), UnorderedKeyError> {
+        let ordered = {
+            let bytes = key.bytes();
+            let bytes = bytes.as_ref();
+            self.with_path(|path| {
+                let below = path
+                    .leaf()
+                    .is_none_or(|leaf| bytes < leaf.key.bytes().as_ref());
+                path.prev();
+                let above = path
+                    .leaf()
+                    .is_none_or(|leaf| leaf.key.bytes().as_ref() < bytes);
+                path.next();
+                above && below
+            })
+        };
+        self.insert_if(ordered, key, value)
+    }
+
+    /// Inserts the entry if its key is ordered between the neighbours of the cursor.
+    fn insert_if(&mut self, ordered: bool, key: K, value: V) -> Result<(), UnorderedKeyError> {
+        if !ordered {
+            return Err(UnorderedKeyError);
+        }
+        #[cfg(feature = "unsafe_opt")]
+        self.invalidate();
+        self.tree.insert(key, value);
+        Ok(())
+    }
+
+    /// Remove the entry at the position of the cursor and move the cursor to the next entry.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        let current = self.with_path(|path| {
+            let current = path.leaf().map(|leaf| key_bytes(&leaf.key))?;
+            path.next();
+            Some(current)
+        })?;
+        #[cfg(feature = "unsafe_opt")]
+        self.invalidate();
+        self.tree
+            .delete_leaf(&current)
+            .map(|leaf| (leaf.key, leaf.value))
+    }
+}
+
+fn key_bytes<K: BytesComparable>(key: &K) -> Vec<u8> {
+    key.bytes().as_ref().to_vec()
+}
+
+/// Stores the encoded key in the buffer of a mutable cursor, reusing its allocation.
+fn remember<K: BytesComparable>(current: &mut Option<Vec<u8>>, key: Option<&K>) {
+    match (current.as_mut(), key) {
+        (Some(buf), Some(key)) => {
+            buf.clear();
+            buf.extend_from_slice(key.bytes().as_ref());
+        }
+        (_, key) => *current = key.map(key_bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::Rng;
+
+    use super::{Direction, InvalidPositionError, Position, UnorderedKeyError};
+    use crate::ART;
+
+    #[test]
+    fn test_cursor_moves_in_order() {
+        let mut rng = rand::thread_rng();
+        let mut tree = ART::<Vec<u8>, u32>::new();
+        let mut expected = BTreeMap::new();
+        for _ in 0..3000 {
+            let len = rng.gen_range(0..20);
+            let key: Vec<u8> = (0..len).map(|_| rng.gen_range(1..=8)).collect();
+            let value = rng.gen();
+            tree.insert(key.clone(), value);
+            expected.insert(key, value);
+        }
+        let mut cursor = tree.cursor_front();
+        for (k, v) in &expected {
+            assert_eq!(cursor.key_value(), Some((k, v)));
+            cursor.move_next();
+        }
+        assert_eq!(cursor.key(), None);
+        cursor.move_prev();
+        for (k, v) in expected.iter().rev() {
+            assert_eq!(cursor.key_value(), Some((k, v)));
+            cursor.move_prev();
+        }
+        assert_eq!(cursor.key(), None);
+        cursor.move_next();
+        assert_eq!(cursor.key(), expected.keys().next());
+        assert_eq!(tree.cursor_back().key(), expected.keys().next_back());
+
+        for _ in 0..1000 {
+            let len = rng.gen_range(0..20);
+            let target: Vec<u8> = (0..len).map(|_| rng.gen_range(1..=8)).collect();
+            assert_eq!(
+                tree.cursor_at(&target).key_value(),
+                expected.range(target.clone()..).next(),
+                "{target:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_cursor_position() {
+        let mut tree = ART::<u32, u32>::new();
+        for i in 0..100 {
+            tree.insert(i * 2, i);
+        }
+        let mut cursor = tree.cursor_at(&40);
+        cursor.move_next();
+        let forward =
+            Position::from_bytes(&cursor.position(Direction::Forward).to_bytes()).unwrap();
+        let backward = cursor.position(Direction::Backward);
+        assert_eq!(forward.key(), Some(42u32.to_be_bytes().as_slice()));
+        assert_eq!(tree.cursor_from(&forward).key(), Some(&42));
+        assert_eq!(tree.cursor_from(&backward).key(), Some(&42));
+
+        tree.delete(&42);
+        assert_eq!(tree.cursor_from(&forward).key(), Some(&44));
+        assert_eq!(tree.cursor_from(&backward).key(), Some(&40));
+
+        let mut cursor = tree.cursor_back();
+        cursor.move_next();
+        let end = cursor.position(Direction::Backward);
+        assert_eq!(Position::from_bytes(&end.to_bytes()), Ok(end.clone()));
+        assert_eq!(tree.cursor_from(&end).key(), None);
+
+        // Positions beyond the entries of the tree fall back to the entries next to them.
+        let last = tree.cursor_back().position(Direction::Backward);
+        tree.delete(&198);
+        assert_eq!(tree.cursor_from(&last).key(), Some(&196));
+        let first = tree.cursor_front().position(Direction::Forward);
+        tree.delete(&0);
+        assert_eq!(tree.cursor_from(&first).key(), Some(&2));
+
+        assert_eq!(Position::from_bytes(&[0]), Err(InvalidPositionError));
+        assert_eq!(Position::from_bytes(&[2, 0]), Err(InvalidPositionError));
+        assert_eq!(Position::from_bytes(&[0, 0, 0]), Err(InvalidPositionError));
+        assert_eq!(
+            Position::from_bytes(&[0, 1, 0, 0, 0, 2, 0]),
+            Err(InvalidPositionError)
+        );
+    }
+
+    #[test]
+    fn test_cursor_long_prefixes() {
+        let mut tree = ART::<String, usize, 2>::new();
+        let keys = ["aaaaaaaa1", "aaaaaaaa2", "aaaaaaab", "aaab", "b"];
+        for (idx, key) in keys.iter().enumerate() {
+            tree.insert((*key).to_string(), idx);
+        }
+        let lower_bound = |target: &str| tree.cursor_at(target).key().cloned();
+        assert_eq!(lower_bound(""), Some("aaaaaaaa1".to_string()));
+        assert_eq!(lower_bound("aaaaaaaa"), Some("aaaaaaaa1".to_string()));
+        assert_eq!(lower_bound("aaaaaaaa3"), Some("aaaaaaab".to_string()));
+        assert_eq!(lower_bound("aaaaaaa9"), Some("aaaaaaaa1".to_string()));
+        assert_eq!(lower_bound("aaaaaaa~"), Some("aaab".to_string()));
+        assert_eq!(lower_bound("aaaaaaac"), Some("aaab".to_string()));
+        assert_eq!(lower_bound("ab"), Some("b".to_string()));
+        assert_eq!(lower_bound("c"), None);
+    }
+
+    #[test]
+    fn test_cursor_mut() {
+        let mut tree = ART::<u32, u32>::new();
+        for key in (0..100).step_by(10) {
+            tree.insert(key, key);
+        }
+        let mut cursor = tree.cursor_mut_at(&25);
+        assert_eq!(cursor.key(), Some(&30));
+        assert_eq!(cursor.insert_before(25, 25), Ok(()));
+        assert_eq!(cursor.insert_before(20, 20), Err(UnorderedKeyError));
+        assert_eq!(cursor.insert_after(35, 35), Ok(()));
+        assert_eq!(cursor.insert_after(40, 40), Err(UnorderedKeyError));
+        assert_eq!(cursor.key(), Some(&30));
+        *cursor.value_mut().unwrap() = 300;
+        cursor.move_prev();
+        assert_eq!(cursor.key(), Some(&25));
+        assert_eq!(cursor.remove_current(), Some((25, 25)));
+        assert_eq!(cursor.key_value(), Some((&30, &300)));
+        assert_eq!(cursor.remove_current(), Some((30, 300)));
+        assert_eq!(cursor.remove_current(), Some((35, 35)));
+        assert_eq!(cursor.key(), Some(&40));
+
+        let mut cursor = tree.cursor_mut_at(&100);
+        assert_eq!(cursor.key(), None);
+        assert_eq!(cursor.insert_before(95, 95), Ok(()));
+        assert_eq!(cursor.insert_after(0, 0), Err(UnorderedKeyError));
+        cursor.move_next();
+        assert_eq!(cursor.key(), Some(&0));
+
+        let mut keys = Vec::new();
+        let mut cursor = tree.cursor_front();
+        while let Some(key) = cursor.key() {
+            keys.push(*key);
+            cursor.move_next();
+        }
+        assert_eq!(keys, [0, 10, 20, 40, 50, 60, 70, 80, 90, 95]);
+    }
+
+    #[test]
+    fn test_cursor_mut_walk() {
+        let mut rng = rand::thread_rng();
+        let mut tree = ART::<u32, u32>::new();
+        let mut expected = BTreeMap::new();
+        for _ in 0..2000 {
+            let key = rng.gen_range(0..10_000) * 2;
+            tree.insert(key, key);
+            expected.insert(key, key);
+        }
+        // Walk forward, removing some entries and inserting others in between, which invalidates
+        // the path kept by the cursor.
+        let mut cursor = tree.cursor_mut_front();
+        while let Some(&key) = cursor.key() {
+            match rng.gen_range(0..4) {
+                0 => {
+                    assert_eq!(cursor.remove_current(), Some((key, key)));
+                    expected.remove(&key);
+                    continue;
+                }
+                1 => {
+                    assert_eq!(cursor.insert_after(key + 1, key + 1), Ok(()));
+                    expected.insert(key + 1, key + 1);
+                    cursor.move_next();
+                }
+                _ => {}
+            }
+            cursor.move_next();
+        }
+        cursor.move_prev();
+        for (k, v) in expected.iter().rev() {
+            assert_eq!(cursor.key_value(), Some((k, v)));
+            cursor.move_prev();
+        }
+        assert_eq!(cursor.key(), None);
+        assert!(tree.iter().eq(expected.iter()));
+    }
+}
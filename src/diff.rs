@@ -0,0 +1,536 @@
+//! Symmetric differences of two trees.
+//!
+//! Replicas that synchronize by anti-entropy repeatedly compare their trees to find the entries
+//! that only one of them holds. [`ART::symmetric_difference`] finds these entries in a single
+//! descent and yields them in key order, tagged with the tree they come from, where a key held by
+//! both trees with different values yields the entries of both. The trees are descended together:
+//! where both branch on the same bytes, only the children under the same byte key are compared
+//! with each other, and a subtree that only one tree has is yielded without comparing it to
+//! anything. Where one tree has a leaf and the other a subtree, the leaf is compared against the
+//! largest key of each child of the subtree to find the only child it has to be merged into.
+//!
+//! The descent is lazy. The iterator keeps the work left on a stack and runs it only until the
+//! next differing entry is found, so taking the first few differences costs only the descent to
+//! them, and the memory used is bounded by the children of the nodes along one path rather than
+//! by the number of differences. The number of differences is not known until the descent ends.
+//!
+//! The versions of a [`SwmrArt`](crate::swmr::SwmrArt) share every node that the mutations
+//! between them did not copy. [`Snapshot::symmetric_difference`] skips a subtree as soon as both
+//! versions share it, so the cost of comparing two versions grows with the changes between them
+//! rather than with their size.
+
+use std::{cmp::Ordering, iter::FusedIterator};
+
+use crate::{
+    node::{Inner, Leaf, Node, NodeStore, Owned, Shared},
+    swmr::Snapshot,
+    BytesComparable, ART,
+};
+
+/// An entry held by only one of two trees, see [`ART::symmetric_difference`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Difference<'a, K, V> {
+    /// An entry of the first tree, whose key the second tree lacks or holds with another value.
+    Left(&'a K, &'a V),
+    /// An entry of the second tree, whose key the first tree lacks or holds with another value.
+    Right(&'a K, &'a V),
+}
+
+impl<'a, K, V> Difference<'a, K, V> {
+    const fn new(left: bool, leaf: &'a Leaf<K, V>) -> Self {
+        if left {
+            Self::Left(&leaf.key, &leaf.value)
+        } else {
+            Self::Right(&leaf.key, &leaf.value)
+        }
+    }
+
+    /// Return the key of the entry.
+    #[must_use]
+    pub const fn key(&self) -> &'a K {
+        match self {
+            Self::Left(key, _) | Self::Right(key, _) => key,
+        }
+    }
+
+    /// Return the value of the entry.
+    #[must_use]
+    pub const fn value(&self) -> &'a V {
+        match self {
+            Self::Left(_, value) | Self::Right(_, value) => value,
+        }
+    }
+
+    /// Return true if the entry comes from the first tree.
+    #[must_use]
+    pub const fn is_left(&self) -> bool {
+        matches!(self, Self::Left(..))
+    }
+}
+
+impl<K, V> Clone for Difference<'_, K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V> Copy for Difference<'_, K, V> {}
+
+/// An iterator over the entries held by only one of two trees in key order, see the
+/// [module documentation](self).
+pub struct SymmetricDifference<
+    'a,
+    K,
+    V,
+    const N: usize = 10,
+    const C4: usize = 4,
+    const C16: usize = 16,
+    const C48: usize = 48,
+> {
+    descent: Descent<'a, K, V, N, Owned, C4, C16, C48>,
+}
+
+impl<'a, K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> Iterator
+    for SymmetricDifference<'a, K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+    V: PartialEq,
+{
+    type Item = Difference<'a, K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.descent.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.descent.size_hint()
+    }
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> FusedIterator
+    for SymmetricDifference<'_, K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+    V: PartialEq,
+{
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> std::fmt::Debug
+    for SymmetricDifference<'_, K, V, N, C4, C16, C48>
+where
+    K: std::fmt::Debug,
+    V: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SymmetricDifference")
+            .field(&self.descent.entries.last())
+            .finish()
+    }
+}
+
+/// An iterator over the entries held by only one of two versions of a
+/// [`SwmrArt`](crate::swmr::SwmrArt) in key order, see [`Snapshot::symmetric_difference`].
+pub struct SnapshotDifference<'a, K, V, const N: usize = 10>
+where
+    K: Clone,
+    V: Clone,
+{
+    descent: Descent<'a, K, V, N, Shared, 4, 16, 48>,
+}
+
+impl<'a, K, V, const N: usize> Iterator for SnapshotDifference<'a, K, V, N>
+where
+    K: BytesComparable + Clone,
+    V: PartialEq + Clone,
+{
+    type Item = Difference<'a, K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.descent.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.descent.size_hint()
+    }
+}
+
+impl<K, V, const N: usize> FusedIterator for SnapshotDifference<'_, K, V, N>
+where
+    K: BytesComparable + Clone,
+    V: PartialEq + Clone,
+{
+}
+
+impl<K, V, const N: usize> std::fmt::Debug for SnapshotDifference<'_, K, V, N>
+where
+    K: Clone + std::fmt::Debug,
+    V: Clone + std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SnapshotDifference")
+            .field(&self.descent.entries.last())
+            .finish()
+    }
+}
+
+/// A subtree of one of the trees reached by the descent, and the number of bytes of its prefix
+/// that the descent has already matched.
+type Branch<'a, K, V, const P: usize, S, const C4: usize, const C16: usize, const C48: usize> =
+    (&'a Node<K, V, P, S, C4, C16, C48>, usize);
+
+/// The work left to a descent, in key order when popped.
+enum Work<'a, K, V, const P: usize, S, const C4: usize, const C16: usize, const C48: usize>
+where
+    S: NodeStore<K, V, P, C4, C16, C48>,
+{
+    /// Subtrees of both trees at the same depth and under the same bytes.
+    Both(
+        Branch<'a, K, V, P, S, C4, C16, C48>,
+        Branch<'a, K, V, P, S, C4, C16, C48>,
+    ),
+    /// A subtree of the first tree if true, or of the second tree if false, whose keys the other
+    /// tree lacks.
+    Only(bool, &'a Node<K, V, P, S, C4, C16, C48>),
+    /// A leaf of the first tree if true, or of the second tree if false, and the subtree of the
+    /// other tree that holds every key of that tree around the key of the leaf.
+    Merge(bool, &'a Leaf<K, V>, &'a Node<K, V, P, S, C4, C16, C48>),
+}
+
+/// A descent into two trees together, which finds the entries held by only one of them.
+struct Descent<'a, K, V, const P: usize, S, const C4: usize, const C16: usize, const C48: usize>
+where
+    S: NodeStore<K, V, P, C4, C16, C48>,
+{
+    stack: Vec<Work<'a, K, V, P, S, C4, C16, C48>>,
+    /// The entries found by the last step that were not yielded yet, in reverse key order.
+    entries: Vec<Difference<'a, K, V>>,
+}
+
+impl<'a, K, V, const P: usize, S, const C4: usize, const C16: usize, const C48: usize>
+    Descent<'a, K, V, P, S, C4, C16, C48>
+where
+    K: BytesComparable,
+    V: PartialEq,
+    S: NodeStore<K, V, P, C4, C16, C48>,
+{
+    fn new(
+        left: Option<&'a Node<K, V, P, S, C4, C16, C48>>,
+        right: Option<&'a Node<K, V, P, S, C4, C16, C48>>,
+    ) -> Self {
+        let stack = match (left, right) {
+            (Some(left), Some(right)) => vec![Work::Both((left, 0), (right, 0))],
+            (Some(left), None) => vec![Work::Only(true, left)],
+            (None, Some(right)) => vec![Work::Only(false, right)],
+            (None, None) => Vec::new(),
+        };
+        Self {
+            stack,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Run the work left until it finds the next entry held by only one of the trees.
+    fn next(&mut self) -> Option<Difference<'a, K, V>> {
+        loop {
+            if let Some(entry) = self.entries.pop() {
+                return Some(entry);
+            }
+            match self.stack.pop()? {
+                Work::Only(left, Node::Leaf(leaf)) => return Some(Difference::new(left, leaf)),
+                Work::Only(left, Node::Inner(inner)) => {
+                    let start = self.stack.len();
+                    self.stack
+                        .extend(inner.children().map(|(_, child)| Work::Only(left, child)));
+                    self.stack[start..].reverse();
+                }
+                Work::Both(left, right) => self.compare(left, right),
+                Work::Merge(left, leaf, node) => self.merge(left, leaf, node),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let pending = self.entries.len();
+        (pending, self.stack.is_empty().then_some(pending))
+    }
+
+    /// Queue the work left in the given subtrees of both trees.
+    fn compare(
+        &mut self,
+        (left, left_skip): Branch<'a, K, V, P, S, C4, C16, C48>,
+        (right, right_skip): Branch<'a, K, V, P, S, C4, C16, C48>,
+    ) {
+        if left_skip == right_skip && std::ptr::eq(left, right) {
+            return;
+        }
+        let (left_inner, right_inner) = match (left, right) {
+            (Node::Inner(left_inner), Node::Inner(right_inner)) => (left_inner, right_inner),
+            (Node::Leaf(leaf), _) => return self.merge(true, leaf, right),
+            (_, Node::Leaf(leaf)) => return self.merge(false, leaf, left),
+        };
+        let left_rest = &left_inner.prefix()[left_skip..];
+        let right_rest = &right_inner.prefix()[right_skip..];
+        let common = left_rest
+            .iter()
+            .zip(right_rest)
+            .take_while(|(l, r)| l == r)
+            .count();
+        let mut left_branches = branches(left, left_inner, left_skip + common);
+        let mut right_branches = branches(right, right_inner, right_skip + common);
+        // The branches are pushed from the greatest byte key to the smallest, so that they are
+        // popped in key order.
+        loop {
+            let order = match (left_branches.last(), right_branches.last()) {
+                (None, None) => return,
+                (Some(_), None) => Ordering::Greater,
+                (None, Some(_)) => Ordering::Less,
+                (Some((l, _)), Some((r, _))) => l.cmp(r),
+            };
+            let work = match order {
+                Ordering::Greater => {
+                    let (_, (node, _)) = left_branches.pop().unwrap_or_else(|| unreachable!());
+                    Work::Only(true, node)
+                }
+                Ordering::Less => {
+                    let (_, (node, _)) = right_branches.pop().unwrap_or_else(|| unreachable!());
+                    Work::Only(false, node)
+                }
+                Ordering::Equal => {
+                    let (_, left) = left_branches.pop().unwrap_or_else(|| unreachable!());
+                    let (_, right) = right_branches.pop().unwrap_or_else(|| unreachable!());
+                    Work::Both(left, right)
+                }
+            };
+            self.stack.push(work);
+        }
+    }
+
+    /// Queue the work left in merging the leaf of one tree into the subtree of the other tree.
+    /// The leaf is merged into the first child whose largest key is not less than its key, or into
+    /// the last child if there is none, and the other children are only in the other tree.
+    fn merge(
+        &mut self,
+        left: bool,
+        leaf: &'a Leaf<K, V>,
+        node: &'a Node<K, V, P, S, C4, C16, C48>,
+    ) {
+        let inner = match node {
+            Node::Leaf(other) => {
+                let (first, second) = match leaf
+                    .key
+                    .bytes()
+                    .as_ref()
+                    .cmp(other.key.bytes().as_ref())
+                {
+                    Ordering::Less => (Difference::new(left, leaf), Difference::new(!left, other)),
+                    Ordering::Greater => {
+                        (Difference::new(!left, other), Difference::new(left, leaf))
+                    }
+                    Ordering::Equal if leaf.value == other.value => return,
+                    // The entry of the first tree comes first.
+                    Ordering::Equal if left => {
+                        (Difference::new(true, leaf), Difference::new(false, other))
+                    }
+                    Ordering::Equal => (Difference::new(true, other), Difference::new(false, leaf)),
+                };
+                // The entries are yielded from the end.
+                self.entries.push(second);
+                self.entries.push(first);
+                return;
+            }
+            Node::Inner(inner) => inner,
+        };
+        let start = self.stack.len();
+        let mut pending = Some(leaf);
+        let mut children = inner.children().peekable();
+        while let Some((_, child)) = children.next() {
+            let work = match pending {
+                Some(leaf)
+                    if children.peek().is_none()
+                        || child.max_leaf().is_some_and(|max| {
+                            leaf.key.bytes().as_ref() <= max.key.bytes().as_ref()
+                        }) =>
+                {
+                    pending = None;
+                    Work::Merge(left, leaf, child)
+                }
+                _ => Work::Only(!left, child),
+            };
+            self.stack.push(work);
+        }
+        self.stack[start..].reverse();
+    }
+}
+
+/// Return the branches of a subtree after the given number of bytes of its prefix in key order. A
+/// subtree whose prefix goes on past these bytes is a single branch under the next byte of its
+/// prefix.
+fn branches<'a, K, V, const P: usize, S, const C4: usize, const C16: usize, const C48: usize>(
+    node: &'a Node<K, V, P, S, C4, C16, C48>,
+    inner: &'a Inner<K, V, P, S, C4, C16, C48>,
+    skip: usize,
+) -> Vec<(u8, Branch<'a, K, V, P, S, C4, C16, C48>)>
+where
+    S: NodeStore<K, V, P, C4, C16, C48>,
+{
+    inner.prefix().get(skip).map_or_else(
+        || {
+            inner
+                .children()
+                .map(|(byte, child)| (byte, (child, 0)))
+                .collect()
+        },
+        |&byte| vec![(byte, (node, skip + 1))],
+    )
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    ART<K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+    V: PartialEq,
+{
+    /// Return an iterator over the entries held by only one of the trees in key order, see the
+    /// [module documentation](crate::diff).
+    #[must_use]
+    pub fn symmetric_difference<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> SymmetricDifference<'a, K, V, N, C4, C16, C48> {
+        SymmetricDifference {
+            descent: Descent::new(self.root.as_ref(), other.root.as_ref()),
+        }
+    }
+}
+
+impl<K, V, const N: usize> Snapshot<K, V, N>
+where
+    K: BytesComparable + Clone,
+    V: PartialEq + Clone,
+{
+    /// Return an iterator over the entries held by only one of the versions in key order,
+    /// skipping the subtrees both versions share, see the [module documentation](crate::diff).
+    #[must_use]
+    pub fn symmetric_difference<'a>(&'a self, other: &'a Self) -> SnapshotDifference<'a, K, V, N> {
+        SnapshotDifference {
+            descent: Descent::new(self.root(), other.root()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::Rng;
+
+    use super::Difference;
+    use crate::{swmr::SwmrArt, ART};
+
+    /// Return the entries held by only one of the maps in key order.
+    fn expected<'a>(
+        left: &'a BTreeMap<Vec<u8>, u8>,
+        right: &'a BTreeMap<Vec<u8>, u8>,
+    ) -> Vec<Difference<'a, Vec<u8>, u8>> {
+        let mut keys: Vec<_> = left.keys().chain(right.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        let mut entries = Vec::new();
+        for key in keys {
+            match (left.get_key_value(key), right.get_key_value(key)) {
+                (Some((_, l)), Some((_, r))) if l == r => {}
+                (l, r) => {
+                    entries.extend(l.map(|(k, v)| Difference::Left(k, v)));
+                    entries.extend(r.map(|(k, v)| Difference::Right(k, v)));
+                }
+            }
+        }
+        entries
+    }
+
+    #[test]
+    fn test_matches_btree() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..50 {
+            let mut maps = [BTreeMap::new(), BTreeMap::new()];
+            for _ in 0..rng.gen_range(0..300) {
+                let key: Vec<u8> = (0..rng.gen_range(0..8))
+                    .map(|_| rng.gen_range(1..4))
+                    .collect();
+                let value = rng.gen_range(0..3);
+                match rng.gen_range(0..4) {
+                    0 => maps[0].insert(key, value),
+                    1 => maps[1].insert(key, value),
+                    _ => {
+                        maps[0].insert(key.clone(), value);
+                        maps[1].insert(key, value)
+                    }
+                };
+            }
+            let trees = maps
+                .clone()
+                .map(|map| map.into_iter().collect::<ART<Vec<u8>, u8>>());
+            let actual: Vec<_> = trees[0].symmetric_difference(&trees[1]).collect();
+            assert_eq!(actual, expected(&maps[0], &maps[1]));
+            assert!(trees[0].symmetric_difference(&trees[0]).next().is_none());
+        }
+    }
+
+    #[test]
+    fn test_leaf_against_subtree() {
+        let mut rng = rand::thread_rng();
+        let many: BTreeMap<Vec<u8>, u8> = (0..500)
+            .map(|_| {
+                let key = (0..rng.gen_range(1..6)).map(|_| rng.gen_range(1..5));
+                (key.collect(), 0)
+            })
+            .collect();
+        let tree: ART<Vec<u8>, u8> = many.clone().into_iter().collect();
+        for _ in 0..200 {
+            let key: Vec<u8> = (0..rng.gen_range(0..7))
+                .map(|_| rng.gen_range(0..6))
+                .collect();
+            let one = BTreeMap::from([(key, rng.gen_range(0..2))]);
+            let single: ART<Vec<u8>, u8> = one.clone().into_iter().collect();
+            let actual: Vec<_> = single.symmetric_difference(&tree).collect();
+            assert_eq!(actual, expected(&one, &many));
+            let actual: Vec<_> = tree.symmetric_difference(&single).collect();
+            assert_eq!(actual, expected(&many, &one));
+        }
+    }
+
+    #[test]
+    fn test_versions() {
+        let mut rng = rand::thread_rng();
+        let mut tree = SwmrArt::<Vec<u8>, u8>::new();
+        let mut model = BTreeMap::new();
+        for _ in 0..2000 {
+            let key: Vec<u8> = (0..4).map(|_| rng.gen_range(1..8)).collect();
+            tree.insert(key.clone(), 0);
+            model.insert(key, 0);
+        }
+        let before = tree.snapshot();
+        let old = model.clone();
+        for _ in 0..20 {
+            let key: Vec<u8> = (0..rng.gen_range(1..5))
+                .map(|_| rng.gen_range(1..8))
+                .collect();
+            if rng.gen_bool(0.5) {
+                tree.insert(key.clone(), 1);
+                model.insert(key, 1);
+            } else {
+                tree.delete(&key);
+                model.remove(&key);
+            }
+        }
+        let after = tree.snapshot();
+        let actual: Vec<_> = before.symmetric_difference(&after).collect();
+        assert_eq!(actual, expected(&old, &model));
+        // The new values are only in the later version.
+        assert!(actual
+            .iter()
+            .filter(|entry| *entry.value() == 1)
+            .all(|entry| !entry.is_left()));
+        assert!(after.symmetric_difference(&after).next().is_none());
+    }
+}
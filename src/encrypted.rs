@@ -0,0 +1,369 @@
+//! Dumps whose entries are encrypted at rest, enabled by the `encryption` feature.
+//!
+//! [`ART::dump_encrypted`] writes the entries of a tree like [`ART::dump`], except that every
+//! value, and every key if [`Encryption::encrypt_keys`] is set, is sealed with XChaCha20-Poly1305
+//! under a 256-bit secret key. Leaving the keys readable lets tools list what a dump holds without
+//! the secret, while the values stay confidential. [`ART::load_encrypted`] reads such a dump back,
+//! and fails instead of returning altered entries if the dump was encrypted with another secret or
+//! was modified in any way: the header, the number of entries, and the readable keys are
+//! authenticated together with the sealed bytes, and every sealed field is bound to its position in
+//! the dump, so entries can be neither changed, swapped, dropped, nor replayed from another dump.
+//!
+//! Sealed bytes do not compress, so encrypted dumps are not compressed.
+//!
+//! # Format
+//!
+//! | Field        | Encoding                                                             |
+//! |--------------|----------------------------------------------------------------------|
+//! | magic        | the 4 bytes `YARE`                                                   |
+//! | version      | `u8`                                                                 |
+//! | flags        | `u8`, 1 if the keys are encrypted and 0 otherwise                    |
+//! | nonce prefix | 16 random bytes, drawn anew for every dump                           |
+//! | count        | `u64`, the number of entries                                         |
+//! | entries      | `count` times the key, encoded or sealed, then the sealed value      |
+//!
+//! A sealed field is the ciphertext of the encoded key or value, tag included, encoded as a byte
+//! string. The nonce of the n-th sealed field of a dump is the nonce prefix followed by n as a
+//! little-endian `u64`, and its associated data is the header followed by the encoded key of the
+//! entry if the field is a value and the keys are readable.
+
+use std::io::{self, Read, Write};
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use rand::RngCore;
+
+use crate::{
+    persist::{invalid_data, Decode, Encode},
+    BytesComparable, ART,
+};
+
+/// The bytes every encrypted dump starts with.
+const MAGIC: &[u8; 4] = b"YARE";
+
+/// The version of the encrypted dump format.
+const VERSION: u8 = 1;
+
+/// The flag set when the keys of a dump are encrypted.
+const ENCRYPTED_KEYS: u8 = 1;
+
+/// The length of the random part of the nonces.
+const NONCE_PREFIX_LEN: usize = 16;
+
+/// The length of the header, which ends with the number of entries.
+const HEADER_LEN: usize = MAGIC.len() + 2 + NONCE_PREFIX_LEN + 8;
+
+/// The secret key and the options of encrypted dumps.
+#[derive(Clone)]
+pub struct Encryption {
+    cipher: XChaCha20Poly1305,
+    encrypt_keys: bool,
+}
+
+impl Encryption {
+    /// Encrypt the values of dumps with the given secret key and leave their keys readable.
+    #[must_use]
+    pub fn new(secret: &[u8; 32]) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new(secret.into()),
+            encrypt_keys: false,
+        }
+    }
+
+    /// Set whether the keys of dumps are encrypted as well. Loading reads this from the dump, so
+    /// it only matters when dumping.
+    #[must_use]
+    pub const fn encrypt_keys(mut self, encrypt_keys: bool) -> Self {
+        self.encrypt_keys = encrypt_keys;
+        self
+    }
+}
+
+impl std::fmt::Debug for Encryption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The secret key is not printed.
+        f.debug_struct("Encryption")
+            .field("encrypt_keys", &self.encrypt_keys)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Seals and opens the fields of a dump in order.
+struct Sealer<'a> {
+    cipher: &'a XChaCha20Poly1305,
+    header: [u8; HEADER_LEN],
+    counter: u64,
+}
+
+impl<'a> Sealer<'a> {
+    const fn new(cipher: &'a XChaCha20Poly1305, header: [u8; HEADER_LEN]) -> Self {
+        Self {
+            cipher,
+            header,
+            counter: 0,
+        }
+    }
+
+    /// Return the nonce of the next field.
+    fn next_nonce(&mut self) -> XNonce {
+        let mut nonce = XNonce::default();
+        let prefix = &self.header[MAGIC.len() + 2..][..NONCE_PREFIX_LEN];
+        nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+        nonce[NONCE_PREFIX_LEN..].copy_from_slice(&self.counter.to_le_bytes());
+        self.counter += 1;
+        nonce
+    }
+
+    fn associated_data(&self, key: &[u8]) -> Vec<u8> {
+        [self.header.as_slice(), key].concat()
+    }
+
+    fn seal(&mut self, plaintext: &[u8], key: &[u8]) -> io::Result<Vec<u8>> {
+        let nonce = self.next_nonce();
+        let aad = self.associated_data(key);
+        self.cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| io::Error::other("the field is too long to be encrypted"))
+    }
+
+    fn open(&mut self, ciphertext: &[u8], key: &[u8]) -> io::Result<Vec<u8>> {
+        let nonce = self.next_nonce();
+        let aad = self.associated_data(key);
+        self.cipher
+            .decrypt(
+                &nonce,
+                Payload {
+                    msg: ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| {
+                invalid_data("the dump was encrypted with another secret key or was modified")
+            })
+    }
+}
+
+/// Keeps a copy of the bytes read through it.
+struct Recording<'a, R: ?Sized> {
+    reader: &'a mut R,
+    bytes: Vec<u8>,
+}
+
+impl<R: Read + ?Sized> Read for Recording<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.bytes.extend_from_slice(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Decode a value that must span all the given bytes.
+fn decode_exact<T: Decode>(mut bytes: &[u8]) -> io::Result<T> {
+    let value = T::decode(&mut bytes)?;
+    if !bytes.is_empty() {
+        return Err(invalid_data("trailing bytes after a sealed field"));
+    }
+    Ok(value)
+}
+
+impl<K, V, const N: usize> ART<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Write all entries of the tree into the given writer, encrypted as set by the given
+    /// [`Encryption`], see the [module documentation](crate::encrypted).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer fails.
+    pub fn dump_encrypted<W>(&self, mut writer: W, encryption: &Encryption) -> io::Result<()>
+    where
+        W: Write,
+        K: Encode,
+        V: Encode,
+    {
+        let mut header = [0; HEADER_LEN];
+        header[..MAGIC.len()].copy_from_slice(MAGIC);
+        header[MAGIC.len()] = VERSION;
+        header[MAGIC.len() + 1] = if encryption.encrypt_keys {
+            ENCRYPTED_KEYS
+        } else {
+            0
+        };
+        rand::thread_rng().fill_bytes(&mut header[MAGIC.len() + 2..][..NONCE_PREFIX_LEN]);
+        header[HEADER_LEN - 8..].copy_from_slice(&(self.len() as u64).to_le_bytes());
+        writer.write_all(&header)?;
+
+        let mut sealer = Sealer::new(&encryption.cipher, header);
+        let mut key_bytes = Vec::new();
+        let mut value_bytes = Vec::new();
+        self.try_for_each(|key, value| {
+            key_bytes.clear();
+            key.encode(&mut key_bytes)?;
+            value_bytes.clear();
+            value.encode(&mut value_bytes)?;
+            if encryption.encrypt_keys {
+                sealer.seal(&key_bytes, &[])?.encode(&mut writer)?;
+                sealer.seal(&value_bytes, &[])?.encode(&mut writer)
+            } else {
+                writer.write_all(&key_bytes)?;
+                sealer.seal(&value_bytes, &key_bytes)?.encode(&mut writer)
+            }
+        })
+    }
+
+    /// Read a tree from the given reader, which must contain a dump written by
+    /// [`ART::dump_encrypted`] with the same secret key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader fails, if the data is not a valid encrypted dump, or if the
+    /// dump was encrypted with another secret key or modified.
+    pub fn load_encrypted<R>(mut reader: R, encryption: &Encryption) -> io::Result<Self>
+    where
+        R: Read,
+        K: Decode,
+        V: Decode,
+    {
+        let mut header = [0; HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(invalid_data("not an encrypted yaart dump"));
+        }
+        let [version, flags] = [header[MAGIC.len()], header[MAGIC.len() + 1]];
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "unsupported encrypted dump version {version}"
+            )));
+        }
+        if flags & !ENCRYPTED_KEYS != 0 {
+            return Err(invalid_data(format!("unsupported flags {flags}")));
+        }
+        let mut count = [0; 8];
+        count.copy_from_slice(&header[HEADER_LEN - 8..]);
+        let count = u64::from_le_bytes(count);
+
+        let mut sealer = Sealer::new(&encryption.cipher, header);
+        let mut tree = Self::new();
+        for _ in 0..count {
+            let (key, value) = if flags & ENCRYPTED_KEYS == 0 {
+                let mut recording = Recording {
+                    reader: &mut reader,
+                    bytes: Vec::new(),
+                };
+                let key = K::decode(&mut recording)?;
+                let key_bytes = recording.bytes;
+                let value = sealer.open(&Vec::decode(&mut reader)?, &key_bytes)?;
+                (key, value)
+            } else {
+                let key = sealer.open(&Vec::decode(&mut reader)?, &[])?;
+                let value = sealer.open(&Vec::decode(&mut reader)?, &[])?;
+                (decode_exact(&key)?, value)
+            };
+            tree.insert(key, decode_exact(&value)?);
+        }
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use super::{Encryption, HEADER_LEN};
+    use crate::ART;
+
+    const SECRET: &[u8; 32] = b"0123456789abcdef0123456789abcdef";
+
+    fn sample_tree() -> ART<String, String> {
+        (0..100)
+            .map(|i| (format!("user{i:03}"), format!("password{i:03}")))
+            .collect()
+    }
+
+    fn contains(haystack: &[u8], needle: &str) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle.as_bytes())
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let tree = sample_tree();
+        for encrypt_keys in [false, true] {
+            let encryption = Encryption::new(SECRET).encrypt_keys(encrypt_keys);
+            let mut buf = Vec::new();
+            tree.dump_encrypted(&mut buf, &encryption).unwrap();
+            assert!(!contains(&buf, "password042"));
+            assert_eq!(contains(&buf, "user042"), !encrypt_keys);
+
+            let loaded =
+                ART::<String, String>::load_encrypted(buf.as_slice(), &encryption).unwrap();
+            assert!(loaded.iter().eq(tree.iter()));
+
+            // The nonces of every dump are random.
+            let mut other = Vec::new();
+            tree.dump_encrypted(&mut other, &encryption).unwrap();
+            assert_ne!(buf, other);
+        }
+
+        let mut buf = Vec::new();
+        let encryption = Encryption::new(SECRET);
+        ART::<String, String>::new()
+            .dump_encrypted(&mut buf, &encryption)
+            .unwrap();
+        assert_eq!(buf.len(), HEADER_LEN);
+        let loaded = ART::<String, String>::load_encrypted(buf.as_slice(), &encryption).unwrap();
+        assert!(loaded.is_empty());
+        assert!(!format!("{encryption:?}").contains("0123"));
+    }
+
+    #[test]
+    fn test_rejects_modified_dumps() {
+        let tree = sample_tree();
+        let encryption = Encryption::new(SECRET);
+        let mut buf = Vec::new();
+        tree.dump_encrypted(&mut buf, &encryption).unwrap();
+        let load = |bytes: &[u8], encryption: &Encryption| {
+            ART::<String, String>::load_encrypted(bytes, encryption).unwrap_err()
+        };
+
+        let other = Encryption::new(&[7; 32]);
+        assert_eq!(load(&buf, &other).kind(), ErrorKind::InvalidData);
+
+        // Changing a readable key, a sealed value, or the number of entries is detected.
+        let position = buf.windows(7).position(|w| w == b"user042").unwrap();
+        for index in [position, buf.len() - 1, HEADER_LEN - 8] {
+            let mut modified = buf.clone();
+            modified[index] ^= 1;
+            assert_eq!(load(&modified, &encryption).kind(), ErrorKind::InvalidData);
+        }
+
+        // Swapping two entries is detected.
+        let entry_len = (buf.len() - HEADER_LEN) / tree.len();
+        let mut swapped = buf.clone();
+        let (first, second) = swapped[HEADER_LEN..].split_at_mut(entry_len);
+        first.swap_with_slice(&mut second[..entry_len]);
+        assert_eq!(load(&swapped, &encryption).kind(), ErrorKind::InvalidData);
+
+        assert_eq!(
+            load(&buf[..buf.len() - 1], &encryption).kind(),
+            ErrorKind::UnexpectedEof
+        );
+        assert_eq!(
+            load(b"YART\x01\x00", &encryption).kind(),
+            ErrorKind::UnexpectedEof
+        );
+        let mut plain = Vec::new();
+        tree.dump(&mut plain).unwrap();
+        assert_eq!(load(&plain, &encryption).kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,215 @@
+//! Errors of fallible operations on trees.
+//!
+//! Fallible operations return the error type that describes their failures precisely, such as
+//! [`KeyTooLong`] or [`TryReserveError`]. [`Error`] gathers all of them, so that a service can
+//! propagate the failures of the trees it uses with `?` and handle them in a single place instead
+//! of panicking.
+
+use std::{collections::TryReserveError, io};
+
+use crate::{bounded::KeyTooLong, BytesComparable, ART};
+
+/// An error returned by [`ART::validate`] when the structure of a tree is inconsistent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptionError {
+    /// The encoded key of the entry where the inconsistency was found, or `None` if it was not
+    /// found at a single entry.
+    pub key: Option<Vec<u8>>,
+    /// What is inconsistent.
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for CorruptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "tree is corrupted at key {key:?}: {}", self.reason),
+            None => write!(f, "tree is corrupted: {}", self.reason),
+        }
+    }
+}
+
+impl std::error::Error for CorruptionError {}
+
+/// Any error returned by the operations of this crate.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A key was longer than the limit of the tree.
+    KeyTooLong(KeyTooLong),
+    /// The memory needed by an operation could not be reserved before running it.
+    OutOfMemory(TryReserveError),
+    /// The structure of a tree is inconsistent.
+    Corrupted(CorruptionError),
+    /// Reading or writing a persisted tree failed.
+    Io(io::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::KeyTooLong(error) => error.fmt(f),
+            Self::OutOfMemory(error) => error.fmt(f),
+            Self::Corrupted(error) => error.fmt(f),
+            Self::Io(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::KeyTooLong(error) => Some(error),
+            Self::OutOfMemory(error) => Some(error),
+            Self::Corrupted(error) => Some(error),
+            Self::Io(error) => Some(error),
+        }
+    }
+}
+
+impl From<KeyTooLong> for Error {
+    fn from(error: KeyTooLong) -> Self {
+        Self::KeyTooLong(error)
+    }
+}
+
+impl From<TryReserveError> for Error {
+    fn from(error: TryReserveError) -> Self {
+        Self::OutOfMemory(error)
+    }
+}
+
+impl From<CorruptionError> for Error {
+    fn from(error: CorruptionError) -> Self {
+        Self::Corrupted(error)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    ART<K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+    /// Check that the path to every leaf spells its encoded key, that the keys are in order, and
+    /// that the tree holds as many entries as it counts. This visits every node, and is meant to
+    /// check trees that were loaded from storage or built from raw parts.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing the first inconsistency that was found.
+    pub fn validate(&self) -> Result<(), CorruptionError> {
+        let mut previous: Option<Vec<u8>> = None;
+        let mut count = 0;
+        for ((key, _), (rebuilt, _)) in self.iter().zip(self.key_paths()) {
+            let key = key.bytes();
+            let key = key.as_ref();
+            let corrupted = |reason| CorruptionError {
+                key: Some(key.to_vec()),
+                reason,
+            };
+            if rebuilt != key {
+                return Err(corrupted("the path to the leaf does not spell its key"));
+            }
+            if previous
+                .as_ref()
+                .is_some_and(|previous| previous.as_slice() >= key)
+            {
+                return Err(corrupted("the key is not greater than the key before it"));
+            }
+            previous = Some(rebuilt);
+            count += 1;
+        }
+        if count != self.len() {
+            return Err(CorruptionError {
+                key: None,
+                reason: "the number of entries does not match the length of the tree",
+            });
+        }
+        Ok(())
+    }
+
+    /// Insert the given key-value pairs one after another, stopping at the first one whose memory
+    /// check fails, see [`ART::insert_checked`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the memory for an insert can not be reserved, in which case the pairs
+    /// before it were inserted and the others were not.
+    pub fn extend_checked<I>(&mut self, iter: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        for (key, value) in iter {
+            self.insert_checked(key, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use super::{CorruptionError, Error};
+    use crate::{bounded::BoundedArt, ART};
+
+    /// A service operation that propagates every kind of failure with `?`.
+    fn store(
+        tree: &mut BoundedArt<String, u32>,
+        key: &str,
+        dump: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        tree.try_insert(key.to_string(), 1)?;
+        let mut copy = ART::<String, u32>::new();
+        copy.extend_checked(tree.tree().iter().map(|(k, v)| (k.clone(), *v)))?;
+        copy.validate()?;
+        copy.dump(&mut *dump)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_error() {
+        let mut tree = BoundedArt::new(4);
+        let mut dump = Vec::new();
+        store(&mut tree, "abc", &mut dump).unwrap();
+        assert!(!dump.is_empty());
+
+        let error = store(&mut tree, "abcdef", &mut dump).unwrap_err();
+        assert!(matches!(error, Error::KeyTooLong(_)));
+        assert!(error.source().is_some());
+        assert_eq!(
+            error.to_string(),
+            "key of 6 bytes exceeds the maximum length of 4 bytes"
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut tree: ART<String, usize> = (0..500).map(|i| (format!("key{i}"), i)).collect();
+        assert_eq!(tree.validate(), Ok(()));
+        tree.len += 1;
+        assert_eq!(
+            tree.validate(),
+            Err(CorruptionError {
+                key: None,
+                reason: "the number of entries does not match the length of the tree",
+            })
+        );
+        tree.len -= 1;
+
+        // Changing the key of a leaf behind the back of the tree breaks its path.
+        let leaf = tree.root.as_mut().unwrap().search_mut(b"key42", 0).unwrap();
+        leaf.key = "kez42".to_string();
+        let error = tree.validate().unwrap_err();
+        assert_eq!(error.key.as_deref(), Some(b"kez42".as_slice()));
+        assert_eq!(
+            Error::from(error).to_string(),
+            "tree is corrupted at key [107, 101, 122, 52, 50]: the path to the leaf does not spell its key"
+        );
+    }
+}
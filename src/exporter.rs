@@ -0,0 +1,188 @@
+//! Export of tree metrics to the registry of the [`metrics`] crate, enabled by the `metrics`
+//! feature.
+//!
+//! Every search, insert, and delete on an [`ART`] increments the [`OPERATIONS`] counter, labeled
+//! with the operation as `op`. The shape of a tree is too expensive to track on every operation,
+//! so it is exported on demand by [`record`], which sets the gauges of a tree labeled with its name
+//! as `tree`. A long-running service calls it periodically, for example before every scrape. Any
+//! recorder of the `metrics` crate collects them, such as the Prometheus exporter of the
+//! `metrics-exporter-prometheus` crate.
+
+use metrics::{describe_counter, describe_gauge, gauge, Unit};
+
+use crate::ART;
+
+/// The counter of operations on trees.
+pub const OPERATIONS: &str = "yaart_operations_total";
+
+/// The gauge of entries in a tree.
+pub const ENTRIES: &str = "yaart_entries";
+
+/// The gauge of inner nodes in a tree, labeled with their kind as `kind`.
+pub const NODES: &str = "yaart_nodes";
+
+/// The gauge of bytes taken by the nodes of a tree, excluding the heap memory owned by the keys
+/// and values.
+pub const NODE_BYTES: &str = "yaart_node_bytes";
+
+/// The gauge of inner nodes above the deepest leaf of a tree.
+pub const DEPTH: &str = "yaart_depth";
+
+/// The shape of a tree at some point in time, see [`ART::gauges`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Gauges {
+    /// The number of entries.
+    pub entries: usize,
+    /// The number of inner nodes of the smallest kind.
+    pub node4: usize,
+    /// The number of inner nodes of the second smallest kind.
+    pub node16: usize,
+    /// The number of inner nodes of the second largest kind.
+    pub node48: usize,
+    /// The number of inner nodes of the largest kind.
+    pub node256: usize,
+    /// The number of bytes taken by the nodes.
+    pub node_bytes: usize,
+    /// The number of inner nodes above the deepest leaf.
+    pub depth: usize,
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    ART<K, V, N, C4, C16, C48>
+{
+    /// Measure the shape of the tree by visiting all of its nodes.
+    #[must_use]
+    pub fn gauges(&self) -> Gauges {
+        let mut gauges = Gauges {
+            entries: self.len,
+            ..Gauges::default()
+        };
+        if let Some(root) = &self.root {
+            root.measure(0, &mut gauges);
+        }
+        gauges
+    }
+}
+
+/// Describe the metrics of this crate to the installed recorder, which uses the descriptions as
+/// the help texts of the metrics.
+pub fn describe() {
+    describe_counter!(OPERATIONS, "Searches, inserts, and deletes on trees");
+    describe_gauge!(ENTRIES, "Entries in a tree");
+    describe_gauge!(NODES, "Inner nodes in a tree by kind");
+    describe_gauge!(
+        NODE_BYTES,
+        Unit::Bytes,
+        "Bytes taken by the nodes of a tree"
+    );
+    describe_gauge!(DEPTH, "Inner nodes above the deepest leaf of a tree");
+}
+
+/// Measure the given tree and set its gauges in the installed recorder, labeled with the given
+/// name of the tree.
+#[allow(clippy::cast_precision_loss)] // Gauges are floating point numbers, and so lose precision.
+pub fn record<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>(
+    name: &str,
+    tree: &ART<K, V, N, C4, C16, C48>,
+) {
+    let gauges = tree.gauges();
+    let name = name.to_owned();
+    gauge!(ENTRIES, "tree" => name.clone()).set(gauges.entries as f64);
+    for (kind, count) in [
+        ("node4", gauges.node4),
+        ("node16", gauges.node16),
+        ("node48", gauges.node48),
+        ("node256", gauges.node256),
+    ] {
+        gauge!(NODES, "tree" => name.clone(), "kind" => kind).set(count as f64);
+    }
+    gauge!(NODE_BYTES, "tree" => name.clone()).set(gauges.node_bytes as f64);
+    gauge!(DEPTH, "tree" => name).set(gauges.depth as f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fmt::Write,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use metrics::{
+        Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+    };
+
+    use super::{record, DEPTH, ENTRIES, NODES, OPERATIONS};
+    use crate::ART;
+
+    /// Keeps the last value of every counter and gauge, by the name and labels of their keys.
+    #[derive(Default)]
+    struct Captured(Mutex<HashMap<String, Arc<AtomicU64>>>);
+
+    impl Captured {
+        fn metric(&self, key: &Key) -> Arc<AtomicU64> {
+            let mut name = key.name().to_string();
+            for label in key.labels() {
+                write!(name, ",{}={}", label.key(), label.value()).unwrap();
+            }
+            Arc::clone(self.0.lock().unwrap().entry(name).or_default())
+        }
+
+        fn counter(&self, name: &str) -> u64 {
+            self.0.lock().unwrap()[name].load(Ordering::Relaxed)
+        }
+
+        fn gauge(&self, name: &str) -> f64 {
+            f64::from_bits(self.0.lock().unwrap()[name].load(Ordering::Relaxed))
+        }
+    }
+
+    impl Recorder for Captured {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.metric(key))
+        }
+
+        fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::from_arc(self.metric(key))
+        }
+
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)] // Small counts are exact floating point numbers.
+    fn test_record() {
+        let recorder = Captured::default();
+        metrics::with_local_recorder(&recorder, || {
+            let mut tree = ART::<u32, u32>::new();
+            for i in 0..100 {
+                tree.insert(i, i);
+            }
+            tree.delete(&0);
+            tree.search(&1);
+            record("ids", &tree);
+        });
+        assert_eq!(recorder.counter(&format!("{OPERATIONS},op=insert")), 100);
+        assert_eq!(recorder.counter(&format!("{OPERATIONS},op=delete")), 1);
+        assert_eq!(recorder.counter(&format!("{OPERATIONS},op=search")), 1);
+        assert_eq!(recorder.gauge(&format!("{ENTRIES},tree=ids")), 99.0);
+        // All keys share their first three bytes, so the root is the only inner node.
+        assert_eq!(
+            recorder.gauge(&format!("{NODES},tree=ids,kind=node256")),
+            1.0
+        );
+        assert_eq!(recorder.gauge(&format!("{NODES},tree=ids,kind=node4")), 0.0);
+        assert_eq!(recorder.gauge(&format!("{DEPTH},tree=ids")), 1.0);
+    }
+}
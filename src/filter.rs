@@ -0,0 +1,179 @@
+//! Read-only views of a tree that hide the entries whose values fail a predicate.
+//!
+//! Rows are often deleted softly, by marking their values instead of removing their keys, so that
+//! the deletion can be undone or replicated later. A [`Filtered`] view, created with
+//! [`ART::filter_values`], looks like a tree without the marked rows: lookups, iterations, and
+//! range scans skip every entry whose value fails the predicate of the view. The predicate is
+//! evaluated on every access rather than once, so the view never copies the tree, and it always
+//! agrees with the values of the tree it borrows.
+
+use std::{borrow::Borrow, ops::RangeBounds};
+
+use crate::{BytesComparable, ART};
+
+/// A read-only view of the entries of a tree whose values satisfy a predicate, see the [module
+/// documentation](self).
+pub struct Filtered<'a, K, V, F, const N: usize = 10> {
+    tree: &'a ART<K, V, N>,
+    predicate: F,
+}
+
+impl<'a, K, V, F, const N: usize> Filtered<'a, K, V, F, N>
+where
+    K: BytesComparable,
+    F: Fn(&V) -> bool,
+{
+    /// Create a view of the entries of the tree whose values satisfy the predicate.
+    pub const fn new(tree: &'a ART<K, V, N>, predicate: F) -> Self {
+        Self { tree, predicate }
+    }
+
+    /// Return the tree the view is taken from, including the entries the view hides.
+    #[must_use]
+    pub const fn tree(&self) -> &'a ART<K, V, N> {
+        self.tree
+    }
+
+    /// Search for the value associated with the given key, which is absent if the value fails
+    /// the predicate.
+    pub fn get<Q>(&self, key: &Q) -> Option<&'a V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree
+            .search(key)
+            .filter(|value| (self.predicate)(value))
+    }
+
+    /// Return true if the view contains the given key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Return an iterator over the visible entries in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a K, &'a V)> + '_ {
+        self.tree
+            .iter()
+            .filter(|(_, value)| (self.predicate)(value))
+    }
+
+    /// Return an iterator over the visible entries within the given range in key order.
+    pub fn range<Q, R>(&self, range: R) -> impl Iterator<Item = (&'a K, &'a V)> + '_
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.tree
+            .scan(range)
+            .filter(|(_, value)| (self.predicate)(value))
+    }
+
+    /// Return the number of visible entries. This evaluates the predicate on every entry of the
+    /// tree.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Return true if no entry is visible.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+}
+
+impl<K, V, F, const N: usize> std::fmt::Debug for Filtered<'_, K, V, F, N>
+where
+    K: std::fmt::Debug,
+    V: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Filtered")
+            .field("tree", &self.tree)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, V, const N: usize> ART<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Return a read-only view of the entries whose values satisfy the predicate, see
+    /// [`Filtered`].
+    pub const fn filter_values<F>(&self, predicate: F) -> Filtered<'_, K, V, F, N>
+    where
+        F: Fn(&V) -> bool,
+    {
+        Filtered::new(self, predicate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::Rng;
+
+    use crate::ART;
+
+    /// A row that is kept in the tree after it is deleted.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Row {
+        value: u32,
+        deleted: bool,
+    }
+
+    #[test]
+    fn test_soft_deletes() {
+        let mut tree = ART::<u32, Row>::new();
+        for key in 0..10 {
+            let deleted = key % 3 == 0;
+            tree.insert(
+                key,
+                Row {
+                    value: key,
+                    deleted,
+                },
+            );
+        }
+        let live = tree.filter_values(|row| !row.deleted);
+        assert_eq!(live.get(&4).map(|row| row.value), Some(4));
+        assert_eq!(live.get(&3), None);
+        assert!(!live.contains_key(&6));
+        assert!(live.tree().search(&6).is_some());
+        let keys: Vec<_> = live.iter().map(|(k, _)| *k).collect();
+        assert_eq!(keys, [1, 2, 4, 5, 7, 8]);
+        let keys: Vec<_> = live.range(2..=6).map(|(k, _)| *k).collect();
+        assert_eq!(keys, [2, 4, 5]);
+        assert_eq!(live.len(), 6);
+        assert!(tree.filter_values(|row| row.value > 9).is_empty());
+    }
+
+    #[test]
+    fn test_matches_filtered_btree() {
+        let mut rng = rand::thread_rng();
+        let mut tree = ART::<u16, u8>::new();
+        let mut expected = BTreeMap::new();
+        for _ in 0..5000 {
+            let key = rng.gen_range(0..2000);
+            let value = rng.gen();
+            tree.insert(key, value);
+            expected.insert(key, value);
+        }
+        let even = |value: &u8| value.is_multiple_of(2);
+        let view = tree.filter_values(even);
+        assert!(view.iter().eq(expected.iter().filter(|(_, v)| even(v))));
+        assert!(view
+            .range(500..1500)
+            .eq(expected.range(500..1500).filter(|(_, v)| even(v))));
+        for key in 0..2000 {
+            assert_eq!(view.get(&key), expected.get(&key).filter(|v| even(v)));
+        }
+    }
+}
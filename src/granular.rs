@@ -0,0 +1,350 @@
+//! A tree that branches on digits of its keys narrower than a byte.
+//!
+//! Every level of an [`ART`] branches on a whole byte of the key, so a node can have up to 256
+//! children. Dense key spaces of small integers end up in large nodes that hold few children at the
+//! bottom of the tree, and in sparse key spaces almost every inner node is a small node with mostly
+//! unused capacity. A [`GranularArt`] splits the encoded keys into digits of 6, 4, or 1 bits,
+//! chosen per tree by its [`Granularity`], and stores the keys under those digits, so that its
+//! nodes branch at most 64, 16, or 2 ways. The tree gets deeper, but its nodes fit the children of
+//! each level more tightly. The keys stay in the same order, and range queries are supported in
+//! every mode.
+//!
+//! The bits of a key are split into digits from the most significant one, and the last digit is
+//! padded with zero bits. Every digit is stored as a byte holding the digit plus one, because a
+//! zero byte after a key would make it a prefix of another key. The digits keep the order of the
+//! keys, different keys have different digits, and no stored byte is zero, so unlike in an
+//! [`ART`], a key may be a prefix of another one followed by any byte.
+
+use std::{
+    borrow::Borrow,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{BytesComparable, ART};
+
+/// The part of the key bytes that a level of a [`GranularArt`] branches on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Granularity {
+    /// Branch on whole bytes, like [`ART`].
+    #[default]
+    Byte,
+    /// Branch on digits of 6 bits that run across the bytes, up to 64 ways.
+    Sextet,
+    /// Branch on the high and then the low nibble of every byte, up to 16 ways.
+    Nibble,
+    /// Branch on every bit of every byte from the most significant one, up to 2 ways.
+    Bit,
+}
+
+impl Granularity {
+    /// Return the number of bits of a digit.
+    #[must_use]
+    pub const fn bits(self) -> u32 {
+        match self {
+            Self::Byte => 8,
+            Self::Sextet => 6,
+            Self::Nibble => 4,
+            Self::Bit => 1,
+        }
+    }
+
+    /// Return the number of distinct digits, which bounds the number of children of an inner
+    /// node.
+    #[must_use]
+    pub const fn fanout(self) -> usize {
+        1 << self.bits()
+    }
+
+    /// Append the digits of the bytes at this granularity to the buffer, each one stored as a byte
+    /// holding the digit plus one except for whole bytes, see the [module documentation](self).
+    #[allow(clippy::cast_possible_truncation)] // A digit is masked to fewer than 8 bits.
+    fn expand(self, bytes: &[u8], buf: &mut Vec<u8>) {
+        if self == Self::Byte {
+            buf.extend_from_slice(bytes);
+            return;
+        }
+        let bits = self.bits();
+        let mask = (1 << bits) - 1;
+        buf.reserve((bytes.len() * 8).div_ceil(bits as usize));
+        let mut acc = 0u32;
+        let mut pending = 0;
+        for &byte in bytes {
+            acc = (acc << 8) | u32::from(byte);
+            pending += 8;
+            while pending >= bits {
+                pending -= bits;
+                buf.push(((acc >> pending) & mask) as u8 + 1);
+            }
+        }
+        if pending > 0 {
+            buf.push(((acc << (bits - pending)) & mask) as u8 + 1);
+        }
+    }
+}
+
+/// A key stored with the digits it is placed by in the tree.
+#[derive(Debug)]
+struct Expanded<K> {
+    key: K,
+    digits: Box<[u8]>,
+}
+
+impl<K> BytesComparable for Expanded<K> {
+    type Target<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        &self.digits
+    }
+}
+
+impl<K> Borrow<[u8]> for Expanded<K> {
+    fn borrow(&self) -> &[u8] {
+        &self.digits
+    }
+}
+
+/// An adaptive radix tree whose levels branch on bytes or on narrower digits of the keys, see the
+/// [module documentation](self).
+#[derive(Debug)]
+pub struct GranularArt<K, V, const N: usize = 10> {
+    tree: ART<Expanded<K>, V, N>,
+    granularity: Granularity,
+}
+
+impl<K, V, const N: usize> GranularArt<K, V, N> {
+    /// Create an empty tree that branches on the given parts of the key bytes.
+    #[must_use]
+    pub const fn new(granularity: Granularity) -> Self {
+        Self {
+            tree: ART::new(),
+            granularity,
+        }
+    }
+
+    /// Return the parts of the key bytes that the tree branches on.
+    #[must_use]
+    pub const fn granularity(&self) -> Granularity {
+        self.granularity
+    }
+
+    /// Return the number of entries in the tree.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Return true if the tree holds no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Return an iterator over the entries in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.tree.iter().map(|(k, v)| (&k.key, v))
+    }
+
+    /// Return the greatest number of inner nodes above a leaf, see [`ART::height`].
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.tree.height()
+    }
+}
+
+impl<K, V, const N: usize> Default for GranularArt<K, V, N> {
+    fn default() -> Self {
+        Self::new(Granularity::default())
+    }
+}
+
+impl<K, V, const N: usize> GranularArt<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Return the digits the given key is placed by in the tree.
+    fn encode<Q>(&self, key: &Q) -> Vec<u8>
+    where
+        Q: BytesComparable + ?Sized,
+    {
+        let bytes = key.bytes();
+        let mut digits = Vec::new();
+        self.granularity.expand(bytes.as_ref(), &mut digits);
+        digits
+    }
+
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(self.encode(key).as_slice())
+    }
+
+    /// Insert the given key-value pair into the tree, returning the value previously associated
+    /// with the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let digits = self.encode(&key).into_boxed_slice();
+        self.tree.insert(Expanded { key, digits }, value)
+    }
+
+    /// Delete the value associated with the given key.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let digits = self.encode(key);
+        self.tree.delete(digits.as_slice())
+    }
+
+    /// Call the closure on the key-value pairs within the given range in key order, until the
+    /// closure returns false.
+    pub fn range_while<Q, R, F>(&self, range: R, mut f: F)
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        R: RangeBounds<Q>,
+        F: FnMut(&K, &V) -> bool,
+    {
+        let start = range.start_bound().map(|key| self.encode(key));
+        let end = range.end_bound().map(|key| self.encode(key));
+        let bounds: (Bound<&[u8]>, Bound<&[u8]>) = (
+            start.as_ref().map(Vec::as_slice),
+            end.as_ref().map(Vec::as_slice),
+        );
+        self.tree
+            .range_while::<[u8], _, _>(bounds, |k, v| f(&k.key, v));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::Rng;
+
+    use super::{GranularArt, Granularity};
+    use crate::ART;
+
+    #[test]
+    fn test_digits() {
+        let mut buf = Vec::new();
+        Granularity::Nibble.expand(&[0xA5, 0x00], &mut buf);
+        assert_eq!(buf, [0xB, 0x6, 0x1, 0x1]);
+        buf.clear();
+        // 0b111111_00 0b0001_0000 splits into 111111, 000001, and 0000 padded to 000000.
+        Granularity::Sextet.expand(&[0xFC, 0x10], &mut buf);
+        assert_eq!(buf, [64, 2, 1]);
+        buf.clear();
+        Granularity::Bit.expand(&[0x81], &mut buf);
+        assert_eq!(buf, [2, 1, 1, 1, 1, 1, 1, 2]);
+        assert_eq!(Granularity::Sextet.fanout(), 64);
+
+        // The digits compare like the bytes, also when one key is a prefix of the other.
+        let mut rng = rand::thread_rng();
+        for granularity in [Granularity::Sextet, Granularity::Nibble, Granularity::Bit] {
+            let digits = |bytes: &[u8]| {
+                let mut buf = Vec::new();
+                granularity.expand(bytes, &mut buf);
+                buf
+            };
+            for _ in 0..1000 {
+                let a: Vec<u8> = (0..rng.gen_range(0..5)).map(|_| rng.gen()).collect();
+                let mut b = a.clone();
+                b.truncate(rng.gen_range(0..=a.len()));
+                b.extend((0..rng.gen_range(0..3)).map(|_| rng.gen::<u8>() % 3));
+                let (da, db) = (digits(&a), digits(&b));
+                assert_eq!(da.cmp(&db), a.cmp(&b), "{a:?} {b:?}");
+                assert!(da.iter().all(|&digit| (1..=64).contains(&digit)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_granularities_match_btree() {
+        let mut rng = rand::thread_rng();
+        for granularity in [
+            Granularity::Byte,
+            Granularity::Sextet,
+            Granularity::Nibble,
+            Granularity::Bit,
+        ] {
+            let mut tree = GranularArt::<Vec<u8>, usize>::new(granularity);
+            let mut expected = BTreeMap::new();
+            // Digits never make a key a prefix of another one followed by a zero byte, which is
+            // not supported when branching on bytes.
+            let lowest = u8::from(granularity == Granularity::Byte);
+            for idx in 0..3000 {
+                let key: Vec<u8> = (0..rng.gen_range(0..4))
+                    .map(|_| rng.gen_range(lowest..=u8::MAX))
+                    .collect();
+                if rng.gen_bool(0.7) {
+                    assert_eq!(tree.insert(key.clone(), idx), expected.insert(key, idx));
+                } else {
+                    assert_eq!(tree.delete(&key), expected.remove(&key));
+                }
+            }
+            assert!(tree.iter().eq(expected.iter()));
+            for (key, value) in &expected {
+                assert_eq!(tree.search(key), Some(value));
+            }
+
+            let (start, end) = (vec![0x40], vec![0xC0, 0x01]);
+            let mut keys = Vec::new();
+            tree.range_while(start.clone()..end.clone(), |k, _| {
+                keys.push(k.clone());
+                true
+            });
+            assert!(keys.iter().eq(expected.range(start..end).map(|(k, _)| k)));
+        }
+    }
+
+    #[test]
+    fn test_fanout() {
+        for (granularity, fanout) in [
+            (Granularity::Byte, 256),
+            // The last digit of a `u16` only has 4 bits.
+            (Granularity::Sextet, 16),
+            (Granularity::Nibble, 16),
+            (Granularity::Bit, 2),
+        ] {
+            let mut tree = GranularArt::<u16, ()>::new(granularity);
+            for key in 0..4096 {
+                tree.insert(key, ());
+            }
+            assert_eq!(tree.granularity(), granularity);
+            assert_eq!(tree.search(&1234), Some(&()));
+            let mut children = BTreeMap::<&[u8], usize>::new();
+            for (key, ()) in &tree.tree {
+                let digits = &key.digits;
+                *children.entry(&digits[..digits.len() - 1]).or_default() += 1;
+            }
+            // Dense keys fill every node that holds leaves.
+            assert!(children.values().all(|&count| count == fanout));
+        }
+    }
+
+    #[test]
+    fn test_depth() {
+        // Spread the keys over the key space the same way on every run.
+        let keys: Vec<u32> = (0..1000u32)
+            .map(|i| i.wrapping_mul(2_654_435_761))
+            .collect();
+        let bytes: ART<u32, ()> = keys.iter().map(|&key| (key, ())).collect();
+        let mut heights = Vec::new();
+        for granularity in [Granularity::Sextet, Granularity::Nibble] {
+            let mut tree = GranularArt::<u32, ()>::new(granularity);
+            for &key in &keys {
+                tree.insert(key, ());
+            }
+            assert_eq!(tree.len(), bytes.len());
+            heights.push(tree.height());
+        }
+        // Sparse keys need more levels of narrower nodes.
+        assert!(bytes.height() <= heights[0] && heights[0] < heights[1]);
+    }
+}
@@ -0,0 +1,242 @@
+//! A tree that spreads its keys by prefixing them with a byte of their hash.
+//!
+//! Keys that share long common prefixes, such as URLs of the same host or identifiers with a
+//! constant namespace, pile up below a single path of the tree, and every lookup walks down the
+//! shared prefix before the keys fan out. A [`HashPrefixedArt`] prepends one byte of the hash of
+//! every encoded key, so the root splits the keys into up to 256 evenly filled subtrees right away,
+//! the way some key-value stores salt hot prefixes. Point lookups are unaffected by the order of
+//! the keys, but the entries are no longer ordered by key, so range queries are only supported by
+//! trees created with [`HashPrefixedArt::ordered`], which leaves the keys as they are.
+
+use std::{
+    borrow::Borrow,
+    hash::{DefaultHasher, Hasher},
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{BytesComparable, ART};
+
+/// An error returned when querying a range of keys in a tree whose keys are prefixed by a hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnorderedError;
+
+impl std::fmt::Display for UnorderedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the keys of the tree are not ordered")
+    }
+}
+
+impl std::error::Error for UnorderedError {}
+
+/// A key stored with the encoded bytes it is placed by in the tree.
+#[derive(Debug)]
+struct Prefixed<K> {
+    key: K,
+    encoded: Box<[u8]>,
+}
+
+impl<K> BytesComparable for Prefixed<K> {
+    type Target<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        &self.encoded
+    }
+}
+
+impl<K> Borrow<[u8]> for Prefixed<K> {
+    fn borrow(&self) -> &[u8] {
+        &self.encoded
+    }
+}
+
+/// An adaptive radix tree whose keys are optionally prefixed by a byte of their hash.
+#[derive(Debug)]
+pub struct HashPrefixedArt<K, V, const N: usize = 10> {
+    tree: ART<Prefixed<K>, V, N>,
+    hashed: bool,
+}
+
+impl<K, V, const N: usize> HashPrefixedArt<K, V, N> {
+    /// Create an empty tree that prefixes its keys with a byte of their hash.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            tree: ART::new(),
+            hashed: true,
+        }
+    }
+
+    /// Create an empty tree that keeps its keys in order and supports range queries.
+    #[must_use]
+    pub const fn ordered() -> Self {
+        Self {
+            tree: ART::new(),
+            hashed: false,
+        }
+    }
+
+    /// Return true if the keys are ordered, which is the case when they are not prefixed.
+    #[must_use]
+    pub const fn is_ordered(&self) -> bool {
+        !self.hashed
+    }
+
+    /// Return the number of entries in the tree.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Return true if the tree holds no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Return an iterator over the entries, in key order if the tree is ordered, and in the order
+    /// of their hashes otherwise.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.tree.iter().map(|(k, v)| (&k.key, v))
+    }
+}
+
+impl<K, V, const N: usize> Default for HashPrefixedArt<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> HashPrefixedArt<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Return the bytes the given key is placed by in the tree.
+    fn encode<Q>(&self, key: &Q) -> Vec<u8>
+    where
+        Q: BytesComparable + ?Sized,
+    {
+        let bytes = key.bytes();
+        let bytes = bytes.as_ref();
+        let mut encoded = Vec::with_capacity(bytes.len() + 1);
+        if self.hashed {
+            let mut hasher = DefaultHasher::new();
+            hasher.write(bytes);
+            encoded.push(hasher.finish().to_be_bytes()[0]);
+        }
+        encoded.extend_from_slice(bytes);
+        encoded
+    }
+
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(self.encode(key).as_slice())
+    }
+
+    /// Insert the given key-value pair into the tree, returning the value previously associated
+    /// with the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let encoded = self.encode(&key).into_boxed_slice();
+        self.tree.insert(Prefixed { key, encoded }, value)
+    }
+
+    /// Delete the value associated with the given key.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let encoded = self.encode(key);
+        self.tree.delete(encoded.as_slice())
+    }
+
+    /// Call the closure on the key-value pairs within the given range in key order, until the
+    /// closure returns false.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error without calling the closure if the keys are prefixed by their hash.
+    pub fn range_while<Q, R, F>(&self, range: R, mut f: F) -> Result<(), UnorderedError>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        R: RangeBounds<Q>,
+        F: FnMut(&K, &V) -> bool,
+    {
+        if self.hashed {
+            return Err(UnorderedError);
+        }
+        let start = range.start_bound().map(Q::bytes);
+        let end = range.end_bound().map(Q::bytes);
+        let bounds: (Bound<&[u8]>, Bound<&[u8]>) = (
+            start.as_ref().map(AsRef::as_ref),
+            end.as_ref().map(AsRef::as_ref),
+        );
+        self.tree
+            .range_while::<[u8], _, _>(bounds, |k, v| f(&k.key, v));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{HashPrefixedArt, UnorderedError};
+    use crate::visit::{Visit, Walk};
+
+    #[test]
+    fn test_hash_prefixed() {
+        let mut tree = HashPrefixedArt::<String, usize>::new();
+        let mut expected = BTreeMap::new();
+        for i in 0..2000 {
+            let key = format!("https://example.com/items/{i}");
+            assert_eq!(tree.insert(key.clone(), i), None);
+            expected.insert(key, i);
+        }
+        for (key, value) in &expected {
+            assert_eq!(tree.search(key.as_str()), Some(value));
+        }
+        assert_eq!(tree.delete("https://example.com/items/7"), Some(7));
+        assert_eq!(tree.search("https://example.com/items/7"), None);
+        assert_eq!(tree.len(), 1999);
+
+        // The keys fan out at the root instead of sharing a single long prefix.
+        let mut root_prefix = None;
+        tree.tree.walk(|visit| {
+            if let Visit::Subtree(prefix) = visit {
+                root_prefix = Some(prefix.len());
+            }
+            Walk::Stop
+        });
+        assert_eq!(root_prefix, Some(0));
+        assert!(!tree.iter().map(|(k, _)| k).is_sorted());
+        assert_eq!(
+            tree.range_while::<str, _, _>(.., |_, _| true),
+            Err(UnorderedError)
+        );
+    }
+
+    #[test]
+    fn test_ordered() {
+        let mut tree = HashPrefixedArt::<u32, u32>::ordered();
+        for i in 0..100 {
+            tree.insert(i, i);
+        }
+        assert!(tree.iter().map(|(k, _)| k).is_sorted());
+        let mut keys = Vec::new();
+        tree.range_while(10..20, |k, _| {
+            keys.push(*k);
+            true
+        })
+        .unwrap();
+        assert_eq!(keys, (10..20).collect::<Vec<_>>());
+        assert_eq!(tree.search(&42), Some(&42));
+    }
+}
@@ -2,6 +2,7 @@ mod indices16;
 mod indices256;
 mod indices4;
 mod indices48;
+mod simd;
 
 pub use indices16::*;
 pub use indices256::*;
@@ -25,6 +26,13 @@ pub trait Indices<T> {
     /// Returns a mutable reference to the child associated to the given key.
     fn child_mut(&mut self, key: u8) -> Option<&mut T>;
 
+    /// Calls the closure on every child and its key in key order, with a mutable reference to the
+    /// child that lives as long as the borrow of the indices.
+    fn for_each_child_mut<'a, F>(&'a mut self, f: F)
+    where
+        T: 'a,
+        F: FnMut(u8, &'a mut T);
+
     /// Returns a shared reference to the child associated with the minimum key.
     fn min(&self) -> Option<&T>;
 
@@ -118,6 +126,24 @@ mod tests {
         }
     }
 
+    fn test_indices_for_each_child_mut<IDX>(indices: &mut IDX, max: u8)
+    where
+        IDX: Indices<usize>,
+    {
+        for i in (0..=max).rev() {
+            indices.add_child(i, i as usize);
+        }
+        let mut keys = Vec::new();
+        indices.for_each_child_mut(|key, child| {
+            keys.push(key);
+            *child += 1;
+        });
+        assert!(keys.into_iter().eq(0..=max));
+        for i in 0..=max {
+            assert_eq!(indices.child_ref(i), Some(&(i as usize + 1)));
+        }
+    }
+
     fn test_indices_min<IDX>(indices: &mut IDX, max: u8)
     where
         IDX: Indices<usize>,
@@ -214,6 +240,21 @@ mod tests {
         test_indices_child_mut(&mut indices, 255);
     }
 
+    #[test]
+    fn test_all_indices_for_each_child_mut() {
+        let mut indices = Indices4::<usize>::default();
+        test_indices_for_each_child_mut(&mut indices, 3);
+
+        let mut indices = Indices16::<usize>::default();
+        test_indices_for_each_child_mut(&mut indices, 15);
+
+        let mut indices = Indices48::<usize>::default();
+        test_indices_for_each_child_mut(&mut indices, 47);
+
+        let mut indices = Indices256::<usize>::default();
+        test_indices_for_each_child_mut(&mut indices, 255);
+    }
+
     #[test]
     fn test_all_indices_min() {
         let mut indices = Indices4::<usize>::default();
@@ -265,7 +306,7 @@ mod tests {
         for i in 0..=3 {
             indices16.add_child(i, i as usize);
         }
-        let indices4 = Indices4::from(&mut indices16);
+        let indices4 = Indices4::<usize>::from(&mut indices16);
         assert_eq!(indices4.len(), 4);
         for i in 0..=3 {
             let child = indices4.child_ref(i);
@@ -284,7 +325,7 @@ mod tests {
         for i in 0..=3 {
             indices4.add_child(i, i as usize);
         }
-        let indices16 = Indices16::from(&mut indices4);
+        let indices16 = Indices16::<usize>::from(&mut indices4);
         assert_eq!(indices16.len(), 4);
         for i in 0..=3 {
             let child = indices16.child_ref(i);
@@ -303,7 +344,7 @@ mod tests {
         for i in 0..=15 {
             indices48.add_child(i, i as usize);
         }
-        let indices16 = Indices16::from(&mut indices48);
+        let indices16 = Indices16::<usize>::from(&mut indices48);
         assert_eq!(indices16.len(), 16);
         for i in 0..=15 {
             let child = indices16.child_ref(i);
@@ -322,7 +363,7 @@ mod tests {
         for i in 0..=15 {
             indices16.add_child(i, i as usize);
         }
-        let indices48 = Indices48::from(&mut indices16);
+        let indices48 = Indices48::<usize>::from(&mut indices16);
         assert_eq!(indices48.len(), 16);
         for i in 0..=15 {
             let child = indices48.child_ref(i);
@@ -341,7 +382,7 @@ mod tests {
         for i in 0..=47 {
             indices256.add_child(i, i as usize);
         }
-        let indices48 = Indices48::from(&mut indices256);
+        let indices48 = Indices48::<usize>::from(&mut indices256);
         assert_eq!(indices48.len(), 48);
         for i in 0..=47 {
             let child = indices48.child_ref(i);
@@ -360,7 +401,7 @@ mod tests {
         for i in 0..=47 {
             indices48.add_child(i, i as usize);
         }
-        let indices256 = Indices256::from(&mut indices48);
+        let indices256 = Indices256::<usize>::from(&mut indices48);
         assert_eq!(indices256.len(), 48);
         for i in 0..=47 {
             let child = indices256.child_ref(i);
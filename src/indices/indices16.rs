@@ -1,37 +1,41 @@
-use super::{ordered_insert, ordered_remove, Indices, Indices4, Indices48};
+use super::{ordered_insert, ordered_remove, simd, Indices, Indices4, Indices48};
 
 /// A data structure for holding indices that uses 2 arrays of the same size to map from byte keys
 /// to their children. The keys and pointers are stored at corresponding positions and the keys are
-/// sorted.
-#[derive(Debug)]
-pub struct Indices16<T> {
+/// sorted. It holds up to `C` children, 16 by default.
+#[derive(Debug, Clone)]
+pub struct Indices16<T, const C: usize = 16> {
     pub(super) len: u8,
-    pub(super) keys: [u8; 16],
-    pub(super) children: [Option<T>; 16],
+    pub(super) keys: [u8; C],
+    pub(super) children: [Option<T>; C],
 }
 
-impl<T> Indices16<T> {
+impl<T, const C: usize> Indices16<T, C> {
     const NONE: Option<T> = None;
 
     fn index_of_key(&self, key: u8) -> Result<usize, usize> {
+        // Only the default capacity fits in a SIMD register.
+        if let Ok(keys) = <&[u8; 16]>::try_from(self.keys.as_slice()) {
+            return simd::search16(keys, self.len as usize, key);
+        }
         self.keys[..self.len as usize].binary_search(&key)
     }
 }
 
-impl<T> Default for Indices16<T> {
+impl<T, const C: usize> Default for Indices16<T, C> {
     fn default() -> Self {
         Self {
             len: 0,
-            keys: [0; 16],
-            children: [Self::NONE; 16],
+            keys: [0; C],
+            children: [Self::NONE; C],
         }
     }
 }
 
-impl<'a, T> IntoIterator for &'a Indices16<T> {
+impl<'a, T, const C: usize> IntoIterator for &'a Indices16<T, C> {
     type Item = (u8, &'a T);
 
-    type IntoIter = Iter<'a, T>;
+    type IntoIter = Iter<'a, T, C>;
 
     fn into_iter(self) -> Self::IntoIter {
         Iter {
@@ -41,7 +45,7 @@ impl<'a, T> IntoIterator for &'a Indices16<T> {
     }
 }
 
-impl<T> Indices<T> for Indices16<T> {
+impl<T, const C: usize> Indices<T> for Indices16<T, C> {
     fn len(&self) -> usize {
         self.len as usize
     }
@@ -79,6 +83,17 @@ impl<T> Indices<T> for Indices16<T> {
             .ok()
     }
 
+    fn for_each_child_mut<'a, F>(&'a mut self, mut f: F)
+    where
+        T: 'a,
+        F: FnMut(u8, &'a mut T),
+    {
+        let len = self.len as usize;
+        for (&key, child) in self.keys[..len].iter().zip(&mut self.children[..len]) {
+            f(key, child.as_mut().expect("child must exist"));
+        }
+    }
+
     fn min(&self) -> Option<&T> {
         self.children[..self.len as usize]
             .first()
@@ -92,8 +107,8 @@ impl<T> Indices<T> for Indices16<T> {
     }
 }
 
-impl<T> From<&mut Indices4<T>> for Indices16<T> {
-    fn from(other: &mut Indices4<T>) -> Self {
+impl<T, const C: usize, const D: usize> From<&mut Indices4<T, D>> for Indices16<T, C> {
+    fn from(other: &mut Indices4<T, D>) -> Self {
         let mut indices = Self::default();
         for i in 0..other.len as usize {
             indices.keys[i] = other.keys[i];
@@ -105,8 +120,8 @@ impl<T> From<&mut Indices4<T>> for Indices16<T> {
     }
 }
 
-impl<T> From<&mut Indices48<T>> for Indices16<T> {
-    fn from(other: &mut Indices48<T>) -> Self {
+impl<T, const C: usize, const D: usize> From<&mut Indices48<T, D>> for Indices16<T, C> {
+    fn from(other: &mut Indices48<T, D>) -> Self {
         let mut indices = Self::default();
         for key in 0..=255 {
             let idx_old = other.keys[key as usize];
@@ -127,12 +142,12 @@ impl<T> From<&mut Indices48<T>> for Indices16<T> {
 
 /// An iterator over the indices and their children.
 #[derive(Debug)]
-pub struct Iter<'a, T> {
-    indices: &'a Indices16<T>,
+pub struct Iter<'a, T, const C: usize> {
+    indices: &'a Indices16<T, C>,
     idx: u8,
 }
 
-impl<'a, T> Iterator for Iter<'a, T> {
+impl<'a, T, const C: usize> Iterator for Iter<'a, T, C> {
     type Item = (u8, &'a T);
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -3,7 +3,7 @@ use super::{Indices, Indices48};
 /// A data structure for holding indices that uses 2 arrays of the same size to map from byte keys
 /// to their children. The keys and pointers are stored at corresponding positions and the keys are
 /// sorted.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Indices256<T> {
     pub(super) len: u16,
     pub(super) children: [Option<T>; 256],
@@ -60,6 +60,18 @@ impl<T> Indices<T> for Indices256<T> {
         self.children[key as usize].as_mut()
     }
 
+    fn for_each_child_mut<'a, F>(&'a mut self, mut f: F)
+    where
+        T: 'a,
+        F: FnMut(u8, &'a mut T),
+    {
+        for (key, child) in (0..=u8::MAX).zip(self.children.iter_mut()) {
+            if let Some(child) = child {
+                f(key, child);
+            }
+        }
+    }
+
     fn min(&self) -> Option<&T> {
         self.children.iter().find_map(|child| child.as_ref())
     }
@@ -69,8 +81,8 @@ impl<T> Indices<T> for Indices256<T> {
     }
 }
 
-impl<T> From<&mut Indices48<T>> for Indices256<T> {
-    fn from(other: &mut Indices48<T>) -> Self {
+impl<T, const C: usize> From<&mut Indices48<T, C>> for Indices256<T> {
+    fn from(other: &mut Indices48<T, C>) -> Self {
         let mut indices = Self::default();
         for key in 0..=255 {
             let idx_old = other.keys[key];
@@ -1,13 +1,15 @@
 use super::{ordered_insert, ordered_remove, Indices, Indices16};
 
-#[derive(Debug)]
-pub struct Indices4<T> {
+/// A data structure for holding indices that uses 2 arrays of the same size to map from byte keys
+/// to their children, searched linearly. It holds up to `C` children, 4 by default.
+#[derive(Debug, Clone)]
+pub struct Indices4<T, const C: usize = 4> {
     pub(super) len: u8,
-    pub(super) keys: [u8; 4],
-    pub(super) children: [Option<T>; 4],
+    pub(super) keys: [u8; C],
+    pub(super) children: [Option<T>; C],
 }
 
-impl<T> Indices4<T> {
+impl<T, const C: usize> Indices4<T, C> {
     const NONE: Option<T> = None;
 
     pub fn free(&mut self) -> (u8, T) {
@@ -26,20 +28,20 @@ impl<T> Indices4<T> {
     }
 }
 
-impl<T> Default for Indices4<T> {
+impl<T, const C: usize> Default for Indices4<T, C> {
     fn default() -> Self {
         Self {
             len: 0,
-            keys: [0; 4],
-            children: [Self::NONE; 4],
+            keys: [0; C],
+            children: [Self::NONE; C],
         }
     }
 }
 
-impl<'a, T> IntoIterator for &'a Indices4<T> {
+impl<'a, T, const C: usize> IntoIterator for &'a Indices4<T, C> {
     type Item = (u8, &'a T);
 
-    type IntoIter = Iter<'a, T>;
+    type IntoIter = Iter<'a, T, C>;
 
     fn into_iter(self) -> Self::IntoIter {
         Iter {
@@ -49,7 +51,7 @@ impl<'a, T> IntoIterator for &'a Indices4<T> {
     }
 }
 
-impl<T> Indices<T> for Indices4<T> {
+impl<T, const C: usize> Indices<T> for Indices4<T, C> {
     fn len(&self) -> usize {
         self.len as usize
     }
@@ -84,6 +86,17 @@ impl<T> Indices<T> for Indices4<T> {
             .map(|idx| self.children[idx].as_mut().expect("child must exist"))
     }
 
+    fn for_each_child_mut<'a, F>(&'a mut self, mut f: F)
+    where
+        T: 'a,
+        F: FnMut(u8, &'a mut T),
+    {
+        let len = self.len as usize;
+        for (&key, child) in self.keys[..len].iter().zip(&mut self.children[..len]) {
+            f(key, child.as_mut().expect("child must exist"));
+        }
+    }
+
     fn min(&self) -> Option<&T> {
         self.children[..self.len as usize]
             .first()
@@ -97,8 +110,8 @@ impl<T> Indices<T> for Indices4<T> {
     }
 }
 
-impl<T> From<&mut Indices16<T>> for Indices4<T> {
-    fn from(other: &mut Indices16<T>) -> Self {
+impl<T, const C: usize, const D: usize> From<&mut Indices16<T, D>> for Indices4<T, C> {
+    fn from(other: &mut Indices16<T, D>) -> Self {
         let mut indices = Self::default();
         for i in 0..other.len as usize {
             indices.keys[i] = other.keys[i];
@@ -112,12 +125,12 @@ impl<T> From<&mut Indices16<T>> for Indices4<T> {
 
 /// An iterator over the indices and their children.
 #[derive(Debug)]
-pub struct Iter<'a, T> {
-    indices: &'a Indices4<T>,
+pub struct Iter<'a, T, const C: usize> {
+    indices: &'a Indices4<T, C>,
     idx: u8,
 }
 
-impl<'a, T> Iterator for Iter<'a, T> {
+impl<'a, T, const C: usize> Iterator for Iter<'a, T, C> {
     type Item = (u8, &'a T);
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -2,15 +2,15 @@ use super::{Indices, Indices16, Indices256};
 
 /// A data structure for holding indices that uses 2 arrays of the same size to map from byte keys
 /// to their children. The keys and pointers are stored at corresponding positions and the keys are
-/// sorted.
-#[derive(Debug)]
-pub struct Indices48<T> {
+/// sorted. It holds up to `C` children, 48 by default.
+#[derive(Debug, Clone)]
+pub struct Indices48<T, const C: usize = 48> {
     pub(super) len: u8,
     pub(super) keys: [u8; 256],
-    pub(super) children: [Option<T>; 48],
+    pub(super) children: [Option<T>; C],
 }
 
-impl<T> Indices48<T> {
+impl<T, const C: usize> Indices48<T, C> {
     const NONE: Option<T> = None;
 
     const fn index_of_key(&self, key: u8) -> Option<usize> {
@@ -22,20 +22,20 @@ impl<T> Indices48<T> {
     }
 }
 
-impl<T> Default for Indices48<T> {
+impl<T, const C: usize> Default for Indices48<T, C> {
     fn default() -> Self {
         Self {
             len: 0,
             keys: [0; 256],
-            children: [Self::NONE; 48],
+            children: [Self::NONE; C],
         }
     }
 }
 
-impl<'a, T> IntoIterator for &'a Indices48<T> {
+impl<'a, T, const C: usize> IntoIterator for &'a Indices48<T, C> {
     type Item = (u8, &'a T);
 
-    type IntoIter = Iter<'a, T>;
+    type IntoIter = Iter<'a, T, C>;
 
     fn into_iter(self) -> Self::IntoIter {
         Iter {
@@ -45,7 +45,7 @@ impl<'a, T> IntoIterator for &'a Indices48<T> {
     }
 }
 
-impl<T> Indices<T> for Indices48<T> {
+impl<T, const C: usize> Indices<T> for Indices48<T, C> {
     fn len(&self) -> usize {
         self.len as usize
     }
@@ -59,14 +59,12 @@ impl<T> Indices<T> for Indices48<T> {
     }
 
     fn add_child(&mut self, key: u8, child: T) {
-        for idx in 0u8..48u8 {
-            if self.children[idx as usize].is_none() {
-                self.len += 1;
-                self.keys[key as usize] = idx + 1;
-                self.children[idx as usize] = Some(child);
-                break;
-            }
-        }
+        let Some(idx) = self.children.iter().position(Option::is_none) else {
+            return;
+        };
+        self.len += 1;
+        self.keys[key as usize] = slot(idx);
+        self.children[idx] = Some(child);
     }
 
     fn child_ref(&self, key: u8) -> Option<&T> {
@@ -79,6 +77,25 @@ impl<T> Indices<T> for Indices48<T> {
             .map(|idx| self.children[idx].as_mut().expect("child must exist"))
     }
 
+    fn for_each_child_mut<'a, F>(&'a mut self, mut f: F)
+    where
+        T: 'a,
+        F: FnMut(u8, &'a mut T),
+    {
+        // The children are stored in the order they were added, so they are taken out of their
+        // slots in the order of their keys.
+        let mut children: Vec<Option<&mut T>> =
+            self.children.iter_mut().map(Option::as_mut).collect();
+        for (key, &idx) in (0..=u8::MAX).zip(self.keys.iter()) {
+            if idx > 0 {
+                f(
+                    key,
+                    children[idx as usize - 1].take().expect("child must exist"),
+                );
+            }
+        }
+    }
+
     fn min(&self) -> Option<&T> {
         self.keys.iter().find(|&&idx| idx > 0).map(|&idx| {
             self.children[idx as usize - 1]
@@ -96,14 +113,14 @@ impl<T> Indices<T> for Indices48<T> {
     }
 }
 
-impl<T> From<&mut Indices16<T>> for Indices48<T> {
-    fn from(other: &mut Indices16<T>) -> Self {
+impl<T, const C: usize, const D: usize> From<&mut Indices16<T, D>> for Indices48<T, C> {
+    fn from(other: &mut Indices16<T, D>) -> Self {
         let mut indices = Self::default();
-        for idx in 0..other.len {
-            let key = other.keys[idx as usize];
-            let child = other.children[idx as usize].take();
-            indices.keys[key as usize] = idx + 1;
-            indices.children[idx as usize] = child;
+        for idx in 0..other.len as usize {
+            let key = other.keys[idx];
+            let child = other.children[idx].take();
+            indices.keys[key as usize] = slot(idx);
+            indices.children[idx] = child;
         }
         indices.len = other.len;
         other.len = 0;
@@ -111,7 +128,7 @@ impl<T> From<&mut Indices16<T>> for Indices48<T> {
     }
 }
 
-impl<T> From<&mut Indices256<T>> for Indices48<T> {
+impl<T, const C: usize> From<&mut Indices256<T>> for Indices48<T, C> {
     fn from(other: &mut Indices256<T>) -> Self {
         let mut indices = Self::default();
         for key in 0..=255 {
@@ -126,14 +143,19 @@ impl<T> From<&mut Indices256<T>> for Indices48<T> {
     }
 }
 
+/// Returns the number stored in the keys for the child at the given position of the array.
+fn slot(idx: usize) -> u8 {
+    u8::try_from(idx + 1).expect("the capacity must be less than 256")
+}
+
 /// An iterator over the indices and their children.
 #[derive(Debug)]
-pub struct Iter<'a, T> {
-    indices: &'a Indices48<T>,
+pub struct Iter<'a, T, const C: usize> {
+    indices: &'a Indices48<T, C>,
     key: u16,
 }
 
-impl<'a, T> Iterator for Iter<'a, T> {
+impl<'a, T, const C: usize> Iterator for Iter<'a, T, C> {
     type Item = (u8, &'a T);
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -0,0 +1,201 @@
+//! Searching the sorted keys of [`Indices16`](super::Indices16) with SIMD instructions.
+//!
+//! `std::simd` is not stable, so the keys are compared with the intrinsics of `std::arch`, which
+//! need `unsafe` code and are only compiled with the `unsafe_opt` feature. The search is selected
+//! by the features of the CPU the first time it runs, and kept for every later search:
+//!
+//! - On x86 and x86-64, AVX2 is preferred over SSE2. The 16 keys fit into a single 128-bit
+//!   register either way, but the AVX2 variant is compiled to the VEX encoding, which does not mix
+//!   legacy SSE with the AVX code of the rest of a program.
+//! - On 64-bit ARM, NEON is used, which compares unsigned bytes without flipping their sign bits.
+//! - On every other CPU, and without the feature, the keys are binary searched.
+//!
+//! The tests compare every search the CPU supports with the binary search on every length, every
+//! key, and the byte values around the sign bit, and run in a reduced form under Miri, with
+//! `cargo +nightly miri test --features unsafe_opt simd`, or under a sanitizer, with
+//! `RUSTFLAGS=-Zsanitizer=address cargo +nightly test --features unsafe_opt simd`.
+
+/// A search of the first `len` of 16 sorted keys for a key.
+#[cfg(any(test, feature = "unsafe_opt"))]
+type Search16 = fn(&[u8; 16], usize, u8) -> Result<usize, usize>;
+
+/// Searches the first `len` keys for the given key, with the same result as a binary search.
+pub fn search16(keys: &[u8; 16], len: usize, key: u8) -> Result<usize, usize> {
+    #[cfg(feature = "unsafe_opt")]
+    {
+        static SELECTED: std::sync::OnceLock<Search16> = std::sync::OnceLock::new();
+        SELECTED.get_or_init(|| supported()[0])(keys, len, key)
+    }
+    #[cfg(not(feature = "unsafe_opt"))]
+    search16_binary(keys, len, key)
+}
+
+fn search16_binary(keys: &[u8; 16], len: usize, key: u8) -> Result<usize, usize> {
+    keys[..len].binary_search(&key)
+}
+
+/// Returns the searches the CPU supports, from the most to the least preferred, which always ends
+/// with the binary search.
+#[cfg(any(test, feature = "unsafe_opt"))]
+#[allow(clippy::vec_init_then_push, unused_mut)] // The vectorized searches depend on the target.
+fn supported() -> Vec<Search16> {
+    let mut searches: Vec<Search16> = Vec::new();
+    #[cfg(all(
+        feature = "unsafe_opt",
+        any(target_arch = "x86", target_arch = "x86_64")
+    ))]
+    {
+        if std::arch::is_x86_feature_detected!("avx2") {
+            // SAFETY: The CPU supports AVX2, as checked above.
+            searches.push(|keys, len, key| unsafe { search16_avx2(keys, len, key) });
+        }
+        if std::arch::is_x86_feature_detected!("sse2") {
+            // SAFETY: The CPU supports SSE2, as checked above.
+            searches.push(|keys, len, key| unsafe { search16_sse2(keys, len, key) });
+        }
+    }
+    #[cfg(all(feature = "unsafe_opt", target_arch = "aarch64"))]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        // SAFETY: The CPU supports NEON, as checked above.
+        searches.push(|keys, len, key| unsafe { search16_neon(keys, len, key) });
+    }
+    searches.push(search16_binary);
+    searches
+}
+
+/// Defines a search with the SSE2 intrinsics, compiled for the given target feature.
+#[cfg(all(
+    feature = "unsafe_opt",
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+macro_rules! search16_x86 {
+    ($name:ident, $feature:literal) => {
+        #[target_feature(enable = $feature)]
+        fn $name(keys: &[u8; 16], len: usize, key: u8) -> Result<usize, usize> {
+            #[cfg(target_arch = "x86")]
+            use std::arch::x86::{
+                _mm_cmpeq_epi8, _mm_cmplt_epi8, _mm_loadu_si128, _mm_movemask_epi8, _mm_set1_epi8,
+                _mm_xor_si128,
+            };
+            #[cfg(target_arch = "x86_64")]
+            use std::arch::x86_64::{
+                _mm_cmpeq_epi8, _mm_cmplt_epi8, _mm_loadu_si128, _mm_movemask_epi8, _mm_set1_epi8,
+                _mm_xor_si128,
+            };
+
+            // SAFETY: The array holds 16 bytes, which is what the unaligned load reads.
+            let keys = unsafe { _mm_loadu_si128(keys.as_ptr().cast()) };
+            let needle = _mm_set1_epi8(i8::from_ne_bytes([key]));
+            // Only the first `len` keys are in use.
+            let valid = (1_u32 << len) - 1;
+            let equal = _mm_movemask_epi8(_mm_cmpeq_epi8(keys, needle)).cast_unsigned() & valid;
+            if equal != 0 {
+                return Ok(equal.trailing_zeros() as usize);
+            }
+            // The comparison is signed, so flipping the sign bits makes it compare the bytes as
+            // unsigned.
+            let flip = _mm_set1_epi8(i8::MIN);
+            let less = _mm_cmplt_epi8(_mm_xor_si128(keys, flip), _mm_xor_si128(needle, flip));
+            let less = _mm_movemask_epi8(less).cast_unsigned() & valid;
+            Err(less.count_ones() as usize)
+        }
+    };
+}
+
+#[cfg(all(
+    feature = "unsafe_opt",
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+search16_x86!(search16_sse2, "sse2");
+
+#[cfg(all(
+    feature = "unsafe_opt",
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+search16_x86!(search16_avx2, "avx2");
+
+#[cfg(all(feature = "unsafe_opt", target_arch = "aarch64"))]
+#[target_feature(enable = "neon")]
+#[allow(clippy::cast_possible_truncation)] // There are at most 16 keys.
+fn search16_neon(keys: &[u8; 16], len: usize, key: u8) -> Result<usize, usize> {
+    use std::arch::aarch64::{
+        vaddvq_u8, vandq_u8, vceqq_u8, vcltq_u8, vdupq_n_u8, vget_lane_u64, vld1q_u8,
+        vreinterpret_u64_u8, vreinterpretq_u16_u8, vshrn_n_u16,
+    };
+
+    const LANES: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+    // SAFETY: Both arrays hold 16 bytes, which is what the loads read.
+    let (keys, lanes) = unsafe { (vld1q_u8(keys.as_ptr()), vld1q_u8(LANES.as_ptr())) };
+    let needle = vdupq_n_u8(key);
+    // Only the first `len` keys are in use.
+    let valid = vcltq_u8(lanes, vdupq_n_u8(len as u8));
+    let equal = vandq_u8(vceqq_u8(keys, needle), valid);
+    // Narrowing every 16-bit lane by 4 bits leaves 4 bits of the mask for every key.
+    let equal = vreinterpret_u64_u8(vshrn_n_u16::<4>(vreinterpretq_u16_u8(equal)));
+    let equal = vget_lane_u64::<0>(equal);
+    if equal != 0 {
+        return Ok(equal.trailing_zeros() as usize / 4);
+    }
+    let less = vandq_u8(vcltq_u8(keys, needle), valid);
+    Err(usize::from(vaddvq_u8(vandq_u8(less, vdupq_n_u8(1)))))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::{search16, supported};
+
+    /// Check the search of every key in the first `len` keys against a binary search, with the
+    /// selected search and with every other search the CPU supports.
+    fn check(keys: &[u8; 16], len: usize) {
+        let searches = supported();
+        for key in 0..=u8::MAX {
+            let expected = keys[..len].binary_search(&key);
+            for search in searches
+                .iter()
+                .copied()
+                .chain([search16 as super::Search16])
+            {
+                match search(keys, len, key) {
+                    // Duplicate keys do not occur in the indices, where any match is fine.
+                    Ok(idx) => assert_eq!(keys[idx], key),
+                    Err(idx) => assert_eq!(Err(idx), expected),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_search16() {
+        let mut rng = rand::thread_rng();
+        let rounds = if cfg!(miri) { 2 } else { 100 };
+        for len in 0..=16 {
+            for _ in 0..rounds {
+                let mut keys = [0; 16];
+                rng.fill(&mut keys);
+                keys[..len].sort_unstable();
+                check(&keys, len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_search16_edges() {
+        // Keys around the sign bit, where a signed comparison would go wrong, and at both ends.
+        let edges = [0, 1, 0x7E, 0x7F, 0x80, 0x81, 0xFE, 0xFF];
+        // Every subset of the edges, padded with unused keys that must be ignored.
+        let step = if cfg!(miri) { 37 } else { 1 };
+        for mask in (0_u32..1 << edges.len()).step_by(step) {
+            let used: Vec<u8> = (0..edges.len())
+                .filter(|&bit| mask & (1 << bit) != 0)
+                .map(|bit| edges[bit])
+                .collect();
+            for padding in [0, 0x80, 0xFF] {
+                let mut keys = [padding; 16];
+                keys[..used.len()].copy_from_slice(&used);
+                check(&keys, used.len());
+            }
+        }
+    }
+}
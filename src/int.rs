@@ -0,0 +1,205 @@
+//! A tree specialized for fixed-size integer keys.
+//!
+//! All keys of an [`IntArt`] have the same width, so none of them is a prefix of another and the
+//! depth of the tree is bounded by the width of the key. The partial keys of the inner nodes are
+//! wide enough to hold any prefix inline, so no prefix is ever moved to the heap. Searching never
+//! goes through [`BytesComparable::bytes`]: the byte key at each inner node is shifted out of the
+//! integer, the prefixes are skipped by adding their lengths to the depth without comparing them,
+//! and the integers are compared once at the leaf.
+//!
+//! [`ArtU64Map`] is the tree for `u64` identifiers, which holds at most 8 inner nodes on the path
+//! to any of its leaves. Inserts and deletes go through the generic tree and encode the key with
+//! [`BytesComparable::bytes`], because their cost is dominated by growing and shrinking nodes
+//! rather than by encoding the key.
+
+use crate::{BytesComparable, ART};
+
+mod private {
+    pub trait Sealed: Copy {
+        /// The number of bytes of the key.
+        const WIDTH: usize;
+
+        /// Returns the byte at the given index of the big-endian encoding of the key, which must be
+        /// less than [`Self::WIDTH`].
+        fn byte_at(self, index: usize) -> u8;
+    }
+
+    macro_rules! impl_sealed {
+        ($($ty:ty),*) => {
+            $(
+                impl Sealed for $ty {
+                    const WIDTH: usize = std::mem::size_of::<Self>();
+
+                    #[allow(clippy::cast_possible_truncation)] // Only the lowest byte is kept.
+                    fn byte_at(self, index: usize) -> u8 {
+                        (self >> (8 * (Self::WIDTH - 1 - index))) as u8
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_sealed!(u32, u64);
+}
+
+/// An unsigned integer type that can be used as the key of an [`IntArt`].
+pub trait IntKey: BytesComparable + Copy + Eq + private::Sealed {}
+
+impl IntKey for u32 {}
+impl IntKey for u64 {}
+
+/// The number of prefix bytes stored in the inner nodes, which covers every prefix of a `u64`.
+const PREFIX_LEN: usize = 8;
+
+/// An adaptive radix tree whose keys are `u32` or `u64`.
+#[derive(Debug)]
+pub struct IntArt<K, V> {
+    tree: ART<K, V, PREFIX_LEN>,
+}
+
+/// An adaptive radix tree whose keys are `u64` identifiers, which are searched for without being
+/// encoded, see the [module documentation](self).
+pub type ArtU64Map<V> = IntArt<u64, V>;
+
+impl<K, V> IntArt<K, V> {
+    /// Create an empty tree.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { tree: ART::new() }
+    }
+
+    /// Return the tree for reading.
+    #[must_use]
+    pub const fn tree(&self) -> &ART<K, V, PREFIX_LEN> {
+        &self.tree
+    }
+}
+
+impl<K, V> Default for IntArt<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> IntArt<K, V>
+where
+    K: IntKey,
+{
+    /// Search for the value associated with the given key.
+    pub fn search(&self, key: K) -> Option<&V> {
+        self.tree
+            .root
+            .as_ref()
+            .and_then(|root| root.descend_optimistic(K::WIDTH, |depth| key.byte_at(depth)))
+            .filter(|leaf| leaf.key == key)
+            .map(|leaf| &leaf.value)
+    }
+
+    /// Insert the given key-value pair into the tree, returning the value previously associated
+    /// with the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.tree.insert(key, value)
+    }
+
+    /// Delete the value associated with the given key.
+    pub fn delete(&mut self, key: K) -> Option<V> {
+        self.tree.delete(&key)
+    }
+
+    /// Find the minimum key-value pair in the tree.
+    #[must_use]
+    pub fn min(&self) -> Option<(K, &V)> {
+        self.tree.min().map(|(key, value)| (*key, value))
+    }
+
+    /// Find the maximum key-value pair in the tree.
+    #[must_use]
+    pub fn max(&self) -> Option<(K, &V)> {
+        self.tree.max().map(|(key, value)| (*key, value))
+    }
+
+    /// Return an iterator over the key-value pairs of the tree in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (K, &V)> + '_ {
+        self.tree.iter().map(|(key, value)| (*key, value))
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for IntArt<K, V>
+where
+    K: IntKey,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self {
+            tree: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::Rng;
+
+    use super::{private::Sealed, ArtU64Map, IntArt};
+
+    #[test]
+    fn test_int_art() {
+        let mut rng = rand::thread_rng();
+        let mut tree = IntArt::<u64, u64>::new();
+        let mut expected = BTreeMap::new();
+        for _ in 0..10000 {
+            // Small keys share long prefixes of zero bytes.
+            let key = if rng.gen() {
+                rng.gen::<u64>()
+            } else {
+                rng.gen_range(0..1000)
+            };
+            assert_eq!(tree.insert(key, key + 1), expected.insert(key, key + 1));
+        }
+        for _ in 0..10000 {
+            let key = rng.gen_range(0..2000);
+            assert_eq!(tree.search(key), expected.get(&key));
+        }
+        for (key, value) in &expected {
+            assert_eq!(tree.search(*key), Some(value));
+        }
+        assert!(tree.iter().eq(expected.iter().map(|(k, v)| (*k, v))));
+        assert_eq!(tree.min(), expected.first_key_value().map(|(k, v)| (*k, v)));
+        assert_eq!(tree.max(), expected.last_key_value().map(|(k, v)| (*k, v)));
+        for key in 0..1000 {
+            assert_eq!(tree.delete(key), expected.remove(&key));
+            assert_eq!(tree.search(key), None);
+        }
+
+        // No key is a prefix of another, so every leaf is at most 8 inner nodes deep.
+        assert!(tree.tree().height() <= 8);
+
+        let tree: IntArt<u32, u32> = (0..100).map(|i| (i * 7, i)).collect();
+        assert_eq!(tree.search(70), Some(&10));
+        assert_eq!(tree.search(71), None);
+    }
+
+    #[test]
+    fn test_byte_at() {
+        let key = 0x0102_0304_u32;
+        assert!((0..4).map(|i| key.byte_at(i)).eq(key.to_be_bytes()));
+        let key = 0x0102_0304_0506_0708_u64;
+        assert!((0..8).map(|i| key.byte_at(i)).eq(key.to_be_bytes()));
+    }
+
+    #[test]
+    fn test_u64_map() {
+        let mut map = ArtU64Map::new();
+        for id in (0..100_000_u64).map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15)) {
+            assert_eq!(map.insert(id, id.count_ones()), None);
+        }
+        assert_eq!(map.tree().len(), 100_000);
+        assert!(map.tree().height() <= 8);
+        let id = 4242_u64.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        assert_eq!(map.search(id), Some(&id.count_ones()));
+        assert_eq!(map.search(id + 1), None);
+        assert_eq!(map.delete(id), Some(id.count_ones()));
+        assert_eq!(map.search(id), None);
+    }
+}
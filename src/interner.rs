@@ -0,0 +1,184 @@
+//! A string interner built on top of the tree.
+//!
+//! Each distinct byte string is assigned a [`Symbol`] that never changes for the lifetime of the
+//! [`Interner`]. The bytes of a string are stored once and shared between the tree, which maps
+//! strings to symbols, and the symbol table, which maps symbols back to strings. Because the tree
+//! keeps the strings sorted, the interned strings can also be visited in byte order, which gives
+//! deterministic output for dumps and debugging.
+
+use std::{borrow::Borrow, sync::Arc};
+
+use crate::{BytesComparable, ART};
+
+/// A handle to a string stored in an [`Interner`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// Return the number identifying the symbol. Symbols are numbered in the order their strings
+    /// were first interned, starting from zero.
+    #[must_use]
+    pub const fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<Symbol> for u32 {
+    fn from(symbol: Symbol) -> Self {
+        symbol.0
+    }
+}
+
+/// The bytes of an interned string, shared between the tree and the symbol table.
+#[derive(Debug, Clone)]
+struct Interned(Arc<[u8]>);
+
+impl Borrow<[u8]> for Interned {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl BytesComparable for Interned {
+    type Target<'a> = &'a [u8];
+
+    fn bytes(&self) -> Self::Target<'_> {
+        &self.0
+    }
+}
+
+/// A map between byte strings and [`Symbol`]s.
+#[derive(Default)]
+pub struct Interner {
+    symbols: ART<Interned, Symbol>,
+    strings: Vec<Interned>,
+}
+
+impl Interner {
+    /// Create an empty interner.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            symbols: ART::new(),
+            strings: Vec::new(),
+        }
+    }
+
+    /// Return the number of interned strings.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Return true if no string has been interned.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Return the symbol of the given string, interning it if it has not been seen before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than `u32::MAX` strings are interned.
+    pub fn intern<B>(&mut self, string: B) -> Symbol
+    where
+        B: AsRef<[u8]>,
+    {
+        let string = string.as_ref();
+        if let Some(symbol) = self.get(string) {
+            return symbol;
+        }
+        let symbol = Symbol(
+            u32::try_from(self.strings.len()).expect("the number of symbols must fit in a u32"),
+        );
+        let interned = Interned(Arc::from(string));
+        self.strings.push(interned.clone());
+        self.symbols.insert(interned, symbol);
+        symbol
+    }
+
+    /// Return the symbol of the given string if it has been interned.
+    pub fn get<B>(&self, string: B) -> Option<Symbol>
+    where
+        B: AsRef<[u8]>,
+    {
+        self.symbols.search(string.as_ref()).copied()
+    }
+
+    /// Return the string of the given symbol, or `None` if it was created by another interner.
+    #[must_use]
+    pub fn resolve(&self, symbol: Symbol) -> Option<&[u8]> {
+        self.strings
+            .get(symbol.0 as usize)
+            .map(|interned| interned.0.as_ref())
+    }
+
+    /// Call the closure on every interned string and its symbol, in byte order of the strings.
+    pub fn for_each_sorted<F>(&self, mut f: F)
+    where
+        F: FnMut(Symbol, &[u8]),
+    {
+        let Ok(()) = self.symbols.try_for_each(|interned, symbol| {
+            f(*symbol, &interned.0);
+            Ok::<_, std::convert::Infallible>(())
+        });
+    }
+}
+
+impl std::fmt::Debug for Interner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut map = f.debug_map();
+        self.for_each_sorted(|symbol, string| {
+            map.entry(&String::from_utf8_lossy(string), &symbol.0);
+        });
+        map.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interner;
+
+    #[test]
+    fn test_intern_and_resolve() {
+        let mut interner = Interner::new();
+        let words = ["select", "selection", "self", "sell", "select", "", "self"];
+        let symbols: Vec<_> = words.iter().map(|word| interner.intern(word)).collect();
+        assert_eq!(interner.len(), 5);
+        assert_eq!(symbols[0], symbols[4]);
+        assert_eq!(symbols[2], symbols[6]);
+        for (word, symbol) in words.iter().zip(&symbols) {
+            assert_eq!(interner.resolve(*symbol), Some(word.as_bytes()));
+            assert_eq!(interner.get(word), Some(*symbol));
+        }
+        assert_eq!(
+            symbols.iter().map(|s| s.as_u32()).collect::<Vec<_>>(),
+            [0, 1, 2, 3, 0, 4, 2]
+        );
+        assert_eq!(interner.get("sel"), None);
+    }
+
+    #[test]
+    fn test_for_each_sorted() {
+        let mut interner = Interner::new();
+        for word in ["pear", "apple", "peach", "apricot"] {
+            interner.intern(word);
+        }
+        let mut sorted = Vec::new();
+        interner.for_each_sorted(|symbol, string| sorted.push((string.to_vec(), symbol.as_u32())));
+        assert_eq!(
+            sorted,
+            [
+                (b"apple".to_vec(), 1),
+                (b"apricot".to_vec(), 3),
+                (b"peach".to_vec(), 2),
+                (b"pear".to_vec(), 0),
+            ]
+        );
+        assert_eq!(
+            format!("{interner:?}"),
+            r#"{"apple": 1, "apricot": 3, "peach": 2, "pear": 0}"#
+        );
+    }
+}
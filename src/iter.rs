@@ -0,0 +1,832 @@
+//! Iterators over the entries of a tree in key order.
+
+use std::iter::FusedIterator;
+
+use crate::{
+    node::{Leaf, Owned, Path},
+    BytesComparable, ART,
+};
+
+/// An iterator over the key-value pairs of a tree in key order, see [`ART::iter`].
+pub struct Iter<
+    'a,
+    K,
+    V,
+    const N: usize = 10,
+    const C4: usize = 4,
+    const C16: usize = 16,
+    const C48: usize = 48,
+> {
+    path: Path<'a, K, V, N, Owned, C4, C16, C48>,
+    /// The number of leaves that were not yielded yet.
+    remaining: usize,
+}
+
+impl<'a, K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> Iterator
+    for Iter<'a, K, V, N, C4, C16, C48>
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let leaf = self.path.leaf()?;
+        self.path.next();
+        self.remaining -= 1;
+        Some((&leaf.key, &leaf.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+
+    // `try_fold` can not be overridden on stable Rust, as its signature names the unstable `Try`
+    // trait, so only full scans through `fold` and the methods built on it, such as `for_each`
+    // and `count`, get to recurse over the children of the nodes.
+    fn fold<B, F>(self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, Self::Item) -> B,
+    {
+        self.path
+            .fold_rest(init, |acc, leaf| f(acc, (&leaf.key, &leaf.value)))
+    }
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> ExactSizeIterator
+    for Iter<'_, K, V, N, C4, C16, C48>
+{
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> FusedIterator
+    for Iter<'_, K, V, N, C4, C16, C48>
+{
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> std::fmt::Debug
+    for Iter<'_, K, V, N, C4, C16, C48>
+where
+    K: std::fmt::Debug,
+    V: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Iter")
+            .field(&self.path.leaf().map(|leaf| (&leaf.key, &leaf.value)))
+            .finish()
+    }
+}
+
+/// An iterator over the encoded keys of a tree and their values in key order, see
+/// [`ART::iter_encoded`].
+pub struct Encoded<
+    'a,
+    K,
+    V,
+    const N: usize = 10,
+    const C4: usize = 4,
+    const C16: usize = 16,
+    const C48: usize = 48,
+> {
+    path: Path<'a, K, V, N, Owned, C4, C16, C48>,
+    /// The number of leaves that were not yielded yet.
+    remaining: usize,
+}
+
+impl<'a, K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> Iterator
+    for Encoded<'a, K, V, N, C4, C16, C48>
+where
+    K: BytesComparable<Target<'a> = &'a [u8]>,
+{
+    type Item = (&'a [u8], &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let leaf = self.path.leaf()?;
+        self.path.next();
+        self.remaining -= 1;
+        Some((leaf.key.bytes(), &leaf.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+
+    fn fold<B, F>(self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, Self::Item) -> B,
+    {
+        self.path
+            .fold_rest(init, |acc, leaf| f(acc, (leaf.key.bytes(), &leaf.value)))
+    }
+}
+
+impl<'a, K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    ExactSizeIterator for Encoded<'a, K, V, N, C4, C16, C48>
+where
+    K: BytesComparable<Target<'a> = &'a [u8]>,
+{
+}
+
+impl<'a, K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> FusedIterator
+    for Encoded<'a, K, V, N, C4, C16, C48>
+where
+    K: BytesComparable<Target<'a> = &'a [u8]>,
+{
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> std::fmt::Debug
+    for Encoded<'_, K, V, N, C4, C16, C48>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Encoded").finish_non_exhaustive()
+    }
+}
+
+/// An iterator over the key-value pairs of a tree in key order, annotated with the position of
+/// their leaves, see [`ART::iter_annotated`].
+pub struct Annotated<
+    'a,
+    K,
+    V,
+    const N: usize = 10,
+    const C4: usize = 4,
+    const C16: usize = 16,
+    const C48: usize = 48,
+> {
+    path: Path<'a, K, V, N, Owned, C4, C16, C48>,
+    /// The number of leaves that were not yielded yet.
+    remaining: usize,
+}
+
+impl<'a, K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> Iterator
+    for Annotated<'a, K, V, N, C4, C16, C48>
+{
+    type Item = (usize, Option<u8>, &'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let leaf = self.path.leaf()?;
+        let item = (
+            self.path.depth(),
+            self.path.byte_key(),
+            &leaf.key,
+            &leaf.value,
+        );
+        self.path.next();
+        self.remaining -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> ExactSizeIterator
+    for Annotated<'_, K, V, N, C4, C16, C48>
+{
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> FusedIterator
+    for Annotated<'_, K, V, N, C4, C16, C48>
+{
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> std::fmt::Debug
+    for Annotated<'_, K, V, N, C4, C16, C48>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Annotated").finish_non_exhaustive()
+    }
+}
+
+/// An iterator over the keys of a tree rebuilt from the paths to their leaves, together with their
+/// values, see [`ART::key_paths`].
+pub struct KeyPaths<
+    'a,
+    K,
+    V,
+    const N: usize = 10,
+    const C4: usize = 4,
+    const C16: usize = 16,
+    const C48: usize = 48,
+> {
+    path: Path<'a, K, V, N, Owned, C4, C16, C48>,
+    /// The number of leaves that were not yielded yet.
+    remaining: usize,
+}
+
+impl<'a, K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> Iterator
+    for KeyPaths<'a, K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+    type Item = (Vec<u8>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let leaf = self.path.leaf()?;
+        let mut key = Vec::new();
+        self.path.rebuild_key(&mut key);
+        self.path.next();
+        self.remaining -= 1;
+        Some((key, &leaf.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> ExactSizeIterator
+    for KeyPaths<'_, K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> FusedIterator
+    for KeyPaths<'_, K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> std::fmt::Debug
+    for KeyPaths<'_, K, V, N, C4, C16, C48>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyPaths").finish_non_exhaustive()
+    }
+}
+
+/// An iterator over the entries whose keys are prefixes of a query, from the shortest key to the
+/// longest, see [`ART::prefixes_of`].
+#[derive(Debug)]
+pub struct PrefixesOf<'a, K, V> {
+    leaves: std::vec::IntoIter<&'a Leaf<K, V>>,
+}
+
+impl<'a, K, V> Iterator for PrefixesOf<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.leaves.next().map(|leaf| (&leaf.key, &leaf.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.leaves.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for PrefixesOf<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.leaves.next_back().map(|leaf| (&leaf.key, &leaf.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for PrefixesOf<'_, K, V> {}
+
+impl<K, V> FusedIterator for PrefixesOf<'_, K, V> {}
+
+/// An iterator over the keys and mutable values of a tree in key order, see [`ART::iter_mut`].
+#[derive(Debug)]
+pub struct IterMut<'a, K, V> {
+    leaves: std::vec::IntoIter<&'a mut Leaf<K, V>>,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.leaves.next().map(|leaf| (&leaf.key, &mut leaf.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.leaves.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IterMut<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.leaves
+            .next_back()
+            .map(|leaf| (&leaf.key, &mut leaf.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+impl<K, V> FusedIterator for IterMut<'_, K, V> {}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    ART<K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+    /// Return an iterator over the keys and mutable values of the tree in key order. The leaves
+    /// are collected in a single pass over the tree before the first entry is yielded.
+    #[must_use]
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        let mut leaves = Vec::with_capacity(self.len);
+        if let Some(root) = &mut self.root {
+            root.leaves_mut(&mut leaves);
+        }
+        IterMut {
+            leaves: leaves.into_iter(),
+        }
+    }
+
+    /// Return an iterator over every entry whose encoded key is a prefix of the query, including
+    /// the query itself, from the shortest key to the longest. The entries are collected in a
+    /// single descent along the path of the query, so the last entry is the longest prefix match.
+    #[must_use]
+    pub fn prefixes_of(&self, query: &[u8]) -> PrefixesOf<'_, K, V> {
+        let mut leaves = Vec::new();
+        if let Some(root) = &self.root {
+            root.prefixes_of(query, &mut leaves);
+        }
+        PrefixesOf {
+            leaves: leaves.into_iter(),
+        }
+    }
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    ART<K, V, N, C4, C16, C48>
+{
+    /// Return an iterator over the key-value pairs of the tree in key order.
+    #[must_use]
+    pub fn iter(&self) -> Iter<'_, K, V, N, C4, C16, C48> {
+        let mut path = Path::new(self.root.as_ref());
+        path.seek_first();
+        Iter {
+            path,
+            remaining: self.len,
+        }
+    }
+
+    /// Return an iterator over the encoded keys of the tree and their values in key order. The
+    /// keys are borrowed from the leaves as they are encoded, so they can be written out without
+    /// decoding or copying them. This is only available for keys that store their encoded form,
+    /// such as strings and byte strings, while fixed-width keys such as integers encode into an
+    /// array on the stack, see [`BytesComparable`].
+    #[must_use]
+    pub fn iter_encoded<'a>(&'a self) -> Encoded<'a, K, V, N, C4, C16, C48>
+    where
+        K: BytesComparable<Target<'a> = &'a [u8]>,
+    {
+        let mut path = Path::new(self.root.as_ref());
+        path.seek_first();
+        Encoded {
+            path,
+            remaining: self.len,
+        }
+    }
+
+    /// Return an iterator over the key-value pairs of the tree in key order, together with the
+    /// number of inner nodes above their leaves and the byte key under which their leaves are
+    /// stored in their parents, which is `None` for a leaf at the root. This shows how the keys are
+    /// distributed over the tree.
+    #[must_use]
+    pub fn iter_annotated(&self) -> Annotated<'_, K, V, N, C4, C16, C48> {
+        let mut path = Path::new(self.root.as_ref());
+        path.seek_first();
+        Annotated {
+            path,
+            remaining: self.len,
+        }
+    }
+
+    /// Return an iterator over the encoded keys of the tree and their values in key order, where
+    /// every key is rebuilt from the prefixes and byte keys on the path to its leaf. The rebuilt
+    /// key matches the encoded key of the leaf unless the tree is corrupted, which makes it useful
+    /// to validate the encoding of keys and the structure of the tree.
+    #[must_use]
+    pub fn key_paths(&self) -> KeyPaths<'_, K, V, N, C4, C16, C48> {
+        let mut path = Path::new(self.root.as_ref());
+        path.seek_first();
+        KeyPaths {
+            path,
+            remaining: self.len,
+        }
+    }
+}
+
+impl<'a, K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> IntoIterator
+    for &'a ART<K, V, N, C4, C16, C48>
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, N, C4, C16, C48>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> IntoIterator
+    for &'a mut ART<K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// Defines an iterator that owns the leaves moved out of a tree and maps each of them to an item.
+macro_rules! owning_iter {
+    ($(#[$attr:meta])* $name:ident, $item:ty, $leaf:ident => $map:expr) => {
+        $(#[$attr])*
+        #[derive(Debug)]
+        pub struct $name<K, V> {
+            leaves: std::vec::IntoIter<Leaf<K, V>>,
+        }
+
+        impl<K, V> Iterator for $name<K, V> {
+            type Item = $item;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                self.leaves.next().map(|$leaf| $map)
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.leaves.size_hint()
+            }
+        }
+
+        impl<K, V> DoubleEndedIterator for $name<K, V> {
+            fn next_back(&mut self) -> Option<Self::Item> {
+                self.leaves.next_back().map(|$leaf| $map)
+            }
+        }
+
+        impl<K, V> ExactSizeIterator for $name<K, V> {}
+
+        impl<K, V> std::iter::FusedIterator for $name<K, V> {}
+    };
+}
+
+owning_iter!(
+    /// An iterator moving the key-value pairs out of a tree in key order, see
+    /// [`ART::into_iter`](IntoIterator::into_iter).
+    IntoIter,
+    (K, V),
+    leaf => (leaf.key, leaf.value)
+);
+
+owning_iter!(
+    /// An iterator moving the keys out of a tree in key order, see [`ART::into_keys`].
+    IntoKeys,
+    K,
+    leaf => leaf.key
+);
+
+owning_iter!(
+    /// An iterator moving the values out of a tree in key order, see [`ART::into_values`].
+    IntoValues,
+    V,
+    leaf => leaf.value
+);
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    ART<K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+    /// Moves the leaves out of the tree in key order.
+    fn into_leaves(self) -> std::vec::IntoIter<Leaf<K, V>> {
+        let mut leaves = Vec::with_capacity(self.len);
+        if let Some(root) = self.root {
+            root.into_leaves(&mut leaves);
+        }
+        leaves.into_iter()
+    }
+
+    /// Return the entries of the tree in key order, cloning them into a vector allocated once.
+    #[must_use]
+    pub fn to_vec(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.iter()
+            .fold(Vec::with_capacity(self.len), |mut entries, (key, value)| {
+                entries.push((key.clone(), value.clone()));
+                entries
+            })
+    }
+
+    /// Consume the tree and return its entries in key order.
+    #[must_use]
+    pub fn into_sorted_vec(self) -> Vec<(K, V)> {
+        self.into_iter().collect()
+    }
+
+    /// Consume the tree and return an iterator over its keys in key order.
+    #[must_use]
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys {
+            leaves: self.into_leaves(),
+        }
+    }
+
+    /// Consume the tree and return an iterator over its values in key order.
+    #[must_use]
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues {
+            leaves: self.into_leaves(),
+        }
+    }
+
+    /// Remove every entry from the tree, handing the key-value pairs to the closure in key order.
+    /// Unlike draining the tree through [`ART::into_iter`], the entries are not moved into a
+    /// vector first, so the closure can flush or recycle them while the tree is torn down.
+    ///
+    /// The tree is empty as soon as this is called, so if the closure panics, the entries it was
+    /// not given yet are dropped.
+    pub fn clear_with<F>(&mut self, mut f: F)
+    where
+        F: FnMut(K, V),
+    {
+        self.len = 0;
+        if let Some(root) = self.root.take() {
+            root.into_each_leaf(&mut |leaf| f(leaf.key, leaf.value));
+        }
+    }
+
+    /// Consume the tree, handing its key-value pairs to the closure in key order, see
+    /// [`ART::clear_with`].
+    pub fn into_each<F>(mut self, f: F)
+    where
+        F: FnMut(K, V),
+    {
+        self.clear_with(f);
+    }
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> IntoIterator
+    for ART<K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            leaves: self.into_leaves(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        rc::Rc,
+    };
+
+    use rand::Rng;
+
+    use crate::{BytesComparable, ART};
+
+    #[test]
+    fn test_iter() {
+        let mut rng = rand::thread_rng();
+        let mut tree = ART::<i32, u32>::new();
+        let mut expected = BTreeMap::new();
+        for _ in 0..5000 {
+            let (key, value) = (rng.gen(), rng.gen());
+            tree.insert(key, value);
+            expected.insert(key, value);
+        }
+        assert!(tree.iter().eq(expected.iter()));
+        let mut iter = tree.iter();
+        assert_eq!(iter.len(), expected.len());
+        iter.by_ref().take(100).for_each(drop);
+        assert_eq!(
+            iter.size_hint(),
+            (expected.len() - 100, Some(expected.len() - 100))
+        );
+        let mut scan = tree.scan::<i32, _>(..);
+        assert_eq!(scan.size_hint(), (0, Some(expected.len())));
+        scan.by_ref().take(100).for_each(drop);
+        assert_eq!(scan.size_hint(), (0, Some(expected.len() - 100)));
+        scan.by_ref().for_each(drop);
+        assert_eq!(scan.size_hint(), (0, Some(0)));
+        assert_eq!(tree.iter_annotated().len(), expected.len());
+        assert_eq!(tree.key_paths().skip(10).len(), expected.len() - 10);
+        let mut iter = (&tree).into_iter();
+        iter.by_ref().for_each(drop);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.len(), 0);
+        assert_eq!(ART::<i32, u32>::new().iter().next(), None);
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let build = || -> ART<String, u32> {
+            [("b", 2), ("a", 1), ("c", 3)]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect()
+        };
+        let keys: Vec<String> = build().into_keys().collect();
+        assert_eq!(keys, ["a", "b", "c"]);
+        let values: Vec<u32> = build().into_values().rev().collect();
+        assert_eq!(values, [3, 2, 1]);
+        let tree = build();
+        let mut iter = tree.into_iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next(), Some(("a".to_string(), 1)));
+        assert_eq!(iter.next_back(), Some(("c".to_string(), 3)));
+        assert_eq!(iter.collect::<Vec<_>>(), [("b".to_string(), 2)]);
+        assert_eq!(ART::<u8, u8>::new().into_keys().next(), None);
+    }
+
+    #[test]
+    fn test_clear_with() {
+        let mut tree: ART<String, usize> = (0..1000).map(|i| (format!("key{i}"), i)).collect();
+        let expected: Vec<_> = tree.iter().map(|(k, v)| (k.clone(), *v)).collect();
+        let mut flushed = Vec::new();
+        tree.clear_with(|k, v| flushed.push((k, v)));
+        assert_eq!(flushed, expected);
+        assert!(tree.is_empty());
+        assert_eq!(tree.iter().next(), None);
+
+        // The tree can be reused after it was cleared.
+        tree.insert("a".to_string(), 1);
+        tree.insert("b".to_string(), 2);
+        let mut values = Vec::new();
+        tree.into_each(|_, v| values.push(v));
+        assert_eq!(values, [1, 2]);
+
+        // A panicking closure leaves the tree empty, and the remaining entries are dropped.
+        let mut tree: ART<u32, Rc<()>> = ART::new();
+        let value = Rc::new(());
+        for i in 0..100 {
+            tree.insert(i, Rc::clone(&value));
+        }
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            tree.clear_with(|k, _| assert!(k < 10, "flushing failed"));
+        }));
+        assert!(result.is_err());
+        assert!(tree.is_empty());
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn test_iter_fold() {
+        let mut rng = rand::thread_rng();
+        let mut tree = ART::<u16, u16, 1>::new();
+        let mut keys = BTreeSet::new();
+        for _ in 0..2000 {
+            let key = rng.gen();
+            tree.insert(key, key);
+            keys.insert(key);
+        }
+        let expected: Vec<_> = keys.into_iter().collect();
+        for skip in [0, 1, 7, 300, expected.len() - 1, expected.len()] {
+            let mut iter = tree.iter();
+            iter.by_ref().take(skip).for_each(drop);
+            let rest = iter.fold(Vec::new(), |mut acc, (k, _)| {
+                acc.push(*k);
+                acc
+            });
+            assert_eq!(rest, expected[skip..]);
+        }
+        assert_eq!(tree.iter().count(), expected.len());
+    }
+
+    #[test]
+    fn test_iter_encoded() {
+        let mut rng = rand::thread_rng();
+        let mut tree = ART::<Vec<u8>, usize>::new();
+        for idx in 0..2000 {
+            let key: Vec<u8> = (0..rng.gen_range(1..8))
+                .map(|_| rng.gen_range(1..=4))
+                .collect();
+            tree.insert(key, idx);
+        }
+        let expected: Vec<_> = tree.iter().map(|(k, v)| (k.as_slice(), v)).collect();
+        let mut encoded = tree.iter_encoded();
+        assert_eq!(encoded.len(), expected.len());
+        assert_eq!(encoded.next(), Some(expected[0]));
+        assert_eq!(encoded.collect::<Vec<_>>(), expected[1..]);
+
+        let tree: ART<String, ()> = [("b".to_string(), ()), ("a".to_string(), ())]
+            .into_iter()
+            .collect();
+        let keys: Vec<_> = tree.iter_encoded().map(|(key, ())| key).collect();
+        assert_eq!(keys, [b"a", b"b"]);
+    }
+
+    #[test]
+    fn test_iter_annotated() {
+        let mut tree = ART::<String, u32>::new();
+        tree.insert("a".to_string(), 1);
+        assert_eq!(
+            tree.iter_annotated().collect::<Vec<_>>(),
+            [(0, None, &"a".to_string(), &1)]
+        );
+        tree.insert("ab".to_string(), 2);
+        tree.insert("ac".to_string(), 3);
+        tree.insert("b".to_string(), 4);
+        let annotated: Vec<_> = tree
+            .iter_annotated()
+            .map(|(depth, byte_key, key, _)| (depth, byte_key, key.as_str()))
+            .collect();
+        assert_eq!(
+            annotated,
+            [
+                (2, Some(0), "a"),
+                (2, Some(b'b'), "ab"),
+                (2, Some(b'c'), "ac"),
+                (1, Some(b'b'), "b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_vec() {
+        let mut rng = rand::thread_rng();
+        let mut tree = ART::<u32, u32>::new();
+        let mut expected = BTreeMap::new();
+        for _ in 0..1000 {
+            let key = rng.gen_range(0..2000);
+            tree.insert(key, key * 2);
+            expected.insert(key, key * 2);
+        }
+        let expected: Vec<_> = expected.into_iter().collect();
+        let entries = tree.to_vec();
+        assert_eq!(entries, expected);
+        assert_eq!(entries.capacity(), expected.len());
+        assert_eq!(tree.into_sorted_vec(), expected);
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut tree: ART<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        let mut iter = tree.iter_mut();
+        assert_eq!(iter.len(), 1000);
+        assert_eq!(iter.next_back().map(|(k, v)| (*k, *v)), Some((999, 999)));
+        for (key, value) in iter {
+            *value = key * 2;
+        }
+        // The entry taken from the back is not modified.
+        let expected = (0..1000).map(|i| (i, if i == 999 { i } else { i * 2 }));
+        assert!(tree.iter().map(|(k, v)| (*k, *v)).eq(expected));
+        assert_eq!(ART::<u8, u8>::new().iter_mut().next(), None);
+    }
+
+    #[test]
+    fn test_prefixes_of() {
+        let mut rng = rand::thread_rng();
+        let mut tree = ART::<Vec<u8>, usize>::new();
+        for i in 0..2000 {
+            let key: Vec<u8> = (0..rng.gen_range(1..6))
+                .map(|_| rng.gen_range(b'a'..=b'c'))
+                .collect();
+            tree.insert(key, i);
+        }
+        for _ in 0..500 {
+            let query: Vec<u8> = (0..rng.gen_range(0..8))
+                .map(|_| rng.gen_range(b'a'..=b'd'))
+                .collect();
+            let mut expected: Vec<_> = tree
+                .iter()
+                .filter(|(key, _)| query.starts_with(key))
+                .collect();
+            expected.sort_by_key(|(key, _)| key.len());
+            assert!(tree.prefixes_of(&query).eq(expected));
+        }
+
+        let tree: ART<&str, ()> = ["config", "config.net", "config.net.dns", "configure"]
+            .into_iter()
+            .map(|key| (key, ()))
+            .collect();
+        let keys: Vec<_> = tree
+            .prefixes_of(b"config.net.dns.timeout")
+            .map(|(key, ())| *key)
+            .collect();
+        assert_eq!(keys, ["config", "config.net", "config.net.dns"]);
+        assert_eq!(tree.prefixes_of(b"con").count(), 0);
+    }
+
+    #[test]
+    fn test_key_paths() {
+        let mut rng = rand::thread_rng();
+        let mut tree = ART::<Vec<u8>, usize, 3>::new();
+        for idx in 0..3000 {
+            let len = rng.gen_range(0..24);
+            let key: Vec<u8> = (0..len).map(|_| rng.gen_range(1..=3)).collect();
+            tree.insert(key, idx);
+        }
+        let mut count = 0;
+        for ((key, value), (rebuilt, rebuilt_value)) in tree.iter().zip(tree.key_paths()) {
+            assert_eq!(key.bytes(), rebuilt.as_slice());
+            assert_eq!(value, rebuilt_value);
+            count += 1;
+        }
+        assert_eq!(count, tree.iter().count());
+    }
+}
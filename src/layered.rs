@@ -0,0 +1,377 @@
+//! A frozen tree with a mutable delta on top.
+//!
+//! A [`CompactArt`] is the fastest tree to search, but it can not be modified, and an [`ART`]
+//! accepts writes but is larger and slower to search. A [`LayeredArt`] combines both in the spirit
+//! of a log-structured merge tree with a single level: writes go to a small delta [`ART`], lookups
+//! search the delta before the frozen base, and [`LayeredArt::compact`] merges the delta into a new
+//! base in a single pass over the entries of both layers, which are already sorted. The delta is
+//! typically compacted once it grows past a fraction of the base, so most lookups are served by
+//! the frozen layout while writes never have to rebuild it.
+//!
+//! A deleted key of the base is marked by a tombstone in the delta, which hides the entry of the
+//! base from lookups and from [`LayeredArt::iter`], which merges both layers in key order. The
+//! compaction drops the tombstones together with the entries they hide.
+
+use std::{borrow::Borrow, iter::Peekable, slice};
+
+use crate::{compact::CompactArt, BytesComparable, ART};
+
+/// A frozen base tree with a mutable delta, see the [module documentation](self).
+pub struct LayeredArt<K, V> {
+    base: CompactArt<K, V>,
+    /// The changes since the last compaction, where `None` is a tombstone.
+    delta: ART<K, Option<V>>,
+    len: usize,
+}
+
+impl<K, V> LayeredArt<K, V>
+where
+    K: BytesComparable,
+{
+    /// Create an empty tree.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::from_base(ART::<K, V>::new().freeze())
+    }
+
+    /// Create a tree on top of the given frozen base.
+    #[must_use]
+    pub const fn from_base(base: CompactArt<K, V>) -> Self {
+        Self {
+            len: base.len(),
+            base,
+            delta: ART::new(),
+        }
+    }
+
+    /// Return the frozen base of the tree.
+    #[must_use]
+    pub const fn base(&self) -> &CompactArt<K, V> {
+        &self.base
+    }
+
+    /// Return the delta of the tree, which holds the changes made since the last compaction, with
+    /// `None` as the tombstone of a deleted key.
+    #[must_use]
+    pub const fn delta(&self) -> &ART<K, Option<V>> {
+        &self.delta
+    }
+
+    /// Return the number of entries in the tree.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the tree holds no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Search for the value associated with the given key, in the delta and then in the base
+    /// unless the delta holds a tombstone for the key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.delta
+            .search(key)
+            .map_or_else(|| self.base.search(key), Option::as_ref)
+    }
+
+    /// Return true if the tree contains the given key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.search(key).is_some()
+    }
+
+    /// Insert the given key-value pair into the delta, returning the value that the delta
+    /// previously held for the key. A value of the base is shadowed until the next compaction
+    /// drops it, and is not returned.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let present = match self.delta.search(&key) {
+            Some(value) => value.is_some(),
+            None => self.base.search(&key).is_some(),
+        };
+        if !present {
+            self.len += 1;
+        }
+        self.delta.insert(key, Some(value)).flatten()
+    }
+
+    /// Delete the given key, returning true if the tree contained it. A key of the base is hidden
+    /// by a tombstone until the next compaction drops it, which needs a copy of the key.
+    pub fn delete<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q> + Clone,
+        Q: BytesComparable + ?Sized,
+    {
+        let Some((base_key, _)) = self.base.search_key_value(key) else {
+            // A key that is not in the base needs no tombstone.
+            let deleted = self.delta.delete(key).is_some_and(|value| value.is_some());
+            if deleted {
+                self.len -= 1;
+            }
+            return deleted;
+        };
+        let base_key = base_key.clone();
+        let deleted = self
+            .delta
+            .insert(base_key, None)
+            .is_none_or(|value| value.is_some());
+        if deleted {
+            self.len -= 1;
+        }
+        deleted
+    }
+
+    /// Return an iterator over the entries of both layers in key order, where the entries of the
+    /// delta replace those of the base and tombstones hide them.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            base: self.base.entries().iter().peekable(),
+            delta: self.delta.iter().peekable(),
+        }
+    }
+
+    /// Merge the delta into a new frozen base, leaving the delta empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`ART::freeze`].
+    pub fn compact(&mut self) {
+        if self.delta.is_empty() {
+            return;
+        }
+        let base = std::mem::replace(&mut self.base, ART::<K, V>::new().freeze()).into_entries();
+        let delta = std::mem::take(&mut self.delta);
+        let mut merged = Vec::with_capacity(self.len);
+        let mut base = base.into_iter().peekable();
+        for (key, value) in delta {
+            while let Some((base_key, _)) = base.peek() {
+                let order = base_key.bytes().as_ref().cmp(key.bytes().as_ref());
+                if order.is_gt() {
+                    break;
+                }
+                // A base entry with the key of the delta entry is dropped.
+                let entry = base.next();
+                if order.is_lt() {
+                    merged.extend(entry);
+                }
+            }
+            if let Some(value) = value {
+                merged.push((key, value));
+            }
+        }
+        merged.extend(base);
+        self.base = CompactArt::from_sorted(merged.into_boxed_slice());
+    }
+}
+
+/// An iterator over the entries of a [`LayeredArt`] in key order, see [`LayeredArt::iter`].
+pub struct Iter<'a, K, V> {
+    base: Peekable<slice::Iter<'a, (K, V)>>,
+    delta: Peekable<crate::iter::Iter<'a, K, Option<V>>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V>
+where
+    K: BytesComparable,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let order = match (self.base.peek(), self.delta.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (Some((base_key, _)), Some((delta_key, _))) => {
+                    base_key.bytes().as_ref().cmp(delta_key.bytes().as_ref())
+                }
+            };
+            if order.is_lt() {
+                return self.base.next().map(|(k, v)| (k, v));
+            }
+            if order.is_eq() {
+                self.base.next();
+            }
+            if let Some((key, Some(value))) = self.delta.next() {
+                return Some((key, value));
+            }
+        }
+    }
+}
+
+impl<K, V> std::iter::FusedIterator for Iter<'_, K, V> where K: BytesComparable {}
+
+impl<K, V> std::fmt::Debug for Iter<'_, K, V>
+where
+    K: std::fmt::Debug,
+    V: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Iter")
+            .field("base", &self.base)
+            .field("delta", &self.delta)
+            .finish()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a LayeredArt<K, V>
+where
+    K: BytesComparable,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V> Default for LayeredArt<K, V>
+where
+    K: BytesComparable,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> From<CompactArt<K, V>> for LayeredArt<K, V>
+where
+    K: BytesComparable,
+{
+    fn from(base: CompactArt<K, V>) -> Self {
+        Self::from_base(base)
+    }
+}
+
+impl<K, V> std::fmt::Debug for LayeredArt<K, V>
+where
+    K: std::fmt::Debug,
+    V: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LayeredArt")
+            .field("base", &self.base)
+            .field("delta", &self.delta)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::Rng;
+
+    use super::LayeredArt;
+    use crate::ART;
+
+    #[test]
+    fn test_matches_btree() {
+        let mut rng = rand::thread_rng();
+        let mut tree = LayeredArt::<u16, usize>::new();
+        let mut expected = BTreeMap::new();
+        for idx in 0..20_000 {
+            let key = rng.gen_range(0..5000);
+            if rng.gen_bool(0.7) {
+                tree.insert(key, idx);
+                expected.insert(key, idx);
+            } else {
+                assert_eq!(tree.delete(&key), expected.remove(&key).is_some());
+            }
+            if idx % 1000 == 999 {
+                assert!(tree.iter().eq(expected.iter()));
+                tree.compact();
+                assert!(tree.delta().is_empty());
+                assert!(tree
+                    .base()
+                    .entries()
+                    .iter()
+                    .map(|(k, v)| (k, v))
+                    .eq(expected.iter()));
+            }
+            assert_eq!(tree.len(), expected.len());
+        }
+        for key in 0..5000 {
+            assert_eq!(tree.search(&key), expected.get(&key));
+        }
+        assert!(tree.iter().eq(expected.iter()));
+    }
+
+    #[test]
+    fn test_delta_shadows_base() {
+        let base: ART<String, u32> = (0..100).map(|i| (format!("key{i:02}"), i)).collect();
+        let mut tree = LayeredArt::from(base.freeze());
+        assert_eq!(tree.insert("key05".to_string(), 500), None);
+        assert_eq!(tree.insert("key05".to_string(), 501), Some(500));
+        assert_eq!(tree.insert("new".to_string(), 1), None);
+        assert_eq!(tree.search("key05"), Some(&501));
+        assert_eq!(tree.search("key06"), Some(&6));
+        assert_eq!(tree.len(), 101);
+        assert_eq!(tree.base().search("key05"), Some(&5));
+
+        tree.compact();
+        assert_eq!(tree.len(), 101);
+        assert_eq!(tree.base().search("key05"), Some(&501));
+        assert_eq!(tree.base().search("new"), Some(&1));
+        assert!(tree.delta().is_empty());
+        assert!(LayeredArt::<String, u32>::default().is_empty());
+    }
+
+    #[test]
+    fn test_tombstones() {
+        let base: ART<u32, u32> = (0..10).map(|i| (i, i)).collect();
+        let mut tree = LayeredArt::from(base.freeze());
+        assert!(tree.delete(&3));
+        assert!(!tree.delete(&3));
+        assert!(!tree.delete(&30));
+        tree.insert(20, 20);
+        assert!(tree.delete(&20));
+        // Only the key of the base needs a tombstone.
+        assert_eq!(tree.delta().len(), 1);
+        assert_eq!(tree.delta().search(&3), Some(&None));
+        assert!(!tree.contains_key(&3));
+        assert_eq!(tree.base().search(&3), Some(&3));
+
+        assert_eq!(tree.insert(3, 33), None);
+        assert_eq!(tree.search(&3), Some(&33));
+        assert!(tree.delete(&4));
+        assert!(tree.delete(&9));
+        tree.insert(15, 15);
+        let entries: Vec<_> = tree.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(
+            entries,
+            [
+                (0, 0),
+                (1, 1),
+                (2, 2),
+                (3, 33),
+                (5, 5),
+                (6, 6),
+                (7, 7),
+                (8, 8),
+                (15, 15)
+            ]
+        );
+        assert_eq!(tree.len(), entries.len());
+
+        tree.compact();
+        assert!(tree
+            .base()
+            .entries()
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .eq(entries));
+        assert_eq!(tree.len(), 9);
+    }
+}
@@ -0,0 +1,428 @@
+//! Persisted trees whose subtrees are loaded on first access.
+//!
+//! Loading a dump with [`ART::load`] reads every entry into memory, even if only a few keys are
+//! ever queried. [`ART::dump_partitioned`] writes the entries grouped by the first encoded bytes of
+//! their keys, with an index of the groups in front, and a [`LazyArt`] opened on such a dump only
+//! reads that index. The subtree of a group is loaded the first time a key of the group is looked
+//! up, or when it is hydrated explicitly, and stays resident until it is evicted, so a dump far
+//! larger than the memory of the process can be queried as long as the working set fits.
+//!
+//! # Format
+//!
+//! All integers are encoded as in the dumps of [`persist`](crate::persist).
+//!
+//! | Field      | Encoding                                                                 |
+//! |------------|--------------------------------------------------------------------------|
+//! | magic      | the 4 bytes `YARL`                                                       |
+//! | version    | `u8`                                                                     |
+//! | prefix len | `u64`, the number of encoded key bytes that groups are formed by         |
+//! | groups     | `u64`, the number of groups                                              |
+//! | index      | for every group in key order, its prefix as a byte string, its number of |
+//! |            | entries as a `u64`, and the length of its entries in bytes as a `u64`    |
+//! | entries    | the encoded keys and values of every group, one group after another      |
+
+use std::{
+    borrow::Borrow,
+    collections::BTreeMap,
+    io::{self, Read, Seek, SeekFrom, Write},
+};
+
+use crate::{
+    persist::{invalid_data, Decode, Encode},
+    BytesComparable, ART,
+};
+
+/// The bytes every partitioned dump starts with.
+const MAGIC: &[u8; 4] = b"YARL";
+
+/// The version of the partitioned dump format.
+const VERSION: u8 = 1;
+
+/// A group of entries of a partitioned dump.
+#[derive(Debug)]
+struct Group<K, V> {
+    /// The offset of the entries from the end of the index.
+    offset: u64,
+    /// The length of the entries in bytes.
+    len: u64,
+    /// The number of entries.
+    count: u64,
+    /// The entries, if they are resident.
+    tree: Option<ART<K, V>>,
+}
+
+impl<K, V> ART<K, V>
+where
+    K: BytesComparable,
+{
+    /// Write all entries of the tree into the given writer, grouped by their first `prefix_len`
+    /// encoded bytes, so that the dump can be opened by a [`LazyArt`]. A key shorter than
+    /// `prefix_len` bytes is grouped by the whole key.
+    ///
+    /// The groups are encoded in memory before the dump is written, so that the index can be
+    /// written in front of them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer fails.
+    pub fn dump_partitioned<W>(&self, mut writer: W, prefix_len: usize) -> io::Result<()>
+    where
+        W: Write,
+        K: Encode,
+        V: Encode,
+    {
+        // Grouping by a truncated key keeps every group contiguous, since truncation preserves the
+        // order of the keys.
+        let mut groups: Vec<(Vec<u8>, u64, Vec<u8>)> = Vec::new();
+        self.try_for_each(|key, value| {
+            let bytes = key.bytes();
+            let bytes = bytes.as_ref();
+            let prefix = &bytes[..prefix_len.min(bytes.len())];
+            if groups.last().is_none_or(|(last, ..)| last != prefix) {
+                groups.push((prefix.to_vec(), 0, Vec::new()));
+            }
+            let Some((_, count, entries)) = groups.last_mut() else {
+                unreachable!("a group was pushed");
+            };
+            *count += 1;
+            key.encode(entries)?;
+            value.encode(entries)
+        })?;
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        (prefix_len as u64).encode(&mut writer)?;
+        (groups.len() as u64).encode(&mut writer)?;
+        for (prefix, count, entries) in &groups {
+            prefix.encode(&mut writer)?;
+            count.encode(&mut writer)?;
+            (entries.len() as u64).encode(&mut writer)?;
+        }
+        for (_, _, entries) in &groups {
+            writer.write_all(entries)?;
+        }
+        Ok(())
+    }
+}
+
+/// A tree read from a dump written by [`ART::dump_partitioned`], whose groups are loaded on first
+/// access, see the [module documentation](self).
+#[derive(Debug)]
+pub struct LazyArt<K, V, R> {
+    reader: R,
+    /// The position of the first group in the reader.
+    base: u64,
+    prefix_len: usize,
+    groups: BTreeMap<Vec<u8>, Group<K, V>>,
+    len: usize,
+}
+
+impl<K, V, R> LazyArt<K, V, R>
+where
+    K: BytesComparable + Decode,
+    V: Decode,
+    R: Read + Seek,
+{
+    /// Read the index of the dump in the given reader, without loading any group.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader fails or if the data is not a valid partitioned dump.
+    pub fn open(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; MAGIC.len() + 1];
+        reader.read_exact(&mut header)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(invalid_data("not a partitioned yaart dump"));
+        }
+        let version = header[MAGIC.len()];
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "unsupported partitioned dump version {version}"
+            )));
+        }
+        let prefix_len = usize::decode(&mut reader)?;
+        let group_count = u64::decode(&mut reader)?;
+        let mut groups = BTreeMap::new();
+        let mut offset = 0u64;
+        let mut len = 0usize;
+        for _ in 0..group_count {
+            let prefix = Vec::<u8>::decode(&mut reader)?;
+            let count = u64::decode(&mut reader)?;
+            let group_len = u64::decode(&mut reader)?;
+            let group = Group {
+                offset,
+                len: group_len,
+                count,
+                tree: None,
+            };
+            if prefix.len() > prefix_len || groups.insert(prefix, group).is_some() {
+                return Err(invalid_data("invalid group prefix in the index"));
+            }
+            offset = offset
+                .checked_add(group_len)
+                .ok_or_else(|| invalid_data("the groups are too long"))?;
+            len = usize::try_from(count)
+                .ok()
+                .and_then(|count| len.checked_add(count))
+                .ok_or_else(|| invalid_data("the dump holds too many entries"))?;
+        }
+        let base = reader.stream_position()?;
+        Ok(Self {
+            reader,
+            base,
+            prefix_len,
+            groups,
+            len,
+        })
+    }
+
+    /// Return the number of entries in the dump, resident or not.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the dump holds no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the number of groups in the dump.
+    #[must_use]
+    pub fn groups(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Return the prefixes of the groups that are resident, in key order.
+    pub fn resident(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.groups
+            .iter()
+            .filter(|(_, group)| group.tree.is_some())
+            .map(|(prefix, _)| prefix.as_slice())
+    }
+
+    /// Return the group prefix of the given encoded key.
+    fn prefix_of<'k>(&self, key: &'k [u8]) -> &'k [u8] {
+        &key[..self.prefix_len.min(key.len())]
+    }
+
+    /// Return the tree of the group with the given prefix, loading it if it is not resident, or
+    /// `None` if the dump has no such group.
+    fn load(&mut self, prefix: &[u8]) -> io::Result<Option<&mut ART<K, V>>> {
+        let Some(group) = self.groups.get_mut(prefix) else {
+            return Ok(None);
+        };
+        if group.tree.is_none() {
+            self.reader
+                .seek(SeekFrom::Start(self.base + group.offset))?;
+            let mut entries = (&mut self.reader).take(group.len);
+            let mut tree = ART::new();
+            for _ in 0..group.count {
+                let key = K::decode(&mut entries)?;
+                let value = V::decode(&mut entries)?;
+                if self.prefix_len.min(key.bytes().as_ref().len()) != prefix.len()
+                    || !key.bytes().as_ref().starts_with(prefix)
+                {
+                    return Err(invalid_data("an entry is stored in the wrong group"));
+                }
+                tree.insert(key, value);
+            }
+            if entries.limit() != 0 {
+                return Err(invalid_data("trailing bytes after the entries of a group"));
+            }
+            group.tree = Some(tree);
+        }
+        Ok(group.tree.as_mut())
+    }
+
+    /// Search for the value associated with the given key, loading the group of the key if it is
+    /// not resident.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the group of the key has to be loaded and the reader fails or the
+    /// entries of the group are invalid, in which case the group stays unloaded.
+    pub fn get<Q>(&mut self, key: &Q) -> io::Result<Option<&V>>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let bytes = key.bytes();
+        let prefix = self.prefix_of(bytes.as_ref()).to_vec();
+        Ok(self.load(&prefix)?.and_then(|tree| tree.search(key)))
+    }
+
+    /// Return the value associated with the given key if its group is resident, without loading
+    /// anything.
+    pub fn get_resident<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let bytes = key.bytes();
+        let prefix = self.prefix_of(bytes.as_ref());
+        self.groups.get(prefix)?.tree.as_ref()?.search(key)
+    }
+
+    /// Load every group whose prefix starts with the given bytes, or every group whose prefix the
+    /// given bytes start with if they are longer than the prefixes, and return the number of
+    /// groups that were loaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader fails or the entries of a group are invalid, in which case
+    /// the groups before it stay loaded.
+    pub fn hydrate(&mut self, prefix: &[u8]) -> io::Result<usize> {
+        let pending: Vec<Vec<u8>> = self
+            .groups
+            .iter()
+            .filter(|(group_prefix, group)| {
+                group.tree.is_none()
+                    && (group_prefix.starts_with(prefix) || prefix.starts_with(group_prefix))
+            })
+            .map(|(group_prefix, _)| group_prefix.clone())
+            .collect();
+        for group_prefix in &pending {
+            self.load(group_prefix)?;
+        }
+        Ok(pending.len())
+    }
+
+    /// Drop the entries of every resident group whose prefix starts with the given bytes, and
+    /// return the number of groups that were evicted. They are loaded again on their next access.
+    pub fn evict(&mut self, prefix: &[u8]) -> usize {
+        self.groups
+            .iter_mut()
+            .filter(|(group_prefix, _)| group_prefix.starts_with(prefix))
+            .filter_map(|(_, group)| group.tree.take())
+            .count()
+    }
+
+    /// Load every group, and return all entries as a tree.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader fails or the entries of a group are invalid.
+    pub fn into_art(mut self) -> io::Result<ART<K, V>> {
+        self.hydrate(&[])?;
+        let mut tree = ART::new();
+        for group in self.groups.into_values() {
+            for (key, value) in group.tree.into_iter().flatten() {
+                tree.insert(key, value);
+            }
+        }
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, ErrorKind, Read, Seek, SeekFrom};
+
+    use super::LazyArt;
+    use crate::ART;
+
+    /// Counts the bytes read from the inner reader.
+    struct Counting {
+        inner: Cursor<Vec<u8>>,
+        read: usize,
+    }
+
+    impl Read for Counting {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let read = self.inner.read(buf)?;
+            self.read += read;
+            Ok(read)
+        }
+    }
+
+    impl Seek for Counting {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    fn sample_tree() -> ART<String, u32> {
+        let mut tree = ART::new();
+        for group in ["apple", "banana", "cherry", "durian"] {
+            for i in 0..250 {
+                tree.insert(format!("{group}/{i:04}"), i);
+            }
+        }
+        tree.insert("a".to_string(), 0);
+        tree
+    }
+
+    #[test]
+    fn test_loads_groups_on_access() {
+        let tree = sample_tree();
+        let mut dump = Vec::new();
+        tree.dump_partitioned(&mut dump, 2).unwrap();
+        let total = dump.len();
+        let mut lazy = LazyArt::<String, u32, _>::open(Counting {
+            inner: Cursor::new(dump),
+            read: 0,
+        })
+        .unwrap();
+        assert_eq!(lazy.len(), tree.len());
+        assert_eq!(lazy.groups(), 5);
+        assert_eq!(lazy.resident().count(), 0);
+        let index_len = lazy.reader.read;
+
+        assert_eq!(lazy.get("banana/0042").unwrap(), Some(&42));
+        assert!(lazy.resident().eq([b"ba".as_slice()]));
+        let group_len = lazy.reader.read - index_len;
+        assert!(group_len < total / 3);
+        // Resident groups are not read again, and keys without a group read nothing.
+        assert_eq!(lazy.get("banana/0999").unwrap(), None);
+        assert_eq!(lazy.get("zebra").unwrap(), None);
+        assert_eq!(lazy.reader.read, index_len + group_len);
+        assert_eq!(lazy.get_resident("banana/0007"), Some(&7));
+        assert_eq!(lazy.get_resident("cherry/0007"), None);
+
+        // The key shorter than the prefixes has a group of its own.
+        assert_eq!(lazy.get("a").unwrap(), Some(&0));
+        assert_eq!(lazy.hydrate(b"c").unwrap(), 1);
+        assert_eq!(lazy.hydrate(b"cherry").unwrap(), 0);
+        assert!(lazy.resident().eq([b"a".as_slice(), b"ba", b"ch"]));
+        assert_eq!(lazy.evict(b"b"), 1);
+        assert_eq!(lazy.evict(b""), 2);
+        assert_eq!(lazy.resident().count(), 0);
+        assert_eq!(lazy.get("banana/0042").unwrap(), Some(&42));
+
+        let loaded = lazy.into_art().unwrap();
+        assert!(loaded.iter().eq(tree.iter()));
+    }
+
+    #[test]
+    fn test_rejects_invalid_dumps() {
+        let tree = sample_tree();
+        let mut dump = Vec::new();
+        tree.dump_partitioned(&mut dump, 1).unwrap();
+
+        let mut plain = Vec::new();
+        tree.dump(&mut plain).unwrap();
+        let error = LazyArt::<String, u32, _>::open(Cursor::new(plain)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        // A truncated group fails to load, and can be loaded again once the data is complete.
+        let mut truncated = dump.clone();
+        truncated.truncate(dump.len() - 1);
+        let mut lazy = LazyArt::<String, u32, _>::open(Cursor::new(truncated)).unwrap();
+        assert_eq!(lazy.get("apple/0001").unwrap(), Some(&1));
+        let error = lazy.get("durian/0001").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+        assert!(lazy.resident().eq([b"a".as_slice()]));
+        lazy.reader.get_mut().push(*dump.last().unwrap());
+        assert_eq!(lazy.get("durian/0001").unwrap(), Some(&1));
+
+        let mut empty = Vec::new();
+        ART::<String, u32>::new()
+            .dump_partitioned(&mut empty, 1)
+            .unwrap();
+        let lazy = LazyArt::<String, u32, _>::open(Cursor::new(empty)).unwrap();
+        assert!(lazy.is_empty());
+        assert_eq!(lazy.groups(), 0);
+    }
+}
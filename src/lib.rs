@@ -1,4 +1,65 @@
 //! # Adaptive Radix Tree
+//!
+//! ## Thread safety
+//!
+//! None of the types in this crate use interior mutability or raw pointers outside of the standard
+//! synchronization primitives, so their auto traits follow from the bounds on the keys and values:
+//!
+//! - [`ART`], [`ArtMap`], [`bloom::BloomArt`], [`bounded::BoundedArt`], [`compact::CompactArt`],
+//!   [`granular::GranularArt`], [`hashed::HashPrefixedArt`], [`layered::LayeredArt`],
+//!   [`merkle::MerkleArt`], [`mvcc::MvccArt`], [`namespace::Namespaced`], [`order::ReorderedArt`],
+//!   [`packed::PackedArt`], [`pinned::PinnedArt`], [`recycle::RecyclingArt`], [`slab::SlabArt`],
+//!   and [`versioned::VersionedArt`] are `Send` when `K: Send` and `V: Send`, and `Sync` when
+//!   `K: Sync` and `V: Sync`. The same holds for [`int::IntArt`], [`set::ArtSet`], and
+//!   [`shadow::ShadowArt`], for [`queue::ArtPriorityQueue`] with priorities as keys and items as
+//!   values, and for [`meta::MetaArt`], [`weighted::WeightedArt`], and [`lazy::LazyArt`] when their
+//!   metadata, weights, and readers satisfy the bounds as well. A tree can be moved into another
+//!   thread, or shared behind an `Arc` for read-only use.
+//! - The iterators that borrow a tree, [`iter::Iter`], [`iter::Encoded`], [`iter::Annotated`],
+//!   [`iter::KeyPaths`], and [`iter::PrefixesOf`], as well as [`cursor::Cursor`], are `Send` and
+//!   `Sync` when `K: Sync` and `V: Sync`, as they only hold shared references into the tree.
+//!   [`iter::IterMut`] is `Send` when `K: Send` and `V: Send`. [`cursor::CursorMut`] is `Send` when
+//!   `K: Send + Sync` and `V: Send + Sync`, as it borrows the tree mutably and, with the
+//!   `unsafe_opt` feature, keeps shared references into it. Both are `Sync` when `K: Sync` and
+//!   `V: Sync`.
+//! - [`sharded::ShardedArt`] and [`prefix_sharded::PrefixShardedArt`] are `Send` and `Sync` when
+//!   `K: Send + Sync` and `V: Send + Sync`, as their shards can be read from several threads at
+//!   once.
+//! - [`swmr::SwmrArt`], [`swmr::Reader`], [`swmr::ReadHandle`], and [`swmr::Snapshot`] are `Send`
+//!   and `Sync` when `K: Send + Sync` and `V: Send + Sync`, because their nodes are shared between
+//!   threads.
+//! - [`observe::ObservedArt`] is `Send` and `Sync` under the same bounds as [`ART`], because its
+//!   observers are required to be `Send` and `Sync`.
+//! - [`compact::ArcSnapshot`] is `Send` and `Sync` when `K: Send + Sync` and `V: Send + Sync`,
+//!   as its clones share the same frozen tree.
+//! - [`interner::Interner`] and [`bulk::BulkBuilder`] are always `Send` and `Sync`.
+//! - `arena::ArenaArt` of the `bumpalo` feature is neither `Send` nor `Sync`, because it borrows
+//!   an arena that can not be shared between threads.
+//!
+//! ## Unsafe code
+//!
+//! The crate is compiled without `unsafe` code unless the `unsafe_opt` feature is enabled, which
+//! enables the optimizations that need it, each with a safe fallback that is used without the
+//! feature. These are the SIMD search of the keys of inner nodes with up to 16 children, and the
+//! path a [`cursor::CursorMut`] keeps between its moves. The tests of the optimizations are meant
+//! to run under Miri and the sanitizers.
+//!
+//! ## Panic safety
+//!
+//! Keys are encoded by the implementations of [`BytesComparable`] of their types, and the keys
+//! of shared trees are cloned by their implementations of [`Clone`], which may panic. A mutation
+//! of a tree in which such a call panics leaves the tree structurally valid, so it can still be
+//! used after the panic is caught, and it needs no poisoning:
+//!
+//! - An insert that panics leaves the tree as it was before the insert, without the new entry.
+//! - A delete that panics leaves the tree as it was before the delete, with the entry it deleted.
+//! - A mutation of a [`swmr::SwmrArt`] that panics publishes no new version.
+//!
+//! The same holds for panicking predicates and closures given to mutations, such as the one of
+//! [`ART::remove_where`], which are called before the tree is modified.
+//!
+//! The `check-invariants` feature is the exception, as its checks encode the keys on the path of a
+//! mutation after the tree was modified, and a panic there leaves the length of the tree stale.
 
 #![warn(
     clippy::pedantic,
@@ -8,21 +69,191 @@
     missing_debug_implementations
 )]
 #![deny(clippy::all, missing_docs, rust_2018_idioms, rust_2021_compatibility)]
+#![cfg_attr(not(feature = "unsafe_opt"), forbid(unsafe_code))]
 
+#[cfg(feature = "bumpalo")]
+pub mod arena;
+pub mod balance;
+pub mod bloom;
+pub mod bounded;
+pub mod bulk;
+pub mod cached;
+pub mod cidr;
+pub mod codec;
+pub mod compact;
+pub mod cursor;
+pub mod diff;
+#[cfg(feature = "encryption")]
+pub mod encrypted;
+pub mod error;
+#[cfg(feature = "metrics")]
+pub mod exporter;
+pub mod filter;
+pub mod granular;
+pub mod hashed;
 mod indices;
+pub mod int;
+pub mod interner;
+pub mod iter;
+pub mod layered;
+pub mod lazy;
+pub mod map;
+pub mod merkle;
+pub mod meta;
+pub mod mvcc;
+pub mod namespace;
 mod node;
+pub mod observe;
+pub mod order;
+mod os;
+pub mod packed;
+pub mod persist;
+pub mod pinned;
+pub mod prefix_sharded;
+#[cfg(feature = "probe")]
+pub mod probe;
+pub mod queue;
+#[cfg(feature = "raw")]
+pub mod raw;
+pub mod recycle;
+pub mod scan;
+pub mod segment;
+pub mod set;
+pub mod shadow;
+pub mod sharded;
+pub mod slab;
+pub mod split;
+#[cfg(feature = "timestamps")]
+pub mod stamped;
+#[cfg(feature = "stats")]
+pub mod stats;
+pub mod swmr;
+pub mod testing;
+#[cfg(feature = "json")]
+pub mod text;
+pub mod transaction;
+pub mod versioned;
+pub mod visit;
+pub mod weighted;
 
-use std::borrow::Borrow;
+use std::{
+    borrow::Borrow,
+    collections::TryReserveError,
+    ops::{Bound, RangeBounds},
+};
 
-use self::node::{debug_print, Node};
+use self::node::{debug_print, Leaf, Node, Owned, Path};
+pub use self::{error::Error, map::ArtMap};
+
+/// Counts an operation on a tree in the registry of the `metrics` feature, and does nothing without
+/// the feature.
+macro_rules! count_op {
+    ($op:literal) => {
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!($crate::exporter::OPERATIONS, "op" => $op).increment(1);
+    };
+}
 
 /// An adaptive radix tree.
-#[derive(Default)]
-pub struct ART<K, V, const N: usize = 10> {
-    root: Option<Node<K, V, N>>,
+///
+/// An inner node stores up to `N` bytes of its prefix. `C4`, `C16`, and `C48` are the capacities
+/// of the three smaller kinds of inner nodes, which hold up to 4, 16, and 48 children by default
+/// before growing into the next kind, so that the layout of the nodes can be tuned to a cache
+/// hierarchy. The capacities must satisfy `2 <= C4 < C16 < C48 < 256`, which is checked when the
+/// tree is compiled. With the `unsafe_opt` feature, only the default capacity of the second kind
+/// is searched with SIMD instructions. Cursors, transactions, persistence, and the tree types built
+/// on an [`ART`] use the default capacities.
+pub struct ART<
+    K,
+    V,
+    const N: usize = 10,
+    const C4: usize = 4,
+    const C16: usize = 16,
+    const C48: usize = 48,
+> {
+    root: Option<Node<K, V, N, Owned, C4, C16, C48>>,
+    len: usize,
+    compress_paths: bool,
+    #[cfg(feature = "stats")]
+    stats: stats::TreeStats,
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    ART<K, V, N, C4, C16, C48>
+{
+    /// Create an empty tree.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            root: None,
+            len: 0,
+            compress_paths: true,
+            #[cfg(feature = "stats")]
+            stats: stats::TreeStats::NONE,
+        }
+    }
+
+    /// Create an empty tree whose inner nodes store no prefixes, so that every byte of a key that
+    /// is shared with another key takes a level of the tree. Leaves are still placed as high in the
+    /// tree as their keys allow.
+    ///
+    /// Such a tree is slower and larger than a tree with compressed paths for most workloads, but
+    /// it is a simple baseline for differential testing, and it can be faster when keys share no
+    /// prefixes, as the prefixes never have to be compared.
+    #[must_use]
+    pub const fn without_path_compression() -> Self {
+        Self {
+            root: None,
+            len: 0,
+            compress_paths: false,
+            #[cfg(feature = "stats")]
+            stats: stats::TreeStats::NONE,
+        }
+    }
+
+    /// Return true if the inner nodes of the tree store the prefixes shared by their keys.
+    #[must_use]
+    pub const fn compresses_paths(&self) -> bool {
+        self.compress_paths
+    }
+
+    /// Create a tree with the given root, counting its leaves.
+    pub(crate) fn from_root(root: Option<Node<K, V, N, Owned, C4, C16, C48>>) -> Self {
+        let len = root
+            .as_ref()
+            .map_or(0, |root| root.fold_leaves(0, &mut |len, _| len + 1));
+        Self {
+            root,
+            len,
+            compress_paths: true,
+            #[cfg(feature = "stats")]
+            stats: stats::TreeStats::NONE,
+        }
+    }
+
+    /// Return the number of entries in the tree.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the tree holds no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
 }
 
-impl<K, V, const N: usize> std::fmt::Debug for ART<K, V, N>
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> Default
+    for ART<K, V, N, C4, C16, C48>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> std::fmt::Debug
+    for ART<K, V, N, C4, C16, C48>
 where
     K: std::fmt::Debug,
     V: std::fmt::Debug,
@@ -36,7 +267,8 @@ where
     }
 }
 
-impl<K, V, const N: usize> ART<K, V, N>
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    ART<K, V, N, C4, C16, C48>
 where
     K: BytesComparable,
 {
@@ -46,21 +278,56 @@ where
         K: Borrow<Q>,
         Q: BytesComparable + ?Sized,
     {
+        count_op!("search");
         self.root
             .as_ref()
             .and_then(|node| node.search(key.bytes().as_ref(), 0))
             .map(|leaf| &leaf.value)
     }
 
-    /// Insert the given key-value pair into the tree.
-    pub fn insert(&mut self, key: K, value: V) {
-        // Insert into the current root if the tree is not empty. Otherwise,
-        // create a new leaf as the root.
-        if let Some(ref mut root) = self.root {
-            root.insert(key, value, 0);
-        } else {
-            self.root = Some(Node::new_leaf(key, value));
+    /// Search for the entry associated with the given key, returning the stored key together with
+    /// its value. The stored key can differ from the given one when the key type encodes several
+    /// keys into the same bytes.
+    pub fn search_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.root
+            .as_ref()
+            .and_then(|node| node.search(key.bytes().as_ref(), 0))
+            .map(|leaf| (&leaf.key, &leaf.value))
+    }
+
+    /// Insert the given key-value pair into the tree, returning the value previously associated
+    /// with the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        count_op!("insert");
+        let replaced =
+            self.mutate(|tree| Node::insert_root(&mut tree.root, key, value, tree.compress_paths));
+        if replaced.is_none() {
+            self.len += 1;
         }
+        replaced
+    }
+
+    /// Insert the given key-value pair into the tree like [`ART::insert`], after checking that the
+    /// memory the insert needs at most can be allocated.
+    ///
+    /// The check is best-effort, and the insert itself is not fallible. The boxes holding the nodes
+    /// can not be allocated fallibly, so the check reserves as many bytes as the insert allocates
+    /// at most and releases them right before inserting. An allocation that fails anyway, for
+    /// example because another thread took the memory in between, still aborts the process. The
+    /// check also runs when the key is already in the tree, although overwriting a value allocates
+    /// nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error without changing the tree if the memory can not be reserved.
+    pub fn insert_checked(&mut self, key: K, value: V) -> Result<Option<V>, TryReserveError> {
+        let needed = Node::<K, V, N, Owned, C4, C16, C48>::max_insert_alloc(key.bytes().as_ref());
+        Vec::<u8>::new().try_reserve_exact(needed)?;
+        Ok(self.insert(key, value))
     }
 
     /// Delete the value associated with the given key.
@@ -69,21 +336,148 @@ where
         K: Borrow<Q>,
         Q: BytesComparable + ?Sized,
     {
-        let Some(mut root) = self.root.take() else {
-            return None;
-        };
-        // Handles special case when the root is a leaf. Otherwise, start deleting from within the inner node.
-        let Node::Leaf(leaf) = root else {
-            let deleted = root.delete(key.bytes().as_ref(), 0).map(|leaf| leaf.value);
-            self.root = Some(root);
-            return deleted;
+        self.delete_leaf(key.bytes().as_ref())
+            .map(|leaf| leaf.value)
+    }
+
+    /// Remove every entry for which the predicate returns true, returning the removed entries in
+    /// key order. The entries to remove are collected in a single pass over the tree, then removed
+    /// in a single descent that visits every node on their paths once, instead of descending from
+    /// the root for every entry.
+    pub fn remove_where<F>(&mut self, mut predicate: F) -> Vec<(K, V)>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let victims: Vec<Vec<u8>> = self
+            .iter()
+            .filter(|(k, v)| predicate(k, v))
+            .map(|(k, _)| k.bytes().as_ref().to_vec())
+            .collect();
+        let victims: Vec<&[u8]> = victims.iter().map(Vec::as_slice).collect();
+        let mut deleted = Vec::with_capacity(victims.len());
+        self.mutate(|tree| {
+            Node::delete_sorted_root(&mut tree.root, &victims, tree.compress_paths, &mut deleted);
+        });
+        self.len -= deleted.len();
+        deleted
+            .into_iter()
+            .map(|leaf| (leaf.key, leaf.value))
+            .collect()
+    }
+
+    /// Delete the leaf with the given encoded key, returning it with the key it was stored with.
+    pub(crate) fn delete_leaf(&mut self, key: &[u8]) -> Option<Leaf<K, V>> {
+        count_op!("delete");
+        let leaf =
+            self.mutate(|tree| Node::delete_root(&mut tree.root, key, tree.compress_paths))?;
+        self.len -= 1;
+        Some(leaf)
+    }
+
+    /// Run a mutation of the nodes of the tree, adding the transitions of node kinds it made to
+    /// the statistics of the tree with the `stats` feature.
+    pub(crate) fn mutate<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        #[cfg(feature = "stats")]
+        let before = stats::metrics().transitions;
+        let result = f(self);
+        #[cfg(feature = "stats")]
+        self.stats.transitions.add_since(&before);
+        result
+    }
+
+    /// Call the closure on every key-value pair in key order, stopping at the first error.
+    pub(crate) fn try_for_each<E, F>(&self, mut f: F) -> Result<(), E>
+    where
+        F: FnMut(&K, &V) -> Result<(), E>,
+    {
+        self.root.as_ref().map_or(Ok(()), |root| {
+            root.try_for_each_leaf(&mut |leaf| f(&leaf.key, &leaf.value))
+        })
+    }
+
+    /// Call the closure on the key-value pairs within the given range in key order, until the
+    /// closure returns false. The entries after that are never visited.
+    pub fn range_while<Q, R, F>(&self, range: R, mut f: F)
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        R: RangeBounds<Q>,
+        F: FnMut(&K, &V) -> bool,
+    {
+        let mut path = Path::new(self.root.as_ref());
+        match range.start_bound() {
+            Bound::Included(start) => path.seek(start.bytes().as_ref()),
+            Bound::Excluded(start) => {
+                let start = start.bytes();
+                path.seek(start.as_ref());
+                if path
+                    .leaf()
+                    .is_some_and(|leaf| leaf.key.bytes().as_ref() == start.as_ref())
+                {
+                    path.next();
+                }
+            }
+            Bound::Unbounded => path.seek_first(),
+        }
+        let end = match range.end_bound() {
+            Bound::Included(end) => Bound::Included(end.bytes()),
+            Bound::Excluded(end) => Bound::Excluded(end.bytes()),
+            Bound::Unbounded => Bound::Unbounded,
         };
-        // If the key matches, return the leaf's value. Otherwise, put it back as the root.
-        if !leaf.match_key(key.bytes().as_ref()) {
-            self.root = Some(Node::Leaf(leaf));
-            return None;
+        while let Some(leaf) = path.leaf() {
+            let in_range = match &end {
+                Bound::Included(end) => leaf.key.bytes().as_ref() <= end.as_ref(),
+                Bound::Excluded(end) => leaf.key.bytes().as_ref() < end.as_ref(),
+                Bound::Unbounded => true,
+            };
+            if !in_range || !f(&leaf.key, &leaf.value) {
+                return;
+            }
+            path.next();
+        }
+    }
+
+    /// Call the closure on the key-value pairs within the given range in descending key order,
+    /// until the closure returns false. The descent goes straight to the end of the range, so the
+    /// last entries before a bound are found without visiting the entries before them.
+    pub fn range_rev_while<Q, R, F>(&self, range: R, mut f: F)
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        R: RangeBounds<Q>,
+        F: FnMut(&K, &V) -> bool,
+    {
+        let mut path = Path::new(self.root.as_ref());
+        match range.end_bound() {
+            Bound::Included(end) | Bound::Excluded(end) => {
+                let end = end.bytes();
+                path.seek(end.as_ref());
+                let included = matches!(range.end_bound(), Bound::Included(_));
+                match path.leaf() {
+                    // Every key is before the end.
+                    None => path.seek_last(),
+                    Some(leaf) if included && leaf.key.bytes().as_ref() == end.as_ref() => {}
+                    Some(_) => path.prev(),
+                }
+            }
+            Bound::Unbounded => path.seek_last(),
+        }
+        let start = match range.start_bound() {
+            Bound::Included(start) => Bound::Included(start.bytes()),
+            Bound::Excluded(start) => Bound::Excluded(start.bytes()),
+            Bound::Unbounded => Bound::Unbounded,
         };
-        Some(leaf.value)
+        while let Some(leaf) = path.leaf() {
+            let in_range = match &start {
+                Bound::Included(start) => leaf.key.bytes().as_ref() >= start.as_ref(),
+                Bound::Excluded(start) => leaf.key.bytes().as_ref() > start.as_ref(),
+                Bound::Unbounded => true,
+            };
+            if !in_range || !f(&leaf.key, &leaf.value) {
+                return;
+            }
+            path.prev();
+        }
     }
 
     /// Find the minimum key-value pair in the tree.
@@ -103,7 +497,26 @@ where
     }
 }
 
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> FromIterator<(K, V)>
+    for ART<K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+    /// Build a tree from the entries using the same partitioning as [`bulk::BulkBuilder`] on the
+    /// calling thread. The last of several entries with the same key is kept.
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self::from_root(Node::build(iter.into_iter().collect(), 0))
+    }
+}
+
 /// A type that can be turn into bytes for comparison.
+///
+/// The bytes are computed on every operation that compares keys, so they should be cheap to
+/// produce. Types that own their encoded form return a borrowed slice, and fixed-width types such
+/// as integers return an array on the stack, such that no operation allocates to encode a key.
+/// Implementations for composite keys should follow the same approach, by returning an array when
+/// the encoded length is bounded, or by storing the encoded form in the key like
+/// [`codec::Normalized`] does. Any key can be wrapped into a [`cached::Cached`] for the latter.
 pub trait BytesComparable {
     /// The container type that holds the bytes representing our value, which can be
     /// referenced to get the slice of bytes.
@@ -212,7 +625,10 @@ impl BytesComparable for str {
 }
 
 impl BytesComparable for &str {
-    type Target<'a> = &'a [u8] where Self: 'a ;
+    type Target<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
 
     fn bytes(&self) -> Self::Target<'_> {
         self.as_bytes()
@@ -236,7 +652,74 @@ impl BytesComparable for [u8] {
 }
 
 impl BytesComparable for &[u8] {
-    type Target<'a> = &'a [u8] where Self: 'a;
+    type Target<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        self
+    }
+}
+
+impl<const N: usize> BytesComparable for [u8; N] {
+    type Target<'a> = &'a [u8];
+
+    fn bytes(&self) -> Self::Target<'_> {
+        self
+    }
+}
+
+impl<const N: usize> BytesComparable for &[u8; N] {
+    type Target<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        *self
+    }
+}
+
+impl BytesComparable for Box<[u8]> {
+    type Target<'a> = &'a [u8];
+
+    fn bytes(&self) -> Self::Target<'_> {
+        self
+    }
+}
+
+impl BytesComparable for std::sync::Arc<[u8]> {
+    type Target<'a> = &'a [u8];
+
+    fn bytes(&self) -> Self::Target<'_> {
+        self
+    }
+}
+
+impl BytesComparable for std::borrow::Cow<'_, [u8]> {
+    type Target<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        self
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl BytesComparable for bytes::Bytes {
+    type Target<'a> = &'a [u8];
+
+    fn bytes(&self) -> Self::Target<'_> {
+        self
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl BytesComparable for bytes::BytesMut {
+    type Target<'a> = &'a [u8];
 
     fn bytes(&self) -> Self::Target<'_> {
         self
@@ -245,11 +728,179 @@ impl BytesComparable for &[u8] {
 
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashMap, ops::Range};
+    use std::{
+        cell::Cell,
+        collections::{BTreeMap, HashMap},
+        ops::{Bound, Range},
+        sync::Arc,
+    };
 
     use rand::{distributions::Alphanumeric, seq::SliceRandom, Rng};
 
-    use crate::ART;
+    use crate::{prefix_sharded::PrefixShardedArt, sharded::ShardedArt, swmr, ART};
+
+    const fn assert_send<T: Send>() {}
+
+    const fn assert_sync<T: Sync>() {}
+
+    // These are never called, they only have to type check for all keys and values satisfying the
+    // documented bounds.
+    #[allow(dead_code)]
+    const fn assert_send_when_parts_are_send<K: Send, V: Send>() {
+        assert_send::<ART<K, V>>();
+        assert_send::<crate::ArtMap<K, V>>();
+        assert_send::<crate::observe::ObservedArt<K, V>>();
+        assert_send::<crate::versioned::VersionedArt<K, V>>();
+        assert_send::<crate::compact::CompactArt<K, V>>();
+        assert_send::<crate::slab::SlabArt<K, V>>();
+        assert_send::<crate::meta::MetaArt<K, V>>();
+        assert_send::<crate::int::IntArt<K, V>>();
+        assert_send::<crate::bounded::BoundedArt<K, V>>();
+        assert_send::<crate::bloom::BloomArt<K, V>>();
+        assert_send::<crate::granular::GranularArt<K, V>>();
+        assert_send::<crate::hashed::HashPrefixedArt<K, V>>();
+        assert_send::<crate::layered::LayeredArt<K, V>>();
+        assert_send::<crate::merkle::MerkleArt<K, V>>();
+        assert_send::<crate::mvcc::MvccArt<K, V>>();
+        assert_send::<crate::namespace::Namespaced<'_, K, V>>();
+        assert_send::<crate::order::ReorderedArt<K, V>>();
+        assert_send::<crate::packed::PackedArt<K, V>>();
+        assert_send::<crate::pinned::PinnedArt<K, V>>();
+        assert_send::<crate::queue::ArtPriorityQueue<K, V>>();
+        assert_send::<crate::set::ArtSet<K>>();
+        assert_send::<crate::shadow::ShadowArt<K, V>>();
+        assert_send::<crate::weighted::WeightedArt<K, V, u64>>();
+        assert_send::<crate::lazy::LazyArt<K, V, std::io::Cursor<Vec<u8>>>>();
+    }
+
+    #[allow(dead_code)]
+    const fn assert_sync_when_parts_are_sync<K: Sync, V: Sync>() {
+        assert_sync::<ART<K, V>>();
+        assert_sync::<crate::ArtMap<K, V>>();
+        assert_sync::<crate::observe::ObservedArt<K, V>>();
+        assert_sync::<crate::versioned::VersionedArt<K, V>>();
+        assert_sync::<crate::compact::CompactArt<K, V>>();
+        assert_sync::<crate::slab::SlabArt<K, V>>();
+        assert_sync::<crate::meta::MetaArt<K, V>>();
+        assert_sync::<crate::int::IntArt<K, V>>();
+        assert_sync::<crate::bounded::BoundedArt<K, V>>();
+        assert_sync::<crate::bloom::BloomArt<K, V>>();
+        assert_sync::<crate::granular::GranularArt<K, V>>();
+        assert_sync::<crate::hashed::HashPrefixedArt<K, V>>();
+        assert_sync::<crate::layered::LayeredArt<K, V>>();
+        assert_sync::<crate::merkle::MerkleArt<K, V>>();
+        assert_sync::<crate::mvcc::MvccArt<K, V>>();
+        assert_sync::<crate::namespace::Namespaced<'_, K, V>>();
+        assert_sync::<crate::order::ReorderedArt<K, V>>();
+        assert_sync::<crate::packed::PackedArt<K, V>>();
+        assert_sync::<crate::pinned::PinnedArt<K, V>>();
+        assert_sync::<crate::queue::ArtPriorityQueue<K, V>>();
+        assert_sync::<crate::set::ArtSet<K>>();
+        assert_sync::<crate::shadow::ShadowArt<K, V>>();
+        assert_sync::<crate::weighted::WeightedArt<K, V, u64>>();
+        assert_sync::<crate::lazy::LazyArt<K, V, std::io::Cursor<Vec<u8>>>>();
+    }
+
+    #[allow(dead_code)]
+    const fn assert_recycling_send_when_parts_are_send<K: Send + 'static, V: Send + 'static>() {
+        assert_send::<crate::recycle::RecyclingArt<K, V>>();
+    }
+
+    #[allow(dead_code)]
+    const fn assert_recycling_sync_when_parts_are_sync<K: Sync + 'static, V: Sync + 'static>() {
+        assert_sync::<crate::recycle::RecyclingArt<K, V>>();
+    }
+
+    #[allow(dead_code)]
+    const fn assert_shared_types_when_parts_are_send_sync<K, V>()
+    where
+        K: Send + Sync + Clone,
+        V: Send + Sync + Clone,
+    {
+        assert_send::<ShardedArt<K, V, 4>>();
+        assert_sync::<ShardedArt<K, V, 4>>();
+        assert_send::<PrefixShardedArt<K, V>>();
+        assert_sync::<PrefixShardedArt<K, V>>();
+        assert_send::<swmr::SwmrArt<K, V>>();
+        assert_sync::<swmr::SwmrArt<K, V>>();
+        assert_send::<swmr::Reader<K, V>>();
+        assert_sync::<swmr::Reader<K, V>>();
+        assert_send::<swmr::ReadHandle<K, V>>();
+        assert_sync::<swmr::ReadHandle<K, V>>();
+        assert_send::<swmr::Snapshot<K, V>>();
+        assert_sync::<swmr::Snapshot<K, V>>();
+        assert_send::<crate::compact::ArcSnapshot<K, V>>();
+        assert_sync::<crate::compact::ArcSnapshot<K, V>>();
+    }
+
+    #[allow(dead_code)]
+    const fn assert_interner() {
+        assert_send::<crate::interner::Interner>();
+        assert_sync::<crate::interner::Interner>();
+        assert_send::<crate::bulk::BulkBuilder>();
+        assert_sync::<crate::bulk::BulkBuilder>();
+    }
+
+    #[allow(dead_code)]
+    const fn assert_borrowing_types_when_parts_are_sync<'a, K: Sync + 'a, V: Sync + 'a>() {
+        assert_send::<crate::iter::Iter<'a, K, V>>();
+        assert_sync::<crate::iter::Iter<'a, K, V>>();
+        assert_send::<crate::iter::Encoded<'a, K, V>>();
+        assert_sync::<crate::iter::Encoded<'a, K, V>>();
+        assert_send::<crate::iter::Annotated<'a, K, V>>();
+        assert_sync::<crate::iter::Annotated<'a, K, V>>();
+        assert_send::<crate::iter::KeyPaths<'a, K, V>>();
+        assert_sync::<crate::iter::KeyPaths<'a, K, V>>();
+        assert_send::<crate::iter::PrefixesOf<'a, K, V>>();
+        assert_sync::<crate::iter::PrefixesOf<'a, K, V>>();
+        assert_send::<crate::cursor::Cursor<'a, K, V>>();
+        assert_sync::<crate::cursor::Cursor<'a, K, V>>();
+        assert_sync::<crate::iter::IterMut<'a, K, V>>();
+        assert_sync::<crate::cursor::CursorMut<'a, K, V>>();
+    }
+
+    #[allow(dead_code)]
+    const fn assert_cursor_mut_when_parts_are_send_sync<'a, K, V>()
+    where
+        K: Send + Sync + 'a,
+        V: Send + Sync + 'a,
+    {
+        assert_send::<crate::cursor::CursorMut<'a, K, V>>();
+    }
+
+    #[allow(dead_code)]
+    const fn assert_iter_mut_when_parts_are_send<'a, K: Send + 'a, V: Send + 'a>() {
+        assert_send::<crate::iter::IterMut<'a, K, V>>();
+    }
+
+    #[test]
+    fn test_auto_traits() {
+        // A tree holding values that are not `Sync` can still be moved to another thread.
+        assert_send::<ART<String, Cell<u32>>>();
+        let mut tree = ART::<String, Cell<u32>>::new();
+        tree.insert("hello".to_string(), Cell::new(1));
+        let tree = std::thread::spawn(move || {
+            tree.search("hello").expect("value must exist").set(2);
+            tree
+        })
+        .join()
+        .expect("thread must not panic");
+        assert_eq!(tree.search("hello").map(Cell::get), Some(2));
+
+        // A tree can be shared for reading by many threads.
+        let mut tree = ART::<String, u32>::new();
+        tree.insert("hello".to_string(), 2);
+        let tree = Arc::new(tree);
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let tree = Arc::clone(&tree);
+                std::thread::spawn(move || tree.search("hello").copied())
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().expect("thread must not panic"), Some(2));
+        }
+    }
 
     fn get_key_samples(
         prefix_sizes: Range<usize>,
@@ -291,6 +942,89 @@ mod tests {
         keys
     }
 
+    #[test]
+    fn test_range_while() {
+        let mut tree = ART::<u64, u64>::new();
+        for key in 0..1000 {
+            tree.insert(key * 2, key);
+        }
+        let mut visited = Vec::new();
+        tree.range_while(10..=20, |k, _| {
+            visited.push(*k);
+            true
+        });
+        assert_eq!(visited, [10, 12, 14, 16, 18, 20]);
+
+        visited.clear();
+        tree.range_while((Bound::Excluded(10), Bound::Excluded(20)), |k, _| {
+            visited.push(*k);
+            true
+        });
+        assert_eq!(visited, [12, 14, 16, 18]);
+
+        // The closure is not called again once it returned false.
+        visited.clear();
+        tree.range_while(1.., |k, v| {
+            visited.push(*k);
+            *v < 3
+        });
+        assert_eq!(visited, [2, 4, 6]);
+
+        visited.clear();
+        tree.range_while(..5, |k, _| {
+            visited.push(*k);
+            true
+        });
+        assert_eq!(visited, [0, 2, 4]);
+        tree.range_while(2000.., |_, _| unreachable!());
+    }
+
+    #[test]
+    fn test_range_rev_while() {
+        let mut tree = ART::<u64, u64>::new();
+        for key in 0..1000 {
+            tree.insert(key * 2, key);
+        }
+        let mut visited = Vec::new();
+        tree.range_rev_while(10..=20, |k, _| {
+            visited.push(*k);
+            true
+        });
+        assert_eq!(visited, [20, 18, 16, 14, 12, 10]);
+
+        visited.clear();
+        tree.range_rev_while((Bound::Excluded(10), Bound::Excluded(20)), |k, _| {
+            visited.push(*k);
+            true
+        });
+        assert_eq!(visited, [18, 16, 14, 12]);
+
+        // The latest 3 events before a time that falls between two keys.
+        visited.clear();
+        tree.range_rev_while(..1001, |k, _| {
+            visited.push(*k);
+            visited.len() < 3
+        });
+        assert_eq!(visited, [1000, 998, 996]);
+
+        visited.clear();
+        tree.range_rev_while(1990.., |k, _| {
+            visited.push(*k);
+            true
+        });
+        assert_eq!(visited, [1998, 1996, 1994, 1992, 1990]);
+
+        visited.clear();
+        tree.range_rev_while(..=3, |k, _| {
+            visited.push(*k);
+            true
+        });
+        assert_eq!(visited, [2, 0]);
+        tree.range_rev_while(..0, |_, _| unreachable!());
+        tree.range_rev_while(5..5, |_, _| unreachable!());
+        ART::<u64, u64>::new().range_rev_while(.., |_, _| unreachable!());
+    }
+
     #[test]
     fn test_insert_tree_tiny() {
         let mut tree = ART::<String, String>::default();
@@ -302,6 +1036,249 @@ mod tests {
         assert_eq!(v1.map(String::as_str), Some("world"));
     }
 
+    #[test]
+    fn test_custom_capacities() {
+        let mut rng = rand::thread_rng();
+        let mut tree = ART::<u32, u32, 10, 8, 24, 64>::new();
+        let mut expected = BTreeMap::new();
+        for _ in 0..10000 {
+            let key = rng.gen_range(0..4096) << rng.gen_range(0..20);
+            if rng.gen_bool(0.6) {
+                assert_eq!(tree.insert(key, key), expected.insert(key, key));
+            } else {
+                assert_eq!(tree.delete(&key), expected.remove(&key));
+            }
+        }
+        assert_eq!(tree.len(), expected.len());
+        assert!(tree.iter().eq(expected.iter()));
+    }
+
+    #[test]
+    fn test_without_path_compression() {
+        let mut rng = rand::thread_rng();
+        let mut tree = ART::<String, usize>::without_path_compression();
+        let mut expected = BTreeMap::new();
+        for i in 0..5000 {
+            let key: String = (0..rng.gen_range(1..8))
+                .map(|_| rng.gen_range('a'..='c'))
+                .collect();
+            if rng.gen_bool(0.6) {
+                assert_eq!(tree.insert(key.clone(), i), expected.insert(key, i));
+            } else {
+                assert_eq!(tree.delete(&key), expected.remove(&key));
+            }
+        }
+        assert!(!tree.compresses_paths());
+        assert!(tree.iter().eq(expected.iter()));
+        for key in expected.keys() {
+            assert_eq!(tree.search(key), expected.get(key));
+        }
+
+        // Every byte shared by the keys takes a level of the tree.
+        let mut tree = ART::<&str, ()>::without_path_compression();
+        tree.insert("prefix1", ());
+        tree.insert("prefix2", ());
+        assert!(tree.iter_annotated().all(|(depth, ..)| depth == 7));
+        tree.delete("prefix1");
+        assert!(tree.iter_annotated().all(|(depth, ..)| depth == 0));
+    }
+
+    #[test]
+    fn test_remove_where() {
+        let mut rng = rand::thread_rng();
+        for compress in [true, false] {
+            let mut tree = if compress {
+                ART::<String, u32>::new()
+            } else {
+                ART::without_path_compression()
+            };
+            let mut expected = BTreeMap::new();
+            for i in 0..5000 {
+                let key: String = (0..rng.gen_range(1..6))
+                    .map(|_| rng.gen_range('a'..='e'))
+                    .collect();
+                tree.insert(key.clone(), i);
+                expected.insert(key, i);
+            }
+            let removed = tree.remove_where(|k, v| k.starts_with('b') || v % 3 == 0);
+            let (gone, kept): (BTreeMap<_, _>, BTreeMap<_, _>) = expected
+                .into_iter()
+                .partition(|(k, v)| k.starts_with('b') || v % 3 == 0);
+            assert!(removed.iter().map(|(k, v)| (k, v)).eq(gone.iter()));
+            assert_eq!(tree.len(), kept.len());
+            assert!(tree.iter().eq(kept.iter()));
+            let mut expected = kept;
+            for i in 0..2000 {
+                let key: String = (0..rng.gen_range(1..6))
+                    .map(|_| rng.gen_range('a'..='e'))
+                    .collect();
+                if rng.gen_bool(0.5) {
+                    assert_eq!(tree.insert(key.clone(), i), expected.insert(key, i));
+                } else {
+                    assert_eq!(tree.delete(&key), expected.remove(&key));
+                }
+            }
+            assert!(tree.iter().eq(expected.iter()));
+            assert_eq!(tree.remove_where(|_, _| true).len(), expected.len());
+            assert!(tree.is_empty());
+            assert_eq!(tree.iter().count(), 0);
+        }
+    }
+
+    #[test]
+    fn test_len() {
+        let mut tree: ART<String, u32> = [("a", 1), ("b", 2), ("a", 3)]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        assert_eq!(tree.len(), 2);
+        tree.insert("c".to_string(), 3);
+        tree.insert("c".to_string(), 4);
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.delete("a"), Some(3));
+        assert_eq!(tree.delete("a"), None);
+        assert_eq!(tree.len(), 2);
+        let result = tree.transaction(|txn| {
+            txn.delete("b");
+            txn.delete("x");
+            txn.insert("d".to_string(), 5);
+            Ok::<_, ()>(())
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(tree.len(), 2);
+        tree.delete("c");
+        tree.delete("d");
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_byte_container_keys() {
+        let mut arrays = ART::<[u8; 3], usize>::new();
+        let mut borrowed = ART::<&[u8; 3], usize>::new();
+        let mut boxed = ART::<Box<[u8]>, usize>::new();
+        let mut shared = ART::<Arc<[u8]>, usize>::new();
+        let mut cows = ART::<std::borrow::Cow<'_, [u8]>, usize>::new();
+        let keys = [*b"abc", *b"abd", *b"b\xff\x00", *b"aaa"];
+        for (idx, key) in keys.iter().enumerate() {
+            arrays.insert(*key, idx);
+            borrowed.insert(key, idx);
+            boxed.insert(Box::from(key.as_slice()), idx);
+            shared.insert(Arc::from(key.as_slice()), idx);
+            cows.insert(std::borrow::Cow::Borrowed(key.as_slice()), idx);
+        }
+        for (idx, key) in keys.iter().enumerate() {
+            assert_eq!(arrays.search(key), Some(&idx));
+            assert_eq!(borrowed.search(&key), Some(&idx));
+            assert_eq!(boxed.search(key.as_slice()), Some(&idx));
+            assert_eq!(shared.search(key.as_slice()), Some(&idx));
+            assert_eq!(cows.search(key.as_slice()), Some(&idx));
+        }
+        let mut sorted = keys;
+        sorted.sort_unstable();
+        assert!(arrays.iter().map(|(k, _)| k).eq(&sorted));
+        assert!(shared
+            .iter()
+            .map(|(k, _)| &**k)
+            .eq(sorted.iter().map(<[u8; 3]>::as_slice)));
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_bytes_keys() {
+        let received = bytes::Bytes::from_static(b"GET /index.html\nGET /about.html\n");
+        let mut tree = ART::<bytes::Bytes, usize>::new();
+        for (idx, line) in received.split(|&b| b == b'\n').enumerate() {
+            // Slicing shares the buffer of the request instead of copying the line.
+            tree.insert(received.slice_ref(line), idx);
+        }
+        assert_eq!(tree.search(b"GET /about.html".as_slice()), Some(&1));
+        assert_eq!(tree.search(b"".as_slice()), Some(&2));
+
+        let mut tree = ART::<bytes::BytesMut, usize>::new();
+        tree.insert(bytes::BytesMut::from(&b"key"[..]), 0);
+        assert_eq!(tree.search(b"key".as_slice()), Some(&0));
+    }
+
+    #[test]
+    fn test_insert_checked() {
+        let mut tree = ART::<String, u32>::new();
+        assert_eq!(tree.insert_checked("a".to_string(), 1), Ok(None));
+        assert_eq!(tree.insert_checked("ab".to_string(), 2), Ok(None));
+        assert_eq!(tree.insert_checked("a".to_string(), 10), Ok(Some(1)));
+        for i in 0..300 {
+            assert_eq!(tree.insert_checked(format!("a{i}"), i), Ok(None));
+        }
+        assert_eq!(tree.len(), 302);
+        assert_eq!(tree.search("a"), Some(&10));
+        assert_eq!(tree.search("a299"), Some(&299));
+    }
+
+    // The checks of the nodes encode keys after the tree was modified.
+    #[cfg(not(feature = "check-invariants"))]
+    #[test]
+    fn test_panic_safety() {
+        use crate::BytesComparable;
+
+        thread_local! {
+            /// The number of keys that can still be encoded before encoding a [`Fragile`] key
+            /// panics.
+            static ENCODINGS_LEFT: Cell<usize> = const { Cell::new(usize::MAX) };
+        }
+
+        /// A key whose encoding panics once the encodings left run out.
+        #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+        struct Fragile(Vec<u8>);
+
+        impl BytesComparable for Fragile {
+            type Target<'a>
+                = &'a [u8]
+            where
+                Self: 'a;
+
+            fn bytes(&self) -> Self::Target<'_> {
+                let left = ENCODINGS_LEFT.get();
+                assert!(left > 0, "encoding panicked");
+                ENCODINGS_LEFT.set(left - 1);
+                &self.0
+            }
+        }
+
+        let mut rng = rand::thread_rng();
+        for mut tree in [
+            ART::<Fragile, usize>::new(),
+            ART::without_path_compression(),
+        ] {
+            let mut expected = BTreeMap::new();
+            for idx in 0..3000 {
+                // Short keys over a small alphabet share long prefixes, so that most inserts split
+                // leaves or prefixes.
+                let key: Vec<u8> = (0..rng.gen_range(1..6))
+                    .map(|_| rng.gen_range(1..4))
+                    .collect();
+                ENCODINGS_LEFT.set(rng.gen_range(0..8));
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    if rng.gen_bool(0.7) {
+                        tree.insert(Fragile(key.clone()), idx);
+                    } else {
+                        tree.delete(&Fragile(key.clone()));
+                    }
+                }));
+                ENCODINGS_LEFT.set(usize::MAX);
+                // A panicking mutation changes nothing.
+                if result.is_ok() {
+                    if tree.search(&Fragile(key.clone())).is_some() {
+                        expected.insert(key, idx);
+                    } else {
+                        expected.remove(&key);
+                    }
+                }
+                assert_eq!(tree.validate(), Ok(()));
+                assert_eq!(tree.len(), expected.len());
+            }
+            assert!(tree.iter().map(|(k, v)| (&k.0, v)).eq(expected.iter()));
+        }
+    }
+
     #[test]
     fn test_all_operations() {
         let keys = get_key_samples(0..256, 256, 64);
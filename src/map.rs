@@ -0,0 +1,790 @@
+//! A map with the interface of [`BTreeMap`](std::collections::BTreeMap).
+//!
+//! [`ArtMap`] wraps an [`ART`] behind the methods and trait implementations of the ordered map of
+//! the standard library, with the same names and signatures where possible, so that code using a
+//! `BTreeMap` can usually switch to an adaptive radix tree by changing the type. The deviations are
+//! deliberate:
+//!
+//! - Keys are ordered by their encoding of [`BytesComparable`] instead of [`Ord`], and lookups take
+//!   any borrowed form of the key that implements [`BytesComparable`] as well. The encodings of the
+//!   crate keep the order of [`Ord`] for integers, strings, and byte strings.
+//! - A key can not be a prefix of another key followed by a zero byte, as in an [`ART`].
+//! - [`ArtMap::iter`], [`ArtMap::keys`], [`ArtMap::values`], and [`ArtMap::range`] can not be
+//!   reversed, and the iterators of the keys and values are opaque types.
+//! - [`ArtMap::iter_mut`], [`ArtMap::values_mut`], and the owning iterators collect the entries
+//!   into a vector before yielding the first one.
+//! - [`ArtMap::range`] returns an empty iterator instead of panicking when the start of the range
+//!   is after its end, and it returns a [`Scan`], which can be resumed later.
+//! - `range_mut`, `first_entry`, `last_entry`, `extract_if`, and the cursors are not provided, see
+//!   [`ART::cursor_mut_at`] for a cursor that mutates the tree.
+//! - Cloning a map inserts the entries into a new tree one by one.
+
+use std::{
+    borrow::Borrow,
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+    ops::{Index, RangeBounds},
+};
+
+use crate::{
+    iter::{IntoIter, IntoKeys, IntoValues, Iter, IterMut},
+    node::Leaf,
+    scan::Scan,
+    BytesComparable, ART,
+};
+
+/// An ordered map backed by an adaptive radix tree, see the [module documentation](self).
+pub struct ArtMap<K, V> {
+    tree: ART<K, V>,
+}
+
+impl<K, V> ArtMap<K, V> {
+    /// Makes a new, empty map.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { tree: ART::new() }
+    }
+
+    /// Returns the number of elements in the map.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Returns true if the map contains no elements.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Clears the map, removing all elements.
+    pub fn clear(&mut self) {
+        self.tree = ART::new();
+    }
+
+    /// Gets an iterator over the entries of the map, sorted by key.
+    #[must_use]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.tree.iter()
+    }
+
+    /// Gets an iterator over the keys of the map, in sorted order.
+    pub fn keys(&self) -> impl ExactSizeIterator<Item = &K> + FusedIterator + '_ {
+        self.iter().map(|(k, _)| k)
+    }
+
+    /// Gets an iterator over the values of the map, in order by key.
+    pub fn values(&self) -> impl ExactSizeIterator<Item = &V> + FusedIterator + '_ {
+        self.iter().map(|(_, v)| v)
+    }
+
+    /// Returns a reference to the underlying tree.
+    #[must_use]
+    pub const fn as_art(&self) -> &ART<K, V> {
+        &self.tree
+    }
+
+    /// Returns the underlying tree.
+    #[must_use]
+    pub fn into_art(self) -> ART<K, V> {
+        self.tree
+    }
+}
+
+impl<K, V> ArtMap<K, V>
+where
+    K: BytesComparable,
+{
+    /// Returns a reference to the value corresponding to the key.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(key)
+    }
+
+    /// Returns the key-value pair corresponding to the supplied key.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search_key_value(key)
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.leaf_mut(key.bytes().as_ref())
+            .map(|leaf| &mut leaf.value)
+    }
+
+    /// Returns true if the map contains a value for the specified key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Inserts a key-value pair into the map, returning the old value if the key was present. The
+    /// key is not updated, like with [`BTreeMap::insert`](std::collections::BTreeMap::insert).
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.tree.insert(key, value)
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key was previously in the
+    /// map.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.delete(key)
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the key was previously in
+    /// the map.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree
+            .delete_leaf(key.bytes().as_ref())
+            .map(|leaf| (leaf.key, leaf.value))
+    }
+
+    /// Returns the first key-value pair in the map, whose key is the minimum key.
+    #[must_use]
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.tree.min()
+    }
+
+    /// Returns the last key-value pair in the map, whose key is the maximum key.
+    #[must_use]
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.tree.max()
+    }
+
+    /// Removes and returns the first element in the map, whose key is the minimum key.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let key = self.tree.min()?.0.bytes().as_ref().to_vec();
+        self.tree
+            .delete_leaf(&key)
+            .map(|leaf| (leaf.key, leaf.value))
+    }
+
+    /// Removes and returns the last element in the map, whose key is the maximum key.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let key = self.tree.max()?.0.bytes().as_ref().to_vec();
+        self.tree
+            .delete_leaf(&key)
+            .map(|leaf| (leaf.key, leaf.value))
+    }
+
+    /// Retains only the elements specified by the predicate, visiting them in ascending key order.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let rejected: Vec<Vec<u8>> = self
+            .iter_mut()
+            .filter_map(|(k, v)| (!f(k, v)).then(|| k.bytes().as_ref().to_vec()))
+            .collect();
+        for key in rejected {
+            self.tree.delete_leaf(&key);
+        }
+    }
+
+    /// Moves all elements from `other` into `self`, leaving `other` empty. The values of `other`
+    /// replace the values of the keys that are in both maps.
+    pub fn append(&mut self, other: &mut Self) {
+        std::mem::take(other).tree.into_each(|k, v| {
+            self.insert(k, v);
+        });
+    }
+
+    /// Splits the collection into two at the given key, returning everything after the given key,
+    /// including the key.
+    #[must_use]
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let key = key.bytes();
+        let key = key.as_ref();
+        self.tree
+            .remove_where(|k, _| k.bytes().as_ref() >= key)
+            .into_iter()
+            .collect()
+    }
+
+    /// Constructs an iterator over a sub-range of elements in the map, in key order.
+    pub fn range<Q, R>(&self, range: R) -> Scan<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.tree.scan(range)
+    }
+
+    /// Gets the given key's corresponding entry in the map for in-place manipulation.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        let encoded = key.bytes().as_ref().to_vec();
+        if self.leaf_mut(&encoded).is_some() {
+            Entry::Occupied(OccupiedEntry {
+                key: encoded,
+                map: self,
+            })
+        } else {
+            Entry::Vacant(VacantEntry { key, map: self })
+        }
+    }
+
+    /// Gets a mutable iterator over the entries of the map, sorted by key.
+    #[must_use]
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        self.tree.iter_mut()
+    }
+
+    /// Gets a mutable iterator over the values of the map, in order by key.
+    pub fn values_mut(&mut self) -> impl ExactSizeIterator<Item = &mut V> + FusedIterator + '_ {
+        self.iter_mut().map(|(_, v)| v)
+    }
+
+    /// Creates a consuming iterator visiting all the keys, in sorted order.
+    #[must_use]
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        self.tree.into_keys()
+    }
+
+    /// Creates a consuming iterator visiting all the values, in order by key.
+    #[must_use]
+    pub fn into_values(self) -> IntoValues<K, V> {
+        self.tree.into_values()
+    }
+
+    fn leaf(&self, key: &[u8]) -> Option<&Leaf<K, V>> {
+        self.tree.root.as_ref().and_then(|root| root.search(key, 0))
+    }
+
+    fn leaf_mut(&mut self, key: &[u8]) -> Option<&mut Leaf<K, V>> {
+        self.tree
+            .root
+            .as_mut()
+            .and_then(|root| root.search_mut(key, 0))
+    }
+}
+
+/// A view into a single entry in a map, which may either be vacant or occupied, see
+/// [`ArtMap::entry`].
+#[derive(Debug)]
+pub enum Entry<'a, K, V>
+where
+    K: BytesComparable,
+{
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K, V>),
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K, V>),
+}
+
+/// A view into a vacant entry in an [`ArtMap`].
+pub struct VacantEntry<'a, K, V> {
+    key: K,
+    map: &'a mut ArtMap<K, V>,
+}
+
+/// A view into an occupied entry in an [`ArtMap`].
+pub struct OccupiedEntry<'a, K, V> {
+    /// The encoded key of the entry.
+    key: Vec<u8>,
+    map: &'a mut ArtMap<K, V>,
+}
+
+impl<'a, K, V> Entry<'a, K, V>
+where
+    K: BytesComparable,
+{
+    /// Ensures a value is in the entry by inserting the default if empty, and returns a mutable
+    /// reference to the value in the entry.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default function if empty,
+    /// and returns a mutable reference to the value in the entry.
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        self.or_insert_with_key(|_| default())
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default function called with
+    /// the key if empty, and returns a mutable reference to the value in the entry.
+    pub fn or_insert_with_key<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce(&K) -> V,
+    {
+        match self {
+            Self::Occupied(entry) => entry.into_mut(),
+            Self::Vacant(entry) => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default value if empty, and returns a
+    /// mutable reference to the value in the entry.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any potential inserts into the
+    /// map.
+    #[must_use]
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Self::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+
+    /// Returns a reference to this entry's key.
+    #[must_use]
+    pub fn key(&self) -> &K {
+        match self {
+            Self::Occupied(entry) => entry.key(),
+            Self::Vacant(entry) => entry.key(),
+        }
+    }
+}
+
+impl<K, V> std::fmt::Debug for VacantEntry<'_, K, V>
+where
+    K: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("VacantEntry").field(&self.key).finish()
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V>
+where
+    K: BytesComparable,
+{
+    /// Gets a reference to the key that would be used when inserting a value through the entry.
+    #[must_use]
+    pub const fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes ownership of the key.
+    #[must_use]
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Sets the value of the entry with the key of the entry, and returns a mutable reference to
+    /// it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let encoded = self.key.bytes().as_ref().to_vec();
+        self.map.tree.insert(self.key, value);
+        let Some(leaf) = self.map.leaf_mut(&encoded) else {
+            unreachable!("the key is in the map after inserting it")
+        };
+        &mut leaf.value
+    }
+}
+
+impl<K, V> std::fmt::Debug for OccupiedEntry<'_, K, V>
+where
+    K: BytesComparable + std::fmt::Debug,
+    V: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OccupiedEntry")
+            .field("key", self.key())
+            .field("value", self.get())
+            .finish()
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V>
+where
+    K: BytesComparable,
+{
+    /// Gets a reference to the key in the entry.
+    #[must_use]
+    pub fn key(&self) -> &K {
+        &self.leaf().key
+    }
+
+    /// Gets a reference to the value in the entry.
+    #[must_use]
+    pub fn get(&self) -> &V {
+        &self.leaf().value
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    pub fn get_mut(&mut self) -> &mut V {
+        let Some(leaf) = self.map.leaf_mut(&self.key) else {
+            unreachable!("an occupied entry is in the map")
+        };
+        &mut leaf.value
+    }
+
+    /// Converts the entry into a mutable reference to its value.
+    #[must_use]
+    pub fn into_mut(self) -> &'a mut V {
+        let Some(leaf) = self.map.leaf_mut(&self.key) else {
+            unreachable!("an occupied entry is in the map")
+        };
+        &mut leaf.value
+    }
+
+    /// Sets the value of the entry, and returns the entry's old value.
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    /// Takes the value of the entry out of the map, and returns it.
+    #[must_use]
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Takes the key-value pair out of the map, and returns it.
+    #[must_use]
+    pub fn remove_entry(self) -> (K, V) {
+        let Some(leaf) = self.map.tree.delete_leaf(&self.key) else {
+            unreachable!("an occupied entry is in the map")
+        };
+        (leaf.key, leaf.value)
+    }
+
+    fn leaf(&self) -> &Leaf<K, V> {
+        let Some(leaf) = self.map.leaf(&self.key) else {
+            unreachable!("an occupied entry is in the map")
+        };
+        leaf
+    }
+}
+
+impl<K, V> Default for ArtMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> std::fmt::Debug for ArtMap<K, V>
+where
+    K: std::fmt::Debug,
+    V: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V> Clone for ArtMap<K, V>
+where
+    K: BytesComparable + Clone,
+    V: Clone,
+{
+    fn clone(&self) -> Self {
+        self.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+}
+
+impl<K, V> PartialEq for ArtMap<K, V>
+where
+    K: PartialEq,
+    V: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K, V> Eq for ArtMap<K, V>
+where
+    K: Eq,
+    V: Eq,
+{
+}
+
+impl<K, V> PartialOrd for ArtMap<K, V>
+where
+    K: PartialOrd,
+    V: PartialOrd,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K, V> Ord for ArtMap<K, V>
+where
+    K: Ord,
+    V: Ord,
+{
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K, V> Hash for ArtMap<K, V>
+where
+    K: Hash,
+    V: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for entry in self {
+            entry.hash(state);
+        }
+    }
+}
+
+impl<K, V, Q> Index<&Q> for ArtMap<K, V>
+where
+    K: BytesComparable + Borrow<Q>,
+    Q: BytesComparable + ?Sized,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for ArtMap<K, V>
+where
+    K: BytesComparable,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, const M: usize> From<[(K, V); M]> for ArtMap<K, V>
+where
+    K: BytesComparable,
+{
+    fn from(entries: [(K, V); M]) -> Self {
+        entries.into_iter().collect()
+    }
+}
+
+impl<K, V> Extend<(K, V)> for ArtMap<K, V>
+where
+    K: BytesComparable,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K, V> Extend<(&'a K, &'a V)> for ArtMap<K, V>
+where
+    K: BytesComparable + Copy,
+    V: Copy,
+{
+    fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(&k, &v)| (k, v)));
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a ArtMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a mut ArtMap<K, V>
+where
+    K: BytesComparable,
+{
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V> IntoIterator for ArtMap<K, V>
+where
+    K: BytesComparable,
+{
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tree.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{hash_map::DefaultHasher, BTreeMap},
+        hash::{Hash, Hasher},
+        ops::Bound,
+    };
+
+    use rand::Rng;
+
+    use super::{ArtMap, Entry};
+
+    fn hash<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_matches_btree_map() {
+        let mut rng = rand::thread_rng();
+        let mut map = ArtMap::new();
+        let mut expected = BTreeMap::new();
+        for idx in 0..20_000u32 {
+            let key = rng.gen_range(0..3000u32);
+            match rng.gen_range(0..8) {
+                0..=2 => assert_eq!(map.insert(key, idx), expected.insert(key, idx)),
+                3 => assert_eq!(map.remove(&key), expected.remove(&key)),
+                4 => assert_eq!(map.remove_entry(&key), expected.remove_entry(&key)),
+                5 => {
+                    *map.entry(key).or_default() += 1;
+                    *expected.entry(key).or_default() += 1;
+                }
+                6 => assert_eq!(map.pop_first(), expected.pop_first()),
+                _ => assert_eq!(map.pop_last(), expected.pop_last()),
+            }
+        }
+        assert_eq!(map.len(), expected.len());
+        assert!(map.iter().eq(expected.iter()));
+        assert!(map.keys().eq(expected.keys()));
+        assert!(map.values().eq(expected.values()));
+        assert_eq!(map.first_key_value(), expected.first_key_value());
+        assert_eq!(map.last_key_value(), expected.last_key_value());
+        for bounds in [
+            (Bound::Included(100), Bound::Excluded(200)),
+            (Bound::Excluded(100), Bound::Included(200)),
+            (Bound::Unbounded, Bound::Included(50)),
+            (Bound::Included(2990), Bound::Unbounded),
+        ] {
+            assert!(map.range(bounds).eq(expected.range(bounds)));
+        }
+        // A reversed range is empty instead of panicking.
+        assert_eq!(
+            map.range((Bound::Included(200), Bound::Excluded(100)))
+                .next(),
+            None
+        );
+
+        map.retain(|k, v| {
+            *v += 1;
+            k % 3 == 0
+        });
+        expected.retain(|k, v| {
+            *v += 1;
+            k % 3 == 0
+        });
+        assert!(map.iter().eq(expected.iter()));
+
+        let mut tail = map.split_off(&1500);
+        let expected_tail = expected.split_off(&1500);
+        assert!(map.iter().eq(expected.iter()));
+        assert!(tail.iter().eq(expected_tail.iter()));
+        map.append(&mut tail);
+        assert!(tail.is_empty());
+        assert_eq!(map.len(), expected.len() + expected_tail.len());
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut map: ArtMap<String, Vec<u32>> = ArtMap::new();
+        map.entry("a".to_string()).or_default().push(1);
+        map.entry("a".to_string())
+            .and_modify(|v| v.push(2))
+            .or_insert_with(|| vec![0]);
+        map.entry("b".to_string())
+            .and_modify(|v| v.push(2))
+            .or_insert_with_key(|k| vec![u32::try_from(k.len()).unwrap()]);
+        assert_eq!(map["a"], [1, 2]);
+        assert_eq!(map["b"], [1]);
+
+        match map.entry("a".to_string()) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), "a");
+                assert_eq!(entry.insert(vec![3]), [1, 2]);
+                assert_eq!(entry.get(), &[3]);
+                assert_eq!(entry.remove_entry(), ("a".to_string(), vec![3]));
+            }
+            Entry::Vacant(_) => unreachable!("the key was inserted"),
+        }
+        match map.entry("c".to_string()) {
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), "c");
+                entry.insert(vec![4]).push(5);
+            }
+            Entry::Occupied(_) => unreachable!("the key was not inserted"),
+        }
+        assert_eq!(map.get("c"), Some(&vec![4, 5]));
+        assert!(!map.contains_key("a"));
+        assert_eq!(
+            format!("{:?}", map.entry("b".to_string())),
+            r#"Occupied(OccupiedEntry { key: "b", value: [1] })"#
+        );
+    }
+
+    #[test]
+    fn test_traits() {
+        let map = ArtMap::from([(3u8, "c"), (1, "a"), (2, "b")]);
+        assert_eq!(format!("{map:?}"), r#"{1: "a", 2: "b", 3: "c"}"#);
+        let mut copy = map.clone();
+        assert_eq!(copy, map);
+        assert_eq!(hash(&copy), hash(&map));
+        copy.extend([(&4, &"d")]);
+        assert!(copy > map);
+        assert_ne!(hash(&copy), hash(&map));
+        for (_, value) in &mut copy {
+            *value = "x";
+        }
+        assert!(copy.values_mut().all(|value| *value == "x"));
+        let keys: Vec<u8> = copy.clone().into_keys().collect();
+        assert_eq!(keys, [1, 2, 3, 4]);
+        assert_eq!(copy.into_iter().count(), 4);
+        let total: usize = map.into_values().map(str::len).sum();
+        assert_eq!(total, 3);
+        let mut emptied: ArtMap<u8, u8> = (0..10).map(|i| (i, i)).collect();
+        emptied.clear();
+        assert!(emptied.is_empty());
+        assert_eq!(emptied.get_key_value(&1), None);
+    }
+}
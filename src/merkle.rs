@@ -0,0 +1,458 @@
+//! A tree that maintains a hash of every subtree, so that replicas can find where they diverge.
+//!
+//! A [`MerkleArt`] hashes every entry from its key and its value, encoded with
+//! [`Encode`], and every inner node from the byte keys and hashes of its children, like a Merkle
+//! tree over the trie. The shape of the trie, apart from the kinds of its nodes, only depends on
+//! the keys it holds, so two trees holding the same entries have the same
+//! [`MerkleArt::root_hash`]. The hashes of the inner nodes are indexed by the bytes that all keys
+//! below the node start with, like the maxima of a [`WeightedArt`](crate::weighted::WeightedArt),
+//! and a write only recomputes the hashes of the inner nodes on the path to its key.
+//!
+//! [`MerkleArt::diff_by_hash`] descends both trees together and skips every pair of subtrees with
+//! equal hashes, so a single divergent entry is found with a number of comparisons that grows with
+//! the depth of the trees rather than with their size. The hashes are 64-bit FNV-1a, which is
+//! enough to detect accidental divergence between replicas, but not to resist forged entries.
+
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    collections::HashMap,
+    io::{self, Write},
+    ops::Bound,
+};
+
+use crate::{
+    node::{Inner, Node, Owned},
+    persist::Encode,
+    weighted::{inners_on_path, successor},
+    BytesComparable, ART,
+};
+
+type MerkleNode<K, V, const N: usize> = Node<K, (V, u64), N>;
+type MerkleInner<K, V, const N: usize> = Inner<K, (V, u64), N, Owned, 4, 16, 48>;
+
+/// An adaptive radix tree that maintains a hash of every subtree, see the [module
+/// documentation](self).
+#[derive(Debug)]
+pub struct MerkleArt<K, V, const N: usize = 10> {
+    /// The entries of the tree with their hashes.
+    tree: ART<K, (V, u64), N>,
+    /// The hash of every inner node, indexed by the bytes that all keys below the node start with.
+    hashes: HashMap<Box<[u8]>, u64>,
+}
+
+impl<K, V, const N: usize> MerkleArt<K, V, N> {
+    /// Create an empty tree.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            tree: ART::new(),
+            hashes: HashMap::new(),
+        }
+    }
+
+    /// Return the number of entries in the tree.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Return true if the tree holds no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Return an iterator over the entries in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.tree.iter().map(|(key, (value, _))| (key, value))
+    }
+}
+
+impl<K, V, const N: usize> Default for MerkleArt<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> MerkleArt<K, V, N>
+where
+    K: BytesComparable,
+    V: Encode,
+{
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(key).map(|(value, _)| value)
+    }
+
+    /// Insert the given key-value pair into the tree, returning the value previously associated
+    /// with the key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value fails to encode.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let bytes = key.bytes().as_ref().to_vec();
+        let mut fnv = Fnv::new();
+        fnv.write_u64(bytes.len() as u64);
+        fnv.update(&bytes);
+        value.encode(&mut fnv).expect("the value can be encoded");
+        let previous = self.tree.insert(key, (value, fnv.finish()));
+        // Inserting only adds inner nodes, so every inner node on the path is refreshed.
+        update_hashes(self.tree.root.as_ref(), &bytes, &mut self.hashes, &[]);
+        previous.map(|(value, _)| value)
+    }
+
+    /// Delete the value associated with the given key.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let bytes = key.bytes();
+        let bytes = bytes.as_ref();
+        let before: Vec<_> = inners_on_path(self.tree.root.as_ref(), bytes)
+            .into_iter()
+            .map(|(_, depth)| depth)
+            .collect();
+        let (value, _) = self.tree.delete(key)?;
+        update_hashes(self.tree.root.as_ref(), bytes, &mut self.hashes, &before);
+        Some(value)
+    }
+
+    /// Return the hash of the whole tree, which is equal for trees holding the same entries, and
+    /// 0 for an empty tree.
+    #[must_use]
+    pub fn root_hash(&self) -> u64 {
+        self.tree
+            .root
+            .as_ref()
+            .map_or(0, |root| node_hash(root, &mut Vec::new(), &self.hashes))
+    }
+
+    /// Return the ranges of encoded keys in which the trees hold different entries. The ranges are
+    /// disjoint and in key order, and every entry held by only one of the trees, or with different
+    /// values, is in one of them.
+    #[must_use]
+    pub fn diff_by_hash(&self, other: &Self) -> Vec<KeyRange> {
+        let mut ranges = Vec::new();
+        match (self.tree.root.as_ref(), other.tree.root.as_ref()) {
+            (None, None) => {}
+            (Some(_), None) | (None, Some(_)) => ranges.push(key_range(&[])),
+            (Some(left), Some(right)) => {
+                let left = (left, 0, node_hash(left, &mut Vec::new(), &self.hashes));
+                let right = (right, 0, node_hash(right, &mut Vec::new(), &other.hashes));
+                let mut path = Vec::new();
+                diverging_ranges(left, right, &mut path, self, other, &mut ranges);
+            }
+        }
+        ranges
+    }
+}
+
+/// A range of encoded keys, which starts at an included key and ends before an excluded key or is
+/// unbounded.
+pub type KeyRange = (Bound<Vec<u8>>, Bound<Vec<u8>>);
+
+/// Return the range of the keys below the byte keys on the given path. A key that ends at an
+/// inner node is stored below the byte key 0, so the range starts at the path without its trailing
+/// zero bytes.
+fn key_range(path: &[u8]) -> KeyRange {
+    let len = path
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |last| last + 1);
+    let end = successor(path).map_or(Bound::Unbounded, Bound::Excluded);
+    (Bound::Included(path[..len].to_vec()), end)
+}
+
+/// A streaming 64-bit FNV-1a hash.
+struct Fnv(u64);
+
+impl Fnv {
+    const fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.update(&value.to_le_bytes());
+    }
+
+    const fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Write for Fnv {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Return the hash of the node, whose keys start with the given bytes before its prefix.
+fn node_hash<K, V, const N: usize>(
+    node: &MerkleNode<K, V, N>,
+    path: &mut Vec<u8>,
+    hashes: &HashMap<Box<[u8]>, u64>,
+) -> u64 {
+    match node {
+        Node::Leaf(leaf) => leaf.value.1,
+        Node::Inner(inner) => {
+            let depth = path.len();
+            path.extend_from_slice(inner.prefix());
+            let hash = *hashes
+                .get(path.as_slice())
+                .expect("every inner node has a hash");
+            path.truncate(depth);
+            hash
+        }
+    }
+}
+
+/// Compute the hash of the inner node, whose keys start with the given bytes, from the hashes of
+/// its children.
+fn inner_hash<K, V, const N: usize>(
+    inner: &MerkleInner<K, V, N>,
+    path: &mut Vec<u8>,
+    hashes: &HashMap<Box<[u8]>, u64>,
+) -> u64 {
+    let mut fnv = Fnv::new();
+    for (byte_key, child) in inner.children() {
+        path.push(byte_key);
+        fnv.update(&[byte_key]);
+        fnv.write_u64(node_hash(child, path, hashes));
+        path.pop();
+    }
+    fnv.finish()
+}
+
+/// Recompute the hashes of the inner nodes on the path to the key from the bottom up, after the
+/// entry of the key was written. The hashes of the inner nodes that were on the path before the
+/// write, whose prefix lengths are given, are dropped if the nodes are gone.
+fn update_hashes<K, V, const N: usize>(
+    root: Option<&MerkleNode<K, V, N>>,
+    key: &[u8],
+    hashes: &mut HashMap<Box<[u8]>, u64>,
+    before: &[usize],
+) where
+    K: BytesComparable,
+{
+    let inners = inners_on_path(root, key);
+    for len in before {
+        if inners.iter().all(|(_, depth)| depth != len) {
+            hashes.remove(&key[..*len]);
+        }
+    }
+    let mut path = Vec::new();
+    for (inner, depth) in inners.into_iter().rev() {
+        path.clear();
+        path.extend_from_slice(&key[..depth]);
+        let hash = inner_hash(inner, &mut path, hashes);
+        hashes.insert(path.as_slice().into(), hash);
+    }
+}
+
+/// A subtree reached by the descent into both trees, the number of bytes of its prefix that the
+/// descent has already matched, and its hash.
+type Branch<'a, K, V, const N: usize> = (&'a MerkleNode<K, V, N>, usize, u64);
+
+/// Collect the ranges in which the given subtrees of both trees differ. The given bytes are those
+/// that the descent has matched so far.
+fn diverging_ranges<K, V, const N: usize>(
+    (left, left_skip, left_hash): Branch<'_, K, V, N>,
+    (right, right_skip, right_hash): Branch<'_, K, V, N>,
+    path: &mut Vec<u8>,
+    left_tree: &MerkleArt<K, V, N>,
+    right_tree: &MerkleArt<K, V, N>,
+    ranges: &mut Vec<KeyRange>,
+) {
+    if left_hash == right_hash {
+        return;
+    }
+    let (Node::Inner(left_inner), Node::Inner(right_inner)) = (left, right) else {
+        // The subtrees hold different entries, and a leaf does not narrow them down further.
+        ranges.push(key_range(path));
+        return;
+    };
+    let left_rest = &left_inner.prefix()[left_skip..];
+    let right_rest = &right_inner.prefix()[right_skip..];
+    let common = left_rest
+        .iter()
+        .zip(right_rest)
+        .take_while(|(l, r)| l == r)
+        .count();
+    let depth = path.len();
+    path.extend_from_slice(&left_rest[..common]);
+    let left_branches = branches(
+        left,
+        left_inner,
+        left_skip + common,
+        left_hash,
+        path,
+        left_tree,
+    );
+    let right_branches = branches(
+        right,
+        right_inner,
+        right_skip + common,
+        right_hash,
+        path,
+        right_tree,
+    );
+    let mut left_branches = left_branches.into_iter().peekable();
+    let mut right_branches = right_branches.into_iter().peekable();
+    loop {
+        let order = match (left_branches.peek(), right_branches.peek()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some((l, _)), Some((r, _))) => l.cmp(r),
+        };
+        let (byte_key, left, right) = match order {
+            Ordering::Less => {
+                let (byte_key, _) = left_branches.next().unwrap_or_else(|| unreachable!());
+                (byte_key, None, None)
+            }
+            Ordering::Greater => {
+                let (byte_key, _) = right_branches.next().unwrap_or_else(|| unreachable!());
+                (byte_key, None, None)
+            }
+            Ordering::Equal => {
+                let (byte_key, left) = left_branches.next().unwrap_or_else(|| unreachable!());
+                let (_, right) = right_branches.next().unwrap_or_else(|| unreachable!());
+                (byte_key, Some(left), Some(right))
+            }
+        };
+        path.push(byte_key);
+        if let (Some(left), Some(right)) = (left, right) {
+            diverging_ranges(left, right, path, left_tree, right_tree, ranges);
+        } else {
+            // A branch of only one of the trees is a range of its own.
+            ranges.push(key_range(path));
+        }
+        path.pop();
+    }
+    path.truncate(depth);
+}
+
+/// Return the branches of a subtree with the given hash after the given number of bytes of its
+/// prefix in key order, with their hashes. A subtree whose prefix goes on past these bytes is a
+/// single branch under the next byte of its prefix. The given bytes are those that the descent has
+/// matched so far.
+fn branches<'a, K, V, const N: usize>(
+    node: &'a MerkleNode<K, V, N>,
+    inner: &'a MerkleInner<K, V, N>,
+    skip: usize,
+    hash: u64,
+    path: &mut Vec<u8>,
+    tree: &MerkleArt<K, V, N>,
+) -> Vec<(u8, Branch<'a, K, V, N>)> {
+    if let Some(&byte_key) = inner.prefix().get(skip) {
+        return vec![(byte_key, (node, skip + 1, hash))];
+    }
+    inner
+        .children()
+        .map(|(byte_key, child)| {
+            path.push(byte_key);
+            let hash = node_hash(child, path, &tree.hashes);
+            path.pop();
+            (byte_key, (child, 0, hash))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        ops::{Bound, RangeBounds},
+    };
+
+    use rand::Rng;
+
+    use super::MerkleArt;
+
+    #[test]
+    fn test_root_hash() {
+        let mut rng = rand::thread_rng();
+        let mut keys: Vec<u32> = (0..2000).map(|_| rng.gen_range(0..5000)).collect();
+        let mut forward = MerkleArt::<u32, u64>::new();
+        for &key in &keys {
+            forward.insert(key, u64::from(key) * 3);
+        }
+        keys.reverse();
+        let mut backward = MerkleArt::<u32, u64>::new();
+        for &key in &keys {
+            backward.insert(key, u64::from(key) * 3);
+            // Entries deleted again leave no trace in the hashes.
+            backward.insert(key + 10_000, 0);
+            backward.delete(&(key + 10_000));
+        }
+        assert_eq!(forward.root_hash(), backward.root_hash());
+        assert!(forward.diff_by_hash(&backward).is_empty());
+
+        backward.insert(keys[0], 1);
+        assert_ne!(forward.root_hash(), backward.root_hash());
+        assert_eq!(backward.search(&keys[0]), Some(&1));
+        assert_eq!(MerkleArt::<u32, u64>::default().root_hash(), 0);
+    }
+
+    #[test]
+    fn test_diff_by_hash() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let mut left = MerkleArt::<Vec<u8>, u32>::new();
+            let mut right = MerkleArt::<Vec<u8>, u32>::new();
+            let mut models = [BTreeMap::new(), BTreeMap::new()];
+            for _ in 0..rng.gen_range(0..500) {
+                let key: Vec<u8> = (0..rng.gen_range(0..6))
+                    .map(|_| rng.gen_range(1..5))
+                    .collect();
+                let value = rng.gen_range(0..3);
+                if rng.gen_bool(0.9) {
+                    left.insert(key.clone(), value);
+                    right.insert(key.clone(), value);
+                    models[0].insert(key.clone(), value);
+                    models[1].insert(key, value);
+                } else if rng.gen_bool(0.5) {
+                    left.insert(key.clone(), value);
+                    models[0].insert(key, value);
+                } else {
+                    right.delete(&key);
+                    models[1].remove(&key);
+                }
+            }
+            let ranges = left.diff_by_hash(&right);
+            assert!(ranges
+                .windows(2)
+                .all(|pair| match (&pair[0].1, &pair[1].0) {
+                    (Bound::Excluded(end), Bound::Included(start)) => end <= start,
+                    _ => false,
+                }));
+            let mut keys: Vec<_> = models[0].keys().chain(models[1].keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let diverges = models[0].get(key) != models[1].get(key);
+                let covered = ranges.iter().any(|range| range.contains(key));
+                assert!(!diverges || covered, "{key:?} not in {ranges:?}");
+            }
+            assert_eq!(ranges.is_empty(), left.root_hash() == right.root_hash());
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! A tree whose leaves carry metadata next to their values.
+//!
+//! The leaves of a [`MetaArt`] store a metadata value of type `M` together with every value, such
+//! as the time an entry was inserted or a version number. Reads can return the metadata together
+//! with the value, so the values themselves do not have to be wrapped in a struct. The metadata
+//! defaults to `()`, which takes no space in the leaves.
+
+use std::borrow::Borrow;
+
+use crate::{BytesComparable, ART};
+
+/// An adaptive radix tree that stores metadata of type `M` with every value.
+#[derive(Debug)]
+pub struct MetaArt<K, V, M = (), const N: usize = 10> {
+    tree: ART<K, (V, M), N>,
+}
+
+impl<K, V, M, const N: usize> MetaArt<K, V, M, N> {
+    /// Create an empty tree.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { tree: ART::new() }
+    }
+}
+
+impl<K, V, M, const N: usize> Default for MetaArt<K, V, M, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, M, const N: usize> MetaArt<K, V, M, N>
+where
+    K: BytesComparable,
+{
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(key).map(|(value, _)| value)
+    }
+
+    /// Search for the value associated with the given key together with its metadata.
+    pub fn search_with_meta<Q>(&self, key: &Q) -> Option<(&V, &M)>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(key).map(|(value, meta)| (value, meta))
+    }
+
+    /// Return the metadata of the value associated with the given key.
+    pub fn meta<Q>(&self, key: &Q) -> Option<&M>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(key).map(|(_, meta)| meta)
+    }
+
+    /// Return the metadata of the value associated with the given key for modification.
+    pub fn meta_mut<Q>(&mut self, key: &Q) -> Option<&mut M>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree
+            .root
+            .as_mut()
+            .and_then(|root| root.search_mut(key.bytes().as_ref(), 0))
+            .map(|leaf| &mut leaf.value.1)
+    }
+
+    /// Insert the given key-value pair with the default metadata, returning the value and metadata
+    /// previously associated with the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<(V, M)>
+    where
+        M: Default,
+    {
+        self.insert_with_meta(key, value, M::default())
+    }
+
+    /// Insert the given key-value pair with the given metadata, returning the value and metadata
+    /// previously associated with the key.
+    pub fn insert_with_meta(&mut self, key: K, value: V, meta: M) -> Option<(V, M)> {
+        self.tree.insert(key, (value, meta))
+    }
+
+    /// Delete the value associated with the given key, returning it with its metadata.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<(V, M)>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.delete(key)
+    }
+
+    /// Return an iterator over the keys, values, and metadata of the tree in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V, &M)> + '_ {
+        self.tree
+            .iter()
+            .map(|(key, (value, meta))| (key, value, meta))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MetaArt;
+
+    #[test]
+    fn test_meta() {
+        let mut tree = MetaArt::<String, u32, u64>::new();
+        assert_eq!(tree.insert_with_meta("a".to_string(), 1, 100), None);
+        assert_eq!(tree.insert("b".to_string(), 2), None);
+        assert_eq!(tree.search_with_meta("a"), Some((&1, &100)));
+        assert_eq!(tree.meta("b"), Some(&0));
+        *tree.meta_mut("b").unwrap() += 5;
+        assert_eq!(
+            tree.insert_with_meta("b".to_string(), 20, 200),
+            Some((2, 5))
+        );
+        assert_eq!(
+            tree.iter().collect::<Vec<_>>(),
+            [(&"a".to_string(), &1, &100), (&"b".to_string(), &20, &200)]
+        );
+        assert_eq!(tree.delete("a"), Some((1, 100)));
+        assert_eq!(tree.search("a"), None);
+        assert_eq!(tree.meta_mut("a"), None);
+
+        // The metadata defaults to nothing.
+        let mut plain = MetaArt::<u32, &str>::default();
+        plain.insert(1, "one");
+        assert_eq!(plain.search_with_meta(&1), Some((&"one", &())));
+    }
+}
@@ -0,0 +1,348 @@
+//! A tree that keeps past versions of its values for reads at a timestamp.
+//!
+//! Every write to an [`MvccArt`] is stamped with the next [`Timestamp`], and the leaf of its key
+//! keeps a short chain of the versions written to it, where a delete writes a tombstone. A read
+//! takes a timestamp and sees every value as it was right after the write with that timestamp, so
+//! a reader that remembers [`MvccArt::now`] can scan the tree a page at a time while writes go on
+//! in between, and still see a single consistent state of the tree. Nothing is frozen or copied
+//! for this: writes only append to the chains of the keys they modify.
+//!
+//! Chains grow with every write, so old versions have to be pruned once no reader needs them.
+//! [`MvccArt::gc`] takes the oldest timestamp that readers still use and drops every version that
+//! is hidden by a newer one at that time, together with the keys that were deleted before it.
+
+use std::{
+    borrow::Borrow,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{BytesComparable, ART};
+
+/// The time of a write to an [`MvccArt`], which is also the time a read is made at. Timestamps
+/// increase with every write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(u64);
+
+impl Timestamp {
+    /// Return the number of writes made up to this time.
+    #[must_use]
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+}
+
+/// What was reclaimed by [`MvccArt::gc`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    /// The number of versions that were dropped, including tombstones.
+    pub versions: usize,
+    /// The number of keys that were removed from the tree because they were deleted.
+    pub entries: usize,
+    /// The number of bytes taken by the dropped versions and keys in the tree, not counting the
+    /// memory owned by the keys and values themselves.
+    pub bytes: usize,
+}
+
+/// A value written at a timestamp, or a tombstone if the key was deleted.
+#[derive(Debug)]
+struct Version<V> {
+    ts: Timestamp,
+    value: Option<V>,
+}
+
+/// The versions of a key from the oldest to the newest.
+type Chain<V> = Vec<Version<V>>;
+
+/// Return the value of the latest version of the chain written at or before the timestamp.
+fn visible<V>(chain: &Chain<V>, ts: Timestamp) -> Option<&V> {
+    chain
+        .iter()
+        .rev()
+        .find(|version| version.ts <= ts)
+        .and_then(|version| version.value.as_ref())
+}
+
+/// An adaptive radix tree whose values can be read as they were at any past timestamp that was
+/// not pruned.
+#[derive(Debug)]
+pub struct MvccArt<K, V, const N: usize = 10> {
+    tree: ART<K, Chain<V>, N>,
+    now: Timestamp,
+    /// The number of keys whose latest version is not a tombstone.
+    len: usize,
+    /// The number of versions in all chains, including tombstones.
+    versions: usize,
+}
+
+impl<K, V, const N: usize> MvccArt<K, V, N> {
+    /// Create an empty tree at timestamp zero.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            tree: ART::new(),
+            now: Timestamp(0),
+            len: 0,
+            versions: 0,
+        }
+    }
+
+    /// Return the timestamp of the latest write, at which reads see every write made so far.
+    #[must_use]
+    pub const fn now(&self) -> Timestamp {
+        self.now
+    }
+
+    /// Return the number of keys in the latest version of the tree.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the latest version of the tree holds no key.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the number of versions kept in the tree, including tombstones.
+    #[must_use]
+    pub const fn versions(&self) -> usize {
+        self.versions
+    }
+}
+
+impl<K, V, const N: usize> Default for MvccArt<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> MvccArt<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Search for the value associated with the given key in the latest version of the tree.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.search_at(key, self.now)
+    }
+
+    /// Search for the value associated with the given key right after the write with the given
+    /// timestamp.
+    pub fn search_at<Q>(&self, key: &Q, ts: Timestamp) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        visible(self.tree.search(key)?, ts)
+    }
+
+    /// Call the closure on the key-value pairs within the given range in key order, as they were
+    /// right after the write with the given timestamp, until the closure returns false.
+    pub fn range_at<Q, R, F>(&self, ts: Timestamp, range: R, mut f: F)
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        R: RangeBounds<Q>,
+        F: FnMut(&K, &V) -> bool,
+    {
+        let bounds: (Bound<&Q>, Bound<&Q>) = (range.start_bound(), range.end_bound());
+        self.tree.range_while(bounds, |key, chain| {
+            visible(chain, ts).is_none_or(|value| f(key, value))
+        });
+    }
+
+    /// Insert the given key-value pair as a new version, returning the timestamp of the write.
+    pub fn insert(&mut self, key: K, value: V) -> Timestamp {
+        self.now = Timestamp(self.now.0 + 1);
+        let version = Version {
+            ts: self.now,
+            value: Some(value),
+        };
+        self.versions += 1;
+        if let Some(chain) = chain_mut(&mut self.tree, &key) {
+            if chain.last().is_some_and(|last| last.value.is_none()) {
+                self.len += 1;
+            }
+            chain.push(version);
+        } else {
+            self.len += 1;
+            self.tree.insert(key, vec![version]);
+        }
+        self.now
+    }
+
+    /// Delete the given key by writing a tombstone, returning the timestamp of the write, or
+    /// `None` without writing anything if the key is not in the latest version of the tree.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<Timestamp>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let chain = chain_mut(&mut self.tree, key)?;
+        chain.last()?.value.as_ref()?;
+        self.now = Timestamp(self.now.0 + 1);
+        chain.push(Version {
+            ts: self.now,
+            value: None,
+        });
+        self.versions += 1;
+        self.len -= 1;
+        Some(self.now)
+    }
+}
+
+impl<K, V, const N: usize> MvccArt<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Drop the versions that no read at or after the given timestamp can see, and remove the keys
+    /// that were deleted at or before it. Reads at earlier timestamps may see wrong values
+    /// afterwards.
+    pub fn gc(&mut self, up_to: Timestamp) -> GcStats {
+        let mut stats = GcStats::default();
+        let mut cursor = self.tree.cursor_mut_front();
+        while let Some(chain) = cursor.value_mut() {
+            // The latest version at the timestamp is kept, unless it is a tombstone, because a read
+            // finds no value without it as well.
+            let visible = chain.iter().rposition(|version| version.ts <= up_to);
+            let keep_from = visible.map_or(0, |idx| {
+                if chain[idx].value.is_some() {
+                    idx
+                } else {
+                    idx + 1
+                }
+            });
+            stats.versions += keep_from;
+            chain.drain(..keep_from);
+            if chain.is_empty() {
+                cursor.remove_current();
+                stats.entries += 1;
+            } else {
+                cursor.move_next();
+            }
+        }
+        self.versions -= stats.versions;
+        stats.bytes = stats.versions * std::mem::size_of::<Version<V>>()
+            + stats.entries * std::mem::size_of::<(K, Chain<V>)>();
+        stats
+    }
+}
+
+/// Return the version chain of the given key for modification.
+fn chain_mut<'a, K, V, Q, const N: usize>(
+    tree: &'a mut ART<K, Chain<V>, N>,
+    key: &Q,
+) -> Option<&'a mut Chain<V>>
+where
+    K: BytesComparable,
+    Q: BytesComparable + ?Sized,
+{
+    tree.root
+        .as_mut()
+        .and_then(|root| root.search_mut(key.bytes().as_ref(), 0))
+        .map(|leaf| &mut leaf.value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::Rng;
+
+    use super::{GcStats, MvccArt};
+
+    #[test]
+    fn test_reads_at_timestamps() {
+        let mut tree = MvccArt::<String, u32>::new();
+        let empty = tree.now();
+        let first = tree.insert("a".to_string(), 1);
+        tree.insert("b".to_string(), 2);
+        let second = tree.insert("a".to_string(), 3);
+        let deleted = tree.delete("b").unwrap();
+        assert_eq!(tree.delete("b"), None);
+        assert_eq!(tree.delete("c"), None);
+
+        assert_eq!(tree.search_at("a", empty), None);
+        assert_eq!(tree.search_at("a", first), Some(&1));
+        assert_eq!(tree.search_at("b", first), None);
+        assert_eq!(tree.search_at("a", second), Some(&3));
+        assert_eq!(tree.search_at("b", second), Some(&2));
+        assert_eq!(tree.search_at("b", deleted), None);
+        assert_eq!(tree.search("a"), Some(&3));
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree.versions(), 4);
+        assert_eq!(tree.now(), deleted);
+    }
+
+    #[test]
+    fn test_scan_is_consistent_while_writing() {
+        let mut rng = rand::thread_rng();
+        let mut tree = MvccArt::<u32, u32>::new();
+        for key in 0..1000 {
+            tree.insert(key, 0);
+        }
+        let ts = tree.now();
+        let expected: BTreeMap<u32, u32> = (0..1000).map(|key| (key, 0)).collect();
+
+        // Scan a page at a time, writing in between pages.
+        let mut seen = Vec::new();
+        let mut next = 0;
+        loop {
+            let mut page = Vec::new();
+            tree.range_at(ts, next.., |key, value| {
+                page.push((*key, *value));
+                page.len() < 50
+            });
+            let Some(&(last, _)) = page.last() else {
+                break;
+            };
+            seen.extend(page);
+            next = last + 1;
+            for _ in 0..20 {
+                let key = rng.gen_range(0..2000);
+                if rng.gen_bool(0.5) {
+                    tree.insert(key, key + 1);
+                } else {
+                    tree.delete(&key);
+                }
+            }
+        }
+        assert!(seen.into_iter().eq(expected));
+    }
+
+    #[test]
+    fn test_gc() {
+        let mut tree = MvccArt::<String, u32>::new();
+        tree.insert("a".to_string(), 1);
+        tree.insert("b".to_string(), 1);
+        let old = tree.now();
+        tree.insert("a".to_string(), 2);
+        tree.delete("b");
+        let reader = tree.now();
+        tree.insert("a".to_string(), 3);
+        tree.insert("c".to_string(), 1);
+        tree.delete("c");
+        assert_eq!(tree.versions(), 7);
+
+        let stats = tree.gc(reader);
+        // The first version of "a" and both versions of "b" are gone, and so is the key "b".
+        assert_eq!(stats.versions, 3);
+        assert_eq!(stats.entries, 1);
+        assert!(stats.bytes > 0);
+        assert_eq!(tree.versions(), 4);
+        assert_eq!(tree.search_at("a", reader), Some(&2));
+        assert_eq!(tree.search_at("b", reader), None);
+        assert_eq!(tree.search("a"), Some(&3));
+        assert_eq!(tree.search_at("a", old), None);
+
+        let stats = tree.gc(tree.now());
+        assert_eq!((stats.versions, stats.entries), (3, 1));
+        assert_eq!(tree.versions(), 1);
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree.gc(tree.now()), GcStats::default());
+    }
+}
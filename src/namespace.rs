@@ -0,0 +1,158 @@
+//! Views of a tree that keep the keys of several logical tables apart.
+//!
+//! Storing several tables in one tree usually means concatenating the name of a table with every
+//! key, which is easy to get wrong: the table `user` would see the keys of the table `users` that
+//! start with `s`. A [`Namespaced`] view prepends the name of its table, encoded with
+//! [`encode_segment`], to every key it writes or looks up, and strips it from every key it reads.
+//! Encoded names are never prefixes of each other, so every view only sees the keys written through
+//! a view of the same name, and the keys of a table are stored in one subtree of the tree.
+//!
+//! Keys written to the tree without a view may collide with the keys of the views, so a tree that
+//! is shared by tables should only be written through views.
+
+use std::borrow::Borrow;
+
+use crate::{segment::encode_segment, BytesComparable, ART};
+
+/// A view of the keys of a tree of byte strings that belong to a named table, see the [module
+/// documentation](self).
+#[derive(Debug)]
+pub struct Namespaced<'a, K, V> {
+    tree: &'a mut ART<K, V>,
+    name: Vec<u8>,
+    prefix: Vec<u8>,
+}
+
+impl<'a, K, V> Namespaced<'a, K, V>
+where
+    K: BytesComparable + Borrow<[u8]> + From<Vec<u8>>,
+{
+    /// Create a view of the table with the given name in the tree.
+    pub fn new(tree: &'a mut ART<K, V>, name: &[u8]) -> Self {
+        let mut prefix = Vec::with_capacity(name.len() + 2);
+        encode_segment(name, &mut prefix);
+        Self {
+            tree,
+            name: name.to_vec(),
+            prefix,
+        }
+    }
+
+    /// Return the name of the table.
+    #[must_use]
+    pub fn name(&self) -> &[u8] {
+        &self.name
+    }
+
+    /// Return the key of the tree that the given key of the table is stored under.
+    fn physical(&self, key: &[u8]) -> Vec<u8> {
+        let mut physical = Vec::with_capacity(self.prefix.len() + key.len());
+        physical.extend_from_slice(&self.prefix);
+        physical.extend_from_slice(key);
+        physical
+    }
+
+    /// Search for the value associated with the given key of the table.
+    #[must_use]
+    pub fn get(&self, key: &[u8]) -> Option<&V> {
+        self.tree.search(self.physical(key).as_slice())
+    }
+
+    /// Return true if the table contains the given key.
+    #[must_use]
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Insert the given key-value pair into the table, returning the value previously associated
+    /// with the key.
+    pub fn insert(&mut self, key: &[u8], value: V) -> Option<V> {
+        let physical = self.physical(key);
+        self.tree.insert(K::from(physical), value)
+    }
+
+    /// Delete the value associated with the given key of the table.
+    pub fn remove(&mut self, key: &[u8]) -> Option<V> {
+        self.tree.delete(self.physical(key).as_slice())
+    }
+
+    /// Return an iterator over the keys of the table, without the name of the table, and their
+    /// values in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &V)> + '_ {
+        let stripped = self.prefix.len();
+        self.tree
+            .scan_prefix(&self.prefix)
+            .map(move |(k, v)| (&k.borrow()[stripped..], v))
+    }
+
+    /// Return the number of entries in the table. This visits every entry of the table.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Return true if the table holds no entry.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Remove every entry of the table from the tree, and return them in a tree of their own,
+    /// still prefixed with the name of the table, or `None` if the table is empty.
+    pub fn clear(&mut self) -> Option<ART<K, V>> {
+        self.tree.take_subtree(&self.prefix)
+    }
+}
+
+impl<K, V> ART<K, V>
+where
+    K: BytesComparable + Borrow<[u8]> + From<Vec<u8>>,
+{
+    /// Return a view of the table with the given name in the tree, see [`Namespaced`].
+    pub fn namespace(&mut self, name: &[u8]) -> Namespaced<'_, K, V> {
+        Namespaced::new(self, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Namespaced;
+    use crate::ART;
+
+    #[test]
+    fn test_tables_are_isolated() {
+        let mut tree = ART::<Vec<u8>, u32>::new();
+        let mut user = tree.namespace(b"user");
+        assert_eq!(user.insert(b"alice", 1), None);
+        assert_eq!(user.insert(b"alice", 2), Some(1));
+        user.insert(b"sam", 3);
+        assert_eq!(user.name(), b"user");
+        let mut users = Namespaced::new(&mut tree, b"users");
+        users.insert(b"", 4);
+        users.insert(b"am", 5);
+        // Table names with zero bytes are escaped as well.
+        tree.namespace(b"user\0s").insert(b"x", 6);
+
+        let user = tree.namespace(b"user");
+        assert_eq!(user.get(b"alice"), Some(&2));
+        assert_eq!(user.get(b"am"), None);
+        let keys: Vec<_> = user.iter().map(|(k, v)| (k.to_vec(), *v)).collect();
+        assert_eq!(keys, [(b"alice".to_vec(), 2), (b"sam".to_vec(), 3)]);
+        assert_eq!(user.len(), 2);
+
+        let mut users = tree.namespace(b"users");
+        assert!(users.contains_key(b""));
+        assert_eq!(users.remove(b"am"), Some(5));
+        assert_eq!(users.remove(b"am"), None);
+        assert_eq!(tree.namespace(b"user\0s").len(), 1);
+        assert_eq!(tree.len(), 4);
+
+        // Clearing a table leaves the other tables alone.
+        let cleared = tree.namespace(b"user").clear().unwrap();
+        assert_eq!(cleared.len(), 2);
+        assert!(tree.namespace(b"user").is_empty());
+        assert!(tree.namespace(b"user").clear().is_none());
+        assert_eq!(tree.namespace(b"users").get(b""), Some(&4));
+        assert_eq!(tree.len(), 2);
+    }
+}
@@ -1,19 +1,323 @@
-use std::cmp::min;
+use std::{
+    cmp::{min, Ordering},
+    ops::Deref,
+    sync::Arc,
+};
 
 use crate::{
     indices::{Indices, Indices16, Indices256, Indices4, Indices48},
+    recycle,
+    visit::{Visit, Walk},
     BytesComparable,
 };
 
+/// Emits a `tracing` event at the debug level with the `tracing` feature, and does nothing without
+/// the feature.
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+/// Adds to a counter of the `stats` feature, and does nothing without the feature.
+macro_rules! count {
+    ($($counter:ident).+, $n:expr) => {
+        #[cfg(feature = "stats")]
+        crate::stats::record(|metrics| metrics.$($counter).+ += $n as u64);
+    };
+}
+
+/// Reports a visit of a node at the given depth to the probe of the `probe` feature, and does
+/// nothing without the feature.
+macro_rules! probe {
+    ($node:expr, $depth:expr) => {
+        #[cfg(feature = "probe")]
+        crate::probe::record($node.kind(), $depth);
+    };
+}
+
+/// Prevents [`NodeStore`] from being implemented outside of the crate.
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Decides where the children of inner nodes are stored and how they are referenced.
+///
+/// The traversal algorithms in this module only allocate, take, and mutate children through this
+/// trait, so a new storage backend only has to implement it, and the same algorithms work on trees
+/// that are exclusively owned, on trees whose nodes are shared between versions, or on trees whose
+/// nodes live somewhere else.
+///
+/// The trait is sealed, and the backends are internal to the crate. Implementing it needs the
+/// nodes, the leaves, and the indices of this module, which are not part of the public interface,
+/// so a new backend is added next to [`Owned`] and exposed through a tree type of its own, like
+/// [`Bumped`] is through `arena::ArenaArt`. A backend must uphold the following:
+///
+/// - Dereferencing the pointer returned by [`NodeStore::alloc`] gives back the allocated node.
+/// - [`NodeStore::take`] returns the node behind the pointer, even if the pointer is shared.
+/// - [`NodeStore::get_mut`] returns a node that is not observable through any other pointer, which
+///   means that a shared node has to be copied first.
+///
+/// Pointers have to be dereferenced without any context, so backends that address nodes relative
+/// to a base, such as a memory mapped file or a shared memory segment, have to resolve the base
+/// when the pointer is created. The base can be passed to [`NodeStore::alloc`] as the arena of the
+/// backend, which the operations that allocate nodes take from the tree.
+pub trait NodeStore<
+    K,
+    V,
+    const P: usize,
+    const C4: usize = 4,
+    const C16: usize = 16,
+    const C48: usize = 48,
+>: sealed::Sealed + Sized
+{
+    /// The pointer that holds a child node.
+    type Ptr: Deref<Target = Node<K, V, P, Self, C4, C16, C48>>;
+
+    /// Where new nodes are stored, or `()` for backends that store them on the heap.
+    type Arena: Copy;
+
+    /// Stores a node in the arena and returns a pointer to it.
+    fn alloc(arena: Self::Arena, node: Node<K, V, P, Self, C4, C16, C48>) -> Self::Ptr;
+
+    /// Takes the node out of a pointer, releasing the storage of the pointer.
+    fn take(ptr: Self::Ptr) -> Node<K, V, P, Self, C4, C16, C48>;
+
+    /// Returns a mutable reference to the node behind a pointer.
+    fn get_mut(ptr: &mut Self::Ptr) -> &mut Node<K, V, P, Self, C4, C16, C48>;
+
+    /// Boxes the indices of a node that grows or shrinks into the kind with up to `C48` children.
+    fn box_indices48(indices: Indices48<Self::Ptr, C48>) -> Box<Indices48<Self::Ptr, C48>> {
+        Box::new(indices)
+    }
+
+    /// Boxes the indices of a node that grows into the kind with up to 256 children.
+    fn box_indices256(indices: Indices256<Self::Ptr>) -> Box<Indices256<Self::Ptr>> {
+        Box::new(indices)
+    }
+
+    /// Releases the box of the indices of a node that changed its kind, which holds no children.
+    fn free_indices48(indices: Box<Indices48<Self::Ptr, C48>>) {
+        drop(indices);
+    }
+
+    /// Releases the box of the indices of a node that changed its kind, which holds no children.
+    fn free_indices256(indices: Box<Indices256<Self::Ptr>>) {
+        drop(indices);
+    }
+}
+
+/// Children are allocated on the heap and exclusively owned by their parent.
+#[derive(Debug)]
+pub enum Owned {}
+
+impl sealed::Sealed for Owned {}
+
+impl<K, V, const P: usize, const C4: usize, const C16: usize, const C48: usize>
+    NodeStore<K, V, P, C4, C16, C48> for Owned
+{
+    type Ptr = Box<Node<K, V, P, Self, C4, C16, C48>>;
+
+    type Arena = ();
+
+    fn alloc((): (), node: Node<K, V, P, Self, C4, C16, C48>) -> Self::Ptr {
+        Box::new(node)
+    }
+
+    fn take(ptr: Self::Ptr) -> Node<K, V, P, Self, C4, C16, C48> {
+        *ptr
+    }
+
+    fn get_mut(ptr: &mut Self::Ptr) -> &mut Node<K, V, P, Self, C4, C16, C48> {
+        ptr
+    }
+}
+
+/// Children are reference counted and can be shared between multiple versions of a tree. Mutating
+/// a shared child first copies it, so a modification only copies the path from the root to the
+/// modified node, while the rest of the nodes stay shared.
+#[derive(Debug)]
+pub enum Shared {}
+
+impl sealed::Sealed for Shared {}
+
+impl<K, V, const P: usize, const C4: usize, const C16: usize, const C48: usize>
+    NodeStore<K, V, P, C4, C16, C48> for Shared
+where
+    K: Clone,
+    V: Clone,
+{
+    type Ptr = Arc<Node<K, V, P, Self, C4, C16, C48>>;
+
+    type Arena = ();
+
+    fn alloc((): (), node: Node<K, V, P, Self, C4, C16, C48>) -> Self::Ptr {
+        Arc::new(node)
+    }
+
+    fn take(ptr: Self::Ptr) -> Node<K, V, P, Self, C4, C16, C48> {
+        Arc::try_unwrap(ptr).unwrap_or_else(|ptr| ptr.as_ref().clone())
+    }
+
+    fn get_mut(ptr: &mut Self::Ptr) -> &mut Node<K, V, P, Self, C4, C16, C48> {
+        Arc::make_mut(ptr)
+    }
+}
+
+/// The kind of a node.
+#[cfg(any(feature = "probe", feature = "raw"))]
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    /// A leaf holding a key-value pair.
+    Leaf,
+    /// An inner node with up to 4 children, or the first capacity of the tree.
+    Node4,
+    /// An inner node with up to 16 children, or the second capacity of the tree.
+    Node16,
+    /// An inner node with up to 48 children, or the third capacity of the tree.
+    Node48,
+    /// An inner node with up to 256 children.
+    Node256,
+}
+
+/// Children are allocated on the heap and exclusively owned by their parent like with [`Owned`],
+/// but the boxes of freed nodes and of the indices of nodes that changed their kind are kept in a
+/// pool of the current thread, and reused by later allocations of the same type, see
+/// [`crate::recycle`].
+#[derive(Debug)]
+pub enum Recycled {}
+
+impl sealed::Sealed for Recycled {}
+
+impl<K, V, const P: usize, const C4: usize, const C16: usize, const C48: usize>
+    NodeStore<K, V, P, C4, C16, C48> for Recycled
+where
+    K: 'static,
+    V: 'static,
+{
+    type Ptr = Box<Node<K, V, P, Self, C4, C16, C48>>;
+
+    type Arena = ();
+
+    fn alloc((): (), node: Node<K, V, P, Self, C4, C16, C48>) -> Self::Ptr {
+        recycle::reuse(node)
+    }
+
+    fn take(mut ptr: Self::Ptr) -> Node<K, V, P, Self, C4, C16, C48> {
+        // An empty inner node is left in the box, which needs no allocation and owns nothing.
+        let node = std::mem::replace(&mut *ptr, Node::new_inner(PartialKey::new(&[], 0)));
+        recycle::release(ptr);
+        node
+    }
+
+    fn get_mut(ptr: &mut Self::Ptr) -> &mut Node<K, V, P, Self, C4, C16, C48> {
+        ptr
+    }
+
+    fn box_indices48(indices: Indices48<Self::Ptr, C48>) -> Box<Indices48<Self::Ptr, C48>> {
+        recycle::reuse(indices)
+    }
+
+    fn box_indices256(indices: Indices256<Self::Ptr>) -> Box<Indices256<Self::Ptr>> {
+        recycle::reuse(indices)
+    }
+
+    fn free_indices48(indices: Box<Indices48<Self::Ptr, C48>>) {
+        recycle::release(indices);
+    }
+
+    fn free_indices256(indices: Box<Indices256<Self::Ptr>>) {
+        recycle::release(indices);
+    }
+}
+
+/// Children are allocated in a [`bumpalo::Bump`] that outlives the tree. Taking a node out of its
+/// pointer leaves its memory in the arena, which is only freed when the arena is dropped or reset,
+/// see [`crate::arena`].
+#[cfg(feature = "bumpalo")]
+#[derive(Debug)]
+#[allow(dead_code)] // Only used as a type parameter.
+pub struct Bumped<'a>(std::marker::PhantomData<&'a bumpalo::Bump>);
+
+#[cfg(feature = "bumpalo")]
+impl sealed::Sealed for Bumped<'_> {}
+
+#[cfg(feature = "bumpalo")]
+impl<'a, K, V, const P: usize, const C4: usize, const C16: usize, const C48: usize>
+    NodeStore<K, V, P, C4, C16, C48> for Bumped<'a>
+where
+    K: 'a,
+    V: 'a,
+{
+    type Ptr = bumpalo::boxed::Box<'a, Node<K, V, P, Self, C4, C16, C48>>;
+
+    type Arena = &'a bumpalo::Bump;
+
+    fn alloc(arena: Self::Arena, node: Node<K, V, P, Self, C4, C16, C48>) -> Self::Ptr {
+        bumpalo::boxed::Box::new_in(node, arena)
+    }
+
+    fn take(ptr: Self::Ptr) -> Node<K, V, P, Self, C4, C16, C48> {
+        bumpalo::boxed::Box::into_inner(ptr)
+    }
+
+    fn get_mut(ptr: &mut Self::Ptr) -> &mut Node<K, V, P, Self, C4, C16, C48> {
+        ptr
+    }
+}
+
 /// A node in the ART tree, which can be either an inner node or a leaf node. Leaf nodes hold data of
 /// key-value pairs, and inner nodes holds indices to its children.
-#[derive(Debug)]
-pub enum Node<K, V, const P: usize> {
+pub enum Node<
+    K,
+    V,
+    const P: usize,
+    S: NodeStore<K, V, P, C4, C16, C48> = Owned,
+    const C4: usize = 4,
+    const C16: usize = 16,
+    const C48: usize = 48,
+> {
     Leaf(Leaf<K, V>),
-    Inner(Inner<K, V, P>),
+    Inner(Inner<K, V, P, S, C4, C16, C48>),
+}
+
+impl<K, V, const P: usize, const C4: usize, const C16: usize, const C48: usize, S> Clone
+    for Node<K, V, P, S, C4, C16, C48>
+where
+    K: Clone,
+    V: Clone,
+    S: NodeStore<K, V, P, C4, C16, C48>,
+    S::Ptr: Clone,
+{
+    fn clone(&self) -> Self {
+        match self {
+            Self::Leaf(leaf) => Self::Leaf(leaf.clone()),
+            Self::Inner(inner) => Self::Inner(inner.clone()),
+        }
+    }
 }
 
-impl<K, V, const P: usize> Node<K, V, P> {
+impl<
+        K,
+        V,
+        const P: usize,
+        const C4: usize,
+        const C16: usize,
+        const C48: usize,
+        S: NodeStore<K, V, P, C4, C16, C48>,
+    > Node<K, V, P, S, C4, C16, C48>
+{
+    /// Returns an estimate of the number of bytes an insert of the given key allocates: a leaf, a
+    /// node holding the leaf it splits, the chain of that node if its prefix is too long to be
+    /// stored inline, and the larger indices of an inner node that grows. When the insert splits
+    /// the prefix of an existing node, the chain the rest of that prefix is moved into is not
+    /// counted, as its length is only known once the descent reaches the node.
+    pub const fn max_insert_alloc(key: &[u8]) -> usize {
+        2 * std::mem::size_of::<Self>() + key.len() + std::mem::size_of::<Indices256<S::Ptr>>()
+    }
+
     /// Create a new leaf node.
     pub const fn new_leaf(key: K, value: V) -> Self {
         Self::Leaf(Leaf { key, value })
@@ -23,20 +327,97 @@ impl<K, V, const P: usize> Node<K, V, P> {
     fn new_inner(partial: PartialKey<P>) -> Self {
         Self::Inner(Inner::new(partial))
     }
+
+    /// Returns the kind of the node.
+    #[cfg(any(feature = "probe", feature = "raw"))]
+    pub const fn kind(&self) -> NodeKind {
+        match self {
+            Self::Leaf(_) => NodeKind::Leaf,
+            Self::Inner(inner) => inner.kind(),
+        }
+    }
+
+    /// Folds every leaf under this node in key order.
+    pub fn fold_leaves<'a, B, F>(&'a self, init: B, f: &mut F) -> B
+    where
+        F: FnMut(B, &'a Leaf<K, V>) -> B,
+    {
+        match self {
+            Self::Leaf(leaf) => f(init, leaf),
+            Self::Inner(inner) => inner
+                .indices
+                .children()
+                .fold(init, |acc, (_, child)| child.fold_leaves(acc, f)),
+        }
+    }
+
+    /// Calls the given closure on every leaf under this node in key order, stopping at the first
+    /// error.
+    pub fn try_for_each_leaf<E, F>(&self, f: &mut F) -> Result<(), E>
+    where
+        F: FnMut(&Leaf<K, V>) -> Result<(), E>,
+    {
+        match self {
+            Self::Leaf(leaf) => f(leaf),
+            Self::Inner(inner) => inner
+                .indices
+                .children()
+                .try_for_each(|(_, child)| child.try_for_each_leaf(f)),
+        }
+    }
 }
 
-impl<K, V, const P: usize> Node<K, V, P>
+impl<
+        K,
+        V,
+        const P: usize,
+        const C4: usize,
+        const C16: usize,
+        const C48: usize,
+        S: NodeStore<K, V, P, C4, C16, C48>,
+    > Node<K, V, P, S, C4, C16, C48>
 where
     K: BytesComparable,
 {
+    /// Walks the nodes under this node in key order, calling the visitor on the prefix of every
+    /// inner node and on every leaf. The buffer holds the bytes of the path to this node and is
+    /// restored before returning. Returns false if the visitor stopped the walk.
+    pub fn walk<'a, F>(&'a self, path: &mut Vec<u8>, f: &mut F) -> bool
+    where
+        F: FnMut(Visit<'_, 'a, K, V>) -> Walk,
+    {
+        let inner = match self {
+            Self::Leaf(leaf) => {
+                return f(Visit::Entry(&leaf.key, &leaf.value)) != Walk::Stop;
+            }
+            Self::Inner(inner) => inner,
+        };
+        let depth = path.len();
+        inner.write_prefix(path);
+        let walked = match f(Visit::Subtree(path)) {
+            Walk::Continue => inner.indices.children().all(|(byte_key, child)| {
+                path.push(byte_key);
+                let walked = child.walk(path, f);
+                path.pop();
+                walked
+            }),
+            Walk::SkipSubtree => true,
+            Walk::Stop => false,
+        };
+        path.truncate(depth);
+        walked
+    }
+
     /// Finds the leaf node that matches the given key.
     ///
     /// # Arguments
     ///
     /// - `key`: The key to search for.
     /// - `depth`: The number of bytes in the key to skip. This number increases as we go deeper into the tree
-    /// and depends on the length of prefixes along the path.
+    ///   and depends on the length of prefixes along the path.
     pub fn search(&self, key: &[u8], depth: usize) -> Option<&Leaf<K, V>> {
+        count!(node_visits, 1);
+        probe!(self, depth);
         match &self {
             Self::Leaf(leaf) => {
                 if !leaf.match_key(key) {
@@ -48,24 +429,138 @@ where
         }
     }
 
+    /// Returns the leaf that [`Node::search`] would compare against a key of `len` bytes, whose
+    /// byte at a given depth is returned by `byte_at`, so the key never has to be encoded. The
+    /// prefixes of inner nodes are skipped without comparing them, and the caller compares the key
+    /// of the leaf. This is only correct when no key is a prefix of another, because then every
+    /// byte key on the path is taken from within the key.
+    pub fn descend_optimistic<F>(&self, len: usize, byte_at: F) -> Option<&Leaf<K, V>>
+    where
+        F: Fn(usize) -> u8,
+    {
+        let mut node = self;
+        let mut depth = 0;
+        loop {
+            count!(node_visits, 1);
+            probe!(node, depth);
+            match node {
+                Self::Leaf(leaf) => return Some(leaf),
+                Self::Inner(inner) => {
+                    depth += inner.partial.len;
+                    if depth >= len {
+                        return None;
+                    }
+                    node = inner.child_ref(byte_at(depth))?;
+                    depth += 1;
+                }
+            }
+        }
+    }
+
+    /// Pushes the leaves whose keys are prefixes of the query to the vector, from the shortest key
+    /// to the longest, in a single descent along the path of the query.
+    pub fn prefixes_of<'a>(&'a self, query: &[u8], leaves: &mut Vec<&'a Leaf<K, V>>) {
+        let mut push = |leaf: &'a Leaf<K, V>| {
+            // A key ending at the byte where the descent continues is found again further down.
+            let found = leaves.last().is_some_and(|last| std::ptr::eq(*last, leaf));
+            if !found && query.starts_with(leaf.key.bytes().as_ref()) {
+                leaves.push(leaf);
+            }
+        };
+        let mut node = self;
+        let mut depth = 0;
+        loop {
+            count!(node_visits, 1);
+            probe!(node, depth);
+            let inner = match node {
+                Self::Leaf(leaf) => return push(leaf),
+                Self::Inner(inner) => inner,
+            };
+            if !inner.partial.match_key(query, depth) {
+                return;
+            }
+            depth += inner.partial.len;
+            // A key that ends here is stored under the zero byte, and it is the smallest key there.
+            if let Some(leaf) = inner.child_ref(0).and_then(Self::min_leaf) {
+                push(leaf);
+            }
+            let Some(child) = query.get(depth).and_then(|&byte| inner.child_ref(byte)) else {
+                return;
+            };
+            node = child;
+            depth += 1;
+        }
+    }
+
+    /// Same as [`Node::search`], but returns a mutable reference to the leaf.
+    pub fn search_mut(&mut self, key: &[u8], depth: usize) -> Option<&mut Leaf<K, V>> {
+        count!(node_visits, 1);
+        probe!(self, depth);
+        match self {
+            Self::Leaf(leaf) => leaf.match_key(key).then_some(leaf),
+            Self::Inner(inner) => {
+                if !inner.partial.match_key(key, depth) {
+                    return None;
+                }
+                let next_depth = depth + inner.partial.len;
+                inner
+                    .child_mut(byte_at(key, next_depth))
+                    .and_then(|child| child.search_mut(key, next_depth + 1))
+            }
+        }
+    }
+
     /// Inserts the given key-value pair into the node.
     ///
     /// # Arguments
     ///
+    /// - `arena`: Where the new nodes are stored.
     /// - `key`: The key to insert.
     /// - `value`: The value to insert.
     /// - `depth`: The number of bytes in the key to skip. This number increases as we go deeper into the tree
-    /// and depends on the length of prefixes along the path.
-    pub fn insert(&mut self, key: K, value: V, depth: usize) {
+    ///   and depends on the length of prefixes along the path.
+    /// - `compress`: Whether a leaf split stores the common prefix of the leaves in the new inner
+    ///   node, or only consumes a single byte of it.
+    ///
+    /// Returns the value that was replaced if a leaf with the same key already existed.
+    pub fn insert(
+        &mut self,
+        arena: S::Arena,
+        key: K,
+        value: V,
+        depth: usize,
+        compress: bool,
+    ) -> Option<V> {
+        count!(node_visits, 1);
+        probe!(self, depth);
         match self {
+            Self::Leaf(leaf) if !compress => {
+                if leaf.match_key(key.bytes().as_ref()) {
+                    return Some(std::mem::replace(&mut leaf.value, value));
+                }
+                // Without path compression, a split only consumes the byte at the current depth.
+                // The new key is inserted into the new inner node, which splits the old leaf again
+                // if both keys have the same byte.
+                let byte_key = byte_at(leaf.key.bytes().as_ref(), depth);
+                trace!(depth, root = depth == 0, "leaf split");
+                let old_leaf = std::mem::replace(self, Self::new_inner(PartialKey::new(&[], 0)));
+                self.add_child(arena, byte_key, old_leaf);
+                // The new key is encoded again below, so the guard undoes the split if that panics.
+                let guard = SplitGuard(self);
+                let Self::Inner(inner) = &mut *guard.0 else {
+                    unreachable!("the node was just replaced with an inner node")
+                };
+                let old_value = inner.insert_recursive(arena, key, value, depth, compress);
+                std::mem::forget(guard);
+                old_value
+            }
             Self::Leaf(leaf) => {
                 // Here we create a scope to avoid borrowing `key` for too long in order to move it into the new leaf.
                 let (partial, k_new, k_old) = {
                     let new_key_bytes = key.bytes();
                     // If the leaf's key matches the new key, then update it's value and return early.
                     if leaf.match_key(new_key_bytes.as_ref()) {
-                        leaf.value = value;
-                        return;
+                        return Some(std::mem::replace(&mut leaf.value, value));
                     }
                     // Calculates the common prefix length between the new key and the leaf's key.
                     let old_key_bytes = leaf.key.bytes();
@@ -83,16 +578,23 @@ where
                         byte_at(old_key_bytes.as_ref(), new_depth),
                     )
                 };
+                trace!(
+                    depth,
+                    root = depth == 0,
+                    prefix = %partial.redacted(),
+                    "leaf split"
+                );
                 // Replace the current node, then add the old leaf and new leaf as its children.
                 let new_leaf = Self::new_leaf(key, value);
                 let old_leaf = std::mem::replace(self, Self::new_inner(partial));
-                self.add_child(k_new, new_leaf);
-                self.add_child(k_old, old_leaf);
+                self.add_child(arena, k_new, new_leaf);
+                self.add_child(arena, k_old, old_leaf);
+                None
             }
             Self::Inner(inner) => {
                 // Inner node has no prefix, insert recursively into it without any checks or modifications.
                 if inner.partial.len == 0 {
-                    return inner.insert_recursive(key, value, depth);
+                    return inner.insert_recursive(arena, key, value, depth, compress);
                 }
                 // Find the index at which the new key differs from the inner node's partial key.
                 let (prefix_diff, new_byte_key) = {
@@ -104,51 +606,473 @@ where
                 // The index at which the new key differs is not covered by the current partial key,
                 // so we insert recursively.
                 if prefix_diff >= inner.partial.len {
-                    return inner.insert_recursive(key, value, depth + inner.partial.len);
+                    return inner.insert_recursive(
+                        arena,
+                        key,
+                        value,
+                        depth + inner.partial.len,
+                        compress,
+                    );
                 }
                 // At this point, we found a difference between the new key and the inner node's partial key.
+                trace!(
+                    depth,
+                    root = depth == 0,
+                    prefix = %Redacted(&inner.partial.bytes()[..prefix_diff]),
+                    "prefix split"
+                );
+                // We modify the inner node partial key by skipping the common prefix plus the first byte
+                // where the keys differ. A new inner node is created, and we add the old inner node as its
+                // child.
                 let shift = prefix_diff + 1;
-                let partial = PartialKey::new(&inner.partial.data, prefix_diff);
-                if inner.partial.len <= P {
-                    // The mismatched byte is contained within the partial key data. We modify the inner node
-                    // partial key by skipping the common prefix plus the first byte where the keys differ.
-                    // A new inner node is created, and we add the old inner node as its child.
-                    let byte_key = byte_at(&inner.partial.data, prefix_diff);
-                    inner.partial.len -= shift;
-                    inner.partial.data.copy_within(shift.., 0);
-                    let old_node = std::mem::replace(self, Self::new_inner(partial));
-                    self.add_child(byte_key, old_node);
-                } else {
-                    let Some(leaf) = inner.indices.min_leaf_recursive() else {
-                        unreachable!(
-                            "a leaf must exist in the tree if the prefix is longer than the partial key"
-                        )
-                    };
-                    // The mismatched byte is contained outside of the partial key data. We modify the inner node
-                    // by filling its partial key data with part of the common prefix copied from the minimum leaf's
-                    // key. A new inner node is created, and we add the old inner node as its child.
-                    let byte_key = {
-                        let leaf_key_bytes = leaf.key.bytes();
-                        let offset = depth + shift;
-                        inner.partial.len -= shift;
-                        inner.partial.data[..P]
-                            .copy_from_slice(&leaf_key_bytes.as_ref()[offset..offset + P]);
-                        byte_at(leaf_key_bytes.as_ref(), depth + prefix_diff)
-                    };
-                    let old_node = std::mem::replace(self, Self::new_inner(partial));
-                    self.add_child(byte_key, old_node);
+                let partial = PartialKey::new(inner.partial.bytes(), prefix_diff);
+                let byte_key = inner.partial.bytes()[prefix_diff];
+                inner.partial =
+                    PartialKey::new(&inner.partial.bytes()[shift..], inner.partial.len - shift);
+                let old_node = std::mem::replace(self, Self::new_inner(partial));
+                self.add_child(arena, byte_key, old_node);
+                self.add_child(arena, new_byte_key, Self::new_leaf(key, value));
+                None
+            }
+        }
+    }
+
+    /// Builds a node from unsorted entries by recursively partitioning them on the byte following
+    /// their longest common prefix. When several entries have the same key, the last one is kept.
+    ///
+    /// # Arguments
+    ///
+    /// - `entries`: The key-value pairs to build the node from.
+    /// - `depth`: The number of bytes in the keys to skip, which all entries have in common.
+    pub fn build(entries: Vec<(K, V)>, depth: usize) -> Option<Self>
+    where
+        S: NodeStore<K, V, P, C4, C16, C48, Arena = ()>,
+    {
+        Self::build_partition(Partition::new(entries, depth), depth)
+    }
+
+    fn build_partition(partition: Partition<K, V, P>, depth: usize) -> Option<Self>
+    where
+        S: NodeStore<K, V, P, C4, C16, C48, Arena = ()>,
+    {
+        match partition {
+            Partition::Empty => None,
+            Partition::Leaf(key, value) => Some(Self::new_leaf(key, value)),
+            Partition::Unsplittable(entries) => Self::build_by_insertion(entries, depth),
+            Partition::Groups {
+                partial,
+                depth,
+                groups,
+            } => {
+                let mut node = Self::new_inner(partial);
+                for (byte_key, group) in groups {
+                    if let Some(child) = Self::build(group, depth) {
+                        node.add_child((), byte_key, child);
+                    }
+                }
+                Some(node)
+            }
+        }
+    }
+
+    /// Same as [`Node::build`], but the groups found at the root are built on up to the given
+    /// number of threads.
+    pub fn build_parallel(entries: Vec<(K, V)>, threads: usize) -> Option<Self>
+    where
+        S: NodeStore<K, V, P, C4, C16, C48, Arena = ()>,
+        Self: Send,
+        K: Send,
+        V: Send,
+    {
+        let (partial, depth, mut groups) = match Partition::new(entries, 0) {
+            Partition::Groups {
+                partial,
+                depth,
+                groups,
+            } => (partial, depth, groups),
+            partition => return Self::build_partition(partition, 0),
+        };
+        // Distribute the groups over the threads in contiguous chunks, so the children come back
+        // in key order.
+        let chunk_size = groups.len().div_ceil(threads.max(1));
+        let mut chunks = Vec::new();
+        while !groups.is_empty() {
+            let rest = groups.split_off(chunk_size.min(groups.len()));
+            chunks.push(std::mem::replace(&mut groups, rest));
+        }
+        let children: Vec<Vec<(u8, Option<Self>)>> = std::thread::scope(|scope| {
+            // All threads must be spawned before the first one is joined.
+            #[allow(clippy::needless_collect)]
+            let handles: Vec<_> = chunks
+                .into_iter()
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .into_iter()
+                            .map(|(byte_key, group)| (byte_key, Self::build(group, depth)))
+                            .collect()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|err| std::panic::resume_unwind(err))
+                })
+                .collect()
+        });
+        let mut node = Self::new_inner(partial);
+        for (byte_key, child) in children.into_iter().flatten() {
+            if let Some(child) = child {
+                node.add_child((), byte_key, child);
+            }
+        }
+        Some(node)
+    }
+
+    /// Builds a node by inserting the entries one by one.
+    fn build_by_insertion(entries: Vec<(K, V)>, depth: usize) -> Option<Self>
+    where
+        S: NodeStore<K, V, P, C4, C16, C48, Arena = ()>,
+    {
+        let mut node: Option<Self> = None;
+        for (key, value) in entries {
+            match &mut node {
+                Some(node) => {
+                    node.insert((), key, value, depth, true);
                 }
-                self.add_child(new_byte_key, Self::new_leaf(key, value));
+                None => node = Some(Self::new_leaf(key, value)),
+            }
+        }
+        node
+    }
+
+    /// Pushes a mutable reference to every leaf under this node into the given vector in key
+    /// order.
+    pub fn leaves_mut<'a>(&'a mut self, leaves: &mut Vec<&'a mut Leaf<K, V>>) {
+        match self {
+            Self::Leaf(leaf) => leaves.push(leaf),
+            Self::Inner(inner) => {
+                inner
+                    .indices
+                    .for_each_child_mut(|child| S::get_mut(child).leaves_mut(leaves));
             }
         }
     }
 
-    pub fn delete(&mut self, key: &[u8], depth: usize) -> Option<Leaf<K, V>> {
+    /// Moves every leaf under this node into the given vector in key order.
+    pub fn into_leaves(self, leaves: &mut Vec<Leaf<K, V>>) {
+        self.into_each_leaf(&mut |leaf| leaves.push(leaf));
+    }
+
+    /// Moves every leaf under this node into the given closure in key order. A child is only taken
+    /// out of its parent when it is visited, so the leaves after a panicking call are dropped with
+    /// the rest of the node.
+    pub fn into_each_leaf<F>(self, f: &mut F)
+    where
+        F: FnMut(Leaf<K, V>),
+    {
+        match self {
+            Self::Leaf(leaf) => f(leaf),
+            Self::Inner(mut inner) => {
+                let keys: Vec<u8> = inner.indices.children().map(|(key, _)| key).collect();
+                for key in keys {
+                    if let Some(child) = inner.del_child(key) {
+                        child.into_each_leaf(f);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Splits the subtree, whose keys start with the given path, into pieces made of the keys that
+    /// share their first `len` bytes, or their whole key if it is shorter. Every piece is pushed
+    /// with these bytes, and its root holds the whole prefix of its keys, so that it can be used as
+    /// the root of a tree. Subtrees below `len` bytes are detached as they are, while several
+    /// pieces with the same bytes are pushed for keys that are shorter than `len` bytes and that
+    /// are prefixes of other keys.
+    pub fn split_by_prefix(
+        self,
+        len: usize,
+        path: &mut Vec<u8>,
+        pieces: &mut Vec<(Vec<u8>, Self)>,
+    ) {
+        let mut inner = match self {
+            Self::Leaf(leaf) => {
+                let group = {
+                    let key = leaf.key.bytes();
+                    let key = key.as_ref();
+                    key[..min(len, key.len())].to_vec()
+                };
+                pieces.push((group, Self::Leaf(leaf)));
+                return;
+            }
+            Self::Inner(inner) => inner,
+        };
+        let depth = path.len();
+        path.extend_from_slice(inner.partial.bytes());
+        if path.len() >= len {
+            let shortest = inner
+                .indices
+                .min_leaf_recursive()
+                .map(|leaf| leaf.key.bytes().as_ref().len());
+            if shortest.is_some_and(|shortest| shortest >= len) {
+                inner.partial = PartialKey::new(path, path.len());
+                pieces.push((path[..len].to_vec(), Self::Inner(inner)));
+                path.truncate(depth);
+                return;
+            }
+        }
+        let keys: Vec<u8> = inner.indices.children().map(|(key, _)| key).collect();
+        for key in keys {
+            let Some(child) = inner.del_child(key) else {
+                continue;
+            };
+            if path.len() >= len {
+                // A key shorter than `len` bytes is below this node, so its leaves are split one by
+                // one.
+                let mut leaves = Vec::new();
+                child.into_leaves(&mut leaves);
+                for leaf in leaves {
+                    Self::Leaf(leaf).split_by_prefix(len, path, pieces);
+                }
+                continue;
+            }
+            path.push(key);
+            child.split_by_prefix(len, path, pieces);
+            path.pop();
+        }
+        path.truncate(depth);
+    }
+
+    /// Detaches the subtree of the keys that start with the given prefix from the tree with the
+    /// given root, whose paths must be compressed. The root of the detached subtree holds the whole
+    /// prefix of its keys, so that it can be used as the root of a tree.
+    pub fn take_prefix_root(root: &mut Option<Self>, prefix: &[u8]) -> Option<Self> {
+        let inner = match root.as_mut()? {
+            Self::Leaf(leaf) => {
+                let matched = leaf.key.bytes().as_ref().starts_with(prefix);
+                return if matched { root.take() } else { None };
+            }
+            Self::Inner(inner) => inner,
+        };
+        let partial = inner.partial.bytes();
+        if prefix.len() <= partial.len() {
+            return if partial.starts_with(prefix) {
+                root.take()
+            } else {
+                None
+            };
+        }
+        if !prefix.starts_with(partial) {
+            return None;
+        }
+        let taken = inner.take_prefix(prefix, inner.partial.len)?;
+        if inner.indices.children().next().is_none() {
+            *root = None;
+        } else if let Some(node) = inner.shrink_fully(true) {
+            *root = Some(node);
+        }
+        Some(taken)
+    }
+
+    /// Inserts the given key-value pair into the tree with the given root, compressing the paths
+    /// of new inner nodes if `compress` is set.
+    /// Returns the value that was replaced if the key already existed.
+    pub fn insert_root(root: &mut Option<Self>, key: K, value: V, compress: bool) -> Option<V>
+    where
+        S: NodeStore<K, V, P, C4, C16, C48, Arena = ()>,
+    {
+        Self::insert_root_in(root, (), key, value, compress)
+    }
+
+    /// Same as [`Node::insert_root`], but the new nodes are stored in the given arena.
+    pub fn insert_root_in(
+        root: &mut Option<Self>,
+        arena: S::Arena,
+        key: K,
+        value: V,
+        compress: bool,
+    ) -> Option<V> {
+        // Insert into the current root if the tree is not empty. Otherwise,
+        // create a new leaf as the root.
+        #[cfg(feature = "check-invariants")]
+        let key_bytes = key.bytes().as_ref().to_vec();
+        let replaced = if let Some(root) = root {
+            root.insert(arena, key, value, 0, compress)
+        } else {
+            *root = Some(Self::new_leaf(key, value));
+            None
+        };
+        #[cfg(feature = "check-invariants")]
+        Self::check_path(root.as_ref(), &key_bytes, compress);
+        replaced
+    }
+
+    /// Deletes the leaf matching the given key from the tree with the given root, keeping inner
+    /// nodes uncompressed unless `compress` is set.
+    pub fn delete_root(root: &mut Option<Self>, key: &[u8], compress: bool) -> Option<Leaf<K, V>> {
+        // The root stays in place until the leaf is found, because comparing the keys of the leaves
+        // may panic, and the tree must not lose its root if it does.
+        let node = root.as_mut()?;
+        // Handles special case when the root is a leaf. Otherwise, start deleting from within the inner node.
+        let Self::Leaf(leaf) = node else {
+            let deleted = node.delete(key, 0, compress);
+            #[cfg(feature = "check-invariants")]
+            Self::check_path(root.as_ref(), key, compress);
+            return deleted;
+        };
+        // If the key matches, take the leaf out of the root.
+        if !leaf.match_key(key) {
+            return None;
+        }
+        let Some(Self::Leaf(leaf)) = root.take() else {
+            unreachable!("the root was just matched as a leaf");
+        };
+        Some(leaf)
+    }
+
+    /// Deletes the leaves with the given keys, which must be sorted and in the tree with the given
+    /// root, pushing them to the vector in key order. Each node on the paths of the keys is visited
+    /// once, and shrunk once all of its deleted children were removed.
+    pub fn delete_sorted_root(
+        root: &mut Option<Self>,
+        keys: &[&[u8]],
+        compress: bool,
+        deleted: &mut Vec<Leaf<K, V>>,
+    ) {
+        if keys.is_empty() {
+            return;
+        }
+        match root {
+            None => {}
+            Some(Self::Leaf(_)) => {
+                if let Some(Self::Leaf(leaf)) = root.take() {
+                    deleted.push(leaf);
+                }
+            }
+            Some(Self::Inner(inner)) => {
+                inner.delete_sorted(keys, 0, compress, deleted);
+                if inner.indices.children().next().is_none() {
+                    *root = None;
+                } else if let Some(node) = inner.shrink_fully(compress) {
+                    *root = Some(node);
+                }
+            }
+        }
+    }
+
+    /// Checks the invariants of the inner nodes on the path of the given key, which are the only
+    /// nodes a mutation of the key can change, and panics with a dump of the tree on a violation.
+    #[cfg(feature = "check-invariants")]
+    fn check_path(root: Option<&Self>, key: &[u8], compress: bool) {
+        let Some(root) = root else {
+            return;
+        };
+        if let Err(violation) = root.check_path_recursive(key, 0, compress) {
+            let mut dump = String::new();
+            root.dump(&mut dump, 0, 0);
+            panic!("tree invariant violated: {violation}\n{dump}");
+        }
+    }
+
+    #[cfg(feature = "check-invariants")]
+    fn check_path_recursive(&self, key: &[u8], depth: usize, compress: bool) -> Result<(), String> {
+        let Self::Inner(inner) = self else {
+            return Ok(());
+        };
+        // Without path compression, a node with a single inner child can not be merged into it.
+        let min_len = if compress { 2 } else { 1 };
+        let (kind, len, bounds) = match &inner.indices {
+            InnerIndices::Node4(indices) => ("node4", indices.len(), min_len..=C4),
+            InnerIndices::Node16(indices) => ("node16", indices.len(), shrunk_len(C4) + 1..=C16),
+            InnerIndices::Node48(indices) => ("node48", indices.len(), shrunk_len(C16) + 1..=C48),
+            InnerIndices::Node256(indices) => {
+                ("node256", indices.len(), shrunk_len256(C48) + 1..=256)
+            }
+        };
+        if !bounds.contains(&len) {
+            return Err(format!("{kind} at depth {depth} has {len} children"));
+        }
+        let byte_keys: Vec<u8> = inner.indices.children().map(|(byte, _)| byte).collect();
+        if byte_keys.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(format!("{kind} at depth {depth} has unsorted byte keys"));
+        }
+        if !compress && inner.partial.len > 0 {
+            return Err(format!("{kind} at depth {depth} has a prefix"));
+        }
+        let Some(leaf) = inner.indices.min_leaf_recursive() else {
+            return Err(format!("{kind} at depth {depth} has no leaf"));
+        };
+        let leaf_key = leaf.key.bytes();
+        let prefix = inner.partial.bytes();
+        if (0..prefix.len()).any(|i| byte_at(leaf_key.as_ref(), depth + i) != prefix[i]) {
+            return Err(format!(
+                "prefix of {kind} at depth {depth} disagrees with its leaves"
+            ));
+        }
+        let depth = depth + inner.partial.len;
+        let byte_key = byte_at(key, depth);
+        let Some(child) = inner.child_ref(byte_key) else {
+            return Ok(());
+        };
+        let Some(leaf) = child.min_leaf() else {
+            return Err(format!(
+                "child {byte_key} of {kind} at depth {depth} has no leaf"
+            ));
+        };
+        if byte_at(leaf.key.bytes().as_ref(), depth) != byte_key {
+            return Err(format!(
+                "child {byte_key} of {kind} at depth {depth} holds keys with another byte"
+            ));
+        }
+        child.check_path_recursive(key, depth + 1, compress)
+    }
+
+    /// Writes the structure of the tree in the format of [`debug_print`], with the encoded keys
+    /// of the leaves instead of the keys and values.
+    #[cfg(feature = "check-invariants")]
+    fn dump(&self, out: &mut String, key: u8, level: usize) {
+        use std::fmt::Write;
+
+        let indent = 2 * level;
+        match self {
+            Self::Leaf(leaf) => {
+                let _ = writeln!(
+                    out,
+                    "{:indent$}[{key:03}] leaf: {:?}",
+                    "",
+                    leaf.key.bytes().as_ref()
+                );
+            }
+            Self::Inner(inner) => {
+                let kind = match &inner.indices {
+                    InnerIndices::Node4(_) => "node4",
+                    InnerIndices::Node16(_) => "node16",
+                    InnerIndices::Node48(_) => "node48",
+                    InnerIndices::Node256(_) => "node256",
+                };
+                let len = inner.indices.children().count();
+                let _ = writeln!(
+                    out,
+                    "{:indent$}[{key:03}] {kind} (len: {len}) {:?}",
+                    "", inner.partial
+                );
+                for (key, child) in inner.indices.children() {
+                    child.dump(out, key, level + 1);
+                }
+            }
+        }
+    }
+
+    pub fn delete(&mut self, key: &[u8], depth: usize, compress: bool) -> Option<Leaf<K, V>> {
         let Self::Inner(inner) = self else {
             unreachable!("can not delete child on a leaf node");
         };
-        let deleted = inner.delete_recursive(key, depth);
-        if let Some(node) = inner.shrink() {
+        let deleted = inner.delete_recursive(key, depth, compress);
+        if let Some(node) = inner.shrink(compress) {
             *self = node;
         }
         deleted
@@ -168,18 +1092,59 @@ where
         }
     }
 
-    fn add_child(&mut self, key: u8, child: Self) {
+    fn add_child(&mut self, arena: S::Arena, key: u8, child: Self) {
         // NOTE: Is there a way to avoid this match?
         let Self::Inner(inner) = self else {
             unreachable!("can not add child on a leaf node")
         };
-        inner.add_child(key, child);
+        inner.add_child(arena, key, child);
+    }
+}
+
+/// Undoes the split of a leaf in a tree without path compression if inserting into the inner node
+/// that replaced it panics, by merging the leaf back into the place of the inner node, see
+/// [`Node::insert`]. The guard is forgotten once the insert returns, so it is only dropped while
+/// unwinding.
+struct SplitGuard<
+    'a,
+    K,
+    V,
+    const P: usize,
+    const C4: usize,
+    const C16: usize,
+    const C48: usize,
+    S: NodeStore<K, V, P, C4, C16, C48>,
+>(&'a mut Node<K, V, P, S, C4, C16, C48>)
+where
+    K: BytesComparable;
+
+impl<K, V, const P: usize, const C4: usize, const C16: usize, const C48: usize, S> Drop
+    for SplitGuard<'_, K, V, P, C4, C16, C48, S>
+where
+    K: BytesComparable,
+    S: NodeStore<K, V, P, C4, C16, C48>,
+{
+    fn drop(&mut self) {
+        // The nodes below were restored by their own guards, so the inner node holds only the leaf.
+        if let Node::Inner(inner) = self.0 {
+            if let Some(leaf) = inner.shrink(false) {
+                *self.0 = leaf;
+            }
+        }
     }
 }
 
-pub fn debug_print<K, V, const P: usize>(
+pub fn debug_print<
+    K,
+    V,
+    const P: usize,
+    const C4: usize,
+    const C16: usize,
+    const C48: usize,
+    S: NodeStore<K, V, P, C4, C16, C48>,
+>(
     f: &mut std::fmt::Formatter<'_>,
-    node: &Node<K, V, P>,
+    node: &Node<K, V, P, S, C4, C16, C48>,
     key: u8,
     level: usize,
 ) -> std::fmt::Result
@@ -227,7 +1192,7 @@ where
                     indices.len(),
                     inner.partial
                 )?;
-                for (key, child) in indices {
+                for (key, child) in indices.as_ref() {
                     debug_print(f, child, key, level + 1)?;
                 }
             }
@@ -239,7 +1204,7 @@ where
                     indices.len(),
                     inner.partial
                 )?;
-                for (key, child) in indices {
+                for (key, child) in indices.as_ref() {
                     debug_print(f, child, key, level + 1)?;
                 }
             }
@@ -248,6 +1213,77 @@ where
     Ok(())
 }
 
+/// Entries that are split into groups by the byte following their longest common prefix.
+enum Partition<K, V, const P: usize> {
+    /// There is no entry.
+    Empty,
+    /// There is a single entry.
+    Leaf(K, V),
+    /// All entries fall into the same group, which happens when they share the same key. These are
+    /// left to regular insertion.
+    Unsplittable(Vec<(K, V)>),
+    /// The entries are split into at least two groups.
+    Groups {
+        /// The longest common prefix of the entries.
+        partial: PartialKey<P>,
+        /// The depth of the groups, which is after the prefix and the byte of each group.
+        depth: usize,
+        /// The groups of entries in the order of their byte keys.
+        groups: Vec<(u8, Vec<(K, V)>)>,
+    },
+}
+
+impl<K, V, const P: usize> Partition<K, V, P>
+where
+    K: BytesComparable,
+{
+    fn new(mut entries: Vec<(K, V)>, depth: usize) -> Self {
+        match entries.len() {
+            0 => return Self::Empty,
+            1 => {
+                let (key, value) = entries.pop().expect("entry must exist");
+                return Self::Leaf(key, value);
+            }
+            _ => {}
+        }
+        let (partial, prefix_len) = {
+            let first = entries[0].0.bytes();
+            let first = first.as_ref().get(depth..).unwrap_or_default();
+            let prefix_len = entries[1..]
+                .iter()
+                .map(|(key, _)| {
+                    let key = key.bytes();
+                    longest_common_prefix(first, key.as_ref().get(depth..).unwrap_or_default(), 0)
+                })
+                .min()
+                .unwrap_or_default();
+            (PartialKey::new(first, prefix_len), prefix_len)
+        };
+        let depth = depth + prefix_len;
+        // Group the entries in a single pass, remembering where the group of each byte is.
+        let mut group_of = [usize::MAX; 256];
+        let mut groups: Vec<(u8, Vec<(K, V)>)> = Vec::new();
+        for (key, value) in entries {
+            let byte_key = byte_at(key.bytes().as_ref(), depth);
+            let group = &mut group_of[byte_key as usize];
+            if *group == usize::MAX {
+                *group = groups.len();
+                groups.push((byte_key, Vec::new()));
+            }
+            groups[*group].1.push((key, value));
+        }
+        if groups.len() == 1 {
+            return Self::Unsplittable(groups.pop().expect("group must exist").1);
+        }
+        groups.sort_unstable_by_key(|(byte_key, _)| *byte_key);
+        Self::Groups {
+            partial,
+            depth: depth + 1,
+            groups,
+        }
+    }
+}
+
 /// Count the number of common elements at the beginning of two slices.
 fn longest_common_prefix<T>(lhs: &[T], rhs: &[T], depth: usize) -> usize
 where
@@ -260,6 +1296,20 @@ where
         .count()
 }
 
+/// Returns the number of children at which a node shrinks into the kind with the given capacity.
+/// Shrinking only when the smaller kind would be a quarter empty keeps a node that alternates
+/// between gaining and losing a child from being resized every time.
+const fn shrunk_len(capacity: usize) -> usize {
+    capacity - capacity / 4
+}
+
+/// Returns the number of children at which a `Node256` shrinks into a `Node48` with the given
+/// capacity. A `Node256` is much larger than the kinds below it, so it shrinks one child earlier
+/// than [`shrunk_len`] would have it, at 37 children for the default capacity.
+const fn shrunk_len256(capacity: usize) -> usize {
+    shrunk_len(capacity) + 1
+}
+
 /// Gets the byte at the given position in the slice. If it is out of bounds, then 0 is returned.
 fn byte_at(bytes: &[u8], pos: usize) -> u8 {
     bytes.get(pos).copied().unwrap_or(0)
@@ -281,22 +1331,198 @@ where
     }
 }
 
-#[derive(Debug)]
-pub struct Inner<K, V, const P: usize> {
-    partial: PartialKey<P>,
-    indices: InnerIndices<K, V, P>,
-}
+pub struct Inner<
+    K,
+    V,
+    const P: usize,
+    S: NodeStore<K, V, P, C4, C16, C48>,
+    const C4: usize,
+    const C16: usize,
+    const C48: usize,
+> {
+    partial: PartialKey<P>,
+    indices: InnerIndices<K, V, P, S, C4, C16, C48>,
+}
+
+impl<K, V, const P: usize, const C4: usize, const C16: usize, const C48: usize, S> Clone
+    for Inner<K, V, P, S, C4, C16, C48>
+where
+    S: NodeStore<K, V, P, C4, C16, C48>,
+    S::Ptr: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            partial: self.partial.clone(),
+            indices: match &self.indices {
+                InnerIndices::Node4(indices) => InnerIndices::Node4(indices.clone()),
+                InnerIndices::Node16(indices) => InnerIndices::Node16(indices.clone()),
+                InnerIndices::Node48(indices) => InnerIndices::Node48(indices.clone()),
+                InnerIndices::Node256(indices) => InnerIndices::Node256(indices.clone()),
+            },
+        }
+    }
+}
+
+impl<
+        K,
+        V,
+        const P: usize,
+        const C4: usize,
+        const C16: usize,
+        const C48: usize,
+        S: NodeStore<K, V, P, C4, C16, C48>,
+    > Inner<K, V, P, S, C4, C16, C48>
+{
+    /// Fails to compile when the capacities of the node kinds do not increase, as a node has to be
+    /// able to grow into the next kind and shrink back.
+    const VALID_CAPACITIES: () = assert!(
+        2 <= C4 && C4 < C16 && C16 < C48 && C48 < 256,
+        "node capacities must satisfy 2 <= C4 < C16 < C48 < 256"
+    );
 
-impl<K, V, const P: usize> Inner<K, V, P> {
     fn new(partial: PartialKey<P>) -> Self {
+        let () = Self::VALID_CAPACITIES;
         Self {
             partial,
             indices: InnerIndices::Node4(Indices4::default()),
         }
     }
+
+    /// Returns the kind of the node.
+    #[cfg(any(feature = "probe", feature = "raw"))]
+    pub const fn kind(&self) -> NodeKind {
+        match self.indices {
+            InnerIndices::Node4(_) => NodeKind::Node4,
+            InnerIndices::Node16(_) => NodeKind::Node16,
+            InnerIndices::Node48(_) => NodeKind::Node48,
+            InnerIndices::Node256(_) => NodeKind::Node256,
+        }
+    }
+
+    /// Returns the bytes of the prefix.
+    pub fn prefix(&self) -> &[u8] {
+        self.partial.bytes()
+    }
+
+    /// Returns an iterator over the children and their byte keys in key order.
+    pub fn children(&self) -> impl Iterator<Item = (u8, &Node<K, V, P, S, C4, C16, C48>)> {
+        self.indices.children()
+    }
+
+    /// Returns the first child whose byte key is greater than the given one, or the first child if
+    /// no byte key is given.
+    fn child_after(&self, after: Option<u8>) -> Option<(u8, &Node<K, V, P, S, C4, C16, C48>)> {
+        let start = match after {
+            Some(u8::MAX) => return None,
+            Some(key) => key + 1,
+            None => 0,
+        };
+        match &self.indices {
+            InnerIndices::Node4(_) | InnerIndices::Node16(_) => {
+                self.indices.children().find(|(key, _)| *key >= start)
+            }
+            // Probing the following byte keys of large nodes visits every byte key at most once
+            // when moving through all the children.
+            InnerIndices::Node48(indices) => (start..=u8::MAX)
+                .find_map(|key| indices.child_ref(key).map(|child| (key, &**child))),
+            InnerIndices::Node256(indices) => (start..=u8::MAX)
+                .find_map(|key| indices.child_ref(key).map(|child| (key, &**child))),
+        }
+    }
+
+    /// Returns the last child whose byte key is less than the given one, or the last child if no
+    /// byte key is given.
+    fn child_before(&self, before: Option<u8>) -> Option<(u8, &Node<K, V, P, S, C4, C16, C48>)> {
+        let end = match before {
+            Some(0) => return None,
+            Some(key) => key - 1,
+            None => u8::MAX,
+        };
+        match &self.indices {
+            InnerIndices::Node4(_) | InnerIndices::Node16(_) => self
+                .indices
+                .children()
+                .take_while(|(key, _)| *key <= end)
+                .last(),
+            InnerIndices::Node48(indices) => (0..=end)
+                .rev()
+                .find_map(|key| indices.child_ref(key).map(|child| (key, &**child))),
+            InnerIndices::Node256(indices) => (0..=end)
+                .rev()
+                .find_map(|key| indices.child_ref(key).map(|child| (key, &**child))),
+        }
+    }
+}
+
+/// Gauges for the `exporter` module.
+#[cfg(feature = "metrics")]
+impl<
+        K,
+        V,
+        const P: usize,
+        const C4: usize,
+        const C16: usize,
+        const C48: usize,
+        S: NodeStore<K, V, P, C4, C16, C48>,
+    > Node<K, V, P, S, C4, C16, C48>
+{
+    /// Adds the nodes of the subtree, which starts below the given number of inner nodes, to the
+    /// gauges.
+    pub fn measure(&self, depth: usize, gauges: &mut crate::exporter::Gauges) {
+        gauges.node_bytes += std::mem::size_of::<Self>();
+        let inner = match self {
+            Self::Leaf(_) => {
+                gauges.depth = gauges.depth.max(depth);
+                return;
+            }
+            Self::Inner(inner) => inner,
+        };
+        gauges.node_bytes += inner.partial.chain.as_ref().map_or(0, |chain| chain.len());
+        match &inner.indices {
+            InnerIndices::Node4(_) => gauges.node4 += 1,
+            InnerIndices::Node16(_) => gauges.node16 += 1,
+            InnerIndices::Node48(_) => {
+                gauges.node48 += 1;
+                gauges.node_bytes += std::mem::size_of::<Indices48<S::Ptr, C48>>();
+            }
+            InnerIndices::Node256(_) => {
+                gauges.node256 += 1;
+                gauges.node_bytes += std::mem::size_of::<Indices256<S::Ptr>>();
+            }
+        }
+        for (_, child) in inner.indices.children() {
+            child.measure(depth + 1, gauges);
+        }
+    }
+}
+
+/// Accessors for the `raw` module.
+#[cfg(feature = "raw")]
+impl<
+        K,
+        V,
+        const P: usize,
+        const C4: usize,
+        const C16: usize,
+        const C48: usize,
+        S: NodeStore<K, V, P, C4, C16, C48>,
+    > Inner<K, V, P, S, C4, C16, C48>
+{
+    /// Returns the length of the prefix.
+    pub const fn prefix_len(&self) -> usize {
+        self.partial.len
+    }
 }
 
-impl<K, V, const P: usize> Inner<K, V, P>
+impl<
+        K,
+        V,
+        const P: usize,
+        const C4: usize,
+        const C16: usize,
+        const C48: usize,
+        S: NodeStore<K, V, P, C4, C16, C48>,
+    > Inner<K, V, P, S, C4, C16, C48>
 where
     K: BytesComparable,
 {
@@ -310,19 +1536,29 @@ where
             .and_then(|child| child.search(key, next_depth + 1))
     }
 
-    fn insert_recursive(&mut self, key: K, value: V, depth: usize) {
+    fn insert_recursive(
+        &mut self,
+        arena: S::Arena,
+        key: K,
+        value: V,
+        depth: usize,
+        compress: bool,
+    ) -> Option<V> {
         let byte_key = byte_at(key.bytes().as_ref(), depth);
         if let Some(child) = self.child_mut(byte_key) {
             // Found a child so we recursively insert into it.
-            child.insert(key, value, depth + 1);
+            child.insert(arena, key, value, depth + 1, compress)
         } else {
             // No child found so we insert a new leaf into the current node.
             let leaf = Node::new_leaf(key, value);
-            self.add_child(byte_key, leaf);
+            self.add_child(arena, byte_key, leaf);
+            None
         }
     }
 
-    fn delete_recursive(&mut self, key: &[u8], depth: usize) -> Option<Leaf<K, V>> {
+    fn delete_recursive(&mut self, key: &[u8], depth: usize, compress: bool) -> Option<Leaf<K, V>> {
+        count!(node_visits, 1);
+        probe!(self, depth);
         // The key doesn't match the prefix partial.
         if !self.partial.match_key(key, depth) {
             return None;
@@ -330,9 +1566,7 @@ where
         // Find the child node corresponding to the key.
         let depth = depth + self.partial.len;
         let child_key = byte_at(key, depth);
-        let Some(child) = self.child_mut(child_key) else {
-            return None;
-        };
+        let child = self.child_mut(child_key)?;
         // Do recursion if the child is an inner node.
         match child {
             Node::Leaf(leaf) => {
@@ -348,8 +1582,8 @@ where
                 })
             }
             Node::Inner(inner) => {
-                let deleted = inner.delete_recursive(key, depth + 1);
-                if let Some(node) = inner.shrink() {
+                let deleted = inner.delete_recursive(key, depth + 1, compress);
+                if let Some(node) = inner.shrink(compress) {
                     *child = node;
                 }
                 deleted
@@ -357,90 +1591,234 @@ where
         }
     }
 
-    fn add_child(&mut self, key: u8, child: Node<K, V, P>) {
+    /// Deletes the leaves with the given sorted keys from the subtree, see
+    /// [`Node::delete_sorted_root`].
+    fn delete_sorted(
+        &mut self,
+        keys: &[&[u8]],
+        depth: usize,
+        compress: bool,
+        deleted: &mut Vec<Leaf<K, V>>,
+    ) {
+        count!(node_visits, 1);
+        probe!(self, depth);
+        let depth = depth + self.partial.len;
+        for group in keys.chunk_by(|lhs, rhs| byte_at(lhs, depth) == byte_at(rhs, depth)) {
+            let child_key = byte_at(group[0], depth);
+            let Some(child) = self.child_mut(child_key) else {
+                continue;
+            };
+            if let Node::Inner(inner) = child {
+                inner.delete_sorted(group, depth + 1, compress, deleted);
+                if inner.indices.children().next().is_some() {
+                    if let Some(node) = inner.shrink_fully(compress) {
+                        *child = node;
+                    }
+                    continue;
+                }
+            }
+            // The child is either a deleted leaf or an inner node whose leaves were all deleted.
+            if let Some(Node::Leaf(leaf)) = self.del_child(child_key) {
+                deleted.push(leaf);
+            }
+        }
+    }
+
+    /// Detaches the child subtree of the keys that start with the given prefix, which matches the
+    /// path to this node up to the given depth, see [`Node::take_prefix_root`].
+    fn take_prefix(
+        &mut self,
+        prefix: &[u8],
+        depth: usize,
+    ) -> Option<Node<K, V, P, S, C4, C16, C48>> {
+        count!(node_visits, 1);
+        probe!(self, depth - self.partial.len);
+        let byte_key = prefix[depth];
+        let rest = &prefix[depth + 1..];
+        let child = self.child_mut(byte_key)?;
+        let next_depth = match child {
+            Node::Leaf(leaf) => {
+                if !leaf.key.bytes().as_ref().starts_with(prefix) {
+                    return None;
+                }
+                None
+            }
+            Node::Inner(inner) => {
+                let partial = inner.partial.bytes();
+                if rest.len() <= partial.len() {
+                    if !partial.starts_with(rest) {
+                        return None;
+                    }
+                    None
+                } else if rest.starts_with(partial) {
+                    Some(depth + 1 + partial.len())
+                } else {
+                    return None;
+                }
+            }
+        };
+        let Some(next_depth) = next_depth else {
+            // The whole child is taken, and its prefix is extended with the path leading to it.
+            let mut child = self.del_child(byte_key)?;
+            if let Node::Inner(inner) = &mut child {
+                let mut full = prefix[..=depth].to_vec();
+                full.extend_from_slice(inner.partial.bytes());
+                inner.partial = PartialKey::new(&full, full.len());
+            }
+            return Some(child);
+        };
+        let Node::Inner(inner) = child else {
+            unreachable!("only an inner child is descended into");
+        };
+        let taken = inner.take_prefix(prefix, next_depth)?;
+        if inner.indices.children().next().is_none() {
+            drop(self.del_child(byte_key));
+        } else if let Some(node) = inner.shrink_fully(true) {
+            *child = node;
+        }
+        Some(taken)
+    }
+
+    /// Shrinks the node until it has the smallest kind that fits its children, returning the node
+    /// that replaces it if it was merged into its child.
+    fn shrink_fully(&mut self, compress: bool) -> Option<Node<K, V, P, S, C4, C16, C48>> {
+        loop {
+            let kind = std::mem::discriminant(&self.indices);
+            if let Some(node) = self.shrink(compress) {
+                return Some(node);
+            }
+            if std::mem::discriminant(&self.indices) == kind {
+                return None;
+            }
+        }
+    }
+
+    fn add_child(&mut self, arena: S::Arena, key: u8, child: Node<K, V, P, S, C4, C16, C48>) {
         self.grow();
         match &mut self.indices {
-            InnerIndices::Node4(indices) => indices.add_child(key, Box::new(child)),
-            InnerIndices::Node16(indices) => indices.add_child(key, Box::new(child)),
-            InnerIndices::Node48(indices) => indices.add_child(key, Box::new(child)),
-            InnerIndices::Node256(indices) => indices.add_child(key, Box::new(child)),
+            InnerIndices::Node4(indices) => indices.add_child(key, S::alloc(arena, child)),
+            InnerIndices::Node16(indices) => indices.add_child(key, S::alloc(arena, child)),
+            InnerIndices::Node48(indices) => indices.add_child(key, S::alloc(arena, child)),
+            InnerIndices::Node256(indices) => indices.add_child(key, S::alloc(arena, child)),
         }
     }
 
-    fn del_child(&mut self, key: u8) -> Option<Node<K, V, P>> {
+    fn del_child(&mut self, key: u8) -> Option<Node<K, V, P, S, C4, C16, C48>> {
         match &mut self.indices {
-            InnerIndices::Node4(indices) => indices.del_child(key).map(|child| *child),
-            InnerIndices::Node16(indices) => indices.del_child(key).map(|child| *child),
-            InnerIndices::Node48(indices) => indices.del_child(key).map(|child| *child),
-            InnerIndices::Node256(indices) => indices.del_child(key).map(|child| *child),
+            InnerIndices::Node4(indices) => indices.del_child(key).map(S::take),
+            InnerIndices::Node16(indices) => indices.del_child(key).map(S::take),
+            InnerIndices::Node48(indices) => indices.del_child(key).map(S::take),
+            InnerIndices::Node256(indices) => indices.del_child(key).map(S::take),
         }
     }
 
-    fn child_ref(&self, key: u8) -> Option<&Node<K, V, P>> {
+    pub fn child_ref(&self, key: u8) -> Option<&Node<K, V, P, S, C4, C16, C48>> {
         match &self.indices {
-            InnerIndices::Node4(indices) => indices.child_ref(key).map(Box::as_ref),
-            InnerIndices::Node16(indices) => indices.child_ref(key).map(Box::as_ref),
-            InnerIndices::Node48(indices) => indices.child_ref(key).map(Box::as_ref),
-            InnerIndices::Node256(indices) => indices.child_ref(key).map(Box::as_ref),
+            InnerIndices::Node4(indices) => indices.child_ref(key).map(Deref::deref),
+            InnerIndices::Node16(indices) => indices.child_ref(key).map(Deref::deref),
+            InnerIndices::Node48(indices) => indices.child_ref(key).map(Deref::deref),
+            InnerIndices::Node256(indices) => indices.child_ref(key).map(Deref::deref),
         }
     }
 
-    fn child_mut(&mut self, key: u8) -> Option<&mut Node<K, V, P>> {
+    fn child_mut(&mut self, key: u8) -> Option<&mut Node<K, V, P, S, C4, C16, C48>> {
         match &mut self.indices {
-            InnerIndices::Node4(indices) => indices.child_mut(key).map(Box::as_mut),
-            InnerIndices::Node16(indices) => indices.child_mut(key).map(Box::as_mut),
-            InnerIndices::Node48(indices) => indices.child_mut(key).map(Box::as_mut),
-            InnerIndices::Node256(indices) => indices.child_mut(key).map(Box::as_mut),
+            InnerIndices::Node4(indices) => indices.child_mut(key).map(S::get_mut),
+            InnerIndices::Node16(indices) => indices.child_mut(key).map(S::get_mut),
+            InnerIndices::Node48(indices) => indices.child_mut(key).map(S::get_mut),
+            InnerIndices::Node256(indices) => indices.child_mut(key).map(S::get_mut),
         }
     }
 
     fn grow(&mut self) {
         match &mut self.indices {
             InnerIndices::Node4(indices) => {
-                if indices.len() == 4 {
+                if indices.len() == C4 {
+                    count!(grows, 1);
+                    count!(transitions.grown_4_to_16, 1);
+                    trace!(from = C4, to = C16, prefix = %self.partial.redacted(), "node grown");
                     self.indices = InnerIndices::Node16(Indices16::from(indices));
                 }
             }
             InnerIndices::Node16(indices) => {
-                if indices.len() == 16 {
-                    self.indices = InnerIndices::Node48(Indices48::from(indices));
+                if indices.len() == C16 {
+                    count!(grows, 1);
+                    count!(transitions.grown_16_to_48, 1);
+                    trace!(from = C16, to = C48, prefix = %self.partial.redacted(), "node grown");
+                    self.indices = InnerIndices::Node48(S::box_indices48(Indices48::from(indices)));
                 }
             }
             InnerIndices::Node48(indices) => {
-                if indices.len() == 48 {
-                    self.indices = InnerIndices::Node256(Indices256::from(indices));
+                if indices.len() == C48 {
+                    count!(grows, 1);
+                    count!(transitions.grown_48_to_256, 1);
+                    trace!(from = C48, to = 256, prefix = %self.partial.redacted(), "node grown");
+                    let grown = InnerIndices::Node256(S::box_indices256(Indices256::from(
+                        indices.as_mut(),
+                    )));
+                    if let InnerIndices::Node48(old) = std::mem::replace(&mut self.indices, grown) {
+                        S::free_indices48(old);
+                    }
                 }
             }
             InnerIndices::Node256(_) => {}
         }
     }
 
-    fn shrink(&mut self) -> Option<Node<K, V, P>> {
+    fn shrink(&mut self, compress: bool) -> Option<Node<K, V, P, S, C4, C16, C48>> {
         match &mut self.indices {
             InnerIndices::Node4(indices) => {
-                if indices.len() <= 1 {
-                    let (sub_child_key, mut sub_child) = indices.free();
-                    if let Node::Inner(sub_child) = sub_child.as_mut() {
+                // Merging a single inner child needs its prefix to be extended, so without path
+                // compression, only a single leaf replaces its parent.
+                let mergeable = compress
+                    || indices
+                        .min()
+                        .is_some_and(|child| matches!(**child, Node::Leaf(_)));
+                if indices.len() <= 1 && mergeable {
+                    count!(shrinks, 1);
+                    count!(transitions.merged, 1);
+                    trace!(prefix = %self.partial.redacted(), "node merged into its child");
+                    let (sub_child_key, sub_child) = indices.free();
+                    let mut sub_child = S::take(sub_child);
+                    if let Node::Inner(sub_child) = &mut sub_child {
                         self.partial.push(sub_child_key);
                         self.partial.append(&sub_child.partial);
                         std::mem::swap(&mut self.partial, &mut sub_child.partial);
                     }
-                    return Some(*sub_child);
+                    return Some(sub_child);
                 }
             }
             InnerIndices::Node16(indices) => {
-                if indices.len() <= 3 {
+                if indices.len() <= shrunk_len(C4) {
+                    count!(shrinks, 1);
+                    count!(transitions.shrunk_16_to_4, 1);
+                    trace!(from = C16, to = C4, prefix = %self.partial.redacted(), "node shrunk");
                     self.indices = InnerIndices::Node4(Indices4::from(indices));
                 }
             }
             InnerIndices::Node48(indices) => {
-                if indices.len() <= 12 {
-                    self.indices = InnerIndices::Node16(Indices16::from(indices));
+                if indices.len() <= shrunk_len(C16) {
+                    count!(shrinks, 1);
+                    count!(transitions.shrunk_48_to_16, 1);
+                    trace!(from = C48, to = C16, prefix = %self.partial.redacted(), "node shrunk");
+                    let shrunk = InnerIndices::Node16(Indices16::from(indices.as_mut()));
+                    if let InnerIndices::Node48(old) = std::mem::replace(&mut self.indices, shrunk)
+                    {
+                        S::free_indices48(old);
+                    }
                 }
             }
             InnerIndices::Node256(indices) => {
-                if indices.len() <= 37 {
-                    self.indices = InnerIndices::Node48(Indices48::from(indices));
+                if indices.len() <= shrunk_len256(C48) {
+                    count!(shrinks, 1);
+                    count!(transitions.shrunk_256_to_48, 1);
+                    trace!(from = 256, to = C48, prefix = %self.partial.redacted(), "node shrunk");
+                    let shrunk =
+                        InnerIndices::Node48(S::box_indices48(Indices48::from(indices.as_mut())));
+                    if let InnerIndices::Node256(old) = std::mem::replace(&mut self.indices, shrunk)
+                    {
+                        S::free_indices256(old);
+                    }
                 }
             }
         }
@@ -448,43 +1826,92 @@ where
     }
 
     fn first_mismatch_index(&self, key: &[u8], depth: usize) -> usize {
-        let len = min(P, self.partial.len);
         let mut idx = 0;
-        for (l, r) in self.partial.data[..len].iter().zip(key[depth..].iter()) {
+        for (l, r) in self.partial.bytes().iter().zip(key[depth..].iter()) {
+            count!(prefix_bytes, 1);
             if l != r {
                 return idx;
             }
             idx += 1;
         }
-        if self.partial.len > P {
-            // Prefix is longer than what we've checked, find a leaf. The minimum leaf is
-            // guaranteed to contains the longest common prefix of the current partial key.
-            let Some(leaf) = self.indices.min_leaf_recursive() else {
-                unreachable!(
-                    "a leaf must exist in the tree if the prefix is longer than the partial key"
-                )
-            };
-            idx += longest_common_prefix(leaf.key.bytes().as_ref(), key, depth + idx);
-        }
         idx
     }
+
+    /// Appends the prefix of this node to the buffer.
+    fn write_prefix(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.partial.bytes());
+    }
+
+    /// Compares the given key from the given depth to the prefix of this node. Keys that end within
+    /// the prefix compare as less.
+    fn compare_prefix(&self, key: &[u8], depth: usize) -> Ordering {
+        let rest = key.get(depth..).unwrap_or_default();
+        let prefix = self.partial.bytes();
+        let len = min(rest.len(), prefix.len());
+        rest[..len]
+            .cmp(&prefix[..len])
+            .then(if rest.len() < prefix.len() {
+                Ordering::Less
+            } else {
+                Ordering::Equal
+            })
+    }
 }
 
-#[derive(Debug)]
-enum InnerIndices<K, V, const P: usize> {
-    Node4(Indices4<Box<Node<K, V, P>>>),
-    Node16(Indices16<Box<Node<K, V, P>>>),
-    Node48(Indices48<Box<Node<K, V, P>>>),
-    Node256(Indices256<Box<Node<K, V, P>>>),
+enum InnerIndices<
+    K,
+    V,
+    const P: usize,
+    S: NodeStore<K, V, P, C4, C16, C48>,
+    const C4: usize,
+    const C16: usize,
+    const C48: usize,
+> {
+    Node4(Indices4<S::Ptr, C4>),
+    Node16(Indices16<S::Ptr, C16>),
+    Node48(Box<Indices48<S::Ptr, C48>>),
+    Node256(Box<Indices256<S::Ptr>>),
 }
 
-impl<K, V, const P: usize> InnerIndices<K, V, P> {
+impl<
+        K,
+        V,
+        const P: usize,
+        const C4: usize,
+        const C16: usize,
+        const C48: usize,
+        S: NodeStore<K, V, P, C4, C16, C48>,
+    > InnerIndices<K, V, P, S, C4, C16, C48>
+{
+    /// Returns an iterator over the children and their byte keys in key order.
+    fn children(&self) -> Children<'_, K, V, P, S, C4, C16, C48> {
+        match self {
+            Self::Node4(indices) => Children::Node4(indices.into_iter()),
+            Self::Node16(indices) => Children::Node16(indices.into_iter()),
+            Self::Node48(indices) => Children::Node48(indices.as_ref().into_iter()),
+            Self::Node256(indices) => Children::Node256(indices.as_ref().into_iter()),
+        }
+    }
+
+    /// Calls the closure on every child pointer in key order.
+    fn for_each_child_mut<'a, F>(&'a mut self, mut f: F)
+    where
+        F: FnMut(&'a mut S::Ptr),
+    {
+        match self {
+            Self::Node4(indices) => indices.for_each_child_mut(|_, child| f(child)),
+            Self::Node16(indices) => indices.for_each_child_mut(|_, child| f(child)),
+            Self::Node48(indices) => indices.for_each_child_mut(|_, child| f(child)),
+            Self::Node256(indices) => indices.for_each_child_mut(|_, child| f(child)),
+        }
+    }
+
     fn min_leaf_recursive(&self) -> Option<&Leaf<K, V>> {
         match self {
-            Self::Node4(indices) => indices.min().map(Box::as_ref),
-            Self::Node16(indices) => indices.min().map(Box::as_ref),
-            Self::Node48(indices) => indices.min().map(Box::as_ref),
-            Self::Node256(indices) => indices.min().map(Box::as_ref),
+            Self::Node4(indices) => indices.min().map(Deref::deref),
+            Self::Node16(indices) => indices.min().map(Deref::deref),
+            Self::Node48(indices) => indices.min().map(Deref::deref),
+            Self::Node256(indices) => indices.min().map(Deref::deref),
         }
         .and_then(|child| match child {
             Node::Leaf(leaf) => Some(leaf),
@@ -494,10 +1921,10 @@ impl<K, V, const P: usize> InnerIndices<K, V, P> {
 
     fn max_leaf_recursive(&self) -> Option<&Leaf<K, V>> {
         match self {
-            Self::Node4(indices) => indices.max().map(Box::as_ref),
-            Self::Node16(indices) => indices.max().map(Box::as_ref),
-            Self::Node48(indices) => indices.max().map(Box::as_ref),
-            Self::Node256(indices) => indices.max().map(Box::as_ref),
+            Self::Node4(indices) => indices.max().map(Deref::deref),
+            Self::Node16(indices) => indices.max().map(Deref::deref),
+            Self::Node48(indices) => indices.max().map(Deref::deref),
+            Self::Node256(indices) => indices.max().map(Deref::deref),
         }
         .and_then(|child| match child {
             Node::Leaf(leaf) => Some(leaf),
@@ -506,54 +1933,573 @@ impl<K, V, const P: usize> InnerIndices<K, V, P> {
     }
 }
 
-/// A partial key is used to support path compression. Only a part of the prefix that matches the
-/// original key is stored in the inner node.
+/// An iterator over the children of an inner node, regardless of the type of its indices.
+enum Children<
+    'a,
+    K,
+    V,
+    const P: usize,
+    S: NodeStore<K, V, P, C4, C16, C48>,
+    const C4: usize,
+    const C16: usize,
+    const C48: usize,
+> {
+    Node4(<&'a Indices4<S::Ptr, C4> as IntoIterator>::IntoIter),
+    Node16(<&'a Indices16<S::Ptr, C16> as IntoIterator>::IntoIter),
+    Node48(<&'a Indices48<S::Ptr, C48> as IntoIterator>::IntoIter),
+    Node256(<&'a Indices256<S::Ptr> as IntoIterator>::IntoIter),
+}
+
+impl<'a, K, V, const P: usize, const C4: usize, const C16: usize, const C48: usize, S> Iterator
+    for Children<'a, K, V, P, S, C4, C16, C48>
+where
+    K: 'a,
+    V: 'a,
+    S: NodeStore<K, V, P, C4, C16, C48> + 'a,
+{
+    type Item = (u8, &'a Node<K, V, P, S, C4, C16, C48>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Node4(iter) => iter.next(),
+            Self::Node16(iter) => iter.next(),
+            Self::Node48(iter) => iter.next(),
+            Self::Node256(iter) => iter.next(),
+        }
+        .map(|(key, child)| (key, &**child))
+    }
+}
+
+/// A partial key is used to support path compression. Prefixes of at most `N` bytes are stored
+/// inline in the inner node. A longer prefix stands for a chain of single-child levels, which is
+/// stored as a whole on the heap, so that it never has to be recovered from a leaf below the node.
 #[derive(Debug, Clone)]
 struct PartialKey<const N: usize> {
-    /// The length of the prefix that matches the original key, which can be longer than the length
-    /// of the data array.
+    /// The length of the prefix that matches the original key.
     len: usize,
-    /// The data array that holds the partial prefix.
+    /// The data array that holds the first `N` bytes of the prefix.
     data: [u8; N],
+    /// The whole prefix if it is longer than the data array.
+    chain: Option<Box<[u8]>>,
 }
 
 impl<const N: usize> PartialKey<N> {
-    /// Creates a new partial key from the given key and prefix length. We only copy at most N
-    /// bytes from the key to fill the data array.
+    /// Creates a new partial key from the first `len` bytes of the given key. Bytes beyond the
+    /// first N are only copied if the prefix does not fit into the data array.
     fn new(key: &[u8], len: usize) -> Self {
         let partial_len = min(N, len);
         let mut data = [0; N];
         data[..partial_len].copy_from_slice(&key[..partial_len]);
-        Self { len, data }
+        let chain = (len > N).then(|| key[..len].into());
+        Self { len, data, chain }
     }
 
-    /// Pushes a single byte into the partial key. If the data array is full, then the byte will
-    /// not be written into it. In that case, only the length will be incremented.
-    fn push(&mut self, char: u8) {
-        if self.len < N {
-            self.data[self.len] = char;
+    /// Returns the bytes of the prefix.
+    fn bytes(&self) -> &[u8] {
+        match &self.chain {
+            Some(chain) => chain,
+            None => &self.data[..self.len],
         }
-        self.len += 1;
     }
 
-    /// Appends the data from another partial key into this one. We only copy enough bytes to fill
-    /// the data array. The length will be incremented by the length of the other partial key.
+    /// Pushes a single byte into the partial key.
+    fn push(&mut self, char: u8) {
+        self.extend(&[char]);
+    }
+
+    /// Appends the prefix of another partial key to this one.
     fn append(&mut self, other: &Self) {
-        if self.len < N {
-            let len = min(other.len, N - self.len);
-            self.data[self.len..self.len + len].copy_from_slice(&other.data[..len]);
+        self.extend(other.bytes());
+    }
+
+    /// Appends the given bytes to the prefix, moving it into a chain once it outgrows the data
+    /// array.
+    fn extend(&mut self, bytes: &[u8]) {
+        let len = self.len + bytes.len();
+        if len > N {
+            let mut chain = Vec::with_capacity(len);
+            chain.extend_from_slice(self.bytes());
+            chain.extend_from_slice(bytes);
+            *self = Self::new(&chain, len);
+        } else {
+            self.data[self.len..len].copy_from_slice(bytes);
+            self.len = len;
         }
-        self.len += other.len;
     }
 
-    /// Returns true if the partial key matches the given key. We only check at most N bytes.
+    /// Returns the bytes of the partial key for logging.
+    #[cfg(feature = "tracing")]
+    fn redacted(&self) -> Redacted<'_> {
+        Redacted(self.bytes())
+    }
+
+    /// Returns true if the partial key matches the given key.
     fn match_key(&self, key: &[u8], depth: usize) -> bool {
-        let partial_len = min(N, self.len);
-        self.data[..partial_len]
+        let prefix = self.bytes();
+        prefix
             .iter()
             .zip(key[depth..].iter())
-            .take_while(|(x, y)| x.eq(y))
+            .take_while(|(x, y)| {
+                count!(prefix_bytes, 1);
+                x.eq(y)
+            })
             .count()
-            .eq(&partial_len)
+            .eq(&prefix.len())
+    }
+}
+
+/// Shows at most the first two bytes of a key in hex, so that logs do not reveal whole keys.
+#[cfg(feature = "tracing")]
+struct Redacted<'a>(&'a [u8]);
+
+#[cfg(feature = "tracing")]
+impl std::fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for byte in self.0.iter().take(2) {
+            write!(f, "{byte:02x}")?;
+        }
+        if self.0.len() > 2 {
+            write!(f, "..")?;
+        }
+        Ok(())
+    }
+}
+
+/// A position in a tree, given by the inner nodes on the path from the root to a leaf together with
+/// the byte keys of the children that were taken. Moving to a neighbouring leaf only walks up to
+/// the closest common ancestor and back down, so moving through all leaves takes amortized constant
+/// time per leaf. A path that is not on a leaf is past the end of the tree, and moving it forward
+/// or backward wraps around to the first or last leaf.
+pub struct Path<
+    'a,
+    K,
+    V,
+    const P: usize,
+    S: NodeStore<K, V, P, C4, C16, C48>,
+    const C4: usize = 4,
+    const C16: usize = 16,
+    const C48: usize = 48,
+> {
+    root: Option<&'a Node<K, V, P, S, C4, C16, C48>>,
+    stack: Vec<(&'a Inner<K, V, P, S, C4, C16, C48>, u8)>,
+    leaf: Option<&'a Leaf<K, V>>,
+}
+
+impl<'a, K, V, const P: usize, const C4: usize, const C16: usize, const C48: usize, S>
+    Path<'a, K, V, P, S, C4, C16, C48>
+where
+    K: 'a,
+    V: 'a,
+    S: NodeStore<K, V, P, C4, C16, C48> + 'a,
+{
+    /// Creates a path past the end of the tree with the given root.
+    pub const fn new(root: Option<&'a Node<K, V, P, S, C4, C16, C48>>) -> Self {
+        Self {
+            root,
+            stack: Vec::new(),
+            leaf: None,
+        }
+    }
+
+    /// Returns the leaf the path ends at.
+    pub const fn leaf(&self) -> Option<&'a Leaf<K, V>> {
+        self.leaf
+    }
+
+    /// Returns the number of inner nodes above the leaf the path ends at.
+    pub const fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Returns the byte key under which the leaf the path ends at is stored in its parent, or
+    /// `None` if the leaf is the root.
+    pub fn byte_key(&self) -> Option<u8> {
+        self.stack.last().map(|(_, byte_key)| *byte_key)
+    }
+
+    /// Folds the leaf the path ends at and every leaf after it in key order, by recursing into the
+    /// children after each byte key on the path instead of moving the path from leaf to leaf.
+    pub fn fold_rest<B, F>(self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, &'a Leaf<K, V>) -> B,
+    {
+        let Some(leaf) = self.leaf else {
+            return init;
+        };
+        let mut acc = f(init, leaf);
+        for (inner, byte_key) in self.stack.into_iter().rev() {
+            for (_, child) in inner
+                .indices
+                .children()
+                .skip_while(|(key, _)| *key <= byte_key)
+            {
+                acc = child.fold_leaves(acc, &mut f);
+            }
+        }
+        acc
+    }
+
+    /// Moves to the minimum leaf.
+    pub fn seek_first(&mut self) {
+        self.stack.clear();
+        self.leaf = None;
+        if let Some(root) = self.root {
+            self.descend_min(root);
+        }
+    }
+
+    /// Moves to the maximum leaf.
+    pub fn seek_last(&mut self) {
+        self.stack.clear();
+        self.leaf = None;
+        if let Some(root) = self.root {
+            self.descend_max(root);
+        }
+    }
+
+    /// Moves to the next leaf in key order.
+    pub fn next(&mut self) {
+        if self.leaf.is_none() {
+            return self.seek_first();
+        }
+        self.leaf = None;
+        self.ascend_next();
+    }
+
+    /// Moves to the previous leaf in key order.
+    pub fn prev(&mut self) {
+        if self.leaf.is_none() {
+            return self.seek_last();
+        }
+        self.leaf = None;
+        self.ascend_prev();
+    }
+
+    /// Moves to the minimum leaf of the subtree after the deepest node on the path that has one.
+    fn ascend_next(&mut self) {
+        while let Some((inner, key)) = self.stack.pop() {
+            if let Some((key, child)) = inner.child_after(Some(key)) {
+                self.stack.push((inner, key));
+                return self.descend_min(child);
+            }
+        }
+    }
+
+    /// Moves to the maximum leaf of the subtree before the deepest node on the path that has one.
+    fn ascend_prev(&mut self) {
+        while let Some((inner, key)) = self.stack.pop() {
+            if let Some((key, child)) = inner.child_before(Some(key)) {
+                self.stack.push((inner, key));
+                return self.descend_max(child);
+            }
+        }
+    }
+
+    fn descend_min(&mut self, mut node: &'a Node<K, V, P, S, C4, C16, C48>) {
+        loop {
+            match node {
+                Node::Leaf(leaf) => {
+                    self.leaf = Some(leaf);
+                    return;
+                }
+                Node::Inner(inner) => {
+                    let Some((key, child)) = inner.child_after(None) else {
+                        unreachable!("an inner node must have children")
+                    };
+                    self.stack.push((inner, key));
+                    node = child;
+                }
+            }
+        }
+    }
+
+    fn descend_max(&mut self, mut node: &'a Node<K, V, P, S, C4, C16, C48>) {
+        loop {
+            match node {
+                Node::Leaf(leaf) => {
+                    self.leaf = Some(leaf);
+                    return;
+                }
+                Node::Inner(inner) => {
+                    let Some((key, child)) = inner.child_before(None) else {
+                        unreachable!("an inner node must have children")
+                    };
+                    self.stack.push((inner, key));
+                    node = child;
+                }
+            }
+        }
+    }
+}
+
+impl<'a, K, V, const P: usize, const C4: usize, const C16: usize, const C48: usize, S>
+    Path<'a, K, V, P, S, C4, C16, C48>
+where
+    K: BytesComparable + 'a,
+    V: 'a,
+    S: NodeStore<K, V, P, C4, C16, C48> + 'a,
+{
+    /// Writes the key of the leaf the path ends at into the buffer, rebuilt from the prefixes and
+    /// byte keys along the path. Leaves are created as soon as their key is unique, so the bytes
+    /// after the last inner node can only be taken from the leaf itself, and so can the parts of
+    /// prefixes that are too long to be stored in their node.
+    pub fn rebuild_key(&self, buf: &mut Vec<u8>) {
+        buf.clear();
+        let Some(leaf) = self.leaf else {
+            return;
+        };
+        for (inner, byte_key) in &self.stack {
+            inner.write_prefix(buf);
+            buf.push(*byte_key);
+        }
+        let key = leaf.key.bytes();
+        let key = key.as_ref();
+        if key.len() < buf.len() {
+            // The key ends right before the last byte key on the path, which is the terminator.
+            buf.truncate(key.len());
+        } else {
+            buf.extend_from_slice(&key[buf.len()..]);
+        }
+    }
+
+    /// Moves to the minimum leaf whose key is greater than or equal to the given key.
+    pub fn seek(&mut self, key: &[u8]) {
+        self.stack.clear();
+        self.leaf = None;
+        let Some(mut node) = self.root else {
+            return;
+        };
+        let mut depth = 0;
+        loop {
+            let inner = match node {
+                Node::Leaf(leaf) => {
+                    if leaf.key.bytes().as_ref() >= key {
+                        self.leaf = Some(leaf);
+                    } else {
+                        self.ascend_next();
+                    }
+                    return;
+                }
+                Node::Inner(inner) => inner,
+            };
+            match inner.compare_prefix(key, depth) {
+                // Every key in the subtree is greater than the given key.
+                Ordering::Less => return self.descend_min(node),
+                // Every key in the subtree is less than the given key.
+                Ordering::Greater => return self.ascend_next(),
+                Ordering::Equal => {}
+            }
+            depth += inner.partial.len;
+            if key.len() <= depth {
+                return self.descend_min(node);
+            }
+            let byte_key = key[depth];
+            if let Some(child) = inner.child_ref(byte_key) {
+                self.stack.push((inner, byte_key));
+                node = child;
+                depth += 1;
+                continue;
+            }
+            if let Some((byte_key, child)) = inner.child_after(Some(byte_key)) {
+                self.stack.push((inner, byte_key));
+                self.descend_min(child);
+            } else {
+                self.ascend_next();
+            }
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, ops::Deref};
+
+    use super::{sealed, InnerIndices, Node, NodeStore, Owned};
+
+    thread_local! {
+        static LIVE: Cell<isize> = const { Cell::new(0) };
+    }
+
+    /// A store that keeps track of the number of live children, to check that the algorithms only
+    /// go through the store.
+    enum Counted {}
+
+    impl sealed::Sealed for Counted {}
+
+    struct Tracked<T>(Option<Box<T>>);
+
+    impl<T> Deref for Tracked<T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            self.0.as_ref().expect("pointer must not be taken")
+        }
+    }
+
+    impl<T> Drop for Tracked<T> {
+        fn drop(&mut self) {
+            if self.0.is_some() {
+                LIVE.with(|live| live.set(live.get() - 1));
+            }
+        }
+    }
+
+    impl<K, V, const P: usize, const C4: usize, const C16: usize, const C48: usize>
+        NodeStore<K, V, P, C4, C16, C48> for Counted
+    {
+        type Ptr = Tracked<Node<K, V, P, Self, C4, C16, C48>>;
+
+        type Arena = ();
+
+        fn alloc((): (), node: Node<K, V, P, Self, C4, C16, C48>) -> Self::Ptr {
+            LIVE.with(|live| live.set(live.get() + 1));
+            Tracked(Some(Box::new(node)))
+        }
+
+        fn take(mut ptr: Self::Ptr) -> Node<K, V, P, Self, C4, C16, C48> {
+            LIVE.with(|live| live.set(live.get() - 1));
+            *ptr.0.take().expect("pointer must not be taken")
+        }
+
+        fn get_mut(ptr: &mut Self::Ptr) -> &mut Node<K, V, P, Self, C4, C16, C48> {
+            ptr.0.as_mut().expect("pointer must not be taken")
+        }
+    }
+
+    #[test]
+    fn test_custom_node_store() {
+        let mut root: Option<Node<u32, u32, 4, Counted>> = None;
+        for key in 0..10_000u32 {
+            Node::insert_root(&mut root, key.wrapping_mul(2_654_435_761), key, true);
+        }
+        assert!(LIVE.with(Cell::get) >= 10_000);
+        for key in 0..10_000u32 {
+            let bytes = key.wrapping_mul(2_654_435_761).to_be_bytes();
+            let found = root.as_ref().and_then(|root| root.search(&bytes, 0));
+            assert_eq!(found.map(|leaf| leaf.value), Some(key));
+        }
+        for key in 0..5000u32 {
+            let bytes = key.wrapping_mul(2_654_435_761).to_be_bytes();
+            let deleted = Node::delete_root(&mut root, &bytes, true);
+            assert_eq!(deleted.map(|leaf| leaf.value), Some(key));
+        }
+        drop(root);
+        assert_eq!(LIVE.with(Cell::get), 0);
+    }
+
+    #[test]
+    fn test_shrink_thresholds() {
+        fn kind(root: Option<&Node<u8, u8, 8, Owned>>) -> &'static str {
+            match root {
+                Some(Node::Inner(inner)) => match inner.indices {
+                    InnerIndices::Node4(_) => "node4",
+                    InnerIndices::Node16(_) => "node16",
+                    InnerIndices::Node48(_) => "node48",
+                    InnerIndices::Node256(_) => "node256",
+                },
+                _ => "leaf",
+            }
+        }
+
+        let mut root: Option<Node<u8, u8, 8, Owned>> = None;
+        for key in 0..=255u8 {
+            Node::insert_root(&mut root, key, key, true);
+        }
+        assert_eq!(kind(root.as_ref()), "node256");
+        // The thresholds for the default capacities are 37, 12, 3 and 1 children.
+        for (len, expected) in [
+            (38u8, "node256"),
+            (37, "node48"),
+            (13, "node48"),
+            (12, "node16"),
+        ]
+        .into_iter()
+        .chain([(4, "node16"), (3, "node4"), (2, "node4"), (1, "leaf")])
+        {
+            // Keys are deleted from the largest down, so the remaining keys are `0..len`.
+            for key in (len..=255).rev() {
+                Node::delete_root(&mut root, &[key], true);
+            }
+            assert_eq!(kind(root.as_ref()), expected, "{len} children");
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_events() {
+        use std::{
+            fmt::Write,
+            sync::{Arc, Mutex},
+        };
+
+        use tracing::{
+            field::{Field, Visit},
+            span, Event, Metadata, Subscriber,
+        };
+
+        /// Collects the message and the prefix of every event.
+        struct Collector(Arc<Mutex<Vec<String>>>);
+
+        struct Fields(String);
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                if matches!(field.name(), "message" | "prefix") {
+                    write!(self.0, "{value:?} ").unwrap();
+                }
+            }
+        }
+
+        impl Subscriber for Collector {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+                span::Id::from_u64(1)
+            }
+
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields(String::new());
+                event.record(&mut fields);
+                self.0.lock().unwrap().push(fields.0.trim_end().to_string());
+            }
+
+            fn enter(&self, _: &span::Id) {}
+
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        tracing::subscriber::with_default(Collector(Arc::clone(&events)), || {
+            let mut root: Option<Node<Vec<u8>, (), 10, Owned>> = None;
+            for byte in 0..5 {
+                Node::insert_root(&mut root, vec![0xab, 0xcd, 0xef, byte], (), true);
+            }
+            Node::insert_root(&mut root, vec![0xab, 0x00], (), true);
+        });
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["leaf split abcd..", "node grown abcd..", "prefix split ab",]
+        );
+    }
+
+    #[cfg(feature = "check-invariants")]
+    #[test]
+    #[should_panic(expected = "prefix of node4 at depth 0 disagrees with its leaves")]
+    fn test_invariant_violation() {
+        let mut root: Option<Node<Vec<u8>, (), 10, Owned>> = None;
+        Node::insert_root(&mut root, b"abc".to_vec(), (), true);
+        Node::insert_root(&mut root, b"abd".to_vec(), (), true);
+        let Some(Node::Inner(inner)) = &mut root else {
+            unreachable!("two keys must be under an inner node");
+        };
+        inner.partial.data[0] = b'x';
+        Node::insert_root(&mut root, b"xbe".to_vec(), (), true);
     }
 }
@@ -0,0 +1,368 @@
+//! A tree that notifies observers of its changes.
+//!
+//! Observers are called synchronously for every insert, overwrite, and delete, while the tree is
+//! still borrowed by the writer. Inserts are reported right before the tree is modified and deletes
+//! right after, so an observer always sees the affected key and values without any copies. This
+//! lets caches and secondary indexes that are kept next to the tree apply the same change without
+//! diffing versions of it.
+//!
+//! Subscribers that only care about a namespace of keys watch a prefix with
+//! [`ObservedArt::watch_prefix`]. The watched prefixes are kept in a tree of their own, and the
+//! watchers of a change are found by a single descent along its key, the same way as a lookup, so
+//! the cost of a change does not grow with the number of unrelated watchers.
+
+use std::{
+    borrow::Borrow,
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+use crate::{BytesComparable, ART};
+
+/// The kind of a change made to an [`ObservedArt`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    /// A key that was not in the tree was inserted.
+    Insert,
+    /// The value of a key that was already in the tree was replaced.
+    Overwrite,
+    /// A key was deleted from the tree.
+    Delete,
+}
+
+/// A change made to an [`ObservedArt`], as seen by its observers.
+#[derive(Debug, Clone, Copy)]
+pub struct Change<'a, K, V> {
+    /// The kind of the change.
+    pub kind: ChangeKind,
+    /// The affected key.
+    pub key: &'a K,
+    /// The value associated with the key before the change, which is `None` for inserts.
+    pub old: Option<&'a V>,
+    /// The value associated with the key after the change, which is `None` for deletes.
+    pub new: Option<&'a V>,
+}
+
+/// An event sent to subscribers, made of the kind of a change and a copy of the affected key.
+pub type Event<K> = (ChangeKind, K);
+
+/// A handle to an observer registered on an [`ObservedArt`], used to remove it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+type Observer<K, V> = Box<dyn FnMut(&Change<'_, K, V>) + Send + Sync>;
+
+/// Sends a change to a subscriber watching a prefix, returning false if it hung up.
+type Watcher<K> = Box<dyn Fn(ChangeKind, &K) -> bool + Send + Sync>;
+
+/// An adaptive radix tree that calls the registered observers on every change.
+pub struct ObservedArt<K, V, const N: usize = 10> {
+    tree: ART<K, V, N>,
+    observers: Vec<(ObserverId, Observer<K, V>)>,
+    /// The subscribers watching each prefix.
+    watchers: ART<Box<[u8]>, Vec<Watcher<K>>>,
+    next_id: u64,
+}
+
+impl<K, V, const N: usize> ObservedArt<K, V, N> {
+    /// Create an empty tree without observers.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::from_tree(ART::new())
+    }
+
+    /// Wrap an existing tree. Its current entries are not reported to observers.
+    #[must_use]
+    pub const fn from_tree(tree: ART<K, V, N>) -> Self {
+        Self {
+            tree,
+            observers: Vec::new(),
+            watchers: ART::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Return the observed tree for reading.
+    #[must_use]
+    pub const fn tree(&self) -> &ART<K, V, N> {
+        &self.tree
+    }
+
+    /// Remove all observers and return the observed tree.
+    #[must_use]
+    pub fn into_tree(self) -> ART<K, V, N> {
+        self.tree
+    }
+
+    /// Register a closure that is called on every change, in the order the observers were added.
+    pub fn observe<F>(&mut self, observer: F) -> ObserverId
+    where
+        F: FnMut(&Change<'_, K, V>) + Send + Sync + 'static,
+    {
+        let id = ObserverId(self.next_id);
+        self.next_id += 1;
+        self.observers.push((id, Box::new(observer)));
+        id
+    }
+
+    /// Register a channel that receives the kind and a copy of the key of every change. Changes are
+    /// silently dropped once the receiver has hung up.
+    pub fn subscribe(&mut self, sender: Sender<Event<K>>) -> ObserverId
+    where
+        K: Clone + Send + 'static,
+    {
+        self.observe(move |change| {
+            // A receiver that hung up is not interested in changes anymore.
+            let _ = sender.send((change.kind, change.key.clone()));
+        })
+    }
+
+    /// Remove an observer, returning whether it was registered.
+    pub fn unobserve(&mut self, id: ObserverId) -> bool {
+        let len = self.observers.len();
+        self.observers.retain(|(observer_id, _)| *observer_id != id);
+        self.observers.len() != len
+    }
+
+    /// Calls the observers on the change. It takes the observers rather than the whole tree, so
+    /// that the change can borrow values from the tree.
+    fn notify(observers: &mut [(ObserverId, Observer<K, V>)], change: &Change<'_, K, V>) {
+        for (_, observer) in observers {
+            observer(change);
+        }
+    }
+}
+
+impl<K, V, const N: usize> Default for ObservedArt<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> std::fmt::Debug for ObservedArt<K, V, N>
+where
+    K: std::fmt::Debug,
+    V: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObservedArt")
+            .field("tree", &self.tree)
+            .field("observers", &self.observers.len())
+            .field("watchers", &self.watchers.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, V, const N: usize> ObservedArt<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(key)
+    }
+
+    /// Return a receiver of the events for every key that starts with the given encoded prefix. An
+    /// empty prefix watches every key. Events are silently dropped once the receiver has hung up.
+    pub fn watch_prefix(&mut self, prefix: &[u8]) -> Receiver<Event<K>>
+    where
+        K: Clone + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let mut watchers = self.watchers.delete(prefix).unwrap_or_default();
+        watchers.push(Box::new(move |kind, key: &K| {
+            sender.send((kind, key.clone())).is_ok()
+        }));
+        self.watchers.insert(prefix.into(), watchers);
+        receiver
+    }
+
+    /// Return the number of subscribers watching a prefix, including the ones that hung up since
+    /// the last change under their prefix.
+    #[must_use]
+    pub fn watchers(&self) -> usize {
+        self.watchers
+            .iter()
+            .map(|(_, watchers)| watchers.len())
+            .sum()
+    }
+
+    /// Sends the change to the subscribers watching a prefix of its key, and drops the ones that
+    /// hung up.
+    fn notify_watchers(&mut self, kind: ChangeKind, key: &K) {
+        let mut hung_up = Vec::new();
+        for (prefix, watchers) in self.watchers.prefixes_of(key.bytes().as_ref()) {
+            for (idx, watcher) in watchers.iter().enumerate() {
+                if !watcher(kind, key) {
+                    hung_up.push((prefix.clone(), idx));
+                }
+            }
+        }
+        // Indices are removed from the last one, so that the earlier ones stay valid.
+        for (prefix, idx) in hung_up.into_iter().rev() {
+            let mut watchers = self.watchers.delete(&*prefix).unwrap_or_default();
+            drop(watchers.remove(idx));
+            if !watchers.is_empty() {
+                self.watchers.insert(prefix, watchers);
+            }
+        }
+    }
+
+    /// Insert the given key-value pair into the tree, returning the value previously associated
+    /// with the key. Observers are notified before the tree is modified.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if !self.observers.is_empty() || !self.watchers.is_empty() {
+            let old = self.tree.search(&key);
+            let change = Change {
+                kind: if old.is_some() {
+                    ChangeKind::Overwrite
+                } else {
+                    ChangeKind::Insert
+                },
+                key: &key,
+                old,
+                new: Some(&value),
+            };
+            Self::notify(&mut self.observers, &change);
+            let kind = change.kind;
+            if !self.watchers.is_empty() {
+                self.notify_watchers(kind, &key);
+            }
+        }
+        self.tree.insert(key, value)
+    }
+
+    /// Delete the value associated with the given key. Observers are notified after the tree is
+    /// modified, if the key existed.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let leaf = self.tree.delete_leaf(key.bytes().as_ref())?;
+        Self::notify(
+            &mut self.observers,
+            &Change {
+                kind: ChangeKind::Delete,
+                key: &leaf.key,
+                old: Some(&leaf.value),
+                new: None,
+            },
+        );
+        if !self.watchers.is_empty() {
+            self.notify_watchers(ChangeKind::Delete, &leaf.key);
+        }
+        Some(leaf.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc, Mutex};
+
+    use super::{ChangeKind, ObservedArt};
+
+    #[test]
+    fn test_observers_see_changes() {
+        let mut tree = ObservedArt::<String, u32>::new();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let id = tree.observe({
+            let log = Arc::clone(&log);
+            move |change| {
+                log.lock().unwrap().push((
+                    change.kind,
+                    change.key.clone(),
+                    change.old.copied(),
+                    change.new.copied(),
+                ));
+            }
+        });
+        assert_eq!(tree.insert("a".to_string(), 1), None);
+        assert_eq!(tree.insert("a".to_string(), 2), Some(1));
+        assert_eq!(tree.delete("b"), None);
+        assert_eq!(tree.delete("a"), Some(2));
+        assert!(tree.unobserve(id));
+        assert!(!tree.unobserve(id));
+        tree.insert("c".to_string(), 3);
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                (ChangeKind::Insert, "a".to_string(), None, Some(1)),
+                (ChangeKind::Overwrite, "a".to_string(), Some(1), Some(2)),
+                (ChangeKind::Delete, "a".to_string(), Some(2), None),
+            ]
+        );
+        assert_eq!(tree.search("c"), Some(&3));
+    }
+
+    #[test]
+    fn test_subscribe_with_channel() {
+        let mut tree = ObservedArt::<u32, u32>::default();
+        let (sender, receiver) = mpsc::channel();
+        tree.subscribe(sender);
+        tree.insert(7, 0);
+        tree.insert(7, 1);
+        tree.delete(&7);
+        drop(receiver);
+        // Changes after the receiver hung up are dropped.
+        tree.insert(8, 0);
+        let tree = tree.into_tree();
+        assert_eq!(tree.search(&8), Some(&0));
+
+        let mut tree = ObservedArt::from_tree(tree);
+        let (sender, receiver) = mpsc::channel();
+        tree.subscribe(sender);
+        tree.insert(9, 0);
+        tree.delete(&8);
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            [(ChangeKind::Insert, 9), (ChangeKind::Delete, 8)]
+        );
+    }
+
+    #[test]
+    fn test_watch_prefix() {
+        let mut tree = ObservedArt::<String, u32>::new();
+        let users = tree.watch_prefix(b"users/");
+        let everything = tree.watch_prefix(b"");
+        let admins = tree.watch_prefix(b"users/admin");
+        assert_eq!(tree.watchers(), 3);
+        tree.insert("users/alice".to_string(), 1);
+        tree.insert("groups/admins".to_string(), 2);
+        tree.insert("users/admin".to_string(), 3);
+        tree.insert("users/alice".to_string(), 4);
+        tree.delete("users/admin");
+        tree.delete("users/bob");
+        assert_eq!(
+            users.try_iter().collect::<Vec<_>>(),
+            [
+                (ChangeKind::Insert, "users/alice".to_string()),
+                (ChangeKind::Insert, "users/admin".to_string()),
+                (ChangeKind::Overwrite, "users/alice".to_string()),
+                (ChangeKind::Delete, "users/admin".to_string()),
+            ]
+        );
+        assert_eq!(
+            admins.try_iter().collect::<Vec<_>>(),
+            [
+                (ChangeKind::Insert, "users/admin".to_string()),
+                (ChangeKind::Delete, "users/admin".to_string()),
+            ]
+        );
+        assert_eq!(everything.try_iter().count(), 5);
+
+        // Watchers that hung up are dropped on the next change under their prefix.
+        drop(users);
+        drop(admins);
+        tree.insert("groups/users".to_string(), 5);
+        assert_eq!(tree.watchers(), 3);
+        tree.insert("users/carol".to_string(), 6);
+        assert_eq!(tree.watchers(), 2);
+        tree.insert("users/admin".to_string(), 7);
+        assert_eq!(tree.watchers(), 1);
+        assert_eq!(everything.try_iter().count(), 3);
+    }
+}
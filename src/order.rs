@@ -0,0 +1,305 @@
+//! A tree that orders its keys by a custom order of bytes.
+//!
+//! An [`ART`] orders its keys by comparing their encoded bytes as unsigned integers. Data that was
+//! sorted by another system may follow a different order, such as an alphabet where lowercase
+//! letters come before uppercase ones, or bytes obfuscated with a XOR mask. A [`ReorderedArt`]
+//! takes a [`ByteOrder`] at construction and places every key by the ranks of its bytes in that
+//! order, so its iteration and range queries follow the legacy order while the keys of the
+//! application keep their encoding.
+//!
+//! The ranks are encoded so that no placed key contains a zero byte: the ranks 0 and 1 become two
+//! bytes, `[1, 1]` and `[1, 2]`, and every other rank stays a single byte. The code is prefix free
+//! and keeps the order of the ranks, so the placed keys compare like the ranks of the original
+//! bytes, and unlike in an [`ART`], a key may be a prefix of another one followed by any byte.
+
+use std::{
+    borrow::Borrow,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{BytesComparable, ART};
+
+/// An error returned when an alphabet lists a byte more than once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuplicateByteError(pub u8);
+
+impl std::fmt::Display for DuplicateByteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "byte {:#04x} appears more than once in the alphabet",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for DuplicateByteError {}
+
+/// A total order of the 256 byte values, given by the rank of every byte.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ByteOrder {
+    rank: [u8; 256],
+}
+
+impl ByteOrder {
+    /// The natural order of bytes.
+    #[must_use]
+    pub fn natural() -> Self {
+        Self::xor(0)
+    }
+
+    /// The order of the bytes after applying the given XOR mask to them.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)] // The indices are bytes.
+    pub fn xor(mask: u8) -> Self {
+        let mut rank = [0; 256];
+        for (byte, rank) in rank.iter_mut().enumerate() {
+            *rank = byte as u8 ^ mask;
+        }
+        Self { rank }
+    }
+
+    /// The order where the bytes of the alphabet come first in the order they are listed, followed
+    /// by the other bytes in their natural order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the alphabet lists a byte more than once.
+    #[allow(clippy::cast_possible_truncation)] // There are at most 256 ranks.
+    pub fn alphabet(alphabet: &[u8]) -> Result<Self, DuplicateByteError> {
+        let mut ranked = [false; 256];
+        let mut order = Vec::with_capacity(256);
+        for &byte in alphabet {
+            if std::mem::replace(&mut ranked[usize::from(byte)], true) {
+                return Err(DuplicateByteError(byte));
+            }
+            order.push(byte);
+        }
+        order.extend((0..=u8::MAX).filter(|&byte| !ranked[usize::from(byte)]));
+        let mut rank = [0; 256];
+        for (idx, byte) in order.into_iter().enumerate() {
+            rank[usize::from(byte)] = idx as u8;
+        }
+        Ok(Self { rank })
+    }
+
+    /// Return the rank of the byte in this order.
+    #[must_use]
+    pub const fn rank(&self, byte: u8) -> u8 {
+        self.rank[byte as usize]
+    }
+
+    /// Append the bytes that place the given bytes in this order to the buffer, which compare
+    /// like the ranks of the bytes and contain no zero byte.
+    pub fn encode(&self, bytes: &[u8], buf: &mut Vec<u8>) {
+        for &byte in bytes {
+            match self.rank(byte) {
+                rank @ (0 | 1) => buf.extend_from_slice(&[1, rank + 1]),
+                rank => buf.push(rank),
+            }
+        }
+    }
+}
+
+impl Default for ByteOrder {
+    fn default() -> Self {
+        Self::natural()
+    }
+}
+
+/// A key stored with the bytes it is placed by in the tree.
+#[derive(Debug)]
+struct Ranked<K> {
+    key: K,
+    ranks: Box<[u8]>,
+}
+
+impl<K> BytesComparable for Ranked<K> {
+    type Target<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        &self.ranks
+    }
+}
+
+impl<K> Borrow<[u8]> for Ranked<K> {
+    fn borrow(&self) -> &[u8] {
+        &self.ranks
+    }
+}
+
+/// An adaptive radix tree whose keys are ordered by a custom order of bytes.
+#[derive(Debug)]
+pub struct ReorderedArt<K, V, const N: usize = 10> {
+    tree: ART<Ranked<K>, V, N>,
+    order: ByteOrder,
+}
+
+impl<K, V, const N: usize> ReorderedArt<K, V, N> {
+    /// Create an empty tree that orders its keys by the given order of bytes.
+    #[must_use]
+    pub const fn new(order: ByteOrder) -> Self {
+        Self {
+            tree: ART::new(),
+            order,
+        }
+    }
+
+    /// Return the order of bytes the keys are ordered by.
+    #[must_use]
+    pub const fn order(&self) -> &ByteOrder {
+        &self.order
+    }
+
+    /// Return the number of entries in the tree.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Return true if the tree holds no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Return an iterator over the entries in the order of the tree.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.tree.iter().map(|(k, v)| (&k.key, v))
+    }
+}
+
+impl<K, V, const N: usize> Default for ReorderedArt<K, V, N> {
+    fn default() -> Self {
+        Self::new(ByteOrder::default())
+    }
+}
+
+impl<K, V, const N: usize> ReorderedArt<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Return the bytes the given key is placed by in the tree.
+    fn encode<Q>(&self, key: &Q) -> Vec<u8>
+    where
+        Q: BytesComparable + ?Sized,
+    {
+        let bytes = key.bytes();
+        let mut ranks = Vec::with_capacity(bytes.as_ref().len());
+        self.order.encode(bytes.as_ref(), &mut ranks);
+        ranks
+    }
+
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(self.encode(key).as_slice())
+    }
+
+    /// Insert the given key-value pair into the tree, returning the value previously associated
+    /// with the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let ranks = self.encode(&key).into_boxed_slice();
+        self.tree.insert(Ranked { key, ranks }, value)
+    }
+
+    /// Delete the value associated with the given key.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let ranks = self.encode(key);
+        self.tree.delete(ranks.as_slice())
+    }
+
+    /// Call the closure on the key-value pairs within the given range in the order of the tree,
+    /// until the closure returns false. The bounds are compared in the order of the tree as well.
+    pub fn range_while<Q, R, F>(&self, range: R, mut f: F)
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        R: RangeBounds<Q>,
+        F: FnMut(&K, &V) -> bool,
+    {
+        let start = range.start_bound().map(|key| self.encode(key));
+        let end = range.end_bound().map(|key| self.encode(key));
+        let bounds: (Bound<&[u8]>, Bound<&[u8]>) = (
+            start.as_ref().map(Vec::as_slice),
+            end.as_ref().map(Vec::as_slice),
+        );
+        self.tree
+            .range_while::<[u8], _, _>(bounds, |k, v| f(&k.key, v));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cmp::Ordering, collections::BTreeMap};
+
+    use rand::Rng;
+
+    use super::{ByteOrder, DuplicateByteError, ReorderedArt};
+
+    #[test]
+    fn test_orders() {
+        let lower_first =
+            ByteOrder::alphabet(b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ").unwrap();
+        let tree: ReorderedArt<&str, ()> = {
+            let mut tree = ReorderedArt::new(lower_first);
+            for key in ["Banana", "apple", "Apple", "banana", "cherry", ""] {
+                tree.insert(key, ());
+            }
+            tree
+        };
+        let keys: Vec<_> = tree.iter().map(|(k, ())| *k).collect();
+        assert_eq!(keys, ["", "apple", "banana", "cherry", "Apple", "Banana"]);
+
+        let mut keys = Vec::new();
+        tree.range_while::<&str, _, _>("b".."A", |k, ()| {
+            keys.push(*k);
+            true
+        });
+        assert_eq!(keys, ["banana", "cherry"]);
+        assert_eq!(ByteOrder::alphabet(b"aba"), Err(DuplicateByteError(b'a')));
+    }
+
+    #[test]
+    fn test_matches_sorted() {
+        let mut rng = rand::thread_rng();
+        for order in [
+            ByteOrder::natural(),
+            ByteOrder::xor(0x5A),
+            ByteOrder::alphabet(&[7, 0, 255, 1]).unwrap(),
+        ] {
+            let mut tree = ReorderedArt::<Vec<u8>, usize>::new(order.clone());
+            let mut expected = BTreeMap::new();
+            for idx in 0..3000 {
+                // Any byte may follow a key, including the one ranked first.
+                let key: Vec<u8> = (0..rng.gen_range(0..4)).map(|_| rng.gen()).collect();
+                if rng.gen_bool(0.7) {
+                    assert_eq!(tree.insert(key.clone(), idx), expected.insert(key, idx));
+                } else {
+                    assert_eq!(tree.delete(&key), expected.remove(&key));
+                }
+            }
+            let compare = |a: &[u8], b: &[u8]| -> Ordering {
+                a.iter()
+                    .map(|&byte| order.rank(byte))
+                    .cmp(b.iter().map(|&byte| order.rank(byte)))
+            };
+            let mut sorted: Vec<_> = expected.iter().collect();
+            sorted.sort_by(|(a, _), (b, _)| compare(a, b));
+            assert!(tree.iter().eq(sorted));
+            for (key, value) in &expected {
+                assert_eq!(tree.search(key), Some(value));
+            }
+        }
+    }
+}
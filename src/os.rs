@@ -0,0 +1,179 @@
+//! Byte encodings of platform strings and file paths.
+//!
+//! [`OsStr`], [`OsString`], [`Path`], and [`PathBuf`] are encoded such that the same path is
+//! encoded into the same bytes by every version of the standard library, which allows a tree keyed
+//! by paths to be persisted and loaded again.
+//!
+//! - On Unix, a platform string is an arbitrary sequence of bytes, which is used as is.
+//! - On Windows, a platform string is a sequence of 16-bit code units that may contain unpaired
+//!   surrogates. It is encoded in WTF-8, which is UTF-8 extended to encode unpaired surrogates
+//!   like any other code point. Paths that are valid Unicode are therefore encoded exactly like
+//!   their UTF-8 form, and keys are ordered by code point rather than by UTF-16 code unit, which
+//!   only differs for characters above U+FFFF compared with characters from U+E000 to U+FFFF.
+//! - On other platforms, the encoding used internally by the standard library is used.
+//!
+//! No Unicode normalization is applied, so paths that differ in their normalization form are
+//! different keys, as they are for the file system on most platforms. Keys are ordered by their
+//! bytes, which differs from the order of [`Path`]: the latter compares paths component by
+//! component, so `a/b` sorts before `a-b` as a path, but after it as a key.
+
+use std::{
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+};
+
+use crate::BytesComparable;
+
+/// The encoded form of a platform string.
+#[cfg(not(windows))]
+type Encoded<'a> = &'a [u8];
+
+/// The encoded form of a platform string.
+#[cfg(windows)]
+type Encoded<'a> = Vec<u8>;
+
+#[cfg(unix)]
+fn encode(s: &OsStr) -> Encoded<'_> {
+    std::os::unix::ffi::OsStrExt::as_bytes(s)
+}
+
+#[cfg(windows)]
+#[allow(clippy::cast_possible_truncation)] // Every part is masked to fit into a byte.
+fn encode(s: &OsStr) -> Encoded<'_> {
+    use std::os::windows::ffi::OsStrExt;
+    let mut bytes = Vec::with_capacity(s.len());
+    for unit in char::decode_utf16(s.encode_wide()) {
+        match unit {
+            Ok(c) => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            Err(error) => {
+                // An unpaired surrogate is encoded like a code point in the same range would be.
+                let surrogate = error.unpaired_surrogate();
+                bytes.extend_from_slice(&[
+                    0xe0 | (surrogate >> 12) as u8,
+                    0x80 | ((surrogate >> 6) & 0x3f) as u8,
+                    0x80 | (surrogate & 0x3f) as u8,
+                ]);
+            }
+        }
+    }
+    bytes
+}
+
+#[cfg(not(any(unix, windows)))]
+fn encode(s: &OsStr) -> Encoded<'_> {
+    s.as_encoded_bytes()
+}
+
+impl BytesComparable for OsStr {
+    type Target<'a> = Encoded<'a>;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        encode(self)
+    }
+}
+
+impl BytesComparable for &OsStr {
+    type Target<'a>
+        = Encoded<'a>
+    where
+        Self: 'a;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        encode(self)
+    }
+}
+
+impl BytesComparable for OsString {
+    type Target<'a> = Encoded<'a>;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        encode(self)
+    }
+}
+
+impl BytesComparable for Path {
+    type Target<'a> = Encoded<'a>;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        encode(self.as_os_str())
+    }
+}
+
+impl BytesComparable for &Path {
+    type Target<'a>
+        = Encoded<'a>
+    where
+        Self: 'a;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        encode(self.as_os_str())
+    }
+}
+
+impl BytesComparable for PathBuf {
+    type Target<'a> = Encoded<'a>;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        encode(self.as_os_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::OsStr,
+        path::{Path, PathBuf},
+    };
+
+    use crate::{BytesComparable, ART};
+
+    #[test]
+    fn test_paths_as_keys() {
+        let mut tree = ART::<PathBuf, usize>::new();
+        let paths = [
+            "/usr/bin/env",
+            "/usr/bin",
+            "/usr/lib/libc.so",
+            "/etc/hosts",
+            "a-b",
+            "a/b",
+        ];
+        for (idx, path) in paths.iter().enumerate() {
+            tree.insert(PathBuf::from(path), idx);
+        }
+        for (idx, path) in paths.iter().enumerate() {
+            assert_eq!(tree.search(Path::new(path)), Some(&idx));
+        }
+        // Keys are ordered by their bytes rather than by their components.
+        let keys: Vec<_> = tree.iter().map(|(k, _)| k.to_str().unwrap()).collect();
+        assert_eq!(
+            keys,
+            [
+                "/etc/hosts",
+                "/usr/bin",
+                "/usr/bin/env",
+                "/usr/lib/libc.so",
+                "a-b",
+                "a/b"
+            ]
+        );
+        assert_eq!(Path::new("é").bytes()[..], "é".as_bytes()[..]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_paths() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut tree = ART::<&OsStr, usize>::new();
+        let names: [&[u8]; 4] = [b"caf\xe9", b"caf\xc3\xa9", b"cafe", b"\xff"];
+        for (idx, name) in names.iter().enumerate() {
+            tree.insert(OsStr::from_bytes(name), idx);
+        }
+        let keys: Vec<_> = tree.iter().map(|(k, _)| k.as_bytes()).collect();
+        let mut sorted = names.to_vec();
+        sorted.sort_unstable();
+        assert_eq!(keys, sorted);
+        assert_eq!(tree.search(&OsStr::from_bytes(b"caf\xe9")), Some(&0));
+    }
+}
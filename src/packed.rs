@@ -0,0 +1,242 @@
+//! A tree whose leaves hold several entries.
+//!
+//! Every leaf of an [`ART`] holds a single entry, so a sparse key space needs an inner node for
+//! almost every pair of keys, and every lookup chases a pointer per level down to a single entry.
+//! A [`PackedArt`] packs up to `B` consecutive entries into a bucket, a sorted array stored as the
+//! value of a single leaf, in the spirit of the leaves of HOT and of B+ trees. The tree only
+//! indexes the first key of every bucket, so it holds about `B / 2` to `B` times fewer leaves and
+//! inner nodes. A lookup descends to the bucket whose first key is the greatest one not greater
+//! than the searched key and finishes with a binary search within the bucket.
+//!
+//! A bucket that grows beyond `B` entries is split in two halves. A bucket that loses its first
+//! entry is indexed again by its new first key, and a bucket that loses all of its entries is
+//! removed. Buckets are never merged, so a tree that shrank a lot may hold many small buckets.
+
+use std::{borrow::Borrow, cmp::Ordering};
+
+use crate::{BytesComparable, ART};
+
+/// Consecutive entries sorted by their encoded keys.
+type Bucket<K, V> = Vec<(K, V)>;
+
+/// An adaptive radix tree that packs up to `B` entries into each of its leaves.
+#[derive(Debug)]
+pub struct PackedArt<K, V, const B: usize = 8> {
+    /// The buckets of entries sorted by their encoded keys, indexed by the encoded key of their
+    /// first entry.
+    tree: ART<Box<[u8]>, Bucket<K, V>>,
+    len: usize,
+}
+
+impl<K, V, const B: usize> PackedArt<K, V, B> {
+    /// Asserts at compile time that a bucket can be split.
+    const VALID_CAPACITY: () = assert!(B >= 2, "a bucket must hold at least two entries");
+
+    /// Create an empty tree.
+    #[must_use]
+    pub const fn new() -> Self {
+        let () = Self::VALID_CAPACITY;
+        Self {
+            tree: ART::new(),
+            len: 0,
+        }
+    }
+
+    /// Return the number of entries in the tree.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the tree holds no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the number of buckets, which is the number of leaves of the underlying tree.
+    #[must_use]
+    pub const fn buckets(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Return an iterator over the entries in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.tree
+            .iter()
+            .flat_map(|(_, bucket)| bucket.iter().map(|(k, v)| (k, v)))
+    }
+}
+
+impl<K, V, const B: usize> Default for PackedArt<K, V, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const B: usize> PackedArt<K, V, B>
+where
+    K: BytesComparable,
+{
+    /// Return the key and the entries of the bucket that would hold the given encoded key, which
+    /// is the last bucket whose first key is not greater than the key.
+    fn bucket(&self, key: &[u8]) -> Option<(&[u8], &Bucket<K, V>)> {
+        let mut cursor = self.tree.cursor_at(key);
+        if cursor.key().is_none_or(|first| **first != *key) {
+            cursor.move_prev();
+        }
+        let (first, bucket) = cursor.key_value()?;
+        // Moving back from the first bucket wraps around to the last one.
+        (**first <= *key).then_some((&**first, bucket))
+    }
+
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let key = key.bytes();
+        let (_, bucket) = self.bucket(key.as_ref())?;
+        let idx = position(bucket, key.as_ref()).ok()?;
+        Some(&bucket[idx].1)
+    }
+
+    /// Insert the given key-value pair into the tree, returning the value previously associated
+    /// with the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let bytes = key.bytes().as_ref().to_vec();
+        let first = if let Some((first, _)) = self.bucket(&bytes) {
+            first.to_vec()
+        } else if let Some((first, _)) = self.tree.min() {
+            // The key goes before every bucket, so it becomes the first key of the first one.
+            first.to_vec()
+        } else {
+            self.tree.insert(bytes.into(), vec![(key, value)]);
+            self.len += 1;
+            return None;
+        };
+        let bucket = bucket_mut(&mut self.tree, &first);
+        let idx = match position(bucket, &bytes) {
+            Ok(idx) => return Some(std::mem::replace(&mut bucket[idx].1, value)),
+            Err(idx) => idx,
+        };
+        bucket.insert(idx, (key, value));
+        self.len += 1;
+        let upper = (bucket.len() > B).then(|| bucket.split_off(bucket.len() / 2));
+        if idx == 0 {
+            self.rekey(&first);
+        }
+        if let Some(upper) = upper {
+            let first = upper[0].0.bytes().as_ref().into();
+            self.tree.insert(first, upper);
+        }
+        None
+    }
+
+    /// Delete the value associated with the given key.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let key = key.bytes();
+        let (first, bucket) = self.bucket(key.as_ref())?;
+        let idx = position(bucket, key.as_ref()).ok()?;
+        let first = first.to_vec();
+        let bucket = bucket_mut(&mut self.tree, &first);
+        let (_, value) = bucket.remove(idx);
+        self.len -= 1;
+        if bucket.is_empty() {
+            self.tree.delete(first.as_slice());
+        } else if idx == 0 {
+            self.rekey(&first);
+        }
+        Some(value)
+    }
+
+    /// Index the bucket indexed by the given key by the key of its first entry again.
+    fn rekey(&mut self, first: &[u8]) {
+        let bucket = self.tree.delete(first).expect("the bucket is in the tree");
+        let first = bucket[0].0.bytes().as_ref().into();
+        self.tree.insert(first, bucket);
+    }
+}
+
+/// Return the entries of the bucket indexed by the given key for modification.
+fn bucket_mut<'a, K, V>(
+    tree: &'a mut ART<Box<[u8]>, Vec<(K, V)>>,
+    first: &[u8],
+) -> &'a mut Vec<(K, V)> {
+    tree.root
+        .as_mut()
+        .and_then(|root| root.search_mut(first, 0))
+        .map(|leaf| &mut leaf.value)
+        .expect("the bucket is in the tree")
+}
+
+/// Binary search the bucket for the given encoded key.
+fn position<K, V>(bucket: &[(K, V)], key: &[u8]) -> Result<usize, usize>
+where
+    K: BytesComparable,
+{
+    bucket.binary_search_by(|(k, _)| -> Ordering { k.bytes().as_ref().cmp(key) })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::Rng;
+
+    use super::PackedArt;
+
+    #[test]
+    fn test_packed_matches_btree() {
+        let mut rng = rand::thread_rng();
+        let mut tree = PackedArt::<u32, u32, 4>::new();
+        let mut expected = BTreeMap::new();
+        for _ in 0..20_000 {
+            let key = rng.gen_range(0..3000);
+            if rng.gen_bool(0.6) {
+                assert_eq!(tree.insert(key, key), expected.insert(key, key));
+            } else {
+                assert_eq!(tree.delete(&key), expected.remove(&key));
+            }
+            assert_eq!(tree.len(), expected.len());
+        }
+        for key in 0..3000 {
+            assert_eq!(tree.search(&key), expected.get(&key), "{key}");
+        }
+        assert!(tree.iter().eq(expected.iter()));
+    }
+
+    #[test]
+    fn test_packed_buckets() {
+        let mut tree = PackedArt::<u64, u64>::new();
+        for i in 0..10_000 {
+            tree.insert(i * 7919, i);
+        }
+        // Ascending inserts split the last bucket in halves.
+        assert!(
+            (1250..=2500).contains(&tree.buckets()),
+            "{}",
+            tree.buckets()
+        );
+        for i in (0..10_000).rev() {
+            tree.insert(i * 7919 + 1, i);
+        }
+        assert!(tree.buckets() <= 20_000 * 2 / 8);
+        for i in 0..10_000 {
+            assert_eq!(tree.search(&(i * 7919)), Some(&i));
+            assert_eq!(tree.search(&(i * 7919 + 1)), Some(&i));
+            assert_eq!(tree.search(&(i * 7919 + 2)), None);
+        }
+        for i in 0..10_000 {
+            assert_eq!(tree.delete(&(i * 7919)), Some(i));
+            assert_eq!(tree.delete(&(i * 7919 + 1)), Some(i));
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.buckets(), 0);
+    }
+}
@@ -0,0 +1,452 @@
+//! Dumping a tree into a byte stream and loading it back.
+//!
+//! A dump starts with a small uncompressed header, followed by the entries of the tree in key
+//! order. When a [`Compression`] other than [`Compression::None`] is chosen, everything after the
+//! header is written through a block compressor. Because the entries are sorted, consecutive keys
+//! share long prefixes, which the compressors pick up extremely well.
+//!
+//! # Format
+//!
+//! The format does not depend on the architecture that wrote it, so a dump written on `x86_64` can
+//! be loaded on aarch64 or wasm32. All integers are fixed-width and little-endian, and no field has
+//! the width of `usize`.
+//!
+//! | Field       | Encoding                                                    |
+//! |-------------|-------------------------------------------------------------|
+//! | magic       | the 4 bytes `YART`                                          |
+//! | version     | `u8`                                                        |
+//! | compression | `u8`, 0 for none, 1 for LZ4, and 2 for Zstandard            |
+//! | count       | `u64`, the number of entries                                |
+//! | entries     | `count` times the encoded key followed by the encoded value |
+//!
+//! Integers are encoded as their little-endian bytes, `usize` and `isize` as `u64` and `i64`, and
+//! byte strings as their length as a `u64` followed by the bytes. Implementations of [`Encode`] for
+//! user types should follow the same rules to keep their dumps portable.
+
+use std::{
+    convert::Infallible,
+    io::{self, Read, Write},
+};
+
+use crate::{BytesComparable, ART};
+
+/// The bytes every dump starts with.
+const MAGIC: &[u8; 4] = b"YART";
+
+/// The version of the dump format.
+const VERSION: u8 = 1;
+
+/// A type that can be written into a dump.
+pub trait Encode {
+    /// Write the value into the given writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer fails.
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()>;
+}
+
+/// A type that can be read from a dump.
+pub trait Decode: Sized {
+    /// Read a value from the given reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader fails or if the bytes do not form a valid value.
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self>;
+}
+
+macro_rules! impl_codec_for_int {
+    ($($ty:ty),*) => {
+        $(
+            impl Encode for $ty {
+                fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+                    writer.write_all(&self.to_le_bytes())
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+                    let mut buf = [0; std::mem::size_of::<$ty>()];
+                    reader.read_exact(&mut buf)?;
+                    Ok(Self::from_le_bytes(buf))
+                }
+            }
+        )*
+    };
+}
+
+impl_codec_for_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl Encode for usize {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        u64::try_from(*self).map_err(invalid_data)?.encode(writer)
+    }
+}
+
+impl Decode for usize {
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Self::try_from(u64::decode(reader)?).map_err(invalid_data)
+    }
+}
+
+impl Encode for isize {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        i64::try_from(*self).map_err(invalid_data)?.encode(writer)
+    }
+}
+
+impl Decode for isize {
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Self::try_from(i64::decode(reader)?).map_err(invalid_data)
+    }
+}
+
+impl Encode for () {
+    fn encode<W: Write + ?Sized>(&self, _writer: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Decode for () {
+    fn decode<R: Read + ?Sized>(_reader: &mut R) -> io::Result<Self> {
+        Ok(())
+    }
+}
+
+impl Encode for bool {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        u8::from(*self).encode(writer)
+    }
+}
+
+impl Decode for bool {
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        match u8::decode(reader)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            byte => Err(invalid_data(format!("invalid boolean byte {byte}"))),
+        }
+    }
+}
+
+impl Encode for [u8] {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        (self.len() as u64).encode(writer)?;
+        writer.write_all(self)
+    }
+}
+
+impl Encode for Vec<u8> {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.as_slice().encode(writer)
+    }
+}
+
+impl Decode for Vec<u8> {
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        let len = u64::decode(reader)?;
+        let mut buf = Self::new();
+        reader.take(len).read_to_end(&mut buf)?;
+        if buf.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(buf)
+    }
+}
+
+impl Encode for str {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.as_bytes().encode(writer)
+    }
+}
+
+impl Encode for String {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.as_str().encode(writer)
+    }
+}
+
+impl Decode for String {
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Self::from_utf8(Vec::decode(reader)?).map_err(invalid_data)
+    }
+}
+
+/// The compression applied to the entries of a dump.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Entries are written as is.
+    #[default]
+    None,
+    /// Entries are compressed using the LZ4 frame format.
+    #[cfg(feature = "lz4")]
+    Lz4,
+    /// Entries are compressed using Zstandard.
+    #[cfg(feature = "zstd")]
+    Zstd {
+        /// The compression level, higher levels trade speed for a smaller output. Passing 0 uses
+        /// the default level of the library.
+        level: i32,
+    },
+}
+
+impl Compression {
+    /// The tag written into the header to identify the compression.
+    const fn tag(self) -> u8 {
+        match self {
+            Self::None => 0,
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => 1,
+            #[cfg(feature = "zstd")]
+            Self::Zstd { .. } => 2,
+        }
+    }
+}
+
+impl<K, V, const N: usize> ART<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Write all entries of the tree into the given writer without compression.
+    ///
+    /// The writer is used as is, so it should be buffered if small writes are expensive.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer fails.
+    pub fn dump<W>(&self, writer: W) -> io::Result<()>
+    where
+        W: Write,
+        K: Encode,
+        V: Encode,
+    {
+        self.dump_with(writer, Compression::None)
+    }
+
+    /// Write all entries of the tree into the given writer using the given compression.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer or the compressor fails.
+    pub fn dump_with<W>(&self, mut writer: W, compression: Compression) -> io::Result<()>
+    where
+        W: Write,
+        K: Encode,
+        V: Encode,
+    {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, compression.tag()])?;
+        match compression {
+            Compression::None => self.dump_entries(&mut writer),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => {
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(writer);
+                self.dump_entries(&mut encoder)?;
+                encoder.finish().map_err(io::Error::other)?;
+                Ok(())
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd { level } => {
+                let mut encoder = zstd::Encoder::new(writer, level)?;
+                self.dump_entries(&mut encoder)?;
+                encoder.finish()?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Read a tree from the given reader, which must contain a dump written by [`ART::dump`] or
+    /// [`ART::dump_with`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader fails, if the data is not a valid dump, or if the dump was
+    /// compressed using a method whose feature is not enabled.
+    pub fn load<R>(mut reader: R) -> io::Result<Self>
+    where
+        R: Read,
+        K: Decode,
+        V: Decode,
+    {
+        let mut header = [0; MAGIC.len() + 2];
+        reader.read_exact(&mut header)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(invalid_data("not a yaart dump"));
+        }
+        let [version, tag] = [header[MAGIC.len()], header[MAGIC.len() + 1]];
+        if version != VERSION {
+            return Err(invalid_data(format!("unsupported dump version {version}")));
+        }
+        match tag {
+            0 => Self::load_entries(&mut reader),
+            #[cfg(feature = "lz4")]
+            1 => Self::load_entries(&mut lz4_flex::frame::FrameDecoder::new(reader)),
+            #[cfg(feature = "zstd")]
+            2 => Self::load_entries(&mut zstd::Decoder::new(reader)?),
+            tag => Err(invalid_data(format!(
+                "unsupported compression tag {tag}, the corresponding feature may be disabled"
+            ))),
+        }
+    }
+
+    fn dump_entries<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: Write + ?Sized,
+        K: Encode,
+        V: Encode,
+    {
+        let mut count = 0u64;
+        let Ok(()) = self.try_for_each(|_, _| {
+            count += 1;
+            Ok::<_, Infallible>(())
+        });
+        count.encode(writer)?;
+        self.try_for_each(|key, value| {
+            key.encode(writer)?;
+            value.encode(writer)
+        })
+    }
+
+    fn load_entries<R>(reader: &mut R) -> io::Result<Self>
+    where
+        R: Read + ?Sized,
+        K: Decode,
+        V: Decode,
+    {
+        let count = u64::decode(reader)?;
+        let mut tree = Self::new();
+        for _ in 0..count {
+            let key = K::decode(reader)?;
+            let value = V::decode(reader)?;
+            tree.insert(key, value);
+        }
+        Ok(tree)
+    }
+}
+
+pub(crate) fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compression;
+    use crate::ART;
+
+    fn sample_tree() -> ART<String, u64> {
+        let mut tree = ART::default();
+        for i in 0..2048u64 {
+            tree.insert(format!("2024-01-01T00:00:{i:08}/sensor"), i);
+        }
+        tree
+    }
+
+    fn assert_round_trip(compression: Compression) -> usize {
+        let tree = sample_tree();
+        let mut buf = Vec::new();
+        tree.dump_with(&mut buf, compression)
+            .expect("dump must succeed");
+        let loaded = ART::<String, u64>::load(buf.as_slice()).expect("load must succeed");
+        for i in 0..2048u64 {
+            let key = format!("2024-01-01T00:00:{i:08}/sensor");
+            assert_eq!(loaded.search(&key), Some(&i));
+        }
+        assert_eq!(loaded.min(), tree.min());
+        assert_eq!(loaded.max(), tree.max());
+        buf.len()
+    }
+
+    #[test]
+    fn test_dump_load_uncompressed() {
+        assert_round_trip(Compression::None);
+    }
+
+    #[test]
+    fn test_dump_load_empty() {
+        let tree = ART::<String, u64>::default();
+        let mut buf = Vec::new();
+        tree.dump(&mut buf).expect("dump must succeed");
+        let loaded = ART::<String, u64>::load(buf.as_slice()).expect("load must succeed");
+        assert!(loaded.min().is_none());
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_header() {
+        let err = ART::<String, u64>::load(&b"NOPE\x01\x00"[..]).expect_err("load must fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let err = ART::<String, u64>::load(&b"YART\x01\x7f"[..]).expect_err("load must fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    // The same bytes must be produced and accepted on every target, regardless of its endianness
+    // or pointer width.
+    #[test]
+    fn test_dump_format_is_stable() {
+        let mut tree = ART::<u16, u32>::default();
+        tree.insert(258, 20);
+        tree.insert(1, 10);
+        let expected: &[u8] = &[
+            b'Y', b'A', b'R', b'T', 1, 0, // header
+            2, 0, 0, 0, 0, 0, 0, 0, // count
+            1, 0, 10, 0, 0, 0, // 1 -> 10
+            2, 1, 20, 0, 0, 0, // 258 -> 20
+        ];
+        let mut buf = Vec::new();
+        tree.dump(&mut buf).expect("dump must succeed");
+        assert_eq!(buf, expected);
+        let loaded = ART::<u16, u32>::load(expected).expect("load must succeed");
+        assert_eq!(loaded.search(&1), Some(&10));
+        assert_eq!(loaded.search(&258), Some(&20));
+    }
+
+    #[test]
+    fn test_dump_format_is_stable_for_variable_length_data() {
+        let mut tree = ART::<String, usize>::default();
+        tree.insert("ab".to_string(), 0x0102);
+        let expected: &[u8] = &[
+            b'Y', b'A', b'R', b'T', 1, 0, // header
+            1, 0, 0, 0, 0, 0, 0, 0, // count
+            2, 0, 0, 0, 0, 0, 0, 0, b'a', b'b', // "ab"
+            2, 1, 0, 0, 0, 0, 0, 0, // 0x0102 as a u64
+        ];
+        let mut buf = Vec::new();
+        tree.dump(&mut buf).expect("dump must succeed");
+        assert_eq!(buf, expected);
+        let loaded = ART::<String, usize>::load(expected).expect("load must succeed");
+        assert_eq!(loaded.search("ab"), Some(&0x0102));
+    }
+
+    #[test]
+    fn test_load_rejects_truncated_data() {
+        let expected: &[u8] = &[
+            b'Y', b'A', b'R', b'T', 1, 0, // header
+            1, 0, 0, 0, 0, 0, 0, 0, // count
+            2, 0, 0, 0, 0, 0, 0, 0, b'a', // truncated "ab"
+        ];
+        let err = ART::<String, usize>::load(expected).expect_err("load must fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_dump_load_lz4() {
+        let raw = assert_round_trip(Compression::None);
+        let compressed = assert_round_trip(Compression::Lz4);
+        assert!(compressed < raw / 2);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_dump_load_zstd() {
+        let raw = assert_round_trip(Compression::None);
+        let fast = assert_round_trip(Compression::Zstd { level: 1 });
+        let small = assert_round_trip(Compression::Zstd { level: 19 });
+        assert!(fast < raw / 2);
+        assert!(small < raw / 2);
+    }
+}
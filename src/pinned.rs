@@ -0,0 +1,227 @@
+//! A tree whose values are pinned in boxes of their own.
+//!
+//! The values of an [`ART`] are stored in its leaves, which are moved whenever an insert splits
+//! them or a delete merges them into their parent. A [`PinnedArt`] stores every value in a pinned
+//! box, and only the box moves with the leaf, so the address of a value stays the same from the
+//! insert of its key until the key is deleted or its value is replaced, no matter how many other
+//! keys are inserted or deleted in between. Callers can keep raw pointers to the values for
+//! that long, and values that are not [`Unpin`], such as intrusive list nodes or futures, can be
+//! stored and accessed through [`Pin`] references.
+
+use std::{borrow::Borrow, pin::Pin};
+
+use crate::{BytesComparable, ART};
+
+/// An adaptive radix tree whose values never move, see the [module documentation](self).
+#[derive(Debug)]
+pub struct PinnedArt<K, V, const N: usize = 10> {
+    tree: ART<K, Pin<Box<V>>, N>,
+}
+
+impl<K, V, const N: usize> PinnedArt<K, V, N> {
+    /// Create an empty tree.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { tree: ART::new() }
+    }
+
+    /// Return the number of entries in the tree.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Return true if the tree holds no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Return an iterator over the entries in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, Pin<&V>)> + '_ {
+        self.tree.iter().map(|(k, v)| (k, v.as_ref()))
+    }
+}
+
+impl<K, V, const N: usize> Default for PinnedArt<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> PinnedArt<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<Pin<&V>>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(key).map(Pin::as_ref)
+    }
+
+    /// Search for the value associated with the given key for modification.
+    pub fn search_mut<Q>(&mut self, key: &Q) -> Option<Pin<&mut V>>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.value_mut(key.bytes().as_ref())
+    }
+
+    /// Return the value associated with the given key, inserting the value returned by the closure
+    /// if the key is absent.
+    ///
+    /// The returned value stays at its address until its key is deleted, its value is replaced, or
+    /// the tree is dropped, whatever is inserted into or deleted from the tree in the meantime. A
+    /// pointer to it, such as a [`NonNull`](std::ptr::NonNull) made from the reference, can
+    /// therefore be kept in a side table for that long, and dereferenced while no reference to the
+    /// value is borrowed from the tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not encoded to the same bytes every time.
+    pub fn get_or_insert_ref<F>(&mut self, key: K, default: F) -> Pin<&mut V>
+    where
+        F: FnOnce() -> V,
+    {
+        let encoded = key.bytes().as_ref().to_vec();
+        if self.value_mut(&encoded).is_none() {
+            self.insert(key, default());
+        }
+        self.value_mut(&encoded)
+            .expect("the key is in the tree after inserting it")
+    }
+
+    /// Return the value associated with the given encoded key for modification.
+    fn value_mut(&mut self, key: &[u8]) -> Option<Pin<&mut V>> {
+        self.tree
+            .root
+            .as_mut()
+            .and_then(|root| root.search_mut(key, 0))
+            .map(|leaf| leaf.value.as_mut())
+    }
+
+    /// Insert the given key-value pair into the tree, returning the pinned value previously
+    /// associated with the key. The new value is pinned at a new address.
+    pub fn insert(&mut self, key: K, value: V) -> Option<Pin<Box<V>>> {
+        self.insert_pinned(key, Box::pin(value))
+    }
+
+    /// Insert the given key and value that was already pinned, returning the pinned value
+    /// previously associated with the key. The value keeps its address.
+    pub fn insert_pinned(&mut self, key: K, value: Pin<Box<V>>) -> Option<Pin<Box<V>>> {
+        self.tree.insert(key, value)
+    }
+
+    /// Delete the value associated with the given key, returning it still pinned.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<Pin<Box<V>>>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.delete(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        marker::PhantomPinned,
+        pin::Pin,
+        ptr::{self, NonNull},
+    };
+
+    use super::PinnedArt;
+
+    #[test]
+    fn test_stable_addresses() {
+        let mut tree = PinnedArt::<u32, u64>::new();
+        let mut addresses = HashMap::new();
+        for i in (0..5000).step_by(2) {
+            tree.insert(i, u64::from(i));
+            addresses.insert(i, ptr::from_ref(tree.search(&i).unwrap().get_ref()));
+        }
+        // Growing, shrinking, and splitting the nodes around the values does not move them.
+        for i in (1..5000).step_by(2) {
+            tree.insert(i, u64::from(i));
+        }
+        for i in (1..5000).step_by(2) {
+            tree.delete(&i);
+        }
+        assert_eq!(tree.len(), addresses.len());
+        for (key, value) in tree.iter() {
+            assert_eq!(ptr::from_ref(value.get_ref()), addresses[key]);
+            assert_eq!(*value, u64::from(*key));
+        }
+
+        // A replaced value is pinned at a new address, unless it was pinned by the caller.
+        let pinned = Box::pin(7);
+        let address = ptr::from_ref(pinned.as_ref().get_ref());
+        let old = tree.insert_pinned(0, pinned).unwrap();
+        assert_eq!(ptr::from_ref(old.as_ref().get_ref()), addresses[&0]);
+        assert_eq!(ptr::from_ref(tree.search(&0).unwrap().get_ref()), address);
+        assert!(!tree.is_empty());
+    }
+
+    #[test]
+    fn test_get_or_insert_ref() {
+        let mut tree = PinnedArt::<String, Vec<u32>>::new();
+        let mut side_table = Vec::new();
+        for i in 0..100 {
+            let mut value = tree.get_or_insert_ref(format!("key{i}"), Vec::new);
+            value.push(i);
+            side_table.push((format!("key{i}"), NonNull::from(value.get_mut())));
+        }
+        // Existing values are returned without calling the closure.
+        let value = tree.get_or_insert_ref("key7".to_string(), || unreachable!());
+        assert_eq!(*value, [7]);
+        for i in 100..3000 {
+            tree.insert(format!("key{i}"), vec![i]);
+        }
+        for i in 0..50 {
+            tree.delete(&format!("key{}", 2 * i + 1));
+        }
+        for (key, pointer) in side_table.iter().step_by(2) {
+            let value = tree.search(key.as_str()).unwrap();
+            assert_eq!(NonNull::from(value.get_ref()), *pointer);
+        }
+    }
+
+    #[test]
+    fn test_not_unpin() {
+        #[derive(Debug)]
+        struct Intrusive {
+            count: u32,
+            _pinned: PhantomPinned,
+        }
+
+        let mut tree = PinnedArt::<&str, Intrusive>::new();
+        for key in ["a", "b", "c"] {
+            tree.insert(
+                key,
+                Intrusive {
+                    count: 0,
+                    _pinned: PhantomPinned,
+                },
+            );
+        }
+        let bump = |mut value: Pin<&mut Intrusive>| {
+            // The value is replaced in place, without moving it out of its box.
+            let count = value.count + 1;
+            value.set(Intrusive {
+                count,
+                _pinned: PhantomPinned,
+            });
+        };
+        bump(tree.search_mut("b").unwrap());
+        bump(tree.search_mut("b").unwrap());
+        assert_eq!(tree.search("b").map(|value| value.count), Some(2));
+        assert!(tree.search_mut("d").is_none());
+        assert_eq!(tree.delete("a").map(|value| value.count), Some(0));
+        assert_eq!(tree.len(), 2);
+    }
+}
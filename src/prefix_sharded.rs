@@ -0,0 +1,256 @@
+//! A concurrent map made of one independently locked tree per leading key byte.
+//!
+//! Unlike [`crate::sharded::ShardedArt`], which spreads a fixed number of shards over the key
+//! space, a [`PrefixShardedArt`] dedicates a shard to each of the 256 values of the first byte of
+//! the encoded keys. The shard of a key is therefore known without the map, which makes a shard a
+//! natural unit of work outside of the hot path: shards can be written to and read from storage
+//! independently, or compacted one at a time while the rest of the map keeps serving requests.
+
+use std::{
+    borrow::Borrow,
+    io::{self, Read, Write},
+    sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::{
+    persist::{Decode, Encode},
+    BytesComparable, ART,
+};
+
+/// The number of shards in a [`PrefixShardedArt`].
+pub const SHARDS: usize = 256;
+
+/// A map that routes each key to the tree owning the first byte of its encoded form.
+///
+/// Keys without any byte are stored in the shard of byte 0. Operations on keys that start with
+/// different bytes can proceed in parallel, and the shards are ordered like their bytes.
+#[derive(Debug)]
+pub struct PrefixShardedArt<K, V, const N: usize = 10> {
+    shards: Box<[RwLock<ART<K, V, N>>]>,
+}
+
+impl<K, V, const N: usize> PrefixShardedArt<K, V, N> {
+    /// Create an empty map.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| RwLock::new(ART::new())).collect(),
+        }
+    }
+
+    /// Return the number of entries in the map.
+    ///
+    /// The shards are counted one after another, so the result may be stale under concurrent
+    /// writes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        (0..=u8::MAX).map(|byte| self.shard(byte).len()).sum()
+    }
+
+    /// Return true if the map holds no entry.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        (0..=u8::MAX).all(|byte| self.shard(byte).is_empty())
+    }
+
+    /// Lock the shard of the given leading byte for reading.
+    pub fn shard(&self, byte: u8) -> RwLockReadGuard<'_, ART<K, V, N>> {
+        self.shards[usize::from(byte)]
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the shard of the given leading byte for writing.
+    ///
+    /// Keys inserted through the guard must start with the byte of the shard, otherwise they can
+    /// not be found through the map.
+    pub fn shard_mut(&self, byte: u8) -> RwLockWriteGuard<'_, ART<K, V, N>> {
+        self.shards[usize::from(byte)]
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Consume the map and return its shards, indexed by their leading byte.
+    #[must_use]
+    pub fn into_shards(self) -> Vec<ART<K, V, N>> {
+        self.shards
+            .into_vec()
+            .into_iter()
+            .map(|shard| shard.into_inner().unwrap_or_else(PoisonError::into_inner))
+            .collect()
+    }
+}
+
+impl<K, V, const N: usize> Default for PrefixShardedArt<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> PrefixShardedArt<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Search for the value associated with the given key and return a copy of it.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        V: Clone,
+    {
+        self.with(key, V::clone)
+    }
+
+    /// Search for the value associated with the given key and call the closure on it while the
+    /// shard is locked for reading.
+    pub fn with<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        F: FnOnce(&V) -> R,
+    {
+        self.shard(shard_of(key)).search(key).map(f)
+    }
+
+    /// Insert the given key-value pair into the map, returning the previous value of the key.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.shard_mut(shard_of(&key)).insert(key, value)
+    }
+
+    /// Delete the value associated with the given key.
+    pub fn delete<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.shard_mut(shard_of(key)).delete(key)
+    }
+
+    /// Write the entries of the shard of the given leading byte into the given writer, in the
+    /// format of [`ART::dump`]. The shard is locked for reading while it is written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer fails.
+    pub fn dump_shard<W>(&self, byte: u8, writer: W) -> io::Result<()>
+    where
+        W: Write,
+        K: Encode,
+        V: Encode,
+    {
+        self.shard(byte).dump(writer)
+    }
+
+    /// Read a tree from the given reader and make it the shard of the given leading byte,
+    /// returning the replaced shard.
+    ///
+    /// The tree is read before the shard is locked, so the other shards and the replaced shard
+    /// stay available while the reader is consumed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader fails, if the data is not a valid dump, or if the dump holds
+    /// a key that does not start with the given byte.
+    pub fn load_shard<R>(&self, byte: u8, reader: R) -> io::Result<ART<K, V, N>>
+    where
+        R: Read,
+        K: Decode,
+        V: Decode,
+    {
+        let tree = ART::<K, V, N>::load(reader)?;
+        if let Some((key, _)) = tree.iter().find(|(key, _)| shard_of(*key) != byte) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "key {:?} does not belong to the shard of byte {byte}",
+                    key.bytes().as_ref()
+                ),
+            ));
+        }
+        Ok(std::mem::replace(&mut *self.shard_mut(byte), tree))
+    }
+}
+
+/// Return the leading byte of the encoded key, or 0 for an empty key.
+pub(crate) fn shard_of<Q>(key: &Q) -> u8
+where
+    Q: BytesComparable + ?Sized,
+{
+    key.bytes().as_ref().first().copied().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use super::PrefixShardedArt;
+
+    #[test]
+    fn test_prefix_sharded_concurrent_operations() {
+        let map = PrefixShardedArt::<u32, u32>::new();
+        std::thread::scope(|scope| {
+            for t in 0..8u32 {
+                let map = &map;
+                scope.spawn(move || {
+                    for i in 0..2000u32 {
+                        let key = i.wrapping_mul(2_654_435_761) ^ t;
+                        map.insert(key, t);
+                    }
+                });
+            }
+        });
+        for t in 0..8u32 {
+            for i in 0..2000u32 {
+                let key = i.wrapping_mul(2_654_435_761) ^ t;
+                assert_eq!(map.get(&key), Some(t));
+            }
+        }
+        assert_eq!(map.len(), 16000);
+        assert_eq!(map.delete(&2_654_435_761), Some(0));
+        assert_eq!(map.delete(&2_654_435_761), None);
+
+        let shards = map.into_shards();
+        assert_eq!(shards.len(), 256);
+        for (byte, shard) in shards.iter().enumerate() {
+            assert!(shard
+                .iter()
+                .all(|(key, _)| key.to_be_bytes()[0] as usize == byte));
+        }
+    }
+
+    #[test]
+    fn test_prefix_sharded_parallel_persistence() {
+        let map = PrefixShardedArt::<String, usize>::default();
+        for (i, letter) in (0..1000).zip((b'a'..=b'z').cycle()) {
+            map.insert(format!("{}-{i}", char::from(letter)), i);
+        }
+        map.insert(String::new(), 1000);
+
+        let dumps: Vec<_> = std::thread::scope(|scope| {
+            let mut handles = Vec::new();
+            for byte in (0..=u8::MAX).filter(|&byte| !map.shard(byte).is_empty()) {
+                let map = &map;
+                handles.push(scope.spawn(move || {
+                    let mut buf = Vec::new();
+                    map.dump_shard(byte, &mut buf).unwrap();
+                    (byte, buf)
+                }));
+            }
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(dumps.len(), 27);
+
+        let loaded = PrefixShardedArt::<String, usize>::new();
+        for (byte, buf) in &dumps {
+            assert!(loaded.load_shard(*byte, buf.as_slice()).unwrap().is_empty());
+        }
+        assert_eq!(loaded.len(), 1001);
+        assert_eq!(loaded.get("c-2"), Some(2));
+        assert_eq!(loaded.get(""), Some(1000));
+
+        let (_, buf) = dumps.iter().find(|(byte, _)| *byte == b'a').unwrap();
+        let err = loaded.load_shard(b'b', buf.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(loaded.get("b-1"), Some(1));
+    }
+}
@@ -0,0 +1,170 @@
+//! Traces of the nodes visited by single operations, enabled by the `probe` feature.
+//!
+//! The counters of the `stats` feature tell how much work the operations of a thread did, but not
+//! where a slow query spent it. [`trace`] runs a closure with a [`Probe`] installed on the current
+//! thread, and the probe is called on every node that the tree operations in the closure visit,
+//! with the kind of the node and the depth of the key byte it starts at. Only the operations that
+//! are traced pay for the probe, so a query that is known to be slow can be traced in production
+//! while other threads, and other queries on the same thread, run as before.
+//!
+//! The nodes reported are the same that are counted as node visits by the `stats` feature. Tree
+//! operations run by a probe itself are not reported to it.
+
+use std::{any::Any, cell::Cell};
+
+pub use crate::node::NodeKind;
+
+/// A node visited by a tree operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeVisit {
+    /// The kind of the node.
+    pub kind: NodeKind,
+    /// The number of key bytes on the path to the node, excluding the prefix of the node.
+    pub depth: usize,
+}
+
+/// Receives the nodes visited by the tree operations traced with it, see [`trace`].
+pub trait Probe: Any {
+    /// Called on every visited node, in the order the nodes are visited.
+    fn visit(&mut self, visit: NodeVisit);
+}
+
+/// Collects the visited nodes.
+impl Probe for Vec<NodeVisit> {
+    fn visit(&mut self, visit: NodeVisit) {
+        self.push(visit);
+    }
+}
+
+impl<F> Probe for F
+where
+    F: FnMut(NodeVisit) + 'static,
+{
+    fn visit(&mut self, visit: NodeVisit) {
+        self(visit);
+    }
+}
+
+thread_local! {
+    static PROBE: Cell<Option<Box<dyn Probe>>> = const { Cell::new(None) };
+}
+
+/// Puts back the probe that was installed before a trace, even if the traced closure panics.
+struct Restore(Option<Box<dyn Probe>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        PROBE.set(self.0.take());
+    }
+}
+
+/// Call the closure with the probe installed on the current thread, and return the result of the
+/// closure together with the probe. A trace inside the closure replaces the probe until it returns.
+///
+/// # Panics
+///
+/// Panics if the closure took the probe of the trace with a trace of its own that it did not
+/// finish, which can not happen when the closure only calls [`trace`].
+pub fn trace<P, R, F>(probe: P, f: F) -> (R, P)
+where
+    P: Probe,
+    F: FnOnce() -> R,
+{
+    let restore = Restore(PROBE.replace(Some(Box::new(probe))));
+    let result = f();
+    let probe: Box<dyn Any> = PROBE
+        .take()
+        .expect("the probe is installed during the trace");
+    drop(restore);
+    let probe = probe
+        .downcast::<P>()
+        .expect("the probe of the trace is installed");
+    (result, *probe)
+}
+
+/// Reports a visited node to the probe of the current thread, if any.
+pub(crate) fn record(kind: NodeKind, depth: usize) {
+    // The probe is taken out while it runs, so that tree operations in it are not reported to it.
+    let Some(mut probe) = PROBE.take() else {
+        return;
+    };
+    probe.visit(NodeVisit { kind, depth });
+    PROBE.set(Some(probe));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::{trace, NodeKind, NodeVisit};
+    use crate::ART;
+
+    #[test]
+    fn test_trace() {
+        let mut tree = ART::<u32, u32>::new();
+        for i in 0..=48 {
+            tree.insert(i, i);
+        }
+        let (value, visits) = trace(Vec::new(), || tree.search(&7).copied());
+        assert_eq!(value, Some(7));
+        // The root with the common prefix of all keys and the leaf below it.
+        assert_eq!(
+            visits,
+            [
+                NodeVisit {
+                    kind: NodeKind::Node256,
+                    depth: 0,
+                },
+                NodeVisit {
+                    kind: NodeKind::Leaf,
+                    depth: 4,
+                },
+            ]
+        );
+
+        // Operations outside of a trace are not reported.
+        let ((), visits) = trace(Vec::new(), || ());
+        tree.search(&8);
+        assert!(visits.is_empty());
+
+        // A nested trace gets the visits made while it runs.
+        let (((), inner), outer) = trace(Vec::new(), || {
+            tree.search(&1);
+            trace(Vec::new(), || {
+                tree.insert(100, 100);
+            })
+        });
+        assert_eq!(outer.len(), 2);
+        assert_eq!(
+            inner.first().map(|visit| visit.kind),
+            Some(NodeKind::Node256)
+        );
+    }
+
+    #[test]
+    fn test_closure_probe() {
+        let mut tree = ART::<String, usize>::new();
+        for i in 0..1000 {
+            tree.insert(format!("key{i}"), i);
+        }
+        let deepest = Rc::new(Cell::new(0));
+        let probe = {
+            let deepest = Rc::clone(&deepest);
+            move |visit: NodeVisit| deepest.set(deepest.get().max(visit.depth))
+        };
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            trace(probe, || {
+                tree.delete("key999");
+                panic!("the traced operation panicked");
+            })
+        }));
+        assert!(result.is_err());
+        // The deepest inner node holds the keys starting with "key99" under their sixth byte.
+        assert_eq!(deepest.get(), 5);
+        // The probe of a panicking trace is removed.
+        let (_, visits) = trace(Vec::new(), || tree.search("key1"));
+        assert!(!visits.is_empty());
+        tree.search("key2");
+        assert_eq!(deepest.get(), 5);
+    }
+}
@@ -0,0 +1,220 @@
+//! A priority queue on top of the tree.
+//!
+//! The items of an [`ArtPriorityQueue`] are kept in a tree keyed by their priority, so the items
+//! with the lowest and the highest priority are the minimum and maximum leaves of the tree. The
+//! priority is encoded as a [segment](crate::segment) followed by a sequence number, which keeps
+//! the order of variable-length priorities and gives every item a distinct key. Items with the
+//! same priority are popped from the front in the order they were pushed, and from the back in the
+//! reverse order.
+
+use std::{borrow::Borrow, collections::HashMap};
+
+use crate::{segment::encode_segment, BytesComparable, ART};
+
+/// A handle to an item pushed into an [`ArtPriorityQueue`], valid until the item is popped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ticket(u64);
+
+/// The key of an item, holding its priority, the ticket of the item, and the encoded priority and
+/// sequence number.
+#[derive(Debug)]
+struct QueueKey<P> {
+    priority: P,
+    ticket: Ticket,
+    bytes: Box<[u8]>,
+}
+
+impl<P> Borrow<[u8]> for QueueKey<P> {
+    fn borrow(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl<P> BytesComparable for QueueKey<P> {
+    type Target<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    fn bytes(&self) -> Self::Target<'_> {
+        &self.bytes
+    }
+}
+
+/// A double-ended priority queue of items of type `T` with priorities of type `P`.
+#[derive(Debug)]
+pub struct ArtPriorityQueue<P, T> {
+    tree: ART<QueueKey<P>, T>,
+    tickets: HashMap<Ticket, Box<[u8]>>,
+    next_seq: u64,
+}
+
+impl<P, T> ArtPriorityQueue<P, T> {
+    /// Create an empty queue.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            tree: ART::new(),
+            tickets: HashMap::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Return the number of items in the queue.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.tickets.len()
+    }
+
+    /// Return true if the queue holds no item.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tickets.is_empty()
+    }
+}
+
+impl<P, T> Default for ArtPriorityQueue<P, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P, T> ArtPriorityQueue<P, T>
+where
+    P: BytesComparable,
+{
+    /// Push an item with the given priority, returning a ticket to change its priority later.
+    pub fn push(&mut self, priority: P, item: T) -> Ticket {
+        let ticket = Ticket(self.next_seq);
+        self.insert(priority, ticket, item);
+        ticket
+    }
+
+    /// Return the item with the lowest priority.
+    #[must_use]
+    pub fn peek_min(&self) -> Option<(&P, &T)> {
+        self.tree.min().map(|(key, item)| (&key.priority, item))
+    }
+
+    /// Return the item with the highest priority.
+    #[must_use]
+    pub fn peek_max(&self) -> Option<(&P, &T)> {
+        self.tree.max().map(|(key, item)| (&key.priority, item))
+    }
+
+    /// Remove the item with the lowest priority and return it with its priority.
+    pub fn pop_min(&mut self) -> Option<(P, T)> {
+        let bytes = self.tree.min()?.0.bytes.clone();
+        self.remove_bytes(&bytes)
+    }
+
+    /// Remove the item with the highest priority and return it with its priority.
+    pub fn pop_max(&mut self) -> Option<(P, T)> {
+        let bytes = self.tree.max()?.0.bytes.clone();
+        self.remove_bytes(&bytes)
+    }
+
+    /// Remove the item of the given ticket and return it with its priority.
+    pub fn remove(&mut self, ticket: Ticket) -> Option<(P, T)> {
+        let bytes = self.tickets.get(&ticket)?.clone();
+        self.remove_bytes(&bytes)
+    }
+
+    /// Change the priority of the item of the given ticket, returning its previous priority, or
+    /// `None` if the item is no longer in the queue. The item is ordered as if it was pushed again,
+    /// so it comes after the items that already have the new priority, and the ticket stays valid.
+    pub fn change_priority(&mut self, ticket: Ticket, priority: P) -> Option<P> {
+        let (old, item) = self.remove(ticket)?;
+        self.insert(priority, ticket, item);
+        Some(old)
+    }
+
+    /// Inserts the item of the ticket with the next sequence number.
+    fn insert(&mut self, priority: P, ticket: Ticket, item: T) {
+        let mut bytes = Vec::new();
+        encode_segment(priority.bytes().as_ref(), &mut bytes);
+        bytes.extend_from_slice(&self.next_seq.to_be_bytes());
+        self.next_seq += 1;
+        let key = QueueKey {
+            priority,
+            ticket,
+            bytes: bytes.into_boxed_slice(),
+        };
+        self.tickets.insert(ticket, key.bytes.clone());
+        self.tree.insert(key, item);
+    }
+
+    fn remove_bytes(&mut self, bytes: &[u8]) -> Option<(P, T)> {
+        let leaf = self.tree.delete_leaf(bytes)?;
+        self.tickets.remove(&leaf.key.ticket);
+        Some((leaf.key.priority, leaf.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::ArtPriorityQueue;
+
+    #[test]
+    fn test_priority_queue() {
+        let mut rng = rand::thread_rng();
+        let mut queue = ArtPriorityQueue::<u32, usize>::new();
+        let mut priorities: Vec<u32> = (0..1000).map(|_| rng.gen_range(0..100)).collect();
+        for (item, priority) in priorities.iter().enumerate() {
+            queue.push(*priority, item);
+        }
+        priorities.sort_unstable();
+        assert_eq!(
+            queue.peek_min().map(|(p, _)| *p),
+            priorities.first().copied()
+        );
+        for expected in priorities.iter().rev().take(10) {
+            assert_eq!(queue.pop_max().map(|(p, _)| p), Some(*expected));
+        }
+        for expected in &priorities[..990] {
+            assert_eq!(queue.pop_min().map(|(p, _)| p), Some(*expected));
+        }
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop_min(), None);
+    }
+
+    #[test]
+    fn test_change_priority() {
+        let mut queue = ArtPriorityQueue::<String, &str>::new();
+        let a = queue.push("b".to_string(), "a");
+        queue.push("ba".to_string(), "b");
+        let c = queue.push("b".to_string(), "c");
+        assert_eq!(queue.peek_min(), Some((&"b".to_string(), &"a")));
+        assert_eq!(
+            queue.change_priority(a, "c".to_string()),
+            Some("b".to_string())
+        );
+        assert_eq!(queue.peek_max(), Some((&"c".to_string(), &"a")));
+        assert_eq!(queue.remove(c), Some(("b".to_string(), "c")));
+        assert_eq!(queue.change_priority(c, "a".to_string()), None);
+        assert_eq!(queue.pop_min(), Some(("ba".to_string(), "b")));
+        assert_eq!(queue.pop_min(), Some(("c".to_string(), "a")));
+        assert_eq!(queue.len(), 0);
+    }
+
+    #[test]
+    fn test_change_priority_to_equal_priority() {
+        let mut queue = ArtPriorityQueue::<u32, &str>::new();
+        let a = queue.push(1, "a");
+        queue.push(1, "b");
+        let c = queue.push(2, "c");
+        // The item goes behind the items that already have the priority, as if it was pushed again.
+        assert_eq!(queue.change_priority(a, 1), Some(1));
+        assert_eq!(queue.peek_min(), Some((&1, &"b")));
+        assert_eq!(queue.change_priority(c, 1), Some(2));
+        assert_eq!(queue.peek_max(), Some((&1, &"c")));
+        // The tickets stay valid after their items were reordered.
+        assert_eq!(queue.change_priority(a, 0), Some(1));
+        assert_eq!(queue.pop_min(), Some((0, "a")));
+        assert_eq!(queue.pop_min(), Some((1, "b")));
+        assert_eq!(queue.remove(c), Some((1, "c")));
+        assert!(queue.is_empty());
+    }
+}
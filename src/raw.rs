@@ -0,0 +1,152 @@
+//! Read-only access to the nodes of a tree, enabled by the `raw` feature.
+//!
+//! A [`NodeRef`] shows what the tree looks like inside: the kind of every node, the prefix that was
+//! compressed into it, and its children together with the bytes leading to them. This is meant for
+//! visualizers and experiments with custom traversals. The shape of a tree is an implementation
+//! detail that can change in any release, even one that is compatible by semantic versioning, so
+//! this module is only available with the `raw` feature and code using it should pin the version
+//! of this crate.
+
+pub use crate::node::NodeKind;
+use crate::{node::Node, BytesComparable, ART};
+
+/// A shared reference to a node of a tree, see [`ART::raw_root`].
+pub struct NodeRef<'a, K, V, const N: usize = 10>(&'a Node<K, V, N>);
+
+impl<K, V, const N: usize> Clone for NodeRef<'_, K, V, N> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V, const N: usize> Copy for NodeRef<'_, K, V, N> {}
+
+impl<K, V, const N: usize> std::fmt::Debug for NodeRef<'_, K, V, N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NodeRef")
+            .field("kind", &self.kind())
+            .field("partial", &self.partial())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, K, V, const N: usize> NodeRef<'a, K, V, N> {
+    /// Return the kind of the node.
+    #[must_use]
+    pub const fn kind(self) -> NodeKind {
+        self.0.kind()
+    }
+
+    /// Return the key-value pair of a leaf, or `None` for an inner node.
+    #[must_use]
+    pub const fn leaf(self) -> Option<(&'a K, &'a V)> {
+        match self.0 {
+            Node::Leaf(leaf) => Some((&leaf.key, &leaf.value)),
+            Node::Inner(_) => None,
+        }
+    }
+
+    /// Return the length of the prefix compressed into an inner node, or zero for a leaf.
+    #[must_use]
+    pub const fn prefix_len(self) -> usize {
+        match self.0 {
+            Node::Leaf(_) => 0,
+            Node::Inner(inner) => inner.prefix_len(),
+        }
+    }
+
+    /// Return the bytes of the prefix compressed into an inner node, or an empty slice for a leaf.
+    /// Prefixes longer than `N` bytes are stored out of line in the node.
+    #[must_use]
+    pub fn partial(self) -> &'a [u8] {
+        match self.0 {
+            Node::Leaf(_) => &[],
+            Node::Inner(inner) => inner.prefix(),
+        }
+    }
+
+    /// Return an iterator over the children of an inner node and their byte keys in key order,
+    /// which is empty for a leaf.
+    pub fn children(self) -> impl Iterator<Item = (u8, Self)> {
+        let inner = match self.0 {
+            Node::Leaf(_) => None,
+            Node::Inner(inner) => Some(inner),
+        };
+        inner
+            .into_iter()
+            .flat_map(|inner| inner.children().map(|(key, child)| (key, NodeRef(child))))
+    }
+}
+
+impl<K, V, const N: usize> NodeRef<'_, K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Return the child of an inner node with the given byte key.
+    #[must_use]
+    pub fn child(self, key: u8) -> Option<Self> {
+        match self.0 {
+            Node::Leaf(_) => None,
+            Node::Inner(inner) => inner.child_ref(key).map(NodeRef),
+        }
+    }
+}
+
+impl<K, V, const N: usize> ART<K, V, N> {
+    /// Return the root node of the tree, or `None` if the tree is empty.
+    #[must_use]
+    pub fn raw_root(&self) -> Option<NodeRef<'_, K, V, N>> {
+        self.root.as_ref().map(NodeRef)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NodeKind;
+    use crate::ART;
+
+    #[test]
+    fn test_raw_nodes() {
+        let mut tree = ART::<String, u32, 2>::new();
+        tree.insert("prefix-a".to_string(), 1);
+        tree.insert("prefix-b".to_string(), 2);
+        for c in 'c'..='h' {
+            tree.insert(format!("prefix-{c}"), u32::from(c));
+        }
+        let root = tree.raw_root().unwrap();
+        assert_eq!(root.kind(), NodeKind::Node16);
+        assert_eq!(root.prefix_len(), 7);
+        assert_eq!(root.partial(), b"prefix-");
+        assert_eq!(root.leaf(), None);
+        let keys: Vec<u8> = root.children().map(|(key, _)| key).collect();
+        assert_eq!(keys, b"abcdefgh");
+
+        let child = root.child(b'b').unwrap();
+        assert_eq!(child.kind(), NodeKind::Leaf);
+        assert_eq!(child.leaf(), Some((&"prefix-b".to_string(), &2)));
+        assert_eq!(child.children().count(), 0);
+        assert!(root.child(b'z').is_none());
+    }
+
+    #[test]
+    fn test_long_prefixes() {
+        let long = "a".repeat(40);
+        let mut tree = ART::<String, u32, 2>::new();
+        tree.insert(format!("{long}x"), 1);
+        tree.insert(format!("{long}y"), 2);
+        assert_eq!(tree.raw_root().unwrap().partial(), long.as_bytes());
+
+        // Splitting the prefix in the middle keeps both halves.
+        tree.insert(format!("{}z", &long[..20]), 3);
+        let root = tree.raw_root().unwrap();
+        assert_eq!(root.partial(), &long.as_bytes()[..20]);
+        assert_eq!(root.child(b'a').unwrap().partial(), &long.as_bytes()[21..]);
+        assert_eq!(tree.search(&format!("{long}y")), Some(&2));
+        assert_eq!(tree.search(&format!("{}y", &long[..39])), None);
+
+        // Merging a node into its child joins the prefixes again.
+        tree.delete(&format!("{}z", &long[..20]));
+        assert_eq!(tree.raw_root().unwrap().partial(), long.as_bytes());
+        assert_eq!(tree.search(&format!("{long}x")), Some(&1));
+    }
+}
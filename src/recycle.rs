@@ -0,0 +1,246 @@
+//! A tree that recycles the memory of its nodes through a pool of the current thread.
+//!
+//! Every insert into an [`ART`](crate::ART) allocates a box for the new leaf, every delete frees
+//! one, and inner nodes that grow into or shrink out of the kinds with 48 or 256 children allocate
+//! and free the boxes of their indices. Under heavy churn, these allocations put a lot of pressure
+//! on the allocator. A [`RecyclingArt`] keeps the boxes it frees in a pool of the current thread,
+//! by the type of the box, and takes boxes from the pool before allocating new ones, so that a tree
+//! whose size stays about the same stops allocating after warming up. The pool is shared by all
+//! recycling trees on the thread, and holds at most [`POOL_LIMIT`] boxes of each type, so that a
+//! tree that shrinks for good does not keep its memory forever. [`clear`] frees the pool of the
+//! current thread right away.
+//!
+//! The pool is keyed by the type of the boxes, which is only known for types that contain no
+//! references, so the keys and values of a recycling tree must be `'static`.
+
+use std::{
+    any::{Any, TypeId},
+    borrow::Borrow,
+    cell::RefCell,
+    collections::HashMap,
+};
+
+use crate::{
+    node::{debug_print, Node, Path, Recycled},
+    BytesComparable,
+};
+
+/// The maximum number of boxes of each type kept in the pool of a thread.
+pub const POOL_LIMIT: usize = 256;
+
+thread_local! {
+    static POOL: RefCell<HashMap<TypeId, Vec<Box<dyn Any>>>> = RefCell::new(HashMap::new());
+}
+
+/// Return the number of boxes in the pool of the current thread.
+#[must_use]
+pub fn pooled() -> usize {
+    POOL.with_borrow(|pool| pool.values().map(Vec::len).sum())
+}
+
+/// Free all boxes in the pool of the current thread.
+pub fn clear() {
+    // The boxes are dropped after the pool is released, in case dropping them uses the pool.
+    drop(POOL.take());
+}
+
+/// Moves the value into a box from the pool of the current thread, or into a new box if the pool
+/// holds none of its type.
+#[allow(clippy::unnecessary_box_returns)] // The box is what is reused.
+pub(crate) fn reuse<T: 'static>(value: T) -> Box<T> {
+    let pooled = POOL.with_borrow_mut(|pool| pool.get_mut(&TypeId::of::<T>())?.pop());
+    match pooled {
+        Some(pooled) => {
+            let mut boxed = pooled
+                .downcast::<T>()
+                .expect("boxes are pooled by their type");
+            *boxed = value;
+            boxed
+        }
+        None => Box::new(value),
+    }
+}
+
+/// Puts the box into the pool of the current thread, or frees it if the pool is full.
+pub(crate) fn release<T: 'static>(boxed: Box<T>) {
+    let rejected = POOL.with_borrow_mut(|pool| {
+        let pooled = pool.entry(TypeId::of::<T>()).or_default();
+        if pooled.len() < POOL_LIMIT {
+            pooled.push(boxed);
+            None
+        } else {
+            Some(boxed)
+        }
+    });
+    drop(rejected);
+}
+
+/// An adaptive radix tree that reuses the boxes of its freed nodes, see the [module
+/// documentation](self).
+pub struct RecyclingArt<K, V, const N: usize = 10>
+where
+    K: 'static,
+    V: 'static,
+{
+    root: Option<Node<K, V, N, Recycled>>,
+    len: usize,
+}
+
+impl<K, V, const N: usize> std::fmt::Debug for RecyclingArt<K, V, N>
+where
+    K: std::fmt::Debug + 'static,
+    V: std::fmt::Debug + 'static,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(root) = &self.root {
+            debug_print(f, root, 0, 0)
+        } else {
+            writeln!(f, "empty")
+        }
+    }
+}
+
+impl<K, V, const N: usize> RecyclingArt<K, V, N>
+where
+    K: 'static,
+    V: 'static,
+{
+    /// Create an empty tree.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { root: None, len: 0 }
+    }
+
+    /// Return the number of entries in the tree.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the tree holds no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return an iterator over the entries in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        let mut path = Path::new(self.root.as_ref());
+        path.seek_first();
+        std::iter::from_fn(move || {
+            let leaf = path.leaf()?;
+            path.next();
+            Some((&leaf.key, &leaf.value))
+        })
+    }
+}
+
+impl<K, V, const N: usize> Default for RecyclingArt<K, V, N>
+where
+    K: 'static,
+    V: 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> RecyclingArt<K, V, N>
+where
+    K: BytesComparable + 'static,
+    V: 'static,
+{
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.root
+            .as_ref()
+            .and_then(|node| node.search(key.bytes().as_ref(), 0))
+            .map(|leaf| &leaf.value)
+    }
+
+    /// Insert the given key-value pair into the tree, returning the value previously associated
+    /// with the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let replaced = Node::insert_root(&mut self.root, key, value, true);
+        if replaced.is_none() {
+            self.len += 1;
+        }
+        replaced
+    }
+
+    /// Delete the value associated with the given key.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let leaf = Node::delete_root(&mut self.root, key.bytes().as_ref(), true)?;
+        self.len -= 1;
+        Some(leaf.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::Rng;
+
+    use super::{clear, pooled, RecyclingArt, POOL_LIMIT};
+
+    #[test]
+    fn test_matches_btree() {
+        let mut rng = rand::thread_rng();
+        let mut tree = RecyclingArt::<u16, usize>::new();
+        let mut expected = BTreeMap::new();
+        for idx in 0..20_000 {
+            let key = rng.gen_range(0..2000);
+            if rng.gen_bool(0.5) {
+                assert_eq!(tree.insert(key, idx), expected.insert(key, idx));
+            } else {
+                assert_eq!(tree.delete(&key), expected.remove(&key));
+            }
+        }
+        assert_eq!(tree.len(), expected.len());
+        assert!(tree.iter().eq(expected.iter()));
+        for (key, value) in &expected {
+            assert_eq!(tree.search(key), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_reuses_pool() {
+        clear();
+        let mut tree = RecyclingArt::<u32, u32>::new();
+        for i in 0..300 {
+            tree.insert(i, i);
+        }
+        assert_eq!(pooled(), 0);
+        for i in 0..300 {
+            tree.delete(&i);
+        }
+        // The boxes of the leaves and of the indices of the shrunk nodes are pooled, up to the
+        // limit for each type.
+        let released = pooled();
+        assert!(released > POOL_LIMIT);
+        assert!(released <= 3 * POOL_LIMIT);
+
+        // Churn on a tree of the same type takes the boxes from the pool.
+        for i in 0..100 {
+            tree.insert(i, i);
+        }
+        assert_eq!(pooled(), released - 100 - 1);
+        assert!(tree.iter().map(|(k, _)| *k).eq(0..100));
+
+        // Trees of other types do not share their boxes.
+        let mut other = RecyclingArt::<u32, u64>::new();
+        other.insert(1, 1);
+        assert_eq!(pooled(), released - 101);
+        clear();
+        assert_eq!(pooled(), 0);
+    }
+}
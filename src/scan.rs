@@ -0,0 +1,402 @@
+//! Scans over a range of keys that can be resumed later.
+//!
+//! A [`Scan`] yields the entries of a range of encoded keys in key order. At any point, it can
+//! export a [`ResumeToken`] made of the last key it yielded and the end of its range, and a scan
+//! resumed from the token yields the entries after that key, even when the tree was modified in
+//! between. Tokens can be encoded into bytes, so that a paginated API can hand them to its clients,
+//! or a scan can be continued by another process.
+
+use std::{
+    borrow::Borrow,
+    iter::FusedIterator,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{
+    node::{Leaf, Owned, Path},
+    BytesComparable, ART,
+};
+
+/// The state of a [`Scan`], from which a new scan can continue where it stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumeToken {
+    start: Bound<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+}
+
+/// An error returned when decoding bytes that were not produced by [`ResumeToken::to_bytes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTokenError;
+
+impl std::fmt::Display for InvalidTokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "resume token is truncated or contains an unknown bound")
+    }
+}
+
+impl std::error::Error for InvalidTokenError {}
+
+impl ResumeToken {
+    /// Return the bound the resumed scan starts from, which excludes the last key that was
+    /// yielded.
+    #[must_use]
+    pub fn start(&self) -> Bound<&[u8]> {
+        as_slice(&self.start)
+    }
+
+    /// Return the bound the resumed scan ends at.
+    #[must_use]
+    pub fn end(&self) -> Bound<&[u8]> {
+        as_slice(&self.end)
+    }
+
+    /// Encode the token into bytes. Each bound is encoded as a tag byte, followed by the length of
+    /// its key as a big-endian `u32` and the key itself for a bounded bound.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a key of the token is longer than `u32::MAX` bytes.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for bound in [&self.start, &self.end] {
+            let (tag, key) = match bound {
+                Bound::Unbounded => (0, None),
+                Bound::Included(key) => (1, Some(key)),
+                Bound::Excluded(key) => (2, Some(key)),
+            };
+            bytes.push(tag);
+            if let Some(key) = key {
+                let len = u32::try_from(key.len()).expect("the key must be shorter than 4 GiB");
+                bytes.extend_from_slice(&len.to_be_bytes());
+                bytes.extend_from_slice(key);
+            }
+        }
+        bytes
+    }
+
+    /// Decode a token from the bytes produced by [`ResumeToken::to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes are truncated, have trailing bytes, or contain an unknown
+    /// tag.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidTokenError> {
+        let (start, rest) = decode_bound(bytes)?;
+        let (end, rest) = decode_bound(rest)?;
+        if !rest.is_empty() {
+            return Err(InvalidTokenError);
+        }
+        Ok(Self { start, end })
+    }
+}
+
+fn decode_bound(bytes: &[u8]) -> Result<(Bound<Vec<u8>>, &[u8]), InvalidTokenError> {
+    let (&tag, rest) = bytes.split_first().ok_or(InvalidTokenError)?;
+    if tag == 0 {
+        return Ok((Bound::Unbounded, rest));
+    }
+    let (len, rest) = rest.split_first_chunk::<4>().ok_or(InvalidTokenError)?;
+    let len = usize::try_from(u32::from_be_bytes(*len)).map_err(|_| InvalidTokenError)?;
+    if rest.len() < len {
+        return Err(InvalidTokenError);
+    }
+    let (key, rest) = rest.split_at(len);
+    let bound = match tag {
+        1 => Bound::Included(key.to_vec()),
+        2 => Bound::Excluded(key.to_vec()),
+        _ => return Err(InvalidTokenError),
+    };
+    Ok((bound, rest))
+}
+
+fn as_slice(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key),
+        Bound::Excluded(key) => Bound::Excluded(key),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+fn to_vec<T: AsRef<[u8]>>(bound: Bound<T>) -> Bound<Vec<u8>> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key.as_ref().to_vec()),
+        Bound::Excluded(key) => Bound::Excluded(key.as_ref().to_vec()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// An iterator over the entries within a range of encoded keys in key order, which can be resumed
+/// from a [`ResumeToken`], see [`ART::scan`].
+pub struct Scan<
+    'a,
+    K,
+    V,
+    const N: usize = 10,
+    const C4: usize = 4,
+    const C16: usize = 16,
+    const C48: usize = 48,
+> {
+    path: Path<'a, K, V, N, Owned, C4, C16, C48>,
+    start: Bound<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+    last: Option<&'a Leaf<K, V>>,
+    /// The number of leaves in the tree that were not yielded yet, which bounds the number of
+    /// entries left in the range.
+    remaining: usize,
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    Scan<'_, K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+    /// Return a token from which a new scan yields the entries this scan has not yielded yet.
+    #[must_use]
+    pub fn resume_token(&self) -> ResumeToken {
+        let start = self.last.map_or_else(
+            || self.start.clone(),
+            |leaf| Bound::Excluded(leaf.key.bytes().as_ref().to_vec()),
+        );
+        ResumeToken {
+            start,
+            end: self.end.clone(),
+        }
+    }
+}
+
+impl<'a, K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> Iterator
+    for Scan<'a, K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let leaf = self.path.leaf()?;
+        let key = leaf.key.bytes();
+        let in_range = match &self.end {
+            Bound::Included(end) => key.as_ref() <= end.as_slice(),
+            Bound::Excluded(end) => key.as_ref() < end.as_slice(),
+            Bound::Unbounded => true,
+        };
+        if !in_range {
+            // Leave the path past the end, so that the bound is not compared again.
+            self.path = Path::new(None);
+            self.remaining = 0;
+            return None;
+        }
+        self.path.next();
+        self.last = Some(leaf);
+        self.remaining -= 1;
+        Some((&leaf.key, &leaf.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> FusedIterator
+    for Scan<'_, K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize> std::fmt::Debug
+    for Scan<'_, K, V, N, C4, C16, C48>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scan")
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    ART<K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+    /// Return a resumable iterator over the entries within the given range in key order.
+    pub fn scan<Q, R>(&self, range: R) -> Scan<'_, K, V, N, C4, C16, C48>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let start = to_vec(range.start_bound().map(BytesComparable::bytes));
+        let end = to_vec(range.end_bound().map(BytesComparable::bytes));
+        self.scan_bytes(start, end)
+    }
+
+    /// Return a resumable iterator over the entries whose encoded keys start with the given
+    /// prefix in key order.
+    #[must_use]
+    pub fn scan_prefix(&self, prefix: &[u8]) -> Scan<'_, K, V, N, C4, C16, C48> {
+        // The keys with the prefix end before the prefix whose last byte that can be incremented
+        // is incremented. No such prefix exists if all bytes are 0xFF.
+        let mut end = prefix.to_vec();
+        while end.last() == Some(&u8::MAX) {
+            end.pop();
+        }
+        let end = end.pop().map_or(Bound::Unbounded, |last| {
+            end.push(last + 1);
+            Bound::Excluded(end)
+        });
+        self.scan_bytes(Bound::Included(prefix.to_vec()), end)
+    }
+
+    /// Return up to `limit` entries whose keys are strictly greater than the given key, in key
+    /// order. Passing the last key of a page returns the next page, which is how keyset
+    /// pagination walks through the tree.
+    pub fn scan_after<Q>(&self, key: &Q, limit: usize) -> Vec<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let start = Bound::Excluded(key.bytes().as_ref().to_vec());
+        self.scan_bytes(start, Bound::Unbounded)
+            .take(limit)
+            .collect()
+    }
+
+    /// Return a resumable iterator over the entries a scan had not yielded when it exported the
+    /// given token.
+    #[must_use]
+    pub fn resume(&self, token: &ResumeToken) -> Scan<'_, K, V, N, C4, C16, C48> {
+        self.scan_bytes(token.start.clone(), token.end.clone())
+    }
+
+    fn scan_bytes(
+        &self,
+        start: Bound<Vec<u8>>,
+        end: Bound<Vec<u8>>,
+    ) -> Scan<'_, K, V, N, C4, C16, C48> {
+        let mut path = Path::new(self.root.as_ref());
+        match &start {
+            Bound::Included(start) => path.seek(start),
+            Bound::Excluded(start) => {
+                path.seek(start);
+                if path
+                    .leaf()
+                    .is_some_and(|leaf| leaf.key.bytes().as_ref() == start.as_slice())
+                {
+                    path.next();
+                }
+            }
+            Bound::Unbounded => path.seek_first(),
+        }
+        Scan {
+            path,
+            start,
+            end,
+            last: None,
+            remaining: self.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use super::{InvalidTokenError, ResumeToken};
+    use crate::ART;
+
+    #[test]
+    fn test_scan_pages() {
+        let mut tree: ART<u32, u32> = (0..100).map(|i| (i, i)).collect();
+        let mut scan = tree.scan(10..90);
+        let page: Vec<_> = scan.by_ref().take(30).map(|(k, _)| *k).collect();
+        assert_eq!(page, (10..40).collect::<Vec<_>>());
+        let mut ended = tree.scan(95..);
+        assert_eq!(ended.by_ref().count(), 5);
+        assert_eq!(ended.next(), None);
+        assert_eq!(
+            ended.resume_token().start(),
+            Bound::Excluded(99_u32.to_be_bytes().as_slice())
+        );
+
+        // The token survives being encoded, and the tree being modified in between.
+        let bytes = scan.resume_token().to_bytes();
+        tree.delete(&40);
+        tree.insert(41, 0);
+        let token = ResumeToken::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            token.end(),
+            Bound::Excluded(90_u32.to_be_bytes().as_slice())
+        );
+        let rest: Vec<_> = tree.resume(&token).map(|(k, _)| *k).collect();
+        assert_eq!(rest, (41..90).collect::<Vec<_>>());
+
+        // A token exported before anything was yielded starts over.
+        let scan = tree.scan(..=5);
+        let rest: Vec<_> = tree.resume(&scan.resume_token()).map(|(k, _)| *k).collect();
+        assert_eq!(rest, (0..=5).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_scan_after() {
+        let tree: ART<String, usize> = (0..25).map(|i| (format!("key{i:02}"), i)).collect();
+        let mut pages = Vec::new();
+        let mut page = tree.scan::<str, _>(..).take(10).collect::<Vec<_>>();
+        while let Some(&(last, _)) = page.last() {
+            pages.push(page.iter().map(|(_, v)| **v).collect::<Vec<_>>());
+            page = tree.scan_after(last.as_str(), 10);
+        }
+        assert_eq!(
+            pages,
+            [
+                (0..10).collect(),
+                (10..20).collect(),
+                (20..25).collect::<Vec<_>>()
+            ]
+        );
+        // The key does not have to be in the tree.
+        let page: Vec<_> = tree
+            .scan_after("key175", 2)
+            .into_iter()
+            .map(|(_, v)| *v)
+            .collect();
+        assert_eq!(page, [18, 19]);
+        assert!(tree.scan_after("key00", 0).is_empty());
+    }
+
+    #[test]
+    fn test_scan_prefix() {
+        let tree: ART<Vec<u8>, ()> = [
+            vec![1, 0xFF],
+            vec![1, 0xFF, 0xFF],
+            vec![2],
+            vec![0xFF, 0xFF, 1],
+            vec![0xFF],
+        ]
+        .into_iter()
+        .map(|key| (key, ()))
+        .collect();
+        let keys: Vec<_> = tree.scan_prefix(&[1, 0xFF]).map(|(k, ())| k).collect();
+        assert_eq!(keys, [&vec![1, 0xFF], &vec![1, 0xFF, 0xFF]]);
+        let keys: Vec<_> = tree.scan_prefix(&[0xFF]).map(|(k, ())| k).collect();
+        assert_eq!(keys, [&vec![0xFF], &vec![0xFF, 0xFF, 1]]);
+        assert_eq!(tree.scan_prefix(&[]).count(), 5);
+    }
+
+    #[test]
+    fn test_invalid_token() {
+        let token = ResumeToken {
+            start: Bound::Excluded(b"key".to_vec()),
+            end: Bound::Unbounded,
+        };
+        let bytes = token.to_bytes();
+        assert_eq!(ResumeToken::from_bytes(&bytes), Ok(token));
+        assert_eq!(
+            ResumeToken::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(InvalidTokenError)
+        );
+        assert_eq!(ResumeToken::from_bytes(&[3, 0]), Err(InvalidTokenError));
+        assert_eq!(ResumeToken::from_bytes(&[0, 0, 0]), Err(InvalidTokenError));
+    }
+}
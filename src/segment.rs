@@ -0,0 +1,113 @@
+//! Helpers to build keys out of several variable-length fields.
+//!
+//! Concatenating the bytes of variable-length fields does not preserve their order, because a
+//! field that is a prefix of another is followed by the bytes of the next field instead of ending.
+//! Every segment encoded by [`encode_segment`] therefore ends with [`TERMINATOR`], and every zero
+//! byte within the segment is replaced by [`ESCAPED_ZERO`]. The terminator sorts before any byte
+//! that can continue a segment, so comparing encoded keys byte by byte compares their fields one
+//! after another, and no encoded key is a prefix of another one with a different first field.
+
+/// The byte starting an escape sequence.
+pub const ESCAPE: u8 = 0x00;
+
+/// The bytes ending a segment.
+pub const TERMINATOR: [u8; 2] = [ESCAPE, 0x01];
+
+/// The bytes standing for a zero byte within a segment.
+pub const ESCAPED_ZERO: [u8; 2] = [ESCAPE, 0xFF];
+
+/// An error returned when decoding bytes that were not produced by [`encode_segment`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidSegmentError;
+
+impl std::fmt::Display for InvalidSegmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "segment is not terminated or contains an invalid escape sequence"
+        )
+    }
+}
+
+impl std::error::Error for InvalidSegmentError {}
+
+/// Append the escaped segment followed by [`TERMINATOR`] to the buffer.
+pub fn encode_segment(segment: &[u8], buf: &mut Vec<u8>) {
+    for &b in segment {
+        if b == ESCAPE {
+            buf.extend_from_slice(&ESCAPED_ZERO);
+        } else {
+            buf.push(b);
+        }
+    }
+    buf.extend_from_slice(&TERMINATOR);
+}
+
+/// Decode the segment at the start of the bytes, returning it together with the bytes after its
+/// terminator.
+///
+/// # Errors
+///
+/// Returns an error if the bytes end before a terminator or contain an unknown escape sequence.
+pub fn decode_segment(bytes: &[u8]) -> Result<(Vec<u8>, &[u8]), InvalidSegmentError> {
+    let mut segment = Vec::new();
+    let mut rest = bytes;
+    loop {
+        let Some(pos) = rest.iter().position(|&b| b == ESCAPE) else {
+            return Err(InvalidSegmentError);
+        };
+        segment.extend_from_slice(&rest[..pos]);
+        match rest.get(pos + 1) {
+            Some(&b) if b == TERMINATOR[1] => return Ok((segment, &rest[pos + 2..])),
+            Some(&b) if b == ESCAPED_ZERO[1] => segment.push(0),
+            _ => return Err(InvalidSegmentError),
+        }
+        rest = &rest[pos + 2..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::{decode_segment, encode_segment, InvalidSegmentError};
+
+    fn encode(fields: &[Vec<u8>]) -> Vec<u8> {
+        let mut key = Vec::new();
+        for field in fields {
+            encode_segment(field, &mut key);
+        }
+        key
+    }
+
+    #[test]
+    fn test_segments_keep_order() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..10000 {
+            let fields: Vec<Vec<Vec<u8>>> = (0..2)
+                .map(|_| {
+                    (0..2)
+                        .map(|_| {
+                            let len = rng.gen_range(0..4);
+                            (0..len).map(|_| rng.gen_range(0..3)).collect()
+                        })
+                        .collect()
+                })
+                .collect();
+            let (a, b) = (encode(&fields[0]), encode(&fields[1]));
+            assert_eq!(a.cmp(&b), fields[0].cmp(&fields[1]));
+
+            let (first, rest) = decode_segment(&a).unwrap();
+            let (second, rest) = decode_segment(rest).unwrap();
+            assert_eq!([first, second].as_slice(), fields[0].as_slice());
+            assert!(rest.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_invalid_segments() {
+        assert_eq!(decode_segment(b"abc"), Err(InvalidSegmentError));
+        assert_eq!(decode_segment(b"a\0"), Err(InvalidSegmentError));
+        assert_eq!(decode_segment(b"a\0\x02b\0\x01"), Err(InvalidSegmentError));
+    }
+}
@@ -0,0 +1,154 @@
+//! A set of keys stored in an adaptive radix tree.
+//!
+//! An [`ArtSet`] is a tree whose values are `()`, which take no space in its leaves. The leaves are
+//! not stored inline in their parents, though. Every key is stored in a leaf that is allocated on
+//! its own with the size of a whole node, like the leaves of any other tree, because the inner
+//! nodes can only hold pointers to their children. A set therefore costs as much memory per key as
+//! a tree with small values.
+
+use std::borrow::Borrow;
+
+use crate::{BytesComparable, ART};
+
+/// A set of keys ordered by their encoded bytes.
+#[derive(Debug)]
+pub struct ArtSet<K, const N: usize = 10> {
+    tree: ART<K, (), N>,
+}
+
+impl<K, const N: usize> ArtSet<K, N> {
+    /// Create an empty set.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { tree: ART::new() }
+    }
+
+    /// Return the number of keys in the set.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Return true if the set holds no key.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Return an iterator over the keys in order.
+    #[must_use]
+    pub fn iter(&self) -> Iter<'_, K, N> {
+        Iter(self.tree.iter())
+    }
+}
+
+impl<K, const N: usize> Default for ArtSet<K, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, const N: usize> ArtSet<K, N>
+where
+    K: BytesComparable,
+{
+    /// Return true if the set holds the given key.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(key).is_some()
+    }
+
+    /// Add the given key to the set, returning true if it was not in the set before.
+    pub fn insert(&mut self, key: K) -> bool {
+        self.tree.insert(key, ()).is_none()
+    }
+
+    /// Remove the given key from the set, returning true if it was in the set.
+    pub fn remove<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.delete(key).is_some()
+    }
+}
+
+impl<K, const N: usize> FromIterator<K> for ArtSet<K, N>
+where
+    K: BytesComparable,
+{
+    fn from_iter<T: IntoIterator<Item = K>>(iter: T) -> Self {
+        Self {
+            tree: iter.into_iter().map(|key| (key, ())).collect(),
+        }
+    }
+}
+
+impl<'a, K, const N: usize> IntoIterator for &'a ArtSet<K, N> {
+    type Item = &'a K;
+    type IntoIter = Iter<'a, K, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the keys of an [`ArtSet`] in order.
+#[derive(Debug)]
+pub struct Iter<'a, K, const N: usize>(crate::iter::Iter<'a, K, (), N>);
+
+impl<'a, K, const N: usize> Iterator for Iter<'a, K, N> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(key, ())| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+
+    fn fold<B, F>(self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, Self::Item) -> B,
+    {
+        self.0.fold(init, |acc, (key, ())| f(acc, key))
+    }
+}
+
+impl<K, const N: usize> ExactSizeIterator for Iter<'_, K, N> {}
+
+impl<K, const N: usize> std::iter::FusedIterator for Iter<'_, K, N> {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use rand::Rng;
+
+    use super::ArtSet;
+
+    #[test]
+    fn test_set() {
+        let mut rng = rand::thread_rng();
+        let mut set = ArtSet::<u32>::new();
+        let mut expected = BTreeSet::new();
+        for _ in 0..1000 {
+            let key = rng.gen_range(0..500);
+            if rng.gen_bool(0.7) {
+                assert_eq!(set.insert(key), expected.insert(key));
+            } else {
+                assert_eq!(set.remove(&key), expected.remove(&key));
+            }
+            assert_eq!(set.contains(&key), expected.contains(&key));
+        }
+        assert_eq!(set.len(), expected.len());
+        assert!(set.iter().eq(expected.iter()));
+
+        let collected: ArtSet<u32> = expected.iter().copied().collect();
+        assert!(collected.iter().eq(&set));
+    }
+}
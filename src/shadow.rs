@@ -0,0 +1,771 @@
+//! A crash-consistent file store based on shadow paging.
+//!
+//! A [`ShadowArt`] keeps its entries in memory, split into one tree per leading key byte like a
+//! [`crate::prefix_sharded::PrefixShardedArt`], and mirrors them into a file made of fixed-size
+//! pages. Committed pages are never overwritten. A commit writes every shard changed since the
+//! last commit into free pages, followed by a page table locating all shards, and then makes the
+//! new table current by writing a superblock that points to it. The file keeps two superblock
+//! slots that are written alternately and carry a generation and a checksum, so a crash at any
+//! point leaves either the previous or the new superblock intact, and opening the file recovers
+//! the latest complete commit without a write-ahead log. The pages that a commit replaced are
+//! reused by later commits, which keeps the file from growing without bound.
+//!
+//! The layout of the file is:
+//!
+//! - Page 0 holds the two superblock slots, at offsets 0 and 512.
+//! - Every other page belongs to a shard, to the page table, or is free. Shards and the page table
+//!   are stored in runs of contiguous pages, each one protected by a checksum.
+//! - A shard is stored in the format of [`ART::dump`], and the page table is an array of 256
+//!   extents, each made of the first page, the length in bytes, and the checksum of a shard.
+//!
+//! Every commit synchronizes the file twice, which dominates the cost of small changes. Changes
+//! made with [`ShadowArt::insert_with`] and [`ShadowArt::delete_with`] are committed as set by
+//! their [`Durability`]: right away, in groups that share a single commit once enough changes are
+//! pending or the oldest of them has waited long enough, or only by an explicit commit.
+//! [`ShadowArt::set_durability`] sets the durability of [`ShadowArt::insert_durable`] and
+//! [`ShadowArt::delete_durable`] for the whole store.
+
+use std::{
+    borrow::Borrow,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    persist::{Decode, Encode},
+    prefix_sharded::{shard_of, SHARDS},
+    BytesComparable, ART,
+};
+
+/// The size in bytes of a page of the file.
+pub const PAGE_SIZE: usize = 4096;
+
+/// The magic bytes at the start of a superblock.
+const MAGIC: &[u8; 8] = b"YAARTSHD";
+
+/// The size in bytes of a superblock: the magic, the generation, the extent of the page table, and
+/// the checksum of the superblock itself.
+const SUPERBLOCK_SIZE: usize = 48;
+
+/// The offsets of the two superblock slots, which lie in different sectors of the first page.
+const SLOTS: [u64; 2] = [0, 512];
+
+/// The size in bytes of an encoded extent.
+const EXTENT_SIZE: usize = 24;
+
+/// When a change made to a [`ShadowArt`] is committed to its file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Durability {
+    /// The change is committed by the next commit, whenever it happens.
+    #[default]
+    None,
+    /// The change is committed together with the other pending changes once the group commit
+    /// window of the store is full, see [`ShadowArt::set_group_commit`].
+    Async,
+    /// The change and all other pending changes are committed before the operation returns.
+    Sync,
+}
+
+/// A run of contiguous pages holding `len` bytes with the given checksum.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Extent {
+    page: u64,
+    len: u64,
+    checksum: u64,
+}
+
+impl Extent {
+    fn pages(self) -> usize {
+        usize::try_from(self.len)
+            .expect("extent length fits in memory")
+            .div_ceil(PAGE_SIZE)
+    }
+
+    fn encode(self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.page.to_be_bytes());
+        buf.extend_from_slice(&self.len.to_be_bytes());
+        buf.extend_from_slice(&self.checksum.to_be_bytes());
+    }
+
+    fn decode(buf: &[u8]) -> Self {
+        let word =
+            |idx: usize| u64::from_be_bytes(buf[idx * 8..idx * 8 + 8].try_into().expect("8 bytes"));
+        Self {
+            page: word(0),
+            len: word(1),
+            checksum: word(2),
+        }
+    }
+}
+
+/// The pages of a file that are referenced by the current commit or by the commit in progress.
+#[derive(Debug, Default)]
+struct Pages {
+    used: Vec<bool>,
+}
+
+impl Pages {
+    /// Mark the pages of the given extent as used or free.
+    fn mark(&mut self, extent: Extent, used: bool) {
+        let first = usize::try_from(extent.page).expect("page index fits in memory");
+        let last = first + extent.pages();
+        if self.used.len() < last {
+            self.used.resize(last, false);
+        }
+        self.used[first..last].fill(used);
+    }
+
+    /// Find the first run of free pages that can hold `len` bytes and mark it as used, extending
+    /// the file if no run is long enough.
+    fn allocate(&mut self, len: usize) -> Extent {
+        let pages = len.div_ceil(PAGE_SIZE);
+        let mut start = 0;
+        let mut run = 0;
+        for (idx, &used) in self.used.iter().enumerate() {
+            if pages == 0 {
+                break;
+            }
+            if used {
+                run = 0;
+                start = idx + 1;
+            } else {
+                run += 1;
+                if run == pages {
+                    break;
+                }
+            }
+        }
+        let extent = Extent {
+            page: start as u64,
+            len: len as u64,
+            checksum: 0,
+        };
+        self.mark(extent, true);
+        extent
+    }
+}
+
+/// A map whose entries are committed to a file using shadow paging.
+///
+/// Changes are applied in memory and become durable when [`ShadowArt::commit`] returns, or as set
+/// by their [`Durability`]. After a crash, [`ShadowArt::open`] restores the entries of the last
+/// successful commit.
+#[derive(Debug)]
+pub struct ShadowArt<K, V> {
+    file: File,
+    shards: Vec<ART<K, V>>,
+    dirty: Vec<bool>,
+    extents: Vec<Extent>,
+    table: Extent,
+    generation: u64,
+    pages: Pages,
+    durability: Durability,
+    /// The number of pending changes that fill the group commit window.
+    group_size: usize,
+    /// The time that the oldest pending change waits at most for the group commit window to fill.
+    group_delay: Duration,
+    /// The number of changes since the last commit.
+    pending: usize,
+    /// The time of the oldest change since the last commit.
+    pending_since: Option<Instant>,
+}
+
+impl<K, V> ShadowArt<K, V>
+where
+    K: BytesComparable + Encode + Decode,
+    V: Encode + Decode,
+{
+    /// Open the store in the file at the given path, creating an empty store if the file does not
+    /// exist, and load the entries of its last commit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can not be opened or read, if it is not a store, or if the
+    /// pages of the last commit are corrupted.
+    pub fn open<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut store = Self {
+            file,
+            shards: (0..SHARDS).map(|_| ART::new()).collect(),
+            dirty: vec![false; SHARDS],
+            extents: vec![Extent::default(); SHARDS],
+            table: Extent::default(),
+            generation: 0,
+            pages: Pages::default(),
+            durability: Durability::None,
+            group_size: 64,
+            group_delay: Duration::from_millis(10),
+            pending: 0,
+            pending_since: None,
+        };
+        store.pages.mark(
+            Extent {
+                page: 0,
+                len: PAGE_SIZE as u64,
+                checksum: 0,
+            },
+            true,
+        );
+        if let Some((generation, table)) = store.read_superblock()? {
+            let buf = store.read_extent(table)?;
+            if buf.len() != SHARDS * EXTENT_SIZE {
+                return Err(invalid_data("the page table has an invalid length"));
+            }
+            store.generation = generation;
+            store.table = table;
+            store.pages.mark(table, true);
+            for (idx, entry) in buf.chunks_exact(EXTENT_SIZE).enumerate() {
+                let extent = Extent::decode(entry);
+                store.pages.mark(extent, true);
+                store.extents[idx] = extent;
+                store.shards[idx] = store.read_shard(extent)?;
+            }
+        }
+        Ok(store)
+    }
+
+    /// Write the shards changed since the last commit into free pages and atomically make them
+    /// part of the committed state of the file.
+    ///
+    /// Nothing is written if no shard changed. If an error is returned, the committed state of the
+    /// file is the one before the call, and the changes are kept in memory for a later commit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can not be written or synchronized.
+    pub fn commit(&mut self) -> io::Result<()> {
+        if !self.dirty.contains(&true) {
+            self.pending = 0;
+            self.pending_since = None;
+            return Ok(());
+        }
+        let mut allocated = Vec::new();
+        match self.write_commit(&mut allocated) {
+            Ok((extents, table)) => {
+                for (idx, extent) in extents.into_iter().enumerate() {
+                    if self.dirty[idx] {
+                        self.pages.mark(self.extents[idx], false);
+                        self.extents[idx] = extent;
+                    }
+                }
+                self.pages.mark(self.table, false);
+                self.table = table;
+                self.generation += 1;
+                self.dirty.fill(false);
+                self.pending = 0;
+                self.pending_since = None;
+                Ok(())
+            }
+            Err(error) => {
+                for extent in allocated {
+                    self.pages.mark(extent, false);
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Discard the changes made since the last commit by reloading the changed shards from the
+    /// file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can not be read or if its pages are corrupted.
+    pub fn rollback(&mut self) -> io::Result<()> {
+        for idx in 0..SHARDS {
+            if self.dirty[idx] {
+                self.shards[idx] = self.read_shard(self.extents[idx])?;
+                self.dirty[idx] = false;
+            }
+        }
+        self.pending = 0;
+        self.pending_since = None;
+        Ok(())
+    }
+
+    /// Insert the given key-value pair into the map like [`ShadowArt::insert`], and commit the
+    /// change as set by the given durability.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the change had to be committed and the commit failed, in which case the
+    /// change stays in memory for a later commit, like all changes of a failed commit.
+    pub fn insert_with(
+        &mut self,
+        key: K,
+        value: V,
+        durability: Durability,
+    ) -> io::Result<Option<V>> {
+        let replaced = self.insert(key, value);
+        self.settle(durability)?;
+        Ok(replaced)
+    }
+
+    /// Delete the value associated with the given key like [`ShadowArt::delete`], and commit the
+    /// change as set by the given durability.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the change had to be committed and the commit failed, in which case the
+    /// change stays in memory for a later commit, like all changes of a failed commit.
+    pub fn delete_with<Q>(&mut self, key: &Q, durability: Durability) -> io::Result<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let value = self.delete(key);
+        self.settle(durability)?;
+        Ok(value)
+    }
+
+    /// Insert the given key-value pair into the map, and commit the change as set by the
+    /// durability of the store, see [`ShadowArt::insert_with`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the change had to be committed and the commit failed.
+    pub fn insert_durable(&mut self, key: K, value: V) -> io::Result<Option<V>> {
+        self.insert_with(key, value, self.durability)
+    }
+
+    /// Delete the value associated with the given key, and commit the change as set by the
+    /// durability of the store, see [`ShadowArt::delete_with`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the change had to be committed and the commit failed.
+    pub fn delete_durable<Q>(&mut self, key: &Q) -> io::Result<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.delete_with(key, self.durability)
+    }
+
+    /// Commit the pending changes if the given durability of the last one requires it.
+    fn settle(&mut self, durability: Durability) -> io::Result<()> {
+        let due = match durability {
+            Durability::None => false,
+            Durability::Async => {
+                self.pending >= self.group_size
+                    || self
+                        .pending_since
+                        .is_some_and(|since| since.elapsed() >= self.group_delay)
+            }
+            Durability::Sync => true,
+        };
+        if due {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Write the changed shards and a new page table, followed by the superblock that makes them
+    /// current, and return the new extents of all shards and of the page table. Every extent
+    /// written is pushed to `allocated`, such that the pages can be released on failure.
+    fn write_commit(&mut self, allocated: &mut Vec<Extent>) -> io::Result<(Vec<Extent>, Extent)> {
+        let mut extents = self.extents.clone();
+        let mut buf = Vec::new();
+        for (idx, extent) in extents.iter_mut().enumerate() {
+            if !self.dirty[idx] {
+                continue;
+            }
+            *extent = if self.shards[idx].is_empty() {
+                Extent::default()
+            } else {
+                buf.clear();
+                self.shards[idx].dump(&mut buf)?;
+                let extent = self.write_extent(&buf)?;
+                allocated.push(extent);
+                extent
+            };
+        }
+        buf.clear();
+        for extent in &extents {
+            extent.encode(&mut buf);
+        }
+        let table = self.write_extent(&buf)?;
+        allocated.push(table);
+        self.file.sync_data()?;
+
+        let generation = self.generation + 1;
+        let mut superblock = Vec::with_capacity(SUPERBLOCK_SIZE);
+        superblock.extend_from_slice(MAGIC);
+        superblock.extend_from_slice(&generation.to_be_bytes());
+        table.encode(&mut superblock);
+        superblock.extend_from_slice(&checksum(&superblock).to_be_bytes());
+        self.file
+            .seek(SeekFrom::Start(SLOTS[slot_of(generation)]))?;
+        self.file.write_all(&superblock)?;
+        self.file.sync_data()?;
+        Ok((extents, table))
+    }
+
+    /// Write the given bytes into free pages and return their extent.
+    fn write_extent(&mut self, buf: &[u8]) -> io::Result<Extent> {
+        let mut extent = self.pages.allocate(buf.len());
+        extent.checksum = checksum(buf);
+        let end = (extent.page + extent.pages() as u64) * PAGE_SIZE as u64;
+        let written = (|| {
+            if self.file.metadata()?.len() < end {
+                self.file.set_len(end)?;
+            }
+            self.file
+                .seek(SeekFrom::Start(extent.page * PAGE_SIZE as u64))?;
+            self.file.write_all(buf)
+        })();
+        if let Err(error) = written {
+            self.pages.mark(extent, false);
+            return Err(error);
+        }
+        Ok(extent)
+    }
+
+    fn read_extent(&mut self, extent: Extent) -> io::Result<Vec<u8>> {
+        let len = usize::try_from(extent.len).map_err(invalid_data)?;
+        let mut buf = vec![0; len];
+        self.file
+            .seek(SeekFrom::Start(extent.page * PAGE_SIZE as u64))?;
+        self.file.read_exact(&mut buf)?;
+        if checksum(&buf) != extent.checksum {
+            return Err(invalid_data(format!(
+                "checksum mismatch in the pages starting at {}",
+                extent.page
+            )));
+        }
+        Ok(buf)
+    }
+
+    fn read_shard(&mut self, extent: Extent) -> io::Result<ART<K, V>> {
+        if extent.len == 0 {
+            return Ok(ART::new());
+        }
+        let buf = self.read_extent(extent)?;
+        ART::load(buf.as_slice())
+    }
+
+    /// Read both superblock slots and return the generation and the page table of the latest
+    /// valid one, or `None` if the file has never been committed to.
+    fn read_superblock(&mut self) -> io::Result<Option<(u64, Extent)>> {
+        let mut latest = None;
+        for offset in SLOTS {
+            let mut superblock = [0; SUPERBLOCK_SIZE];
+            self.file.seek(SeekFrom::Start(offset))?;
+            match self.file.read_exact(&mut superblock) {
+                Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => continue,
+                result => result?,
+            }
+            if superblock.iter().all(|&b| b == 0) {
+                continue;
+            }
+            if &superblock[..MAGIC.len()] != MAGIC {
+                return Err(invalid_data("not a yaart shadow store"));
+            }
+            let (body, sum) = superblock.split_at(SUPERBLOCK_SIZE - 8);
+            // A slot with a wrong checksum was torn by a crash during its commit, which therefore
+            // never completed.
+            if checksum(body).to_be_bytes() != sum {
+                continue;
+            }
+            let generation = u64::from_be_bytes(body[8..16].try_into().expect("8 bytes"));
+            let table = Extent::decode(&body[16..]);
+            if latest.is_none_or(|(latest, _)| latest < generation) {
+                latest = Some((generation, table));
+            }
+        }
+        Ok(latest)
+    }
+}
+
+impl<K, V> ShadowArt<K, V>
+where
+    K: BytesComparable,
+{
+    /// Return the number of entries in the map, including the changes that are not committed.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(ART::len).sum()
+    }
+
+    /// Return true if the map holds no entry.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(ART::is_empty)
+    }
+
+    /// Return the number of commits that the file went through.
+    #[must_use]
+    pub const fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Return true if the map has changes that are not committed.
+    #[must_use]
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.contains(&true)
+    }
+
+    /// Return the number of changes made since the last commit.
+    #[must_use]
+    pub const fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Return the durability of [`ShadowArt::insert_durable`] and [`ShadowArt::delete_durable`].
+    #[must_use]
+    pub const fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    /// Set the durability of [`ShadowArt::insert_durable`] and [`ShadowArt::delete_durable`],
+    /// which is [`Durability::None`] for a newly opened store.
+    pub const fn set_durability(&mut self, durability: Durability) {
+        self.durability = durability;
+    }
+
+    /// Set the window of group commits: a change made with [`Durability::Async`] commits all
+    /// pending changes once `size` changes are pending, or once the oldest of them is at least
+    /// `delay` old. A newly opened store groups up to 64 changes and 10 milliseconds.
+    ///
+    /// The window is only checked by changes, so the last changes of a burst stay pending until
+    /// the next change or commit.
+    pub const fn set_group_commit(&mut self, size: usize, delay: Duration) {
+        self.group_size = size;
+        self.group_delay = delay;
+    }
+
+    /// Record a change made since the last commit.
+    fn record_change(&mut self) {
+        self.pending += 1;
+        if self.pending_since.is_none() {
+            self.pending_since = Some(Instant::now());
+        }
+    }
+
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.shards[usize::from(shard_of(key))].search(key)
+    }
+
+    /// Insert the given key-value pair into the map, returning the previous value of the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let idx = usize::from(shard_of(&key));
+        self.dirty[idx] = true;
+        self.record_change();
+        self.shards[idx].insert(key, value)
+    }
+
+    /// Delete the value associated with the given key.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let idx = usize::from(shard_of(key));
+        let value = self.shards[idx].delete(key);
+        if value.is_some() {
+            self.dirty[idx] = true;
+            self.record_change();
+        }
+        value
+    }
+
+    /// Return an iterator over the entries in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.shards.iter().flat_map(ART::iter)
+    }
+}
+
+/// Return the slot of the superblock of the given generation.
+const fn slot_of(generation: u64) -> usize {
+    if generation.is_multiple_of(2) {
+        0
+    } else {
+        1
+    }
+}
+
+/// Compute the 64-bit FNV-1a hash of the given bytes, which detects torn and corrupted pages.
+fn checksum(buf: &[u8]) -> u64 {
+    buf.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::OpenOptions,
+        io::{Seek, SeekFrom, Write},
+        path::PathBuf,
+        time::Duration,
+    };
+
+    use super::{Durability, ShadowArt, PAGE_SIZE, SLOTS};
+
+    /// A file in the temporary directory that is removed when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("yaart-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_shadow_commit_and_reopen() {
+        let file = TempFile::new("commit");
+        let mut store = ShadowArt::<String, u64>::open(&file.0).unwrap();
+        assert!(store.is_empty());
+        for i in 0..1000u64 {
+            store.insert(format!("key{i}"), i);
+        }
+        store.insert(String::new(), 1000);
+        store.commit().unwrap();
+        assert_eq!(store.generation(), 1);
+        assert!(!store.is_dirty());
+
+        store.delete("key1");
+        store.insert("other".to_string(), 1);
+        store.commit().unwrap();
+        store.insert("uncommitted".to_string(), 2);
+        drop(store);
+
+        let mut store = ShadowArt::<String, u64>::open(&file.0).unwrap();
+        assert_eq!(store.generation(), 2);
+        assert_eq!(store.len(), 1001);
+        assert_eq!(store.search("key2"), Some(&2));
+        assert_eq!(store.search("key1"), None);
+        assert_eq!(store.search("other"), Some(&1));
+        assert_eq!(store.search(""), Some(&1000));
+        assert_eq!(store.search("uncommitted"), None);
+        assert!(store.iter().map(|(k, _)| k).is_sorted());
+
+        store.insert("key1".to_string(), 1);
+        store.delete("other");
+        store.rollback().unwrap();
+        assert_eq!(store.search("key1"), None);
+        assert_eq!(store.search("other"), Some(&1));
+    }
+
+    #[test]
+    fn test_shadow_recovers_from_torn_superblock() {
+        let file = TempFile::new("torn");
+        let mut store = ShadowArt::<u32, u32>::open(&file.0).unwrap();
+        store.insert(1, 1);
+        store.commit().unwrap();
+        store.insert(1, 2);
+        store.commit().unwrap();
+        drop(store);
+
+        // Corrupt the superblock of the second commit, as if the crash happened while writing it.
+        let mut raw = OpenOptions::new().write(true).open(&file.0).unwrap();
+        raw.seek(SeekFrom::Start(SLOTS[0] + 20)).unwrap();
+        raw.write_all(&[0xff; 4]).unwrap();
+        drop(raw);
+
+        let store = ShadowArt::<u32, u32>::open(&file.0).unwrap();
+        assert_eq!(store.generation(), 1);
+        assert_eq!(store.search(&1), Some(&1));
+    }
+
+    #[test]
+    fn test_shadow_reuses_pages() {
+        let file = TempFile::new("reuse");
+        let mut store = ShadowArt::<u32, u32>::open(&file.0).unwrap();
+        for round in 0..50 {
+            for i in 0..100 {
+                store.insert(i, round);
+            }
+            store.commit().unwrap();
+        }
+        // The superblocks, two copies of the shard and of the page table fit in eight pages.
+        let len = std::fs::metadata(&file.0).unwrap().len();
+        assert!(len <= 8 * PAGE_SIZE as u64, "file grew to {len} bytes");
+        drop(store);
+        let store = ShadowArt::<u32, u32>::open(&file.0).unwrap();
+        assert_eq!(store.search(&99), Some(&49));
+    }
+
+    #[test]
+    fn test_shadow_group_commit() {
+        let file = TempFile::new("group");
+        let mut store = ShadowArt::<u32, u32>::open(&file.0).unwrap();
+        assert_eq!(store.durability(), Durability::None);
+        store.insert_durable(0, 0).unwrap();
+        assert_eq!(store.generation(), 0);
+
+        // Every synchronous change commits the changes before it as well.
+        assert_eq!(store.insert_with(1, 1, Durability::Sync).unwrap(), None);
+        assert_eq!(store.generation(), 1);
+        assert_eq!(store.pending(), 0);
+
+        // Asynchronous changes share a commit once the window is full.
+        store.set_durability(Durability::Async);
+        store.set_group_commit(10, Duration::from_secs(60));
+        for i in 0..95 {
+            store.insert_durable(i, i + 1).unwrap();
+        }
+        assert_eq!(store.generation(), 10);
+        assert_eq!(store.pending(), 5);
+        assert_eq!(store.delete_durable(&1000).unwrap(), None);
+        assert_eq!(store.pending(), 5);
+        assert_eq!(store.delete_with(&94, Durability::Sync).unwrap(), Some(95));
+        assert_eq!(store.generation(), 11);
+
+        // A change that waited long enough commits with the next one.
+        store.set_group_commit(10, Duration::ZERO);
+        store.insert(200, 200);
+        store.insert_durable(201, 201).unwrap();
+        assert_eq!(store.generation(), 12);
+        store.insert_with(202, 202, Durability::None).unwrap();
+        drop(store);
+
+        let store = ShadowArt::<u32, u32>::open(&file.0).unwrap();
+        assert_eq!(store.len(), 96);
+        assert_eq!(store.search(&93), Some(&94));
+        assert_eq!(store.search(&94), None);
+        assert_eq!(store.search(&201), Some(&201));
+        assert_eq!(store.search(&202), None);
+    }
+
+    #[test]
+    fn test_shadow_rejects_foreign_file() {
+        let file = TempFile::new("foreign");
+        std::fs::write(
+            &file.0,
+            b"definitely not a store, but long enough for a slot....",
+        )
+        .unwrap();
+        assert!(ShadowArt::<u32, u32>::open(&file.0).is_err());
+    }
+}
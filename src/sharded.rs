@@ -0,0 +1,205 @@
+//! A concurrent map made of independently locked trees.
+
+use std::{
+    borrow::Borrow,
+    sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::{BytesComparable, ART};
+
+/// A map that routes each key to one of `S` trees, each protected by its own lock.
+///
+/// The shard of a key is chosen from the first two bytes of its encoded form, such that every shard
+/// owns a contiguous range of the key space and the shards are ordered. Operations on keys that
+/// land on different shards can proceed in parallel. Key sets whose leading bytes are skewed end up
+/// on a few shards, which limits the achievable parallelism.
+#[derive(Debug)]
+pub struct ShardedArt<K, V, const S: usize, const N: usize = 10> {
+    shards: Box<[RwLock<ART<K, V, N>>]>,
+}
+
+impl<K, V, const S: usize, const N: usize> ShardedArt<K, V, S, N> {
+    /// Asserts at compile time that every key has a shard.
+    const VALID_SHARDS: () = assert!(S > 0, "a sharded tree must have at least one shard");
+
+    /// Create an empty map.
+    #[must_use]
+    pub fn new() -> Self {
+        let () = Self::VALID_SHARDS;
+        Self {
+            shards: (0..S).map(|_| RwLock::new(ART::new())).collect(),
+        }
+    }
+
+    /// Consume the map and return its shards in key order.
+    #[must_use]
+    pub fn into_shards(self) -> Vec<ART<K, V, N>> {
+        self.shards
+            .into_vec()
+            .into_iter()
+            .map(|shard| shard.into_inner().unwrap_or_else(PoisonError::into_inner))
+            .collect()
+    }
+
+    fn read_shard(&self, idx: usize) -> RwLockReadGuard<'_, ART<K, V, N>> {
+        self.shards[idx]
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_shard(&self, idx: usize) -> RwLockWriteGuard<'_, ART<K, V, N>> {
+        self.shards[idx]
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<K, V, const S: usize, const N: usize> Default for ShardedArt<K, V, S, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const S: usize, const N: usize> ShardedArt<K, V, S, N>
+where
+    K: BytesComparable,
+{
+    /// Search for the value associated with the given key and return a copy of it.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        V: Clone,
+    {
+        self.with(key, V::clone)
+    }
+
+    /// Search for the value associated with the given key and call the closure on it while the
+    /// shard is locked for reading.
+    pub fn with<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        F: FnOnce(&V) -> R,
+    {
+        self.read_shard(shard_of::<S>(key.bytes().as_ref()))
+            .search(key)
+            .map(f)
+    }
+
+    /// Insert the given key-value pair into the map, returning the previous value of the key.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let idx = shard_of::<S>(key.bytes().as_ref());
+        self.write_shard(idx).insert(key, value)
+    }
+
+    /// Delete the value associated with the given key.
+    pub fn delete<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.write_shard(shard_of::<S>(key.bytes().as_ref()))
+            .delete(key)
+    }
+
+    /// Find the minimum key-value pair in the map and return a copy of it.
+    #[must_use]
+    pub fn min(&self) -> Option<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        (0..S).find_map(|idx| {
+            self.read_shard(idx)
+                .min()
+                .map(|(k, v)| (k.clone(), v.clone()))
+        })
+    }
+
+    /// Find the maximum key-value pair in the map and return a copy of it.
+    #[must_use]
+    pub fn max(&self) -> Option<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        (0..S).rev().find_map(|idx| {
+            self.read_shard(idx)
+                .max()
+                .map(|(k, v)| (k.clone(), v.clone()))
+        })
+    }
+}
+
+/// Maps the leading two bytes of a key onto one of `S` shards while preserving the key order.
+fn shard_of<const S: usize>(key: &[u8]) -> usize {
+    let hi = key.first().copied().unwrap_or(0);
+    let lo = key.get(1).copied().unwrap_or(0);
+    let prefix = usize::from(u16::from_be_bytes([hi, lo]));
+    (prefix * S) >> 16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{shard_of, ShardedArt};
+
+    #[test]
+    fn test_shard_of_preserves_order() {
+        let mut prev = 0;
+        for prefix in 0..=u16::MAX {
+            let idx = shard_of::<7>(&prefix.to_be_bytes());
+            assert!(idx >= prev && idx < 7);
+            prev = idx;
+        }
+        assert_eq!(prev, 6);
+        assert_eq!(shard_of::<7>(&[]), 0);
+    }
+
+    #[test]
+    fn test_sharded_concurrent_operations() {
+        let map = ShardedArt::<u32, u32, 16>::new();
+        std::thread::scope(|scope| {
+            for t in 0..8u32 {
+                let map = &map;
+                scope.spawn(move || {
+                    for i in 0..2000u32 {
+                        let key = i.wrapping_mul(2_654_435_761) ^ t;
+                        map.insert(key, t);
+                    }
+                });
+            }
+        });
+        for t in 0..8u32 {
+            for i in 0..2000u32 {
+                let key = i.wrapping_mul(2_654_435_761) ^ t;
+                assert_eq!(map.get(&key), Some(t));
+            }
+        }
+        let min = map.min().map(|(k, _)| k);
+        let max = map.max().map(|(k, _)| k);
+        let shards = map.into_shards();
+        assert_eq!(shards.len(), 16);
+        let mins: Vec<_> = shards
+            .iter()
+            .filter_map(|s| s.min().map(|(k, _)| *k))
+            .collect();
+        assert_eq!(min, mins.first().copied());
+        assert!(mins.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(
+            max,
+            shards.iter().rev().find_map(|s| s.max().map(|(k, _)| *k))
+        );
+    }
+
+    #[test]
+    fn test_sharded_delete() {
+        let map = ShardedArt::<String, usize, 4>::default();
+        assert_eq!(map.insert("hello".to_string(), 1), None);
+        assert_eq!(map.insert("world".to_string(), 2), None);
+        assert_eq!(map.insert("world".to_string(), 2), Some(2));
+        assert_eq!(map.delete("hello"), Some(1));
+        assert_eq!(map.delete("hello"), None);
+        assert_eq!(map.with("world", |v| v * 10), Some(20));
+    }
+}
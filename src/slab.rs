@@ -0,0 +1,235 @@
+//! A tree whose values live in a slab next to it.
+//!
+//! The leaves of a [`SlabArt`] only hold a [`Handle`], a `u32` index into a slab that owns the
+//! values. Nodes therefore stay small no matter how large the values are, and values never move
+//! when the tree grows, shrinks, or splits its nodes. A handle stays valid until its key is deleted
+//! and can be used to access the value without searching the tree again. The slot of a deleted
+//! value is reused by a later insert, so a handle must not be used after its key was deleted.
+//!
+//! The slab is made of chunks of [`CHUNK`] slots that are allocated once and never grow, so a
+//! growing slab adds chunks instead of moving the ones it has. The address of a value is therefore
+//! stable from the insert of its key until the key is deleted, and overwriting the value keeps it
+//! at the same address.
+
+use std::borrow::Borrow;
+
+use crate::{BytesComparable, ART};
+
+/// The position of a value in the slab of a [`SlabArt`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Handle(u32);
+
+impl Handle {
+    /// Return the index of the value in the slab.
+    #[must_use]
+    pub const fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+/// The number of slots in a chunk of the slab.
+pub const CHUNK: usize = 64;
+
+#[derive(Debug, Clone)]
+enum Slot<V> {
+    Occupied(V),
+    /// A free slot, linked to the next free slot.
+    Vacant(Option<u32>),
+}
+
+/// An adaptive radix tree whose leaves hold handles into a slab of values.
+#[derive(Debug)]
+pub struct SlabArt<K, V, const N: usize = 10> {
+    tree: ART<K, Handle, N>,
+    /// The chunks of the slab, each allocated with a capacity of [`CHUNK`] slots that is never
+    /// exceeded, so that pushing a slot never reallocates a chunk.
+    chunks: Vec<Vec<Slot<V>>>,
+    free: Option<u32>,
+    len: usize,
+}
+
+impl<K, V, const N: usize> SlabArt<K, V, N> {
+    /// Create an empty tree.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            tree: ART::new(),
+            chunks: Vec::new(),
+            free: None,
+            len: 0,
+        }
+    }
+
+    /// Return the number of values.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the tree holds no value.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the value behind the given handle.
+    #[must_use]
+    pub fn get(&self, handle: Handle) -> Option<&V> {
+        match self.slot(handle)? {
+            Slot::Occupied(value) => Some(value),
+            Slot::Vacant(_) => None,
+        }
+    }
+
+    /// Return the value behind the given handle for modification.
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut V> {
+        match self.slot_mut(handle)? {
+            Slot::Occupied(value) => Some(value),
+            Slot::Vacant(_) => None,
+        }
+    }
+
+    fn slot(&self, handle: Handle) -> Option<&Slot<V>> {
+        let idx = handle.0 as usize;
+        self.chunks.get(idx / CHUNK)?.get(idx % CHUNK)
+    }
+
+    fn slot_mut(&mut self, handle: Handle) -> Option<&mut Slot<V>> {
+        let idx = handle.0 as usize;
+        self.chunks.get_mut(idx / CHUNK)?.get_mut(idx % CHUNK)
+    }
+
+    fn alloc(&mut self, value: V) -> Handle {
+        self.len += 1;
+        if let Some(idx) = self.free {
+            let slot = self
+                .slot_mut(Handle(idx))
+                .expect("a slot in the free list must exist");
+            let Slot::Vacant(next) = std::mem::replace(slot, Slot::Occupied(value)) else {
+                unreachable!("a slot in the free list must be vacant")
+            };
+            self.free = next;
+            return Handle(idx);
+        }
+        if self.chunks.last().is_none_or(|chunk| chunk.len() == CHUNK) {
+            self.chunks.push(Vec::with_capacity(CHUNK));
+        }
+        let last = self.chunks.len() - 1;
+        let chunk = &mut self.chunks[last];
+        let idx = u32::try_from(last * CHUNK + chunk.len())
+            .expect("the number of values must fit in a u32");
+        chunk.push(Slot::Occupied(value));
+        Handle(idx)
+    }
+
+    fn release(&mut self, handle: Handle) -> V {
+        let free = self.free;
+        let slot = self
+            .slot_mut(handle)
+            .expect("a handle stored in the tree must point to a slot");
+        let slot = std::mem::replace(slot, Slot::Vacant(free));
+        let Slot::Occupied(value) = slot else {
+            unreachable!("a handle stored in the tree must point to an occupied slot")
+        };
+        self.free = Some(handle.0);
+        self.len -= 1;
+        value
+    }
+}
+
+impl<K, V, const N: usize> Default for SlabArt<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> SlabArt<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Return the handle of the value associated with the given key.
+    pub fn handle<Q>(&self, key: &Q) -> Option<Handle>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(key).copied()
+    }
+
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.handle(key).and_then(|handle| self.get(handle))
+    }
+
+    /// Insert the given key-value pair, returning the handle of the value and the value previously
+    /// associated with the key. Overwriting a value keeps its handle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than `u32::MAX` values are stored.
+    pub fn insert(&mut self, key: K, value: V) -> (Handle, Option<V>) {
+        if let Some(handle) = self.tree.search(&key).copied() {
+            let old = self
+                .get_mut(handle)
+                .map(|old| std::mem::replace(old, value));
+            return (handle, old);
+        }
+        let handle = self.alloc(value);
+        self.tree.insert(key, handle);
+        (handle, None)
+    }
+
+    /// Delete the value associated with the given key, releasing its handle.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let handle = self.tree.delete(key)?;
+        Some(self.release(handle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SlabArt, CHUNK};
+
+    #[test]
+    fn test_slab_handles() {
+        let mut tree = SlabArt::<String, Vec<u32>>::new();
+        let (a, _) = tree.insert("a".to_string(), vec![1]);
+        let (b, _) = tree.insert("b".to_string(), vec![2]);
+        assert_ne!(a, b);
+        assert_eq!(tree.insert("a".to_string(), vec![10]), (a, Some(vec![1])));
+        tree.get_mut(b).unwrap().push(20);
+        assert_eq!(tree.search("b"), Some(&vec![2, 20]));
+        assert_eq!(tree.handle("a"), Some(a));
+        assert_eq!(tree.len(), 2);
+
+        // Handles stay valid while the tree restructures its nodes, and values stay at their
+        // addresses while the slab grows.
+        let addr = std::ptr::from_ref(tree.get(a).unwrap());
+        for i in 0..1000 {
+            tree.insert(format!("key{i}"), vec![i]);
+        }
+        assert!(tree.len() > 10 * CHUNK);
+        assert_eq!(tree.get(a), Some(&vec![10]));
+        assert!(std::ptr::eq(tree.get(a).unwrap(), addr));
+        tree.insert("a".to_string(), vec![11]);
+        assert!(std::ptr::eq(tree.get(a).unwrap(), addr));
+        tree.insert("a".to_string(), vec![10]);
+
+        assert_eq!(tree.delete("a"), Some(vec![10]));
+        assert_eq!(tree.get(a), None);
+        assert_eq!(tree.delete("a"), None);
+        // The slot of a deleted value is reused.
+        let (c, _) = tree.insert("c".to_string(), vec![3]);
+        assert_eq!(c, a);
+        assert_eq!(tree.len(), 1002);
+        assert!(!tree.is_empty());
+    }
+}
@@ -0,0 +1,210 @@
+//! Splitting a tree into independent trees.
+//!
+//! An index that is spread over shards by the prefixes of its keys has to be redistributed when
+//! shards are added or removed. [`ART::partition_by_prefix`] splits a tree into one tree per
+//! prefix by detaching the subtree of every prefix and making it the root of a new tree, so the
+//! leaves and inner nodes are moved rather than rebuilt, and only the nodes above the prefixes are
+//! dropped. [`ART::take_subtree`] does the same for a single prefix, removing its keys from the
+//! tree and returning them as a tree of their own.
+
+use std::collections::{btree_map::Entry, BTreeMap};
+
+use crate::{node::Node, BytesComparable, ART};
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    ART<K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+    /// Split the tree into trees holding the keys that share their first `len` encoded bytes,
+    /// mapped by these bytes. A key shorter than `len` bytes is mapped by the whole key and is
+    /// alone in its tree, while the longer keys that it is a prefix of go to the trees of their own
+    /// first `len` bytes.
+    ///
+    /// Trees without path compression are split by moving their entries one by one.
+    #[must_use]
+    pub fn partition_by_prefix(self, len: usize) -> BTreeMap<Vec<u8>, Self> {
+        let mut trees = BTreeMap::new();
+        if !self.compress_paths {
+            for (key, value) in self {
+                let group = {
+                    let bytes = key.bytes();
+                    let bytes = bytes.as_ref();
+                    bytes[..len.min(bytes.len())].to_vec()
+                };
+                trees
+                    .entry(group)
+                    .or_insert_with(Self::without_path_compression)
+                    .insert(key, value);
+            }
+            return trees;
+        }
+        let Some(root) = self.root else {
+            return trees;
+        };
+        let mut pieces = Vec::new();
+        root.split_by_prefix(len, &mut Vec::new(), &mut pieces);
+        for (group, piece) in pieces {
+            match trees.entry(group) {
+                Entry::Vacant(entry) => {
+                    entry.insert(Self::from_root(Some(piece)));
+                }
+                Entry::Occupied(mut entry) => {
+                    for (key, value) in Self::from_root(Some(piece)) {
+                        entry.get_mut().insert(key, value);
+                    }
+                }
+            }
+        }
+        trees
+    }
+
+    /// Remove every key that starts with the given encoded prefix and return them as a new tree, or
+    /// `None` if no key starts with the prefix.
+    ///
+    /// The keys of a tree without path compression are moved one by one.
+    pub fn take_subtree(&mut self, prefix: &[u8]) -> Option<Self> {
+        if !self.compress_paths {
+            let mut taken = Self::without_path_compression();
+            for (key, value) in self.remove_where(|k, _| k.bytes().as_ref().starts_with(prefix)) {
+                taken.insert(key, value);
+            }
+            return (!taken.is_empty()).then_some(taken);
+        }
+        let root = self.mutate(|tree| Node::take_prefix_root(&mut tree.root, prefix))?;
+        let taken = Self::from_root(Some(root));
+        self.len -= taken.len();
+        Some(taken)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::Rng;
+
+    use crate::ART;
+
+    #[test]
+    fn test_partition_by_prefix() {
+        let mut rng = rand::thread_rng();
+        for compress in [true, false] {
+            let mut expected = BTreeMap::new();
+            for i in 0..3000 {
+                let key: Vec<u8> = (0..rng.gen_range(0..6))
+                    .map(|_| rng.gen_range(1..4))
+                    .collect();
+                expected.insert(key, i);
+            }
+            for len in [0, 1, 2, 4] {
+                let mut groups: BTreeMap<Vec<u8>, BTreeMap<Vec<u8>, usize>> = BTreeMap::new();
+                for (key, value) in &expected {
+                    let group = key[..len.min(key.len())].to_vec();
+                    groups.entry(group).or_default().insert(key.clone(), *value);
+                }
+                let mut tree = if compress {
+                    ART::<Vec<u8>, usize>::new()
+                } else {
+                    ART::without_path_compression()
+                };
+                for (key, value) in &expected {
+                    tree.insert(key.clone(), *value);
+                }
+                let trees = tree.partition_by_prefix(len);
+                assert!(trees.keys().eq(groups.keys()), "{len}");
+                for (group, tree) in &trees {
+                    let expected = &groups[group];
+                    assert_eq!(tree.len(), expected.len());
+                    assert!(tree.iter().eq(expected.iter()));
+                    assert_eq!(tree.compresses_paths(), compress);
+                }
+                // The trees can be modified on their own.
+                for (group, mut tree) in trees {
+                    let mut key = group.clone();
+                    key.extend_from_slice(&[7; 5]);
+                    assert_eq!(tree.insert(key.clone(), 0), None);
+                    assert_eq!(tree.search(&key), Some(&0));
+                    for existing in groups[&group].keys() {
+                        assert!(tree.delete(existing).is_some());
+                    }
+                    assert_eq!(tree.len(), 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_take_subtree() {
+        for compress in [true, false] {
+            let mut tree = if compress {
+                ART::<String, usize>::new()
+            } else {
+                ART::without_path_compression()
+            };
+            let keys = [
+                "user/alice/name",
+                "user/alice/mail",
+                "user/bob/name",
+                "user/bo",
+                "users",
+                "group/admins",
+            ];
+            for (idx, key) in keys.iter().enumerate() {
+                tree.insert((*key).to_string(), idx);
+            }
+            let alice = tree.take_subtree(b"user/alice/").unwrap();
+            assert_eq!(alice.len(), 2);
+            assert_eq!(alice.search("user/alice/mail"), Some(&1));
+            assert_eq!(tree.len(), 4);
+            assert_eq!(tree.search("user/alice/name"), None);
+            assert!(tree.take_subtree(b"user/alice").is_none());
+
+            let mut bob = tree.take_subtree(b"user/b").unwrap();
+            assert!(bob
+                .iter()
+                .map(|(k, _)| k.as_str())
+                .eq(["user/bo", "user/bob/name"]));
+            bob.insert("user/bob/mail".to_string(), 6);
+            assert_eq!(bob.search("user/bob/mail"), Some(&6));
+            assert!(tree
+                .iter()
+                .map(|(k, _)| k.as_str())
+                .eq(["group/admins", "users"]));
+            assert!(tree.take_subtree(b"x").is_none());
+
+            let everything = tree.take_subtree(b"").unwrap();
+            assert_eq!(everything.len(), 2);
+            assert!(tree.is_empty());
+            assert!(tree.take_subtree(b"").is_none());
+            tree.insert("again".to_string(), 7);
+            assert_eq!(tree.len(), 1);
+        }
+
+        let mut rng = rand::thread_rng();
+        let mut tree = ART::<Vec<u8>, usize>::new();
+        let mut expected = BTreeMap::new();
+        for i in 0..3000 {
+            let key: Vec<u8> = (0..rng.gen_range(0..8))
+                .map(|_| rng.gen_range(1..4))
+                .collect();
+            tree.insert(key.clone(), i);
+            expected.insert(key, i);
+        }
+        for _ in 0..50 {
+            let prefix: Vec<u8> = (0..rng.gen_range(1..5))
+                .map(|_| rng.gen_range(1..4))
+                .collect();
+            let (taken, kept): (BTreeMap<_, _>, BTreeMap<_, _>) = expected
+                .into_iter()
+                .partition(|(key, _)| key.starts_with(&prefix));
+            match tree.take_subtree(&prefix) {
+                Some(subtree) => assert!(subtree.iter().eq(taken.iter())),
+                None => assert!(taken.is_empty()),
+            }
+            assert_eq!(tree.len(), kept.len());
+            assert!(tree.iter().eq(kept.iter()));
+            expected = kept;
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! A tree that stamps its entries with the time of their last write, for replication.
+//!
+//! Every write to a [`StampedArt`] advances a logical clock and stamps the entry it modifies with
+//! the new time. [`StampedArt::iter_since`] returns the entries written after a given time, so a
+//! replica that remembers the time it last synchronized at only has to receive the entries that
+//! changed since then, and applies them with [`StampedArt::apply`]. A delete leaves a tombstone
+//! stamped with its time, so that deletes are replicated as well, until
+//! [`StampedArt::purge_tombstones`] drops the tombstones that every replica has seen.
+//!
+//! Finding the changed entries goes through every entry of the tree, so the cost of a
+//! synchronization depends on the size of the tree rather than on the number of changes.
+
+use std::borrow::Borrow;
+
+use crate::{BytesComparable, ART};
+
+/// A value stamped with the time of the write that produced it, or a tombstone if the key was
+/// deleted.
+#[derive(Debug)]
+struct Stamped<V> {
+    time: u64,
+    value: Option<V>,
+}
+
+/// An adaptive radix tree whose entries are stamped with the time of their last write.
+#[derive(Debug)]
+pub struct StampedArt<K, V, const N: usize = 10> {
+    tree: ART<K, Stamped<V>, N>,
+    /// The time of the latest write.
+    now: u64,
+    /// The number of keys that are not deleted.
+    len: usize,
+}
+
+impl<K, V, const N: usize> StampedArt<K, V, N> {
+    /// Create an empty tree at time zero.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            tree: ART::new(),
+            now: 0,
+            len: 0,
+        }
+    }
+
+    /// Return the time of the latest write, which increases with every write.
+    #[must_use]
+    pub const fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Return the number of entries in the tree, not counting tombstones.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the tree holds no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the number of tombstones left by deletes.
+    #[must_use]
+    pub const fn tombstones(&self) -> usize {
+        self.tree.len() - self.len
+    }
+
+    /// Return an iterator over the entries of the tree in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.tree
+            .iter()
+            .filter_map(|(key, stamped)| Some((key, stamped.value.as_ref()?)))
+    }
+
+    /// Return an iterator over the entries written after the given time in key order, together
+    /// with the time of their last write. A deleted key is yielded without a value.
+    pub fn iter_since(&self, time: u64) -> impl Iterator<Item = (&K, Option<&V>, u64)> + '_ {
+        self.tree
+            .iter()
+            .filter(move |(_, stamped)| stamped.time > time)
+            .map(|(key, stamped)| (key, stamped.value.as_ref(), stamped.time))
+    }
+}
+
+impl<K, V, const N: usize> Default for StampedArt<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> StampedArt<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(key)?.value.as_ref()
+    }
+
+    /// Search for the value associated with the given key together with the time it was written.
+    pub fn search_stamped<Q>(&self, key: &Q) -> Option<(&V, u64)>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let stamped = self.tree.search(key)?;
+        Some((stamped.value.as_ref()?, stamped.time))
+    }
+
+    /// Insert the given key-value pair at the next time, returning the value previously
+    /// associated with the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.now += 1;
+        self.write(key, Some(value), self.now)
+    }
+
+    /// Delete the value associated with the given key at the next time, leaving a tombstone. A key
+    /// that is not in the tree is left alone, without advancing the time.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let stamped = stamped_mut(&mut self.tree, key)?;
+        let value = stamped.value.take()?;
+        self.now += 1;
+        stamped.time = self.now;
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// Apply an entry yielded by [`StampedArt::iter_since`] on another tree, writing the value or
+    /// the tombstone with its original time. The entry is ignored if the key was written at a
+    /// later time in this tree, so changes can be applied in any order. Returns true if the entry
+    /// was applied.
+    pub fn apply(&mut self, key: K, value: Option<V>, time: u64) -> bool {
+        if self
+            .tree
+            .search(&key)
+            .is_some_and(|stamped| stamped.time >= time)
+        {
+            return false;
+        }
+        self.now = self.now.max(time);
+        self.write(key, value, time);
+        true
+    }
+
+    /// Write the value or the tombstone of the given key with the given time, returning the value
+    /// it replaced.
+    fn write(&mut self, key: K, value: Option<V>, time: u64) -> Option<V> {
+        let live = value.is_some();
+        if let Some(stamped) = stamped_mut(&mut self.tree, &key) {
+            let previous = std::mem::replace(&mut stamped.value, value);
+            stamped.time = time;
+            match (previous.is_some(), live) {
+                (false, true) => self.len += 1,
+                (true, false) => self.len -= 1,
+                _ => {}
+            }
+            return previous;
+        }
+        if live {
+            self.len += 1;
+        }
+        self.tree.insert(key, Stamped { time, value });
+        None
+    }
+
+    /// Drop the tombstones of the keys deleted at or before the given time, returning how many
+    /// were dropped. Replicas that have not synchronized up to that time miss these deletes.
+    pub fn purge_tombstones(&mut self, up_to: u64) -> usize {
+        self.tree
+            .remove_where(|_, stamped| stamped.value.is_none() && stamped.time <= up_to)
+            .len()
+    }
+}
+
+/// Return the stamped value of the given key for modification.
+fn stamped_mut<'a, K, V, Q, const N: usize>(
+    tree: &'a mut ART<K, Stamped<V>, N>,
+    key: &Q,
+) -> Option<&'a mut Stamped<V>>
+where
+    K: BytesComparable,
+    Q: BytesComparable + ?Sized,
+{
+    tree.root
+        .as_mut()
+        .and_then(|root| root.search_mut(key.bytes().as_ref(), 0))
+        .map(|leaf| &mut leaf.value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::Rng;
+
+    use super::StampedArt;
+
+    #[test]
+    fn test_stamps() {
+        let mut tree = StampedArt::<String, u32>::new();
+        tree.insert("a".to_string(), 1);
+        tree.insert("b".to_string(), 2);
+        let synced = tree.now();
+        assert_eq!(tree.insert("a".to_string(), 3), Some(1));
+        assert_eq!(tree.delete("b"), Some(2));
+        assert_eq!(tree.delete("b"), None);
+        tree.insert("c".to_string(), 4);
+        assert_eq!(tree.now(), 5);
+        assert_eq!(tree.search_stamped("a"), Some((&3, 3)));
+        assert_eq!(tree.search("b"), None);
+        assert_eq!((tree.len(), tree.tombstones()), (2, 1));
+
+        let changes: Vec<_> = tree
+            .iter_since(synced)
+            .map(|(key, value, time)| (key.as_str(), value.copied(), time))
+            .collect();
+        assert_eq!(
+            changes,
+            [("a", Some(3), 3), ("b", None, 4), ("c", Some(4), 5)]
+        );
+        assert_eq!(tree.iter_since(tree.now()).count(), 0);
+
+        assert_eq!(tree.purge_tombstones(3), 0);
+        assert_eq!(tree.purge_tombstones(4), 1);
+        assert_eq!(tree.tombstones(), 0);
+        assert_eq!(tree.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_incremental_replication() {
+        let mut rng = rand::thread_rng();
+        let mut primary = StampedArt::<u32, u32>::new();
+        let mut replica = StampedArt::<u32, u32>::new();
+        let mut expected = BTreeMap::new();
+        let mut synced = 0;
+        for _ in 0..20 {
+            for _ in 0..200 {
+                let key = rng.gen_range(0..500);
+                if rng.gen_bool(0.7) {
+                    primary.insert(key, key + 1);
+                    expected.insert(key, key + 1);
+                } else {
+                    primary.delete(&key);
+                    expected.remove(&key);
+                }
+            }
+            let changes: Vec<_> = primary
+                .iter_since(synced)
+                .map(|(key, value, time)| (*key, value.copied(), time))
+                .collect();
+            assert!(changes.len() <= 200);
+            for (key, value, time) in changes {
+                assert!(replica.apply(key, value, time));
+            }
+            synced = primary.now();
+            assert!(replica.iter().eq(expected.iter()));
+            assert_eq!(replica.len(), expected.len());
+        }
+        // Stale changes are ignored.
+        let (key, value) = primary.iter().next().map(|(k, v)| (*k, *v)).unwrap();
+        assert!(!replica.apply(key, Some(value + 1), 1));
+        assert_eq!(replica.search(&key), Some(&value));
+        assert_eq!(replica.now(), primary.now());
+    }
+}
@@ -0,0 +1,215 @@
+//! Counters of the work done by tree operations, enabled by the `stats` feature.
+//!
+//! Every thread counts the work of the tree operations it runs, which includes searching,
+//! inserting, and deleting in all trees of this crate. To measure a single operation, call
+//! [`reset`] before it and [`metrics`](fn@metrics) after it. None of the trees retry an operation,
+//! as the readers of a [`SwmrArt`](crate::swmr::SwmrArt) never wait for the writer, so there are no
+//! restarts to count.
+//!
+//! The transitions of inner nodes between kinds are also counted for every [`ART`] since it was
+//! created, and returned by [`ART::stats`]. A node only shrinks once it has a quarter fewer
+//! children than the smaller kind holds, so a tree whose nodes still keep growing and shrinking
+//! back shows many transitions compared to its number of entries.
+
+use std::cell::Cell;
+
+use crate::ART;
+
+/// The work counted on the current thread since the last [`reset`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// The number of nodes visited while looking for a key.
+    pub node_visits: u64,
+    /// The number of bytes of partial keys compared with a key.
+    pub prefix_bytes: u64,
+    /// The number of inner nodes grown into a larger kind.
+    pub grows: u64,
+    /// The number of inner nodes shrunk into a smaller kind, or replaced by their only child.
+    pub shrinks: u64,
+    /// The grows and shrinks by the kinds of the nodes.
+    pub transitions: Transitions,
+}
+
+/// The number of inner nodes that changed their kind, by the kinds they changed from and to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Transitions {
+    /// The number of nodes of the smallest kind grown into the second smallest kind.
+    pub grown_4_to_16: u64,
+    /// The number of nodes of the second smallest kind grown into the second largest kind.
+    pub grown_16_to_48: u64,
+    /// The number of nodes of the second largest kind grown into the largest kind.
+    pub grown_48_to_256: u64,
+    /// The number of nodes of the largest kind shrunk into the second largest kind.
+    pub shrunk_256_to_48: u64,
+    /// The number of nodes of the second largest kind shrunk into the second smallest kind.
+    pub shrunk_48_to_16: u64,
+    /// The number of nodes of the second smallest kind shrunk into the smallest kind.
+    pub shrunk_16_to_4: u64,
+    /// The number of nodes of the smallest kind replaced by their only child.
+    pub merged: u64,
+}
+
+impl Transitions {
+    const NONE: Self = Self {
+        grown_4_to_16: 0,
+        grown_16_to_48: 0,
+        grown_48_to_256: 0,
+        shrunk_256_to_48: 0,
+        shrunk_48_to_16: 0,
+        shrunk_16_to_4: 0,
+        merged: 0,
+    };
+
+    /// Return the number of nodes that grew.
+    #[must_use]
+    pub const fn grows(&self) -> u64 {
+        self.grown_4_to_16 + self.grown_16_to_48 + self.grown_48_to_256
+    }
+
+    /// Return the number of nodes that shrunk or were merged into their child.
+    #[must_use]
+    pub const fn shrinks(&self) -> u64 {
+        self.shrunk_256_to_48 + self.shrunk_48_to_16 + self.shrunk_16_to_4 + self.merged
+    }
+
+    /// Add the transitions counted on the current thread since the given count to these ones.
+    pub(crate) fn add_since(&mut self, before: &Self) {
+        let now = METRICS.get().transitions;
+        self.grown_4_to_16 += now.grown_4_to_16.wrapping_sub(before.grown_4_to_16);
+        self.grown_16_to_48 += now.grown_16_to_48.wrapping_sub(before.grown_16_to_48);
+        self.grown_48_to_256 += now.grown_48_to_256.wrapping_sub(before.grown_48_to_256);
+        self.shrunk_256_to_48 += now.shrunk_256_to_48.wrapping_sub(before.shrunk_256_to_48);
+        self.shrunk_48_to_16 += now.shrunk_48_to_16.wrapping_sub(before.shrunk_48_to_16);
+        self.shrunk_16_to_4 += now.shrunk_16_to_4.wrapping_sub(before.shrunk_16_to_4);
+        self.merged += now.merged.wrapping_sub(before.merged);
+    }
+}
+
+/// The statistics of a tree since it was created, see [`ART::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TreeStats {
+    /// The transitions of inner nodes between kinds.
+    pub transitions: Transitions,
+}
+
+impl TreeStats {
+    pub(crate) const NONE: Self = Self {
+        transitions: Transitions::NONE,
+    };
+}
+
+thread_local! {
+    static METRICS: Cell<Metrics> = const { Cell::new(Metrics {
+        node_visits: 0,
+        prefix_bytes: 0,
+        grows: 0,
+        shrinks: 0,
+        transitions: Transitions::NONE,
+    }) };
+}
+
+/// Return the work counted on the current thread since the last [`reset`].
+#[must_use]
+pub fn metrics() -> Metrics {
+    METRICS.get()
+}
+
+/// Reset the counters of the current thread.
+pub fn reset() {
+    METRICS.set(Metrics::default());
+}
+
+/// Updates the counters of the current thread.
+pub(crate) fn record(f: impl FnOnce(&mut Metrics)) {
+    METRICS.with(|metrics| {
+        let mut updated = metrics.get();
+        f(&mut updated);
+        metrics.set(updated);
+    });
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    ART<K, V, N, C4, C16, C48>
+{
+    /// Return the statistics of the tree since it was created. Trees built from other trees, such
+    /// as the trees split off of a tree, start from zero.
+    #[must_use]
+    pub const fn stats(&self) -> TreeStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{metrics, reset, Metrics, Transitions};
+    use crate::ART;
+
+    #[test]
+    fn test_metrics() {
+        let mut tree = ART::<u32, u32>::new();
+        reset();
+        for i in 0..=48 {
+            tree.insert(i, i);
+        }
+        // The root grows from 4 to 16, from 16 to 48, and from 48 to 256 children.
+        assert_eq!(metrics().grows, 3);
+
+        reset();
+        tree.search(&7);
+        let search = metrics();
+        // The root with the common prefix of all keys and the leaf below it.
+        assert_eq!(search.node_visits, 2);
+        assert_eq!(search.prefix_bytes, 3);
+
+        reset();
+        for i in 0..=48 {
+            tree.delete(&i);
+        }
+        assert_eq!(metrics().shrinks, 4);
+        reset();
+        assert_eq!(metrics(), Metrics::default());
+    }
+
+    #[test]
+    fn test_transitions() {
+        let mut tree = ART::<u32, u32>::new();
+        let mut other = ART::<u32, u32>::new();
+        for i in 0..=48 {
+            tree.insert(i, i);
+            // The work of other trees on the same thread is not added to the tree.
+            other.insert(i, i);
+        }
+        reset();
+        for i in 0..=48 {
+            tree.delete(&i);
+        }
+        let transitions = Transitions {
+            grown_4_to_16: 1,
+            grown_16_to_48: 1,
+            grown_48_to_256: 1,
+            shrunk_256_to_48: 1,
+            shrunk_48_to_16: 1,
+            shrunk_16_to_4: 1,
+            merged: 1,
+        };
+        assert_eq!(tree.stats().transitions, transitions);
+        assert_eq!(transitions.grows(), 3);
+        assert_eq!(transitions.shrinks(), 4);
+        assert_eq!(other.stats().transitions.grows(), 3);
+        assert_eq!(other.stats().transitions.shrinks(), 0);
+
+        // A node shrinks only well below the capacity of the smaller kind, so keys going back
+        // and forth across the capacity make it grow once.
+        let mut tree = ART::<u32, u32>::new();
+        for i in 0..16 {
+            tree.insert(i, i);
+        }
+        for _ in 0..10 {
+            tree.insert(16, 16);
+            tree.delete(&16);
+        }
+        let transitions = tree.stats().transitions;
+        assert_eq!(transitions.grown_16_to_48, 1);
+        assert_eq!(transitions.shrinks(), 0);
+    }
+}
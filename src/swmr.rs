@@ -0,0 +1,477 @@
+//! A tree with a single writer and any number of wait-free readers.
+//!
+//! The writer never modifies a version of the tree that readers can observe. Instead, every
+//! mutation copies the nodes on the path from the root to the modified node, while all other nodes
+//! are shared with the previous version. The new version is then published with an atomic pointer
+//! swap, so readers only ever load a pointer and never wait for the writer or for each other.
+//!
+//! [`Reader::with`] only holds on to the latest version while its closure runs, which must not
+//! await anything. Async code that reads across `.await` points, such as a request handler that
+//! looks up several keys in between calls to other services, takes a [`ReadHandle`] instead. A
+//! handle owns a version of the tree, so it is `Send`, `Sync`, and `'static`, it can be held for as
+//! long as needed without blocking the writer, and it reads the same version until it is
+//! refreshed.
+//!
+//! Every call to [`Reader::with`] or [`Reader::get`] loads the latest version on its own. Services
+//! that look up many keys at once use [`Reader::read_batch`] instead, which loads the latest
+//! version once and runs all lookups of the batch on it, so the cost of loading is shared by the
+//! batch and its lookups see the same version.
+
+use std::{borrow::Borrow, ops::Deref, sync::Arc};
+
+use arc_swap::ArcSwap;
+
+use crate::{
+    node::{debug_print, Node, Shared},
+    BytesComparable,
+};
+
+/// An immutable version of a [`SwmrArt`].
+pub struct Snapshot<K, V, const N: usize = 10>
+where
+    K: Clone,
+    V: Clone,
+{
+    root: Option<Node<K, V, N, Shared>>,
+}
+
+impl<K, V, const N: usize> Clone for Snapshot<K, V, N>
+where
+    K: Clone,
+    V: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+        }
+    }
+}
+
+impl<K, V, const N: usize> std::fmt::Debug for Snapshot<K, V, N>
+where
+    K: Clone + std::fmt::Debug,
+    V: Clone + std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(root) = &self.root {
+            debug_print(f, root, 0, 0)
+        } else {
+            writeln!(f, "empty")
+        }
+    }
+}
+
+impl<K, V, const N: usize> Snapshot<K, V, N>
+where
+    K: BytesComparable + Clone,
+    V: Clone,
+{
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.root
+            .as_ref()
+            .and_then(|node| node.search(key.bytes().as_ref(), 0))
+            .map(|leaf| &leaf.value)
+    }
+
+    /// Return the root of the snapshot, if any.
+    pub(crate) const fn root(&self) -> Option<&Node<K, V, N, Shared>> {
+        self.root.as_ref()
+    }
+
+    /// Find the minimum key-value pair in the snapshot.
+    #[must_use]
+    pub fn min(&self) -> Option<(&K, &V)> {
+        self.root
+            .as_ref()
+            .and_then(|root| root.min_leaf().map(|leaf| (&leaf.key, &leaf.value)))
+    }
+
+    /// Find the maximum key-value pair in the snapshot.
+    #[must_use]
+    pub fn max(&self) -> Option<(&K, &V)> {
+        self.root
+            .as_ref()
+            .and_then(|root| root.max_leaf().map(|leaf| (&leaf.key, &leaf.value)))
+    }
+}
+
+/// An adaptive radix tree that is modified by a single writer while being read by any number of
+/// [`Reader`]s on other threads.
+///
+/// Each mutation publishes a new [`Snapshot`] that shares all unmodified nodes with the previous
+/// one. Snapshots held by readers stay valid and unchanged for as long as they are held.
+#[derive(Debug)]
+pub struct SwmrArt<K, V, const N: usize = 10>
+where
+    K: Clone,
+    V: Clone,
+{
+    published: Arc<ArcSwap<Snapshot<K, V, N>>>,
+    latest: Arc<Snapshot<K, V, N>>,
+}
+
+impl<K, V, const N: usize> SwmrArt<K, V, N>
+where
+    K: Clone,
+    V: Clone,
+{
+    /// Create an empty tree.
+    #[must_use]
+    pub fn new() -> Self {
+        let latest = Arc::new(Snapshot { root: None });
+        Self {
+            published: Arc::new(ArcSwap::new(Arc::clone(&latest))),
+            latest,
+        }
+    }
+
+    /// Create a handle for reading the tree, which can be sent to other threads.
+    #[must_use]
+    pub fn reader(&self) -> Reader<K, V, N> {
+        Reader {
+            published: Arc::clone(&self.published),
+        }
+    }
+
+    /// Return the latest version of the tree.
+    #[must_use]
+    pub fn snapshot(&self) -> Arc<Snapshot<K, V, N>> {
+        Arc::clone(&self.latest)
+    }
+}
+
+impl<K, V, const N: usize> Default for SwmrArt<K, V, N>
+where
+    K: Clone,
+    V: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> SwmrArt<K, V, N>
+where
+    K: BytesComparable + Clone,
+    V: Clone,
+{
+    /// Insert the given key-value pair and publish the new version of the tree.
+    pub fn insert(&mut self, key: K, value: V) {
+        self.update(|root| Node::insert_root(root, key, value, true));
+    }
+
+    /// Delete the value associated with the given key and publish the new version of the tree.
+    /// Deleting an absent key publishes nothing, so readers keep the version they loaded.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.latest.search(key)?;
+        self.update(|root| {
+            Node::delete_root(root, key.bytes().as_ref(), true).map(|leaf| leaf.value)
+        })
+    }
+
+    /// Applies a modification to a copy of the latest version, then publishes the copy. Copying the
+    /// root is shallow, the modification itself copies every shared node it mutates.
+    fn update<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut Option<Node<K, V, N, Shared>>) -> R,
+    {
+        let mut next = Snapshot::clone(&self.latest);
+        let result = f(&mut next.root);
+        self.latest = Arc::new(next);
+        self.published.store(Arc::clone(&self.latest));
+        result
+    }
+}
+
+/// A handle for reading the latest version of a [`SwmrArt`].
+#[derive(Debug)]
+pub struct Reader<K, V, const N: usize = 10>
+where
+    K: Clone,
+    V: Clone,
+{
+    published: Arc<ArcSwap<Snapshot<K, V, N>>>,
+}
+
+impl<K, V, const N: usize> Clone for Reader<K, V, N>
+where
+    K: Clone,
+    V: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            published: Arc::clone(&self.published),
+        }
+    }
+}
+
+impl<K, V, const N: usize> Reader<K, V, N>
+where
+    K: BytesComparable + Clone,
+    V: Clone,
+{
+    /// Return the latest version of the tree. The snapshot is not affected by later writes.
+    #[must_use]
+    pub fn snapshot(&self) -> Arc<Snapshot<K, V, N>> {
+        self.published.load_full()
+    }
+
+    /// Return a handle that reads the latest version of the tree until it is refreshed.
+    #[must_use]
+    pub fn handle(&self) -> ReadHandle<K, V, N> {
+        ReadHandle {
+            snapshot: self.snapshot(),
+            reader: self.clone(),
+        }
+    }
+
+    /// Search for the value associated with the given key in the latest version of the tree and
+    /// call the closure on it.
+    pub fn with<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        F: FnOnce(&V) -> R,
+    {
+        self.published.load().search(key).map(f)
+    }
+
+    /// Search for the value associated with the given key in the latest version of the tree and
+    /// return a copy of it.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.with(key, V::clone)
+    }
+
+    /// Call the closure on the latest version of the tree, which is held until the closure
+    /// returns. Like with [`Reader::with`], the closure must not await anything.
+    pub fn read_batch<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Snapshot<K, V, N>) -> R,
+    {
+        f(&self.published.load())
+    }
+}
+
+/// A handle for reading a version of a [`SwmrArt`], which can be held across `.await` points and
+/// moved to the latest version with [`ReadHandle::refresh`].
+#[derive(Debug)]
+pub struct ReadHandle<K, V, const N: usize = 10>
+where
+    K: Clone,
+    V: Clone,
+{
+    snapshot: Arc<Snapshot<K, V, N>>,
+    reader: Reader<K, V, N>,
+}
+
+impl<K, V, const N: usize> Clone for ReadHandle<K, V, N>
+where
+    K: Clone,
+    V: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            snapshot: Arc::clone(&self.snapshot),
+            reader: self.reader.clone(),
+        }
+    }
+}
+
+impl<K, V, const N: usize> Deref for ReadHandle<K, V, N>
+where
+    K: Clone,
+    V: Clone,
+{
+    type Target = Snapshot<K, V, N>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.snapshot
+    }
+}
+
+impl<K, V, const N: usize> ReadHandle<K, V, N>
+where
+    K: BytesComparable + Clone,
+    V: Clone,
+{
+    /// Return true if no version of the tree was published after the one read by the handle.
+    #[must_use]
+    pub fn is_latest(&self) -> bool {
+        Arc::ptr_eq(&self.snapshot, &self.reader.published.load())
+    }
+
+    /// Move the handle to the latest version of the tree, returning true if it read an older one.
+    pub fn refresh(&mut self) -> bool {
+        let latest = self.reader.snapshot();
+        let changed = !Arc::ptr_eq(&self.snapshot, &latest);
+        self.snapshot = latest;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use super::SwmrArt;
+
+    /// A future that returns pending once, letting the test write in between two polls.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: std::pin::Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                Poll::Pending
+            }
+        }
+    }
+
+    fn assert_spawnable<F: Future + Send + 'static>(future: F) -> F {
+        future
+    }
+
+    #[test]
+    fn test_snapshots_are_isolated() {
+        let mut tree = SwmrArt::<String, u32>::new();
+        for i in 0..1000 {
+            tree.insert(format!("key{i}"), i);
+        }
+        let before = tree.snapshot();
+        for i in 0..500 {
+            assert_eq!(tree.delete(&format!("key{i}")), Some(i));
+        }
+        tree.insert("key999".to_string(), 0);
+        let after = tree.snapshot();
+        for i in 0..1000 {
+            let key = format!("key{i}");
+            assert_eq!(before.search(&key), Some(&i));
+            let expected = match i {
+                0..=499 => None,
+                999 => Some(&0),
+                _ => Some(&i),
+            };
+            assert_eq!(after.search(&key), expected);
+        }
+        assert_eq!(before.min(), Some((&"key0".to_string(), &0)));
+        assert_eq!(after.min(), Some((&"key500".to_string(), &500)));
+    }
+
+    #[test]
+    fn test_delete_absent_key_publishes_nothing() {
+        let mut tree = SwmrArt::<u32, u32>::new();
+        tree.insert(1, 1);
+        let mut handle = tree.reader().handle();
+        assert_eq!(tree.delete(&2), None);
+        assert!(handle.is_latest());
+        assert_eq!(tree.delete(&1), Some(1));
+        assert!(handle.refresh());
+    }
+
+    #[test]
+    fn test_readers_observe_writes() {
+        let mut tree = SwmrArt::<u64, u64>::new();
+        let reader = tree.reader();
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    let reader = reader.clone();
+                    scope.spawn(move || {
+                        // Each snapshot must contain a prefix of the writes, because the writer
+                        // inserts keys in increasing order.
+                        let mut seen = 0;
+                        while seen < 1000 {
+                            let snapshot = reader.snapshot();
+                            let max = snapshot.max().map_or(0, |(k, _)| *k + 1);
+                            assert!(max >= seen);
+                            for key in 0..max {
+                                assert_eq!(snapshot.search(&key), Some(&key));
+                            }
+                            seen = max;
+                        }
+                    })
+                })
+                .collect();
+            for key in 0..1000 {
+                tree.insert(key, key);
+            }
+            for handle in handles {
+                handle.join().expect("reader must not panic");
+            }
+        });
+        assert_eq!(reader.get(&999), Some(999));
+        assert_eq!(reader.with(&1000, |v| *v), None);
+    }
+
+    #[test]
+    fn test_read_batch() {
+        let mut tree = SwmrArt::<u32, u32>::new();
+        for i in 0..1000 {
+            tree.insert(i, i);
+        }
+        let reader = tree.reader();
+        let sum = reader.read_batch(|snapshot| {
+            // Writes during the batch are not observed by its lookups.
+            tree.insert(0, 1000);
+            tree.delete(&999);
+            (0..1000)
+                .map(|key| snapshot.search(&key).copied().unwrap_or_default())
+                .sum::<u32>()
+        });
+        assert_eq!(sum, (0..1000).sum::<u32>());
+        assert_eq!(
+            reader.read_batch(|snapshot| [0, 999].map(|key| snapshot.search(&key).copied())),
+            [Some(1000), None]
+        );
+    }
+
+    #[test]
+    fn test_handle_across_await() {
+        let mut tree = SwmrArt::<String, u32>::new();
+        tree.insert("a".to_string(), 1);
+        let reader = tree.reader();
+        let future = assert_spawnable(async move {
+            let mut handle = reader.handle();
+            let before = handle.search("a").copied();
+            YieldOnce(false).await;
+            // The handle still reads the version it started with.
+            let after = handle.search("a").copied();
+            let stale = !handle.is_latest();
+            let refreshed = handle.refresh();
+            (before, after, stale, refreshed, handle.search("a").copied())
+        });
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        tree.insert("a".to_string(), 2);
+        assert_eq!(
+            future.as_mut().poll(&mut cx),
+            Poll::Ready((Some(1), Some(1), true, true, Some(2)))
+        );
+
+        let mut handle = tree.reader().handle();
+        assert!(handle.is_latest());
+        assert!(!handle.refresh());
+    }
+}
@@ -0,0 +1,303 @@
+//! Differential testing of the tree against [`BTreeMap`].
+//!
+//! An [`Oracle`] applies every [`Op`] to both an [`ART`] and a [`BTreeMap`], and panics as soon as
+//! their results differ, naming the operation that diverged. Together with [`random_ops`], this
+//! gives a quick way to hunt for regressions with random operation sequences, in this crate as
+//! well as in crates building on it. The comparison of the whole contents assumes that the `Ord`
+//! implementation of the keys agrees with the order of their bytes, which holds for all key types
+//! implementing [`BytesComparable`] in this crate.
+//!
+//! A random sequence that makes the oracle panic is usually far longer than needed to show the
+//! bug. [`Oracle::shrink`] reduces it to a sequence that still fails but no longer does if any
+//! single operation is removed, and [`shrink`] does the same for any other failure condition.
+
+use std::{
+    collections::BTreeMap,
+    panic::{self, AssertUnwindSafe},
+};
+
+use rand::Rng;
+
+use crate::{BytesComparable, ART};
+
+/// An operation applied by an [`Oracle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op<K, V> {
+    /// Insert a key-value pair.
+    Insert(K, V),
+    /// Delete a key.
+    Delete(K),
+    /// Search for a key.
+    Search(K),
+}
+
+/// A tree and a [`BTreeMap`] that are expected to hold the same entries.
+#[derive(Debug)]
+pub struct Oracle<K, V, const N: usize = 10> {
+    tree: ART<K, V, N>,
+    model: BTreeMap<K, V>,
+    applied: usize,
+}
+
+impl<K, V, const N: usize> Oracle<K, V, N> {
+    /// Create an oracle with an empty tree and map.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            tree: ART::new(),
+            model: BTreeMap::new(),
+            applied: 0,
+        }
+    }
+
+    /// Return the tree under test.
+    #[must_use]
+    pub const fn tree(&self) -> &ART<K, V, N> {
+        &self.tree
+    }
+
+    /// Return the map the tree is compared with.
+    #[must_use]
+    pub const fn model(&self) -> &BTreeMap<K, V> {
+        &self.model
+    }
+
+    /// Return the number of operations applied so far.
+    #[must_use]
+    pub const fn applied(&self) -> usize {
+        self.applied
+    }
+}
+
+impl<K, V, const N: usize> Default for Oracle<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> Oracle<K, V, N>
+where
+    K: BytesComparable + Ord + Clone + std::fmt::Debug,
+    V: Clone + PartialEq + std::fmt::Debug,
+{
+    /// Apply the operation to the tree and the map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tree and the map return different results.
+    pub fn apply(&mut self, op: Op<K, V>) {
+        let idx = self.applied;
+        self.applied += 1;
+        match op {
+            Op::Insert(key, value) => {
+                let expected = self.model.insert(key.clone(), value.clone());
+                let actual = self.tree.insert(key.clone(), value);
+                assert_eq!(actual, expected, "operation {idx}: insert {key:?}");
+            }
+            Op::Delete(key) => {
+                let expected = self.model.remove(&key);
+                let actual = self.tree.delete(&key);
+                assert_eq!(actual, expected, "operation {idx}: delete {key:?}");
+            }
+            Op::Search(key) => {
+                let expected = self.model.get(&key);
+                let actual = self.tree.search(&key);
+                assert_eq!(actual, expected, "operation {idx}: search {key:?}");
+            }
+        }
+    }
+
+    /// Apply all operations, then compare the whole contents of the tree and the map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tree and the map return different results or hold different entries.
+    pub fn run<I>(&mut self, ops: I)
+    where
+        I: IntoIterator<Item = Op<K, V>>,
+    {
+        for op in ops {
+            self.apply(op);
+        }
+        self.check();
+    }
+
+    /// Compare the entries, the minimum, and the maximum of the tree and the map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tree and the map hold different entries.
+    pub fn check(&self) {
+        let applied = self.applied;
+        assert_eq!(
+            self.tree.min(),
+            self.model.first_key_value(),
+            "minimum after {applied} operations"
+        );
+        assert_eq!(
+            self.tree.max(),
+            self.model.last_key_value(),
+            "maximum after {applied} operations"
+        );
+        let mut entries = self.tree.iter();
+        for (idx, expected) in self.model.iter().enumerate() {
+            assert_eq!(
+                entries.next(),
+                Some(expected),
+                "entry {idx} after {applied} operations"
+            );
+        }
+        assert_eq!(
+            entries.next(),
+            None,
+            "extra entry after {applied} operations"
+        );
+    }
+
+    /// Reduce a sequence of operations that makes [`Oracle::run`] panic on a new oracle to a
+    /// minimal one that still does, see [`shrink`]. The panic of every attempt is reported by the
+    /// panic hook as usual.
+    #[must_use]
+    pub fn shrink(ops: Vec<Op<K, V>>) -> Vec<Op<K, V>> {
+        shrink(ops, |ops| {
+            let ops = ops.to_vec();
+            panic::catch_unwind(AssertUnwindSafe(|| Self::new().run(ops))).is_err()
+        })
+    }
+}
+
+/// Reduce a sequence of operations for which `fails` returns true to a minimal subsequence.
+///
+/// `fails` still returns true for the returned subsequence, but no longer does once any single
+/// operation of it is removed. The sequence is returned as is if `fails` returns false for it.
+///
+/// Chunks of operations are removed from the sequence as long as it keeps failing, halving the
+/// size of the chunks when none can be removed, so that long sequences shrink after few attempts.
+pub fn shrink<K, V, F>(mut ops: Vec<Op<K, V>>, mut fails: F) -> Vec<Op<K, V>>
+where
+    K: Clone,
+    V: Clone,
+    F: FnMut(&[Op<K, V>]) -> bool,
+{
+    if !fails(&ops) {
+        return ops;
+    }
+    let mut chunk = ops.len() / 2;
+    while chunk > 0 {
+        let mut removed = false;
+        let mut start = 0;
+        while start < ops.len() {
+            let end = (start + chunk).min(ops.len());
+            let candidate: Vec<_> = ops[..start].iter().chain(&ops[end..]).cloned().collect();
+            if fails(&candidate) {
+                ops = candidate;
+                removed = true;
+            } else {
+                start = end;
+            }
+        }
+        if !removed {
+            chunk /= 2;
+        }
+    }
+    ops
+}
+
+/// Generate `count` random operations on keys drawn from the given keys, with values created by
+/// the given closure. Inserts, deletes, and searches are equally likely.
+///
+/// # Panics
+///
+/// Panics if no keys are given while `count` is not zero.
+pub fn random_ops<K, V, R, F>(rng: &mut R, keys: &[K], count: usize, mut value: F) -> Vec<Op<K, V>>
+where
+    K: Clone,
+    R: Rng,
+    F: FnMut(&mut R) -> V,
+{
+    (0..count)
+        .map(|_| {
+            let key = keys[rng.gen_range(0..keys.len())].clone();
+            match rng.gen_range(0..3) {
+                0 => Op::Insert(key, value(rng)),
+                1 => Op::Delete(key),
+                _ => Op::Search(key),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{random_ops, shrink, Op, Oracle};
+
+    #[test]
+    fn test_random_ops() {
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let keys: Vec<Vec<u8>> = (0..200)
+                .map(|_| {
+                    let len = rng.gen_range(0..6);
+                    (0..len).map(|_| rng.gen_range(1..4)).collect()
+                })
+                .collect();
+            let ops = random_ops(&mut rng, &keys, 2000, Rng::gen::<u16>);
+            Oracle::<Vec<u8>, u16, 2>::new().run(ops);
+
+            let keys: Vec<i32> = (0..500).map(|_| rng.gen()).collect();
+            let ops = random_ops(&mut rng, &keys, 2000, |_| ());
+            Oracle::<i32, ()>::new().run(ops);
+        }
+    }
+
+    /// Keys whose bytes ignore their second field, unlike their `Ord` implementation.
+    #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+    struct Lossy(u8, u8);
+
+    impl crate::BytesComparable for Lossy {
+        type Target<'a> = [u8; 1];
+
+        fn bytes(&self) -> Self::Target<'static> {
+            [self.0]
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "operation 1: search")]
+    fn test_divergence() {
+        let mut oracle = Oracle::<Lossy, u8>::new();
+        oracle.run([Op::Insert(Lossy(1, 1), 1), Op::Search(Lossy(1, 2))]);
+    }
+
+    #[test]
+    fn test_shrink() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let keys: Vec<Lossy> = (0..50).map(|i| Lossy(i / 2, i % 2)).collect();
+        let ops = random_ops(&mut rng, &keys, 1000, Rng::gen::<u8>);
+        let shrunk = Oracle::<Lossy, u8>::shrink(ops);
+        // Two operations on keys with the same bytes are needed to tell them apart.
+        assert_eq!(shrunk.len(), 2, "{shrunk:?}");
+        assert!(matches!(shrunk[0], Op::Insert(..)));
+        let result = std::panic::catch_unwind(|| Oracle::<Lossy, u8>::new().run(shrunk));
+        assert!(result.is_err());
+
+        // Passing sequences are returned as is.
+        let ops = vec![Op::Search(3), Op::Insert(1, ()), Op::Delete(1)];
+        assert_eq!(Oracle::<u8, ()>::shrink(ops.clone()), ops);
+
+        // Other conditions shrink as well.
+        let ops: Vec<Op<u8, ()>> = (0..100).map(Op::Search).collect();
+        let shrunk = shrink(ops, |ops| {
+            ops.contains(&Op::Search(10))
+                && ops
+                    .iter()
+                    .filter(|op| matches!(op, Op::Search(key) if *key > 90))
+                    .count()
+                    > 2
+        });
+        assert_eq!(shrunk.len(), 4);
+        assert_eq!(shrunk[0], Op::Search(10));
+    }
+}
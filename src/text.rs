@@ -0,0 +1,287 @@
+//! Importing and exporting trees with string keys as text, enabled by the `json` feature.
+//!
+//! The binary dumps of [`persist`](crate::persist) are compact, but they can not be inspected or
+//! edited by hand. [`ART::export_jsonl`] writes one JSON object per line, holding the key of an
+//! entry under `key` and its value, serialized with `serde`, under `value`, and
+//! [`ART::export_csv`] writes a CSV table with a `key,value` header, whose value column holds the
+//! value serialized as JSON. Both formats are written in key order and can be read back with
+//! [`ART::import_jsonl`] and [`ART::import_csv`], where a key that appears several times keeps the
+//! last of its values. Fields of the CSV tables are quoted as in RFC 4180 when they contain commas,
+//! quotes, or line breaks.
+
+use std::io::{self, BufRead, Read, Write};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{persist::invalid_data, ART};
+
+/// The header of the CSV tables.
+const CSV_HEADER: &str = "key,value";
+
+impl<V> ART<String, V> {
+    /// Write every entry of the tree as a JSON object on a line of its own, see the [module
+    /// documentation](crate::text).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer fails or if a value can not be serialized.
+    pub fn export_jsonl<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+        V: Serialize,
+    {
+        for (key, value) in self {
+            writer.write_all(b"{\"key\":")?;
+            serde_json::to_writer(&mut writer, key)?;
+            writer.write_all(b",\"value\":")?;
+            serde_json::to_writer(&mut writer, value)?;
+            writer.write_all(b"}\n")?;
+        }
+        Ok(())
+    }
+
+    /// Read a tree from lines of JSON objects written by [`ART::export_jsonl`]. Blank lines are
+    /// skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader fails, or if a line is not an object with a string `key`
+    /// and a `value` of the value type, naming the line.
+    pub fn import_jsonl<R>(reader: R) -> io::Result<Self>
+    where
+        R: BufRead,
+        V: DeserializeOwned,
+    {
+        let mut tree = Self::new();
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = |reason: String| invalid_data(format!("line {}: {reason}", idx + 1));
+            let object: serde_json::Value =
+                serde_json::from_str(&line).map_err(|error| invalid(error.to_string()))?;
+            let serde_json::Value::Object(mut object) = object else {
+                return Err(invalid("not a JSON object".to_string()));
+            };
+            let Some(serde_json::Value::String(key)) = object.remove("key") else {
+                return Err(invalid("the key is missing or not a string".to_string()));
+            };
+            let value = object
+                .remove("value")
+                .ok_or_else(|| invalid("the value is missing".to_string()))?;
+            let value =
+                serde_json::from_value(value).map_err(|error| invalid(error.to_string()))?;
+            tree.insert(key, value);
+        }
+        Ok(tree)
+    }
+
+    /// Write every entry of the tree as a row of a CSV table, see the [module
+    /// documentation](crate::text).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer fails or if a value can not be serialized.
+    pub fn export_csv<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+        V: Serialize,
+    {
+        writeln!(writer, "{CSV_HEADER}")?;
+        for (key, value) in self {
+            let value = serde_json::to_string(value)?;
+            write_csv_field(&mut writer, key)?;
+            writer.write_all(b",")?;
+            write_csv_field(&mut writer, &value)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Read a tree from a CSV table written by [`ART::export_csv`]. Rows may end with `\n` or
+    /// `\r\n`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader fails, if the table does not start with the `key,value`
+    /// header, or if a row does not hold a key and a value of the value type, naming the row.
+    pub fn import_csv<R>(mut reader: R) -> io::Result<Self>
+    where
+        R: Read,
+        V: DeserializeOwned,
+    {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        let mut rows = parse_csv(&text)?.into_iter().enumerate();
+        match rows.next() {
+            Some((_, header)) if header == ["key", "value"] => {}
+            _ => {
+                return Err(invalid_data(
+                    "the table does not start with a key,value header",
+                ))
+            }
+        }
+        let mut tree = Self::new();
+        for (idx, mut row) in rows {
+            let invalid = |reason: String| invalid_data(format!("row {idx}: {reason}"));
+            if row.len() != 2 {
+                return Err(invalid(format!("expected 2 fields, found {}", row.len())));
+            }
+            let value = row.pop().unwrap_or_default();
+            let key = row.pop().unwrap_or_default();
+            let value = serde_json::from_str(&value).map_err(|error| invalid(error.to_string()))?;
+            tree.insert(key, value);
+        }
+        Ok(tree)
+    }
+}
+
+/// Write a field of a CSV row, quoting it if needed.
+fn write_csv_field<W: Write>(writer: &mut W, field: &str) -> io::Result<()> {
+    if field.contains([',', '"', '\n', '\r']) {
+        write!(writer, "\"{}\"", field.replace('"', "\"\""))
+    } else {
+        writer.write_all(field.as_bytes())
+    }
+}
+
+/// Split a CSV table into rows of fields, skipping empty lines.
+fn parse_csv(text: &str) -> io::Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    // Whether the current row has a field, which tells an empty line from a row with an empty
+    // field.
+    let mut started = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => {
+                started = true;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => field.push(c),
+                        None => return Err(invalid_data("unterminated quoted field")),
+                    }
+                }
+                if !matches!(chars.peek(), None | Some(',' | '\n' | '\r')) {
+                    return Err(invalid_data("unexpected character after a quoted field"));
+                }
+            }
+            ',' => {
+                started = true;
+                row.push(std::mem::take(&mut field));
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                if started || !field.is_empty() {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                started = false;
+            }
+            c => {
+                started = true;
+                field.push(c);
+            }
+        }
+    }
+    if started || !field.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use crate::ART;
+
+    fn sample_tree() -> ART<String, Vec<Option<u32>>> {
+        let mut tree = ART::new();
+        tree.insert("plain".to_string(), vec![Some(1), None]);
+        tree.insert("with, comma".to_string(), vec![]);
+        tree.insert(
+            "with \"quotes\"\nand a line break".to_string(),
+            vec![Some(3)],
+        );
+        tree.insert(String::new(), vec![Some(0)]);
+        tree
+    }
+
+    #[test]
+    fn test_jsonl_round_trip() {
+        let tree = sample_tree();
+        let mut buf = Vec::new();
+        tree.export_jsonl(&mut buf).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert_eq!(text.lines().count(), tree.len());
+        assert!(text.contains("{\"key\":\"plain\",\"value\":[1,null]}\n"));
+
+        let loaded = ART::<String, Vec<Option<u32>>>::import_jsonl(text.as_bytes()).unwrap();
+        assert!(loaded.iter().eq(tree.iter()));
+
+        let edited = "{\"key\":\"a\",\"value\":1}\n\n{\"value\":2,\"key\":\"a\"}\n";
+        let loaded = ART::<String, u32>::import_jsonl(edited.as_bytes()).unwrap();
+        assert_eq!(loaded.search("a"), Some(&2));
+        assert_eq!(loaded.len(), 1);
+
+        for (text, reason) in [
+            (
+                "{\"key\":\"a\",\"value\":1}\n[1]",
+                "line 2: not a JSON object",
+            ),
+            (
+                "{\"key\":1,\"value\":1}",
+                "line 1: the key is missing or not a string",
+            ),
+            ("{\"key\":\"a\"}", "line 1: the value is missing"),
+        ] {
+            let error = ART::<String, u32>::import_jsonl(text.as_bytes()).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+            assert_eq!(error.to_string(), reason);
+        }
+        let error = ART::<String, u32>::import_jsonl(b"{\"key\":\"a\",\"value\":-1}".as_slice())
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let tree = sample_tree();
+        let mut buf = Vec::new();
+        tree.export_csv(&mut buf).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert!(text.starts_with("key,value\n,[0]\nplain,\"[1,null]\"\n"));
+        assert!(text.contains("\"with \"\"quotes\"\"\nand a line break\",[3]\n"));
+
+        let loaded = ART::<String, Vec<Option<u32>>>::import_csv(text.as_bytes()).unwrap();
+        assert!(loaded.iter().eq(tree.iter()));
+
+        let edited = "key,value\r\nb,2\r\n\r\n\"a\",1\r\n";
+        let loaded = ART::<String, u32>::import_csv(edited.as_bytes()).unwrap();
+        assert!(loaded
+            .iter()
+            .eq([(&"a".to_string(), &1), (&"b".to_string(), &2)]));
+
+        for text in [
+            "",
+            "id,value\na,1\n",
+            "key,value\na,1,2\n",
+            "key,value\na,x\n",
+            "key,value\n\"a,1\n",
+            "key,value\n\"a\"b,1\n",
+        ] {
+            let error = ART::<String, u32>::import_csv(text.as_bytes()).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{text:?}");
+        }
+    }
+}
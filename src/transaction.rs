@@ -0,0 +1,170 @@
+//! Applying a batch of changes to a tree all at once.
+//!
+//! A [`Transaction`] stages inserts and deletes in an operation buffer without touching the tree.
+//! Reads through the transaction see the staged changes on top of the tree. When the closure given
+//! to [`ART::transaction`] succeeds, the buffer is applied in order. Applying an operation can not
+//! fail, so either every staged change ends up in the tree or, when the closure returns an error or
+//! panics, none of them do.
+
+use std::borrow::Borrow;
+
+use crate::{BytesComparable, ART};
+
+/// A staged change.
+enum Operation<K, V> {
+    Insert(K, V),
+    Delete(Vec<u8>),
+}
+
+/// A set of changes staged against a tree, see [`ART::transaction`].
+pub struct Transaction<'a, K, V, const N: usize = 10> {
+    tree: &'a ART<K, V, N>,
+    operations: Vec<Operation<K, V>>,
+}
+
+impl<K, V, const N: usize> std::fmt::Debug for Transaction<'_, K, V, N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Transaction")
+            .field("operations", &self.operations.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, V, const N: usize> Transaction<'_, K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Stage inserting the given key-value pair.
+    pub fn insert(&mut self, key: K, value: V) {
+        self.operations.push(Operation::Insert(key, value));
+    }
+
+    /// Stage deleting the value associated with the given key.
+    pub fn delete<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let key = key.bytes().as_ref().to_vec();
+        self.operations.push(Operation::Delete(key));
+    }
+
+    /// Search for the value associated with the given key, taking the staged changes into account.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let key_bytes = key.bytes();
+        let key_bytes = key_bytes.as_ref();
+        // The last staged change to the key decides its value.
+        let staged = self
+            .operations
+            .iter()
+            .rev()
+            .find_map(|operation| match operation {
+                Operation::Insert(k, v) => (k.bytes().as_ref() == key_bytes).then_some(Some(v)),
+                Operation::Delete(k) => (k == key_bytes).then_some(None),
+            });
+        staged.unwrap_or_else(|| self.tree.search(key))
+    }
+
+    /// Return the number of staged changes.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Return true if no change has been staged.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+impl<K, V, const N: usize> ART<K, V, N>
+where
+    K: BytesComparable,
+{
+    /// Run the closure with a [`Transaction`] and apply its staged changes if the closure returns
+    /// `Ok`. If the closure returns an error or panics, the tree is left untouched.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the closure.
+    pub fn transaction<F, R, E>(&mut self, f: F) -> Result<R, E>
+    where
+        F: FnOnce(&mut Transaction<'_, K, V, N>) -> Result<R, E>,
+    {
+        let mut txn = Transaction {
+            tree: self,
+            operations: Vec::new(),
+        };
+        let result = f(&mut txn)?;
+        let operations = txn.operations;
+        for operation in operations {
+            match operation {
+                Operation::Insert(key, value) => {
+                    self.insert(key, value);
+                }
+                Operation::Delete(key) => {
+                    self.delete_leaf(&key);
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ART;
+
+    #[test]
+    fn test_transaction_commit() {
+        let mut tree = ART::<String, u32>::new();
+        tree.insert("a".to_string(), 1);
+        tree.insert("b".to_string(), 2);
+        let result = tree.transaction(|txn| {
+            txn.insert("c".to_string(), 3);
+            txn.delete("a");
+            txn.insert("b".to_string(), 20);
+            assert_eq!(txn.search("a"), None);
+            assert_eq!(txn.search("b"), Some(&20));
+            assert_eq!(txn.search("c"), Some(&3));
+            txn.delete("c");
+            txn.insert("a".to_string(), 10);
+            assert_eq!(txn.len(), 5);
+            Ok::<_, ()>(txn.search("a").copied())
+        });
+        assert_eq!(result, Ok(Some(10)));
+        assert_eq!(tree.search("a"), Some(&10));
+        assert_eq!(tree.search("b"), Some(&20));
+        assert_eq!(tree.search("c"), None);
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        let mut tree = ART::<String, u32>::new();
+        tree.insert("a".to_string(), 1);
+        let result = tree.transaction(|txn| {
+            txn.delete("a");
+            txn.insert("b".to_string(), 2);
+            Err::<(), _>("abort")
+        });
+        assert_eq!(result, Err("abort"));
+        assert_eq!(tree.search("a"), Some(&1));
+        assert_eq!(tree.search("b"), None);
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            tree.transaction(|txn| {
+                txn.insert("b".to_string(), 2);
+                assert!(txn.is_empty(), "abort");
+                Ok::<_, ()>(())
+            })
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(tree.search("a"), Some(&1));
+        assert_eq!(tree.search("b"), None);
+    }
+}
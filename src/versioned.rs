@@ -0,0 +1,203 @@
+//! A tree with an undo and redo history.
+//!
+//! Every mutation records the operation that reverts it. Undoing applies the recorded operation,
+//! which in turn records the operation that reapplies the original change on the redo stack. Making
+//! a new change after undoing discards the redo stack, as editors usually do.
+
+use std::borrow::Borrow;
+
+use crate::{BytesComparable, ART};
+
+/// An operation that can be applied to the tree.
+#[derive(Debug)]
+enum Edit<K, V> {
+    Insert(K, V),
+    Delete(K),
+}
+
+/// An adaptive radix tree whose mutations can be undone and redone.
+#[derive(Debug)]
+pub struct VersionedArt<K, V, const N: usize = 10> {
+    tree: ART<K, V, N>,
+    undo: Vec<Edit<K, V>>,
+    redo: Vec<Edit<K, V>>,
+}
+
+impl<K, V, const N: usize> VersionedArt<K, V, N> {
+    /// Create an empty tree with an empty history.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            tree: ART::new(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    /// Return the tree for reading.
+    #[must_use]
+    pub const fn tree(&self) -> &ART<K, V, N> {
+        &self.tree
+    }
+
+    /// Drop the history and return the tree.
+    #[must_use]
+    pub fn into_tree(self) -> ART<K, V, N> {
+        self.tree
+    }
+
+    /// Return the number of changes that can be undone.
+    #[must_use]
+    pub const fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Return the number of changes that can be redone.
+    #[must_use]
+    pub const fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// Forget all changes, such that none can be undone or redone.
+    pub fn clear_history(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+impl<K, V, const N: usize> Default for VersionedArt<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> VersionedArt<K, V, N>
+where
+    K: BytesComparable + Clone,
+{
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(key)
+    }
+
+    /// Insert the given key-value pair into the tree, returning the value previously associated
+    /// with the key, which is now kept in the history.
+    pub fn insert(&mut self, key: K, value: V) -> Option<&V> {
+        let inverse = Self::apply(&mut self.tree, Edit::Insert(key, value));
+        self.record(inverse)
+    }
+
+    /// Delete the given key from the tree, returning the deleted value, which is now kept in the
+    /// history. Deleting a key that does not exist is not recorded.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let inverse = self
+            .tree
+            .delete_leaf(key.bytes().as_ref())
+            .map(|leaf| Edit::Insert(leaf.key, leaf.value));
+        self.record(inverse)
+    }
+
+    /// Revert up to `n` of the most recent changes, returning how many were reverted.
+    pub fn undo(&mut self, n: usize) -> usize {
+        Self::replay(&mut self.tree, &mut self.undo, &mut self.redo, n)
+    }
+
+    /// Reapply up to `n` of the most recently undone changes, returning how many were reapplied.
+    pub fn redo(&mut self, n: usize) -> usize {
+        Self::replay(&mut self.tree, &mut self.redo, &mut self.undo, n)
+    }
+
+    /// Records the inverse of a new change, returning the value the change replaced or deleted.
+    fn record(&mut self, inverse: Option<Edit<K, V>>) -> Option<&V> {
+        self.undo.push(inverse?);
+        self.redo.clear();
+        match self.undo.last() {
+            Some(Edit::Insert(_, value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Applies the last `n` edits of `from`, pushing their inverses onto `to`.
+    fn replay(
+        tree: &mut ART<K, V, N>,
+        from: &mut Vec<Edit<K, V>>,
+        to: &mut Vec<Edit<K, V>>,
+        n: usize,
+    ) -> usize {
+        let mut count = 0;
+        while count < n {
+            let Some(edit) = from.pop() else {
+                break;
+            };
+            if let Some(inverse) = Self::apply(tree, edit) {
+                to.push(inverse);
+            }
+            count += 1;
+        }
+        count
+    }
+
+    /// Applies an edit to the tree, returning the edit that reverts it, or `None` if the edit
+    /// changed nothing.
+    fn apply(tree: &mut ART<K, V, N>, edit: Edit<K, V>) -> Option<Edit<K, V>> {
+        match edit {
+            Edit::Insert(key, value) => Some(match tree.insert(key.clone(), value) {
+                Some(old) => Edit::Insert(key, old),
+                None => Edit::Delete(key),
+            }),
+            Edit::Delete(key) => tree
+                .delete_leaf(key.bytes().as_ref())
+                .map(|leaf| Edit::Insert(leaf.key, leaf.value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VersionedArt;
+
+    #[test]
+    fn test_undo_redo() {
+        let mut tree = VersionedArt::<String, u32>::new();
+        assert_eq!(tree.insert("a".to_string(), 1), None);
+        assert_eq!(tree.insert("b".to_string(), 2), None);
+        assert_eq!(tree.insert("a".to_string(), 10), Some(&1));
+        assert_eq!(tree.delete("b"), Some(&2));
+        assert_eq!(tree.delete("c"), None);
+        assert_eq!(tree.undo_len(), 4);
+
+        assert_eq!(tree.undo(1), 1);
+        assert_eq!(tree.search("b"), Some(&2));
+        assert_eq!(tree.undo(2), 2);
+        assert_eq!(tree.search("a"), Some(&1));
+        assert_eq!(tree.search("b"), None);
+        assert_eq!(tree.undo(5), 1);
+        assert_eq!(tree.tree().min(), None);
+        assert_eq!(tree.redo_len(), 4);
+
+        // Deleting a missing key is not a change, so it keeps the changes that can be redone.
+        assert_eq!(tree.delete("b"), None);
+        assert_eq!(tree.redo_len(), 4);
+        assert_eq!(tree.redo(1), 1);
+        assert_eq!(tree.search("a"), Some(&1));
+        assert_eq!(tree.redo(2), 2);
+        assert_eq!(tree.search("a"), Some(&10));
+        assert_eq!(tree.search("b"), Some(&2));
+
+        // A new change discards the changes that could have been redone.
+        tree.insert("c".to_string(), 3);
+        assert_eq!(tree.redo(1), 0);
+        assert_eq!(tree.undo(10), 4);
+        assert_eq!(tree.tree().min(), None);
+        tree.clear_history();
+        assert_eq!((tree.undo_len(), tree.redo_len()), (0, 0));
+    }
+}
@@ -0,0 +1,112 @@
+//! Walking the nodes of a tree with control over which subtrees are visited.
+//!
+//! [`ART::walk`] visits the subtrees and entries of a tree in key order. Every inner node is shown
+//! to the visitor as a [`Visit::Subtree`] with the bytes that all keys below it start with, so a
+//! whole subtree can be skipped when its prefix rules out every key in it, without visiting any of
+//! its entries. Keys that are a prefix of other keys are placed under a zero byte following the
+//! shared prefix, which therefore shows up in the prefixes of subtrees holding such keys.
+
+use crate::{BytesComparable, ART};
+
+/// A part of the tree shown to the visitor of [`ART::walk`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visit<'p, 'a, K, V> {
+    /// A subtree whose encoded keys all start with the given bytes.
+    Subtree(&'p [u8]),
+    /// An entry of the tree.
+    Entry(&'a K, &'a V),
+}
+
+/// What to do after visiting a part of the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Walk {
+    /// Go on with the walk, entering the subtree that was visited.
+    Continue,
+    /// Go on with the walk after the subtree that was visited, without entering it. After
+    /// visiting an entry, this is the same as [`Walk::Continue`].
+    SkipSubtree,
+    /// End the walk. The visitor is not called again, and no other node is visited.
+    Stop,
+}
+
+impl<K, V, const N: usize, const C4: usize, const C16: usize, const C48: usize>
+    ART<K, V, N, C4, C16, C48>
+where
+    K: BytesComparable,
+{
+    /// Visit the subtrees and entries of the tree in key order, as directed by the visitor.
+    /// Returns false if the visitor stopped the walk, and true if the walk went through.
+    pub fn walk<'a, F>(&'a self, mut f: F) -> bool
+    where
+        F: FnMut(Visit<'_, 'a, K, V>) -> Walk,
+    {
+        self.root
+            .as_ref()
+            .is_none_or(|root| root.walk(&mut Vec::new(), &mut f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Visit, Walk};
+    use crate::{BytesComparable, ART};
+
+    #[test]
+    fn test_walk() {
+        let tree: ART<String, usize> = ["apple", "apricot", "banana", "blueberry", "cherry"]
+            .into_iter()
+            .enumerate()
+            .map(|(value, key)| (key.to_string(), value))
+            .collect();
+
+        // Skip every subtree of keys not starting with a 'b'.
+        let mut entries = Vec::new();
+        let mut subtrees = 0;
+        tree.walk(|visit| match visit {
+            Visit::Subtree(prefix) => {
+                subtrees += 1;
+                if prefix.first().is_some_and(|byte| *byte != b'b') {
+                    Walk::SkipSubtree
+                } else {
+                    Walk::Continue
+                }
+            }
+            Visit::Entry(key, _) => {
+                if key.starts_with('b') {
+                    entries.push(key.as_str());
+                }
+                Walk::Continue
+            }
+        });
+        assert_eq!(entries, ["banana", "blueberry"]);
+        assert_eq!(subtrees, 3);
+
+        // Every entry is under the prefixes of the subtrees above it.
+        let mut prefixes: Vec<Vec<u8>> = Vec::new();
+        let walked = tree.walk(|visit| {
+            match visit {
+                Visit::Subtree(prefix) => {
+                    prefixes.retain(|outer| prefix.starts_with(outer));
+                    prefixes.push(prefix.to_vec());
+                }
+                Visit::Entry(key, _) => {
+                    prefixes.retain(|outer| key.bytes().starts_with(outer));
+                    assert!(!prefixes.is_empty());
+                }
+            }
+            Walk::Continue
+        });
+        assert!(walked);
+
+        let mut visited = 0;
+        assert!(!tree.walk(|visit| {
+            visited += 1;
+            if matches!(visit, Visit::Entry(..)) {
+                Walk::Stop
+            } else {
+                Walk::Continue
+            }
+        }));
+        assert_eq!(visited, 3);
+    }
+}
@@ -0,0 +1,448 @@
+//! A tree whose entries carry weights, with queries for the heaviest entry in a range of keys.
+//!
+//! Every entry of a [`WeightedArt`] may be given a weight, and the tree keeps the greatest weight
+//! found below each of its inner nodes. Those maxima are indexed by the bytes that all keys below
+//! the node start with, which only depends on the keys in the tree, so a write only has to update
+//! the maxima of the inner nodes on the path to its key. [`WeightedArt::max_weight_in_range`] takes
+//! the maximum of every subtree that lies entirely within the range, and only descends into the
+//! subtrees that straddle its bounds, which makes it suited to picking the most urgent task among
+//! those of a key range in a scheduler.
+
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{
+    node::{Inner, Node, Owned},
+    BytesComparable, ART,
+};
+
+type WeightedNode<K, V, W, const N: usize> = Node<K, (V, Option<W>), N, Owned, 4, 16, 48>;
+type WeightedInner<K, V, W, const N: usize> = Inner<K, (V, Option<W>), N, Owned, 4, 16, 48>;
+
+/// An adaptive radix tree whose entries have optional weights.
+#[derive(Debug)]
+pub struct WeightedArt<K, V, W, const N: usize = 10> {
+    tree: ART<K, (V, Option<W>), N>,
+    /// The greatest weight below every inner node, indexed by the bytes that all keys below the
+    /// node start with.
+    maxima: HashMap<Box<[u8]>, Option<W>>,
+}
+
+impl<K, V, W, const N: usize> WeightedArt<K, V, W, N> {
+    /// Create an empty tree.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            tree: ART::new(),
+            maxima: HashMap::new(),
+        }
+    }
+
+    /// Return the number of entries in the tree.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Return true if the tree holds no entry.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Return an iterator over the keys, values, and weights of the tree in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V, Option<&W>)> + '_ {
+        self.tree
+            .iter()
+            .map(|(key, (value, weight))| (key, value, weight.as_ref()))
+    }
+}
+
+impl<K, V, W, const N: usize> Default for WeightedArt<K, V, W, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, W, const N: usize> WeightedArt<K, V, W, N>
+where
+    K: BytesComparable,
+    W: Ord + Clone,
+{
+    /// Search for the value associated with the given key.
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(key).map(|(value, _)| value)
+    }
+
+    /// Return the weight of the entry with the given key, or `None` if the key is not in the tree
+    /// or its entry has no weight.
+    pub fn weight<Q>(&self, key: &Q) -> Option<&W>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        self.tree.search(key)?.1.as_ref()
+    }
+
+    /// Insert the given key-value pair without a weight, returning the value previously associated
+    /// with the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.write(key, value, None)
+    }
+
+    /// Insert the given key-value pair with the given weight, returning the value previously
+    /// associated with the key.
+    pub fn insert_weighted(&mut self, key: K, value: V, weight: W) -> Option<V> {
+        self.write(key, value, Some(weight))
+    }
+
+    fn write(&mut self, key: K, value: V, weight: Option<W>) -> Option<V> {
+        let bytes = key.bytes().as_ref().to_vec();
+        let previous = self.tree.insert(key, (value, weight));
+        // Inserting only adds inner nodes, so every inner node on the path is refreshed.
+        update_maxima(self.tree.root.as_ref(), &bytes, &mut self.maxima, &[]);
+        previous.map(|(value, _)| value)
+    }
+
+    /// Delete the value associated with the given key.
+    pub fn delete<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+    {
+        let bytes = key.bytes();
+        let bytes = bytes.as_ref();
+        let before: Vec<_> = inners_on_path(self.tree.root.as_ref(), bytes)
+            .into_iter()
+            .map(|(_, depth)| depth)
+            .collect();
+        let (value, _) = self.tree.delete(key)?;
+        update_maxima(self.tree.root.as_ref(), bytes, &mut self.maxima, &before);
+        Some(value)
+    }
+
+    /// Return the entry with the greatest weight among those whose keys are within the given
+    /// range, preferring the first one in key order when several have that weight. Entries
+    /// without a weight are never returned.
+    pub fn max_weight_in_range<Q, R>(&self, range: R) -> Option<(&K, &V, &W)>
+    where
+        K: Borrow<Q>,
+        Q: BytesComparable + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let start = range.start_bound().map(Q::bytes);
+        let end = range.end_bound().map(Q::bytes);
+        let bounds = Bounds {
+            start: start.as_ref().map(AsRef::as_ref),
+            end: end.as_ref().map(AsRef::as_ref),
+        };
+        let root = self.tree.root.as_ref()?;
+        let mut best = None;
+        max_in_range(root, &mut Vec::new(), &bounds, &self.maxima, &mut best);
+        let (weight, node, mut path) = best?;
+        let leaf = heaviest_leaf(node, &mut path, weight, &self.maxima);
+        Some((&leaf.key, &leaf.value.0, weight))
+    }
+}
+
+/// The bounds of a range of encoded keys.
+struct Bounds<'a> {
+    start: Bound<&'a [u8]>,
+    end: Bound<&'a [u8]>,
+}
+
+impl Bounds<'_> {
+    fn contains(&self, key: &[u8]) -> bool {
+        RangeBounds::<[u8]>::contains(&(self.start, self.end), key)
+    }
+}
+
+/// Return the smallest key that is greater than every key starting with the given prefix, or
+/// `None` if every byte of the prefix is 0xFF.
+pub(crate) fn successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let last = prefix.iter().rposition(|&b| b != u8::MAX)?;
+    let mut successor = prefix[..=last].to_vec();
+    successor[last] += 1;
+    Some(successor)
+}
+
+/// Return the inner nodes on the path to the key from the root down, together with the lengths of
+/// the prefixes of the key that all keys below them start with.
+pub(crate) fn inners_on_path<'a, K, T, const N: usize>(
+    root: Option<&'a Node<K, T, N>>,
+    key: &[u8],
+) -> Vec<(&'a Inner<K, T, N, Owned, 4, 16, 48>, usize)>
+where
+    K: BytesComparable,
+{
+    let mut inners = Vec::new();
+    let mut node = root;
+    let mut depth = 0;
+    while let Some(Node::Inner(inner)) = node {
+        let prefix = inner.prefix();
+        if !key
+            .get(depth..)
+            .is_some_and(|rest| rest.starts_with(prefix))
+        {
+            break;
+        }
+        depth += prefix.len();
+        inners.push((inner, depth));
+        // A key that ends here is stored below the byte key 0.
+        node = inner.child_ref(key.get(depth).copied().unwrap_or(0));
+        depth += 1;
+    }
+    inners
+}
+
+/// Return the greatest weight below the inner node, whose keys start with the given bytes.
+fn node_maximum<K, V, W, const N: usize>(
+    inner: &WeightedInner<K, V, W, N>,
+    path: &mut Vec<u8>,
+    maxima: &HashMap<Box<[u8]>, Option<W>>,
+) -> Option<W>
+where
+    W: Ord + Clone,
+{
+    let depth = path.len();
+    let maximum = inner
+        .children()
+        .filter_map(|(byte_key, child)| match child {
+            Node::Leaf(leaf) => leaf.value.1.clone(),
+            Node::Inner(child) => {
+                path.truncate(depth);
+                path.push(byte_key);
+                path.extend_from_slice(child.prefix());
+                maxima.get(path.as_slice()).cloned().flatten()
+            }
+        })
+        .max();
+    path.truncate(depth);
+    maximum
+}
+
+/// Recompute the maxima of the inner nodes on the path to the key from the bottom up, after the
+/// entry of the key was written. The maxima of the inner nodes that were on the path before the
+/// write, whose prefix lengths are given, are dropped if the nodes are gone.
+fn update_maxima<K, V, W, const N: usize>(
+    root: Option<&WeightedNode<K, V, W, N>>,
+    key: &[u8],
+    maxima: &mut HashMap<Box<[u8]>, Option<W>>,
+    before: &[usize],
+) where
+    K: BytesComparable,
+    W: Ord + Clone,
+{
+    let inners = inners_on_path(root, key);
+    for len in before {
+        if inners.iter().all(|(_, depth)| depth != len) {
+            maxima.remove(&key[..*len]);
+        }
+    }
+    let mut path = Vec::new();
+    for (inner, depth) in inners.into_iter().rev() {
+        path.clear();
+        path.extend_from_slice(&key[..depth]);
+        let maximum = node_maximum(inner, &mut path, maxima);
+        maxima.insert(path.as_slice().into(), maximum);
+    }
+}
+
+/// The heaviest subtree or leaf found so far, with the bytes that all keys below it start with.
+type Best<'a, K, V, W, const N: usize> = Option<(&'a W, &'a WeightedNode<K, V, W, N>, Vec<u8>)>;
+
+/// Keep the given subtree or leaf if it is heavier than the best one so far.
+fn offer<'a, K, V, W, const N: usize>(
+    best: &mut Best<'a, K, V, W, N>,
+    weight: Option<&'a W>,
+    node: &'a WeightedNode<K, V, W, N>,
+    path: &[u8],
+) where
+    W: Ord,
+{
+    if let Some(weight) = weight {
+        if best.as_ref().is_none_or(|(best, ..)| weight > *best) {
+            *best = Some((weight, node, path.to_vec()));
+        }
+    }
+}
+
+/// Find the heaviest subtree or leaf of the node whose keys are all within the bounds, using the
+/// maxima of the subtrees that lie entirely within them. The given bytes are those that all keys
+/// below the node start with, before its prefix.
+fn max_in_range<'a, K, V, W, const N: usize>(
+    node: &'a WeightedNode<K, V, W, N>,
+    path: &mut Vec<u8>,
+    bounds: &Bounds<'_>,
+    maxima: &'a HashMap<Box<[u8]>, Option<W>>,
+    best: &mut Best<'a, K, V, W, N>,
+) where
+    K: BytesComparable,
+    W: Ord,
+{
+    let inner = match node {
+        Node::Leaf(leaf) => {
+            if bounds.contains(leaf.key.bytes().as_ref()) {
+                offer(best, leaf.value.1.as_ref(), node, path);
+            }
+            return;
+        }
+        Node::Inner(inner) => inner,
+    };
+    let depth = path.len();
+    path.extend_from_slice(inner.prefix());
+    // The keys below the node are within [path, successor).
+    let successor = successor(path);
+    let after_start = match bounds.start {
+        Bound::Included(start) => path.as_slice() >= start,
+        Bound::Excluded(start) => path.as_slice() > start,
+        Bound::Unbounded => true,
+    };
+    let before_end = match bounds.end {
+        Bound::Included(end) | Bound::Excluded(end) => successor
+            .as_ref()
+            .is_some_and(|successor| successor.as_slice() <= end),
+        Bound::Unbounded => true,
+    };
+    let past_end = match bounds.end {
+        Bound::Included(end) => end < path.as_slice(),
+        Bound::Excluded(end) => end <= path.as_slice(),
+        Bound::Unbounded => false,
+    };
+    let before_start = match bounds.start {
+        Bound::Included(start) | Bound::Excluded(start) => successor
+            .as_ref()
+            .is_some_and(|successor| start >= successor.as_slice()),
+        Bound::Unbounded => false,
+    };
+    if after_start && before_end {
+        let maximum = maxima
+            .get(path.as_slice())
+            .expect("every inner node has a maximum");
+        offer(best, maximum.as_ref(), node, &path[..depth]);
+    } else if !past_end && !before_start {
+        for (byte_key, child) in inner.children() {
+            path.push(byte_key);
+            max_in_range(child, path, bounds, maxima, best);
+            path.pop();
+        }
+    }
+    path.truncate(depth);
+}
+
+/// Return the first leaf in key order below the node that has the given weight, which is the
+/// greatest one below the node. The given bytes are those that all keys below the node start with,
+/// before its prefix.
+fn heaviest_leaf<'a, K, V, W, const N: usize>(
+    mut node: &'a WeightedNode<K, V, W, N>,
+    path: &mut Vec<u8>,
+    weight: &W,
+    maxima: &HashMap<Box<[u8]>, Option<W>>,
+) -> &'a crate::node::Leaf<K, (V, Option<W>)>
+where
+    W: Ord,
+{
+    loop {
+        let inner = match node {
+            Node::Leaf(leaf) => return leaf,
+            Node::Inner(inner) => inner,
+        };
+        path.extend_from_slice(inner.prefix());
+        let depth = path.len();
+        node = inner
+            .children()
+            .find(|(byte_key, child)| match child {
+                Node::Leaf(leaf) => leaf.value.1.as_ref() == Some(weight),
+                Node::Inner(child) => {
+                    path.truncate(depth);
+                    path.push(*byte_key);
+                    path.extend_from_slice(child.prefix());
+                    maxima
+                        .get(path.as_slice())
+                        .is_some_and(|max| max.as_ref() == Some(weight))
+                }
+            })
+            .map(|(byte_key, child)| {
+                path.truncate(depth);
+                path.push(byte_key);
+                child
+            })
+            .expect("the weight is below the node");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        ops::Bound::{Excluded, Included},
+    };
+
+    use rand::Rng;
+
+    use super::WeightedArt;
+
+    #[test]
+    fn test_max_weight_in_range() {
+        let mut tree = WeightedArt::<String, u32, u8>::new();
+        tree.insert_weighted("task/a".to_string(), 1, 3);
+        tree.insert_weighted("task/b".to_string(), 2, 9);
+        tree.insert("task/c".to_string(), 3);
+        tree.insert_weighted("task/d".to_string(), 4, 9);
+        tree.insert_weighted("zzz".to_string(), 5, 200);
+        assert_eq!(tree.weight("task/a"), Some(&3));
+        assert_eq!(tree.weight("task/c"), None);
+
+        let max = tree.max_weight_in_range::<str, _>((Included("task/"), Excluded("task0")));
+        assert_eq!(max, Some((&"task/b".to_string(), &2, &9)));
+        let max = tree.max_weight_in_range::<str, _>((Included("task/c"), Included("task/c")));
+        assert_eq!(max, None);
+        let max = tree.max_weight_in_range::<str, _>(..);
+        assert_eq!(max, Some((&"zzz".to_string(), &5, &200)));
+
+        assert_eq!(tree.delete("task/b"), Some(2));
+        let max = tree.max_weight_in_range::<str, _>((Included("task/"), Excluded("task0")));
+        assert_eq!(max, Some((&"task/d".to_string(), &4, &9)));
+        assert_eq!(tree.insert("task/d".to_string(), 40), Some(4));
+        let max = tree.max_weight_in_range::<str, _>((Included("task/"), Excluded("task0")));
+        assert_eq!(max, Some((&"task/a".to_string(), &1, &3)));
+    }
+
+    #[test]
+    fn test_max_weight_matches_scan() {
+        let mut rng = rand::thread_rng();
+        let mut tree = WeightedArt::<u32, (), u16>::new();
+        let mut expected = BTreeMap::new();
+        for _ in 0..5000 {
+            let key = rng.gen_range(0..3000);
+            if rng.gen_bool(0.7) {
+                let weight = rng.gen_range(0..1000);
+                tree.insert_weighted(key, (), weight);
+                expected.insert(key, weight);
+            } else {
+                assert_eq!(tree.delete(&key).is_some(), expected.remove(&key).is_some());
+            }
+        }
+        for _ in 0..500 {
+            let start = rng.gen_range(0..3000);
+            let end = rng.gen_range(start..=3000);
+            let heaviest = expected
+                .range(start..end)
+                .map(|(key, weight)| (*weight, std::cmp::Reverse(*key)))
+                .max()
+                .map(|(weight, std::cmp::Reverse(key))| (key, weight));
+            let max = tree
+                .max_weight_in_range(start..end)
+                .map(|(key, (), weight)| (*key, *weight));
+            assert_eq!(max, heaviest, "{start}..{end}");
+        }
+        assert_eq!(tree.len(), expected.len());
+    }
+}